@@ -0,0 +1,110 @@
+/// Account decoding
+///
+/// Dispatches on the layout version like Migrate: current accounts load
+/// zero-copy, older layouts are upgraded in memory and anything else is
+/// reported as unsupported.
+use crate::Error;
+use borsh::BorshDeserialize;
+use merkle_whitelist_token_sale::state::{
+    BuyerFacts, BuyerFactsV1, BuyerFactsV2, ClosedTokenBase, TokenBase, TokenBaseV1,
+    ZeroCopyAccount,
+};
+use solana_sdk::pubkey::Pubkey;
+use spl_discriminator::SplDiscriminate;
+
+/// Account in its current form, with the layout version it is stored in
+#[derive(Debug)]
+pub struct Decoded<T> {
+    pub account: T,
+    /// Layout version the account is stored in
+    pub version: u8,
+}
+
+impl<T> Decoded<T> {
+    /// Warn on stderr when Migrate would upgrade the account
+    pub fn warn_outdated(self, address: &Pubkey, current: u8) -> T {
+        if self.version != current {
+            eprintln!(
+                "{address} is stored in layout version {}, Migrate upgrades it to {current}",
+                self.version
+            );
+        }
+        self.account
+    }
+}
+
+/// TokenBase stored at `address`, of the current or an older layout
+pub fn token_base(address: &Pubkey, data: &[u8]) -> Result<Decoded<TokenBase>, Error> {
+    if data.get(..8).unwrap_or_default() == ClosedTokenBase::SPL_DISCRIMINATOR_SLICE {
+        return Err(format!("{address} is a closed sale").into());
+    }
+    let offset = TokenBase::DISCRIMINATOR_OFFSET;
+    if data.get(offset..offset + 8).unwrap_or_default() != TokenBase::SPL_DISCRIMINATOR_SLICE {
+        return Err(format!("{address} is not a TokenBase").into());
+    }
+
+    match data.len() {
+        TokenBase::LEN => {
+            let token_base = *TokenBase::load(data)?;
+            check_version(address, "TokenBase", token_base.version, TokenBase::VERSION)?;
+            Ok(Decoded {
+                version: token_base.version,
+                account: token_base,
+            })
+        }
+        TokenBaseV1::LEN => Ok(Decoded {
+            account: TokenBase::from(TokenBaseV1::try_from_slice(data)?),
+            version: 1,
+        }),
+        len => Err(unsupported(address, "TokenBase", len)),
+    }
+}
+
+/// BuyerFacts stored at `address`, of the current or an older layout
+pub fn buyer_facts(address: &Pubkey, data: &[u8]) -> Result<Decoded<BuyerFacts>, Error> {
+    if data.get(..8).unwrap_or_default() != BuyerFacts::SPL_DISCRIMINATOR_SLICE {
+        return Err(format!("{address} is not a BuyerFacts").into());
+    }
+
+    match data.len() {
+        BuyerFacts::LEN => {
+            let buyer_facts = *BuyerFacts::load(data)?;
+            check_version(
+                address,
+                "BuyerFacts",
+                buyer_facts.version,
+                BuyerFacts::VERSION,
+            )?;
+            Ok(Decoded {
+                version: buyer_facts.version,
+                account: buyer_facts,
+            })
+        }
+        BuyerFactsV1::LEN => Ok(Decoded {
+            account: BuyerFacts::from(BuyerFactsV1::try_from_slice(data)?),
+            version: 1,
+        }),
+        BuyerFactsV2::LEN => Ok(Decoded {
+            account: BuyerFacts::from(BuyerFactsV2::try_from_slice(data)?),
+            version: 2,
+        }),
+        len => Err(unsupported(address, "BuyerFacts", len)),
+    }
+}
+
+/// Current-size accounts must carry the current version, a newer one
+/// needs a newer CLI
+fn check_version(address: &Pubkey, name: &str, version: u8, current: u8) -> Result<(), Error> {
+    if version != current {
+        return Err(format!(
+            "{address} is a {name} of unsupported layout version {version}, \
+             this CLI reads up to version {current}"
+        )
+        .into());
+    }
+    Ok(())
+}
+
+fn unsupported(address: &Pubkey, name: &str, len: usize) -> Error {
+    format!("{address} is a {name} of unsupported layout, {len} bytes").into()
+}
@@ -0,0 +1,105 @@
+/// Human readable account and simulation output
+use merkle_whitelist_token_sale::{
+    error::TokenSaleError,
+    processor::quote::Quote,
+    state::{BuyerFacts, TokenBase},
+};
+use solana_rpc_client_api::response::RpcSimulateTransactionResult;
+use solana_sdk::pubkey::Pubkey;
+
+pub fn print_token_base(address: &Pubkey, token_base: &TokenBase) {
+    println!("TokenBase {address}");
+    println!("  sale_authority:          {}", token_base.sale_authority);
+    println!("  mint:                    {}", token_base.mint);
+    println!("  vault:                   {}", token_base.vault);
+    println!("  is_running:              {}", token_base.is_running());
+    println!("  is_registered:           {}", token_base.is_registered());
+    println!("  is_escrow:               {}", token_base.is_escrow());
+    println!(
+        "  is_escrow_settled:       {}",
+        token_base.is_escrow_settled()
+    );
+    println!("  price:                   {}", token_base.price);
+    println!(
+        "  default_purchase_limit:  {}",
+        token_base.default_purchase_limit
+    );
+    println!(
+        "  whitelist_mode:          {:?}",
+        token_base.whitelist_mode().ok()
+    );
+    println!(
+        "  whitelist_root:          {}",
+        hex::encode(token_base.whitelist_root.0)
+    );
+    for record in token_base.root_records() {
+        println!(
+            "    from slot {:<12}   {}",
+            record.activation_slot,
+            hex::encode(record.root.0)
+        );
+    }
+    println!("  root_grace_slots:        {}", token_base.root_grace_slots);
+    println!("  end_slot:                {}", token_base.end_slot);
+    println!("  whitelist_signer:        {}", token_base.whitelist_signer);
+    println!("  gate_key:                {}", token_base.gate_key);
+    println!("  gate_min_amount:         {}", token_base.gate_min_amount);
+    println!("  phase:                   {}", token_base.phase);
+    println!("  referral_bps:            {}", token_base.referral_bps);
+    println!(
+        "  min_registration_slots:  {}",
+        token_base.min_registration_slots
+    );
+    println!(
+        "  purchase_cooldown_slots: {}",
+        token_base.purchase_cooldown_slots
+    );
+    println!("  version:                 {}", token_base.version);
+}
+
+pub fn print_buyer_facts(address: &Pubkey, buyer_facts: &BuyerFacts) {
+    println!("BuyerFacts {address}");
+    println!("  token_base:         {}", buyer_facts.token_base);
+    println!("  buyer:              {}", buyer_facts.buyer);
+    println!("  purchase_limit:     {}", buyer_facts.purchase_limit);
+    println!("  total_purchased:    {}", buyer_facts.total_purchased);
+    println!("  receipt_count:      {}", buyer_facts.receipt_count);
+    println!("  referral_rewards:   {}", buyer_facts.referral_rewards);
+    println!("  referrer:           {}", buyer_facts.referrer);
+    println!("  registered_slot:    {}", buyer_facts.registered_slot);
+    println!("  last_purchase_slot: {}", buyer_facts.last_purchase_slot);
+    println!("  escrow_deposited:   {}", buyer_facts.escrow_deposited);
+    println!("  escrow_voted:       {}", buyer_facts.escrow_voted);
+    println!(
+        "  escrow_refunded:    {}",
+        bool::from(buyer_facts.escrow_refunded)
+    );
+    println!("  payer:              {}", buyer_facts.payer);
+    println!("  version:            {}", buyer_facts.version);
+}
+
+pub fn print_quote(quote: &Quote) {
+    println!("Quote");
+    println!("  amount:          {}", quote.amount);
+    println!("  lamports_cost:   {}", quote.lamports_cost);
+    println!("  purchase_limit:  {}", quote.purchase_limit);
+    println!("  remaining_limit: {}", quote.remaining_limit);
+    match quote.error.and_then(TokenSaleError::from_code) {
+        Some(error) => println!("  eligible:        false, {error:?}"),
+        None => println!("  eligible:        {}", quote.is_eligible()),
+    }
+}
+
+pub fn print_simulation(simulation: &RpcSimulateTransactionResult) {
+    println!("BuyToken simulation");
+    match &simulation.err {
+        Some(error) => println!("  result:         {error}"),
+        None => println!("  result:         ok"),
+    }
+    if let Some(units_consumed) = simulation.units_consumed {
+        println!("  units_consumed: {units_consumed}");
+    }
+    for log in simulation.logs.iter().flatten() {
+        println!("  {log}");
+    }
+}
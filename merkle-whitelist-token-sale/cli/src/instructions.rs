@@ -0,0 +1,228 @@
+/// Instruction builders
+///
+/// One builder per instruction the CLI sends, with the accounts in
+/// instruction order. Skipped optional accounts are passed as the
+/// program id.
+use merkle_whitelist_token_sale::{
+    instruction::{SaleSettings, TokenSaleInstruction},
+    merkle::{WhitelistProof, WhitelistRoot},
+    pda::{BuyerFactsPDA, RegistryPDA, RegistryPagePDA, TokenBasePDA},
+};
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use spl_associated_token_account::get_associated_token_address;
+
+/// Addresses of a sale, derived from its sale authority and mint
+pub struct SaleAddresses {
+    pub program_id: Pubkey,
+    pub sale_authority: Pubkey,
+    pub mint: Pubkey,
+    pub token_base: Pubkey,
+}
+
+impl SaleAddresses {
+    pub fn new(program_id: Pubkey, sale_authority: Pubkey, mint: Pubkey) -> Self {
+        let (token_base, _) = TokenBasePDA::find_pda(&program_id, &sale_authority, &mint);
+        Self {
+            program_id,
+            sale_authority,
+            mint,
+            token_base,
+        }
+    }
+
+    pub fn buyer_facts(&self, buyer: &Pubkey) -> Pubkey {
+        BuyerFactsPDA::find_pda(&self.program_id, &self.token_base, buyer).0
+    }
+
+    /// Global registry, then the sale authority's registry
+    pub fn registries(&self) -> [Pubkey; 2] {
+        [None, Some(&self.sale_authority)]
+            .map(|authority| RegistryPDA::find_pda(&self.program_id, authority).0)
+    }
+
+    /// Registries with their pages `pages`, in the order of [`Self::registries`]
+    fn registry_accounts(&self, pages: [u64; 2]) -> Vec<AccountMeta> {
+        self.registries()
+            .into_iter()
+            .zip(pages)
+            .flat_map(|(registry, page)| {
+                [
+                    AccountMeta::new(registry, false),
+                    AccountMeta::new(
+                        RegistryPagePDA::find_pda(&self.program_id, &registry, page).0,
+                        false,
+                    ),
+                ]
+            })
+            .collect()
+    }
+
+    fn instruction(
+        &self,
+        instruction: TokenSaleInstruction,
+        accounts: Vec<AccountMeta>,
+    ) -> Instruction {
+        Instruction {
+            program_id: self.program_id,
+            accounts,
+            data: borsh::to_vec(&instruction).unwrap(),
+        }
+    }
+
+    /// Accounts of ToggleRunning, ConfigureSale and UpdateSaleSettings
+    fn sale_authority_accounts(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.token_base, false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new_readonly(self.sale_authority, true),
+        ]
+    }
+
+    /// `append_pages` are the append pages of [`Self::registries`]
+    pub fn open_sale(
+        &self,
+        vault: &Pubkey,
+        price: u64,
+        purchase_limit: u64,
+        whitelist_root: WhitelistRoot,
+        append_pages: [u64; 2],
+    ) -> Instruction {
+        self.instruction(
+            TokenSaleInstruction::OpenSale {
+                price,
+                purchase_limit,
+                whitelist_root,
+            },
+            vec![
+                AccountMeta::new(self.token_base, false),
+                AccountMeta::new(self.mint, false),
+                AccountMeta::new_readonly(*vault, false),
+                AccountMeta::new(self.sale_authority, true),
+                AccountMeta::new_readonly(system_program::ID, false),
+            ]
+            .into_iter()
+            .chain(self.registry_accounts(append_pages))
+            .chain([AccountMeta::new_readonly(spl_token::ID, false)])
+            .collect(),
+        )
+    }
+
+    /// `configure_sale` must be a [`TokenSaleInstruction::ConfigureSale`]
+    pub fn configure_sale(&self, configure_sale: TokenSaleInstruction) -> Instruction {
+        self.instruction(configure_sale, self.sale_authority_accounts())
+    }
+
+    pub fn update_sale_settings(&self, settings: SaleSettings) -> Instruction {
+        self.instruction(
+            TokenSaleInstruction::UpdateSaleSettings { settings },
+            self.sale_authority_accounts(),
+        )
+    }
+
+    pub fn toggle_running(&self) -> Instruction {
+        self.instruction(
+            TokenSaleInstruction::ToggleRunning,
+            self.sale_authority_accounts(),
+        )
+    }
+
+    /// `listing_pages` are the pages of [`Self::registries`] listing the
+    /// sale, None for sales opened before the registry. `escrow` is the
+    /// escrow of escrowed sales, closed with the sale
+    pub fn close_sale(
+        &self,
+        listing_pages: Option<[u64; 2]>,
+        escrow: Option<&Pubkey>,
+    ) -> Instruction {
+        let registry_accounts = match listing_pages {
+            Some(pages) => self.registry_accounts(pages),
+            None => vec![AccountMeta::new_readonly(self.program_id, false); 4],
+        };
+
+        self.instruction(
+            TokenSaleInstruction::CloseSale,
+            [
+                AccountMeta::new(self.token_base, false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new(self.sale_authority, true),
+            ]
+            .into_iter()
+            .chain(registry_accounts)
+            .chain([match escrow {
+                Some(escrow) => AccountMeta::new(*escrow, false),
+                None => AccountMeta::new_readonly(self.program_id, false),
+            }])
+            .collect(),
+        )
+    }
+
+    pub fn assign_limit(&self, buyer: &Pubkey, new_purchase_limit: u64) -> Instruction {
+        self.instruction(
+            TokenSaleInstruction::AssignLimit { new_purchase_limit },
+            vec![
+                AccountMeta::new_readonly(self.token_base, false),
+                AccountMeta::new(self.buyer_facts(buyer), false),
+                AccountMeta::new_readonly(*buyer, false),
+                AccountMeta::new_readonly(self.sale_authority, true),
+            ],
+        )
+    }
+
+    /// `escrow` is the escrow of escrowed sales
+    pub fn quote(
+        &self,
+        buyer: &Pubkey,
+        amount: u64,
+        proof: WhitelistProof,
+        escrow: Option<&Pubkey>,
+    ) -> Instruction {
+        self.instruction(
+            TokenSaleInstruction::Quote { amount, proof },
+            vec![
+                AccountMeta::new_readonly(self.token_base, false),
+                AccountMeta::new_readonly(self.mint, false),
+                AccountMeta::new_readonly(self.buyer_facts(buyer), false),
+                AccountMeta::new_readonly(*buyer, false),
+                AccountMeta::new_readonly(self.program_id, false),
+                AccountMeta::new_readonly(self.program_id, false),
+                AccountMeta::new_readonly(self.program_id, false),
+                AccountMeta::new_readonly(*escrow.unwrap_or(&self.program_id), false),
+                AccountMeta::new_readonly(self.program_id, false),
+            ],
+        )
+    }
+
+    /// BuyToken without optional accounts, `buyer` also pays
+    pub fn buy_token(
+        &self,
+        vault: &Pubkey,
+        buyer: &Pubkey,
+        amount: u64,
+        proof: WhitelistProof,
+    ) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.token_base, false),
+            AccountMeta::new(self.mint, false),
+            AccountMeta::new(*vault, false),
+            AccountMeta::new_readonly(self.sale_authority, false),
+            AccountMeta::new(get_associated_token_address(buyer, &self.mint), false),
+            AccountMeta::new(self.buyer_facts(buyer), false),
+            AccountMeta::new(*buyer, true),
+            AccountMeta::new(*buyer, true),
+            AccountMeta::new_readonly(system_program::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+        ];
+        accounts.extend([
+            AccountMeta::new(self.program_id, false),
+            AccountMeta::new(self.program_id, false),
+        ]);
+        accounts.extend((0..5).map(|_| AccountMeta::new_readonly(self.program_id, false)));
+
+        self.instruction(TokenSaleInstruction::BuyToken { amount, proof }, accounts)
+    }
+}
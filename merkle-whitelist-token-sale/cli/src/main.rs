@@ -0,0 +1,546 @@
+//! `token-sale`, operator CLI for merkle whitelist token sales
+//!
+//! Sends sale authority instructions signed by `--keypair`, which also pays
+//! the fees, prints decoded accounts and simulates purchases. Defaults to a
+//! local test validator.
+mod decode;
+mod display;
+mod instructions;
+mod snapshot;
+mod whitelist;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clap::{value_parser, Arg, ArgMatches, Command};
+use instructions::SaleAddresses;
+use merkle_whitelist_token_sale::{
+    client,
+    instruction::{SaleSettings, SaleSettingsV1, SaleSettingsV2, TokenSaleInstruction},
+    merkle::WhitelistRoot,
+    pda::RegistryPDA,
+    processor::quote::Quote,
+    state::{BuyerFacts, TokenBase, WhitelistMode},
+};
+use merkletreers::tree::MerkleTree;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::config::RpcSimulateTransactionConfig;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Keypair, Signer},
+    transaction::Transaction,
+};
+use std::{path::PathBuf, str::FromStr};
+use whitelist::Whitelist;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Connection and signer shared by every subcommand
+struct Config {
+    rpc: RpcClient,
+    keypair: Keypair,
+    program_id: Pubkey,
+}
+
+impl Config {
+    /// Sale of `mint`, managed by `sale_authority` or else `--keypair`
+    fn sale(&self, matches: &ArgMatches) -> SaleAddresses {
+        SaleAddresses::new(
+            self.program_id,
+            matches
+                .get_one::<Pubkey>("sale-authority")
+                .copied()
+                .unwrap_or(self.keypair.pubkey()),
+            *matches.get_one::<Pubkey>("mint").unwrap(),
+        )
+    }
+
+    /// Sign `instruction` with `--keypair` and send it
+    fn send(&self, instruction: Instruction) -> Result<(), Error> {
+        self.send_all(&[instruction])
+    }
+
+    /// Sign `instructions` with `--keypair` and send them in one transaction
+    fn send_all(&self, instructions: &[Instruction]) -> Result<(), Error> {
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.keypair.pubkey()),
+            &[&self.keypair],
+            self.rpc.get_latest_blockhash()?,
+        );
+        let signature = self.rpc.send_and_confirm_transaction(&transaction)?;
+        println!("Signature: {signature}");
+        Ok(())
+    }
+
+    fn token_base(&self, address: &Pubkey) -> Result<TokenBase, Error> {
+        let data = self.rpc.get_account_data(address)?;
+        Ok(decode::token_base(address, &data)?.warn_outdated(address, TokenBase::VERSION))
+    }
+
+    /// Data of every page of `registry`, none if it was not created yet
+    fn registry_pages(&self, registry: &Pubkey) -> Result<Vec<Vec<u8>>, Error> {
+        let page_count = self.rpc.get_multiple_accounts(&[*registry])?[0]
+            .as_ref()
+            .and_then(|account| client::decode_registry(&account.data))
+            .map_or(0, |registry| registry.page_count);
+
+        let addresses = client::registry_page_addresses(&self.program_id, registry, page_count);
+        let mut pages = Vec::new();
+        // getMultipleAccounts takes at most 100 addresses
+        for chunk in addresses.chunks(100) {
+            pages.extend(
+                self.rpc
+                    .get_multiple_accounts(chunk)?
+                    .into_iter()
+                    .flatten()
+                    .map(|account| account.data),
+            );
+        }
+        Ok(pages)
+    }
+
+    /// Pages of the sale's registries OpenSale lists it on
+    fn append_pages(&self, sale: &SaleAddresses) -> Result<[u64; 2], Error> {
+        let registries = self.rpc.get_multiple_accounts(&sale.registries())?;
+        let mut pages = [0; 2];
+        for (page, registry) in pages.iter_mut().zip(registries) {
+            *page = client::registry_append_page(registry.as_ref().map(|r| r.data.as_slice()));
+        }
+        Ok(pages)
+    }
+
+    /// Pages of the sale's registries listing it, None if the sale
+    /// was opened before the registry
+    fn listing_pages(&self, sale: &SaleAddresses) -> Result<Option<[u64; 2]>, Error> {
+        if !self.token_base(&sale.token_base)?.is_registered() {
+            return Ok(None);
+        }
+
+        let mut pages = [0; 2];
+        for (page, registry) in pages.iter_mut().zip(sale.registries()) {
+            let data = self.registry_pages(&registry)?;
+            *page = client::find_registry_page(&sale.token_base, data.iter().map(Vec::as_slice))
+                .ok_or_else(|| format!("{registry} does not list {}", sale.token_base))?;
+        }
+        Ok(Some(pages))
+    }
+
+    fn buyer_facts(&self, address: &Pubkey) -> Result<BuyerFacts, Error> {
+        let data = self.rpc.get_account_data(address)?;
+        Ok(decode::buyer_facts(address, &data)?.warn_outdated(address, BuyerFacts::VERSION))
+    }
+}
+
+fn parse_pubkey(value: &str) -> Result<Pubkey, String> {
+    Pubkey::from_str(value).map_err(|error| error.to_string())
+}
+
+fn parse_whitelist_mode(value: &str) -> Result<WhitelistMode, String> {
+    match value {
+        "merkle" => Ok(WhitelistMode::Merkle),
+        "voucher" => Ok(WhitelistMode::Voucher),
+        "token-holder" => Ok(WhitelistMode::TokenHolder),
+        "collection-holder" => Ok(WhitelistMode::CollectionHolder),
+        _ => Err("expected merkle, voucher, token-holder or collection-holder".to_string()),
+    }
+}
+
+fn pubkey_arg(name: &'static str, help: &'static str) -> Arg<'static> {
+    Arg::new(name)
+        .long(name)
+        .takes_value(true)
+        .value_parser(parse_pubkey)
+        .help(help)
+}
+
+fn u64_arg(name: &'static str, help: &'static str) -> Arg<'static> {
+    Arg::new(name)
+        .long(name)
+        .takes_value(true)
+        .value_parser(value_parser!(u64))
+        .help(help)
+}
+
+fn mint_arg() -> Arg<'static> {
+    pubkey_arg("mint", "Mint of the token being sold").required(true)
+}
+
+fn whitelist_arg() -> Arg<'static> {
+    Arg::new("whitelist")
+        .long("whitelist")
+        .takes_value(true)
+        .value_parser(value_parser!(PathBuf))
+        .help("File with one whitelisted buyer address per line")
+}
+
+fn sale_authority_arg() -> Arg<'static> {
+    pubkey_arg("sale-authority", "Sale authority, defaults to --keypair")
+}
+
+fn command() -> Command<'static> {
+    Command::new("token-sale")
+        .about("Manage merkle whitelist token sales")
+        .subcommand_required(true)
+        .arg(
+            Arg::new("url")
+                .long("url")
+                .global(true)
+                .takes_value(true)
+                .default_value("http://127.0.0.1:8899")
+                .help("JSON RPC URL"),
+        )
+        .arg(
+            Arg::new("keypair")
+                .long("keypair")
+                .global(true)
+                .takes_value(true)
+                .value_parser(value_parser!(PathBuf))
+                .help("Sale authority and fee payer keypair, defaults to ~/.config/solana/id.json"),
+        )
+        .arg(
+            pubkey_arg(
+                "program-id",
+                "Token sale program id, defaults to the crate's",
+            )
+            .global(true),
+        )
+        .subcommand(
+            Command::new("open")
+                .about("Open a sale, whitelisting the buyers of --whitelist")
+                .arg(mint_arg())
+                .arg(pubkey_arg("vault", "Account receiving the lamports raised").required(true))
+                .arg(u64_arg("price", "Lamports per whole token").required(true))
+                .arg(u64_arg("purchase-limit", "Default purchase limit per buyer").required(true))
+                .arg(whitelist_arg()),
+        )
+        .subcommand(
+            Command::new("configure")
+                .about("Change the settings given, leaving the others unchanged")
+                .arg(mint_arg())
+                .arg(u64_arg("price", "Lamports per whole token"))
+                .arg(u64_arg(
+                    "purchase-limit",
+                    "Default purchase limit per buyer",
+                ))
+                .arg(whitelist_arg())
+                .arg(
+                    Arg::new("referral-bps")
+                        .long("referral-bps")
+                        .takes_value(true)
+                        .value_parser(value_parser!(u16))
+                        .help("Referral reward in basis points, 0 disables referrals"),
+                )
+                .arg(u64_arg(
+                    "min-registration-slots",
+                    "Slots between RegisterBuyer and buying",
+                ))
+                .arg(u64_arg(
+                    "purchase-cooldown-slots",
+                    "Slots between purchases",
+                ))
+                .arg(
+                    Arg::new("whitelist-mode")
+                        .long("whitelist-mode")
+                        .takes_value(true)
+                        .value_parser(parse_whitelist_mode)
+                        .help("merkle, voucher, token-holder or collection-holder"),
+                )
+                .arg(pubkey_arg("whitelist-signer", "Voucher signing key"))
+                .arg(pubkey_arg("gate-key", "Gating mint or verified collection"))
+                .arg(u64_arg(
+                    "gate-min-amount",
+                    "Minimum balance of the gating mint",
+                ))
+                .arg(
+                    u64_arg(
+                        "root-activation-slot",
+                        "Slot the new whitelist root activates at",
+                    )
+                    .requires("whitelist"),
+                )
+                .arg(u64_arg(
+                    "root-grace-slots",
+                    "Slots the replaced whitelist root keeps verifying proofs",
+                ))
+                .arg(u64_arg(
+                    "end-slot",
+                    "Slot purchases stop at for good, required by close",
+                )),
+        )
+        .subcommand(
+            Command::new("toggle")
+                .about("Start or pause a sale")
+                .arg(mint_arg()),
+        )
+        .subcommand(Command::new("close").about("Close a sale").arg(mint_arg()))
+        .subcommand(
+            Command::new("assign-limit")
+                .about("Set the purchase limit of a registered buyer")
+                .arg(mint_arg())
+                .arg(pubkey_arg("buyer", "Registered buyer").required(true))
+                .arg(u64_arg("limit", "New purchase limit").required(true)),
+        )
+        .subcommand(
+            Command::new("inspect")
+                .about("Print the decoded TokenBase, and BuyerFacts of --buyer")
+                .arg(mint_arg())
+                .arg(sale_authority_arg())
+                .arg(pubkey_arg("buyer", "Buyer whose BuyerFacts to print")),
+        )
+        .subcommand(
+            Command::new("simulate-buy")
+                .about("Quote and simulate a purchase of a registered buyer, sends nothing")
+                .arg(mint_arg())
+                .arg(sale_authority_arg())
+                .arg(pubkey_arg("buyer", "Registered buyer").required(true))
+                .arg(u64_arg("amount", "Tokens to buy, in base units").required(true))
+                .arg(whitelist_arg()),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Export every buyer of a sale with its limit and purchases")
+                .arg(mint_arg())
+                .arg(sale_authority_arg())
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .value_parser(["csv", "json"])
+                        .default_value("csv")
+                        .help("Output format"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .long("output")
+                        .takes_value(true)
+                        .value_parser(value_parser!(PathBuf))
+                        .help("File to write, stdout if not given"),
+                ),
+        )
+        .subcommand(
+            Command::new("sales")
+                .about("List the open sales from the launchpad registry")
+                .arg(pubkey_arg(
+                    "sale-authority",
+                    "Sale authority whose sales to list, every sale if not given",
+                )),
+        )
+}
+
+/// Root of `--whitelist`, an empty whitelist if not given
+fn whitelist_root(matches: &ArgMatches) -> Result<Option<WhitelistRoot>, Error> {
+    Ok(match matches.get_one::<PathBuf>("whitelist") {
+        Some(path) => Some(Whitelist::read(path)?.root()),
+        None => None,
+    })
+}
+
+fn open(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let sale = config.sale(matches);
+    let whitelist_root =
+        whitelist_root(matches)?.unwrap_or(WhitelistRoot(MerkleTree::new(Vec::new()).root));
+
+    config.send(sale.open_sale(
+        matches.get_one::<Pubkey>("vault").unwrap(),
+        *matches.get_one::<u64>("price").unwrap(),
+        *matches.get_one::<u64>("purchase-limit").unwrap(),
+        whitelist_root,
+        config.append_pages(&sale)?,
+    ))?;
+    println!("TokenBase: {}", sale.token_base);
+    Ok(())
+}
+
+fn configure(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let sale = config.sale(matches);
+    let whitelist_root = whitelist_root(matches)?;
+    let root_activation_slot = matches.get_one::<u64>("root-activation-slot").copied();
+
+    // scheduled roots can only be set by UpdateSaleSettings
+    let (configured_root, scheduled_root) = match root_activation_slot {
+        Some(_) => (None, whitelist_root),
+        None => (whitelist_root, None),
+    };
+    let v1 = SaleSettingsV1 {
+        referral_bps: matches.get_one::<u16>("referral-bps").copied(),
+        min_registration_slots: matches.get_one::<u64>("min-registration-slots").copied(),
+        purchase_cooldown_slots: matches.get_one::<u64>("purchase-cooldown-slots").copied(),
+        whitelist_mode: matches.get_one::<WhitelistMode>("whitelist-mode").copied(),
+        whitelist_signer: matches.get_one::<Pubkey>("whitelist-signer").copied(),
+        gate_key: matches.get_one::<Pubkey>("gate-key").copied(),
+        gate_min_amount: matches.get_one::<u64>("gate-min-amount").copied(),
+        whitelist_root: scheduled_root,
+        root_activation_slot,
+        root_grace_slots: matches.get_one::<u64>("root-grace-slots").copied(),
+    };
+    let settings = SaleSettingsV2 {
+        v1,
+        end_slot: matches.get_one::<u64>("end-slot").copied(),
+    };
+    let price = matches.get_one::<u64>("price").copied();
+    let default_purchase_limit = matches.get_one::<u64>("purchase-limit").copied();
+
+    // ConfigureSale is sent when nothing is set too, the program rejects it
+    let mut instructions = Vec::new();
+    if price.is_some()
+        || default_purchase_limit.is_some()
+        || configured_root.is_some()
+        || settings.is_empty()
+    {
+        instructions.push(sale.configure_sale(TokenSaleInstruction::ConfigureSale {
+            price,
+            default_purchase_limit,
+            whitelist_root: configured_root,
+        }));
+    }
+    if !settings.is_empty() {
+        instructions.push(sale.update_sale_settings(SaleSettings::V2(settings)));
+    }
+    config.send_all(&instructions)
+}
+
+fn inspect(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let sale = config.sale(matches);
+
+    let token_base = config.token_base(&sale.token_base)?;
+    display::print_token_base(&sale.token_base, &token_base);
+
+    if let Some(buyer) = matches.get_one::<Pubkey>("buyer") {
+        let address = sale.buyer_facts(buyer);
+        println!();
+        display::print_buyer_facts(&address, &config.buyer_facts(&address)?);
+    }
+    Ok(())
+}
+
+/// Quote the purchase, then simulate BuyToken without signatures
+fn simulate_buy(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let sale = config.sale(matches);
+    let buyer = matches.get_one::<Pubkey>("buyer").unwrap();
+    let amount = *matches.get_one::<u64>("amount").unwrap();
+    let token_base = config.token_base(&sale.token_base)?;
+    // escrowed sales pay into the escrow, their vault
+    let escrow = token_base.is_escrow().then_some(&token_base.vault);
+
+    // not whitelisted buyers get an empty proof, the quote reports it
+    let whitelist = match matches.get_one::<PathBuf>("whitelist") {
+        Some(path) => Whitelist::read(path)?,
+        None => Whitelist::new(Vec::new()),
+    };
+    let proof = || whitelist.proof(buyer).unwrap_or_default();
+
+    let simulation_config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        commitment: Some(CommitmentConfig::confirmed()),
+        ..RpcSimulateTransactionConfig::default()
+    };
+
+    let quote = Transaction::new_with_payer(
+        &[sale.quote(buyer, amount, proof(), escrow)],
+        Some(&config.keypair.pubkey()),
+    );
+    let simulation = config
+        .rpc
+        .simulate_transaction_with_config(&quote, simulation_config.clone())?
+        .value;
+    if let Some(error) = simulation.err {
+        return Err(format!("Quote failed: {error}").into());
+    }
+    let return_data = simulation.return_data.ok_or("Quote returned no data")?;
+    let quote = Quote::from_return_data(&STANDARD.decode(return_data.data.0)?)
+        .ok_or("Quote returned invalid data")?;
+    display::print_quote(&quote);
+
+    // the buyer pays, the TokenBase PDA mints
+    let buy_token = Transaction::new_with_payer(
+        &[sale.buy_token(&token_base.vault, buyer, amount, proof())],
+        Some(buyer),
+    );
+    let simulation = config
+        .rpc
+        .simulate_transaction_with_config(&buy_token, simulation_config)?
+        .value;
+    println!();
+    display::print_simulation(&simulation);
+    Ok(())
+}
+
+/// Write the snapshot of a sale's buyers as CSV or JSON
+fn export(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let sale = config.sale(matches);
+    let rows = snapshot::snapshot(&config.rpc, &config.program_id, &sale.token_base)?;
+
+    let contents = match matches.get_one::<String>("format").unwrap().as_str() {
+        "json" => snapshot::to_json(&rows),
+        _ => snapshot::to_csv(&rows),
+    };
+    match matches.get_one::<PathBuf>("output") {
+        Some(path) => {
+            std::fs::write(path, contents)?;
+            eprintln!("Exported {} buyers to {}", rows.len(), path.display());
+        }
+        None => print!("{contents}"),
+    }
+    Ok(())
+}
+
+/// Print the open sales listed by the global or a sale authority's registry
+fn sales(config: &Config, matches: &ArgMatches) -> Result<(), Error> {
+    let authority = matches.get_one::<Pubkey>("sale-authority");
+    let (registry, _) = RegistryPDA::find_pda(&config.program_id, authority);
+    let pages = config.registry_pages(&registry)?;
+
+    let sales = client::list_registry_sales(pages.iter().map(Vec::as_slice));
+    for token_base in &sales {
+        println!("{token_base}");
+    }
+    eprintln!("{} open sales", sales.len());
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let matches = command().get_matches();
+
+    let keypair_path = match matches.get_one::<PathBuf>("keypair") {
+        Some(path) => path.clone(),
+        None => PathBuf::from(std::env::var("HOME")?).join(".config/solana/id.json"),
+    };
+    let config = Config {
+        rpc: RpcClient::new_with_commitment(
+            matches.get_one::<String>("url").unwrap().clone(),
+            CommitmentConfig::confirmed(),
+        ),
+        keypair: read_keypair_file(&keypair_path)
+            .map_err(|error| format!("{}: {error}", keypair_path.display()))?,
+        program_id: matches
+            .get_one::<Pubkey>("program-id")
+            .copied()
+            .unwrap_or(merkle_whitelist_token_sale::ID),
+    };
+
+    match matches.subcommand() {
+        Some(("open", matches)) => open(&config, matches),
+        Some(("configure", matches)) => configure(&config, matches),
+        Some(("toggle", matches)) => config.send(config.sale(matches).toggle_running()),
+        Some(("close", matches)) => {
+            let sale = config.sale(matches);
+            let token_base = config.token_base(&sale.token_base)?;
+            let escrow = token_base.is_escrow().then_some(&token_base.vault);
+            config.send(sale.close_sale(config.listing_pages(&sale)?, escrow))
+        }
+        Some(("assign-limit", matches)) => config.send(config.sale(matches).assign_limit(
+            matches.get_one::<Pubkey>("buyer").unwrap(),
+            *matches.get_one::<u64>("limit").unwrap(),
+        )),
+        Some(("inspect", matches)) => inspect(&config, matches),
+        Some(("simulate-buy", matches)) => simulate_buy(&config, matches),
+        Some(("export", matches)) => export(&config, matches),
+        Some(("sales", matches)) => sales(&config, matches),
+        _ => unreachable!("subcommand is required"),
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,144 @@
+/// Sale snapshots
+///
+/// Every BuyerFacts of a sale, found with `getProgramAccounts` filtered by
+/// discriminator and `token_base`, exported as CSV or JSON.
+use crate::Error;
+use merkle_whitelist_token_sale::{
+    client::{buyer_facts_filters, list_buyer_facts},
+    state::BuyerFacts,
+};
+use serde_json::{json, Value};
+use solana_account_decoder::UiAccountEncoding;
+use solana_rpc_client::rpc_client::RpcClient;
+use solana_rpc_client_api::{
+    config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+/// Accounts owned by a program, RPC or a local stand-in
+pub trait ProgramAccounts {
+    /// Accounts of `program_id` with `data_size` bytes of data matching
+    /// every memcmp `(offset, bytes)` filter
+    fn program_accounts(
+        &self,
+        program_id: &Pubkey,
+        data_size: usize,
+        memcmp: &[(usize, Vec<u8>)],
+    ) -> Result<Vec<(Pubkey, Account)>, Error>;
+}
+
+impl ProgramAccounts for RpcClient {
+    fn program_accounts(
+        &self,
+        program_id: &Pubkey,
+        data_size: usize,
+        memcmp: &[(usize, Vec<u8>)],
+    ) -> Result<Vec<(Pubkey, Account)>, Error> {
+        let filters = std::iter::once(RpcFilterType::DataSize(data_size as u64))
+            .chain(memcmp.iter().map(|(offset, bytes)| {
+                RpcFilterType::Memcmp(Memcmp::new_raw_bytes(*offset, bytes.clone()))
+            }))
+            .collect();
+        let config = RpcProgramAccountsConfig {
+            filters: Some(filters),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        Ok(self.get_program_accounts_with_config(program_id, config)?)
+    }
+}
+
+/// Snapshot row of a buyer
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuyerRow {
+    pub buyer: Pubkey,
+    pub buyer_facts: Pubkey,
+    /// Remaining purchase limit
+    pub purchase_limit: u64,
+    pub total_purchased: u64,
+    pub registered_slot: u64,
+    pub last_purchase_slot: u64,
+    pub referrer: Option<Pubkey>,
+}
+
+impl BuyerRow {
+    fn new(address: Pubkey, buyer_facts: &BuyerFacts) -> Self {
+        Self {
+            buyer: buyer_facts.buyer,
+            buyer_facts: address,
+            purchase_limit: buyer_facts.purchase_limit,
+            total_purchased: buyer_facts.total_purchased,
+            registered_slot: buyer_facts.registered_slot,
+            last_purchase_slot: buyer_facts.last_purchase_slot,
+            referrer: buyer_facts.is_referred().then_some(buyer_facts.referrer),
+        }
+    }
+}
+
+/// Every buyer of `token_base`, ordered by buyer
+pub fn snapshot(
+    source: &impl ProgramAccounts,
+    program_id: &Pubkey,
+    token_base: &Pubkey,
+) -> Result<Vec<BuyerRow>, Error> {
+    let accounts = source.program_accounts(
+        program_id,
+        BuyerFacts::LEN,
+        &buyer_facts_filters(token_base),
+    )?;
+    Ok(list_buyer_facts(
+        token_base,
+        accounts
+            .iter()
+            .map(|(address, account)| (*address, account.data.as_slice())),
+    )
+    .iter()
+    .map(|(address, buyer_facts)| BuyerRow::new(*address, buyer_facts))
+    .collect())
+}
+
+const CSV_HEADER: &str =
+    "buyer,buyer_facts,purchase_limit,total_purchased,registered_slot,last_purchase_slot,referrer";
+
+/// One line per buyer after a header, unreferred buyers have an empty referrer
+pub fn to_csv(rows: &[BuyerRow]) -> String {
+    let mut csv = format!("{CSV_HEADER}\n");
+    for row in rows {
+        csv += &format!(
+            "{},{},{},{},{},{},{}\n",
+            row.buyer,
+            row.buyer_facts,
+            row.purchase_limit,
+            row.total_purchased,
+            row.registered_slot,
+            row.last_purchase_slot,
+            row.referrer
+                .map(|referrer| referrer.to_string())
+                .unwrap_or_default(),
+        );
+    }
+    csv
+}
+
+/// Array of buyer objects, unreferred buyers have a null referrer
+pub fn to_json(rows: &[BuyerRow]) -> String {
+    let rows = rows
+        .iter()
+        .map(|row| {
+            json!({
+                "buyer": row.buyer.to_string(),
+                "buyer_facts": row.buyer_facts.to_string(),
+                "purchase_limit": row.purchase_limit,
+                "total_purchased": row.total_purchased,
+                "registered_slot": row.registered_slot,
+                "last_purchase_slot": row.last_purchase_slot,
+                "referrer": row.referrer.map(|referrer| referrer.to_string()),
+            })
+        })
+        .collect::<Vec<_>>();
+    serde_json::to_string_pretty(&Value::Array(rows)).unwrap()
+}
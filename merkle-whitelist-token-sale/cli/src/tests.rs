@@ -0,0 +1,282 @@
+use crate::decode;
+use crate::instructions::SaleAddresses;
+use crate::snapshot::{snapshot, to_csv, to_json, ProgramAccounts};
+use crate::whitelist::Whitelist;
+use crate::Error;
+use bytemuck::Zeroable;
+use merkle_whitelist_token_sale::{
+    instruction::{SaleSettings, TokenSaleInstruction},
+    merkle::{convert_whitelist_proof, pubkey_to_sha256_leaf, verify_membership, WhitelistRoot},
+    state::{BuyerFacts, BuyerFactsV2, ClosedTokenBase, TokenBase},
+};
+use solana_sdk::{account::Account, instruction::Instruction, pubkey::Pubkey};
+use spl_discriminator::SplDiscriminate;
+
+const IDL: &str = include_str!("../../idl/merkle_whitelist_token_sale.json");
+
+/// Accounts the IDL lists for `instruction`, with their writable and signer flags
+fn idl_accounts(instruction: &str) -> Vec<(bool, bool)> {
+    let idl: serde_json::Value = serde_json::from_str(IDL).unwrap();
+    idl["instructions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|idl_instruction| idl_instruction["name"] == instruction)
+        .unwrap()["accounts"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|account| {
+            (
+                account["isMut"].as_bool().unwrap(),
+                account["isSigner"].as_bool().unwrap(),
+            )
+        })
+        .collect()
+}
+
+/// Builders pass the IDL's accounts, in order, with its flags
+#[test]
+fn test_instructions_match_idl() {
+    let sale = SaleAddresses::new(
+        merkle_whitelist_token_sale::ID,
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let (buyer, vault) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+    let configure_sale = TokenSaleInstruction::ConfigureSale {
+        price: Some(1),
+        default_purchase_limit: None,
+        whitelist_root: None,
+    };
+
+    let instructions: [(&str, Instruction); 8] = [
+        (
+            "OpenSale",
+            sale.open_sale(&vault, 1, 1, WhitelistRoot([0; 32]), [0, 0]),
+        ),
+        ("ConfigureSale", sale.configure_sale(configure_sale)),
+        (
+            "UpdateSaleSettings",
+            sale.update_sale_settings(SaleSettings::V1(Default::default())),
+        ),
+        ("ToggleRunning", sale.toggle_running()),
+        ("CloseSale", sale.close_sale(Some([0, 0]), Some(&vault))),
+        ("AssignLimit", sale.assign_limit(&buyer, 1)),
+        ("BuyToken", sale.buy_token(&vault, &buyer, 1, Vec::new())),
+        ("Quote", sale.quote(&buyer, 1, Vec::new(), Some(&vault))),
+    ];
+
+    for (name, instruction) in instructions {
+        let accounts = instruction
+            .accounts
+            .iter()
+            .map(|account| (account.is_writable, account.is_signer))
+            .collect::<Vec<_>>();
+        assert_eq!(accounts, idl_accounts(name), "{name}");
+    }
+}
+
+/// Whitelist files skip blank lines and comments, proofs verify against the root
+#[test]
+fn test_whitelist_proofs() {
+    let buyers = [
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    ];
+    let contents = format!(
+        "# presale buyers\n{}\n\n  {}\n{}\n",
+        buyers[0], buyers[1], buyers[2]
+    );
+    let whitelist = Whitelist::parse(&contents).unwrap();
+
+    for buyer in buyers {
+        let proof = whitelist.proof(&buyer).unwrap();
+        assert!(verify_membership(
+            whitelist.root().0,
+            convert_whitelist_proof(proof),
+            pubkey_to_sha256_leaf(&buyer),
+        ));
+    }
+    assert!(whitelist.proof(&Pubkey::new_unique()).is_none());
+}
+
+/// Invalid addresses are reported with their line
+#[test]
+fn test_whitelist_invalid_address() {
+    let contents = format!("{}\nnot-an-address\n", Pubkey::new_unique());
+    assert_eq!(
+        Whitelist::parse(&contents).err(),
+        Some("line 2: invalid address".to_string())
+    );
+}
+
+/// Configure only changes the settings given
+#[test]
+fn test_configure_arguments() {
+    crate::command().debug_assert();
+
+    let matches = crate::command()
+        .try_get_matches_from([
+            "token-sale",
+            "configure",
+            "--mint",
+            &Pubkey::new_unique().to_string(),
+            "--price",
+            "5",
+            "--whitelist-mode",
+            "token-holder",
+        ])
+        .unwrap();
+    let (_, configure) = matches.subcommand().unwrap();
+
+    assert_eq!(configure.get_one::<u64>("price"), Some(&5));
+    assert_eq!(configure.get_one::<u64>("purchase-limit"), None);
+    assert!(configure.get_one::<u16>("referral-bps").is_none());
+    assert!(configure
+        .get_one::<std::path::PathBuf>("whitelist")
+        .is_none());
+}
+
+/// Local stand-in for `getProgramAccounts`, filters like an RPC node
+struct LocalAccounts(Vec<(Pubkey, Account)>);
+
+impl ProgramAccounts for LocalAccounts {
+    fn program_accounts(
+        &self,
+        program_id: &Pubkey,
+        data_size: usize,
+        memcmp: &[(usize, Vec<u8>)],
+    ) -> Result<Vec<(Pubkey, Account)>, Error> {
+        Ok(self
+            .0
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id && account.data.len() == data_size)
+            .filter(|(_, account)| {
+                memcmp
+                    .iter()
+                    .all(|(offset, bytes)| account.data[*offset..].starts_with(bytes))
+            })
+            .cloned()
+            .collect())
+    }
+}
+
+fn buyer_facts_account(sale: &SaleAddresses, buyer: Pubkey, referrer: Pubkey) -> (Pubkey, Account) {
+    let mut buyer_facts = BuyerFacts::zeroed();
+    buyer_facts.discriminator = BuyerFacts::SPL_DISCRIMINATOR.into();
+    buyer_facts.token_base = sale.token_base;
+    buyer_facts.buyer = buyer;
+    buyer_facts.referrer = referrer;
+    buyer_facts.purchase_limit = 60;
+    buyer_facts.total_purchased = 40;
+    (
+        sale.buyer_facts(&buyer),
+        Account {
+            data: bytemuck::bytes_of(&buyer_facts).to_vec(),
+            owner: sale.program_id,
+            ..Account::default()
+        },
+    )
+}
+
+/// Snapshots list the sale's buyers only, in buyer order, as CSV and JSON
+#[test]
+fn test_snapshot_export() {
+    let program_id = merkle_whitelist_token_sale::ID;
+    let sale = SaleAddresses::new(program_id, Pubkey::new_unique(), Pubkey::new_unique());
+    let other_sale = SaleAddresses::new(program_id, Pubkey::new_unique(), Pubkey::new_unique());
+    let (first, second) = {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        (a.min(b), a.max(b))
+    };
+    let referrer = Pubkey::new_unique();
+
+    let (_, mut foreign) = buyer_facts_account(&sale, Pubkey::new_unique(), Pubkey::default());
+    foreign.owner = Pubkey::new_unique();
+    let source = LocalAccounts(vec![
+        buyer_facts_account(&sale, second, referrer),
+        buyer_facts_account(&other_sale, Pubkey::new_unique(), Pubkey::default()),
+        (Pubkey::new_unique(), foreign),
+        buyer_facts_account(&sale, first, Pubkey::default()),
+    ]);
+
+    let rows = snapshot(&source, &program_id, &sale.token_base).unwrap();
+    assert_eq!(
+        rows.iter().map(|row| row.buyer).collect::<Vec<_>>(),
+        vec![first, second]
+    );
+    assert_eq!(rows[0].buyer_facts, sale.buyer_facts(&first));
+    assert_eq!(rows[1].referrer, Some(referrer));
+
+    let csv = to_csv(&rows);
+    let lines = csv.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("buyer,buyer_facts,purchase_limit"));
+    assert_eq!(
+        lines[1],
+        format!("{first},{},60,40,0,0,", sale.buyer_facts(&first))
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&to_json(&rows)).unwrap();
+    assert_eq!(json[1]["buyer"], second.to_string());
+    assert_eq!(json[1]["referrer"], referrer.to_string());
+    assert_eq!(json[0]["referrer"], serde_json::Value::Null);
+    assert_eq!(json[0]["total_purchased"], 40);
+}
+
+/// Current layouts decode as is, older ones through Migrate's conversion,
+/// anything else is reported
+#[test]
+fn test_decode_layout_versions() {
+    let address = Pubkey::new_unique();
+
+    let mut token_base = TokenBase::zeroed();
+    token_base.discriminator = TokenBase::SPL_DISCRIMINATOR.into();
+    token_base.version = TokenBase::VERSION;
+    token_base.price = 1000;
+    let decoded = decode::token_base(&address, bytemuck::bytes_of(&token_base)).unwrap();
+    assert_eq!(decoded.version, TokenBase::VERSION);
+    assert_eq!(decoded.account.price, 1000);
+
+    // a newer layout of the same size needs a newer CLI
+    token_base.version = TokenBase::VERSION + 1;
+    let error = decode::token_base(&address, bytemuck::bytes_of(&token_base)).unwrap_err();
+    assert!(error.to_string().contains("unsupported layout version"));
+
+    // so does an unknown size
+    token_base.version = TokenBase::VERSION;
+    let mut data = bytemuck::bytes_of(&token_base).to_vec();
+    data.push(0);
+    let error = decode::token_base(&address, &data).unwrap_err();
+    assert!(error.to_string().contains("unsupported layout"));
+
+    let mut closed = ClosedTokenBase::zeroed();
+    closed.discriminator = ClosedTokenBase::SPL_DISCRIMINATOR.into();
+    let error = decode::token_base(&address, bytemuck::bytes_of(&closed)).unwrap_err();
+    assert!(error.to_string().contains("closed sale"));
+
+    let v2 = BuyerFactsV2 {
+        discriminator: BuyerFacts::SPL_DISCRIMINATOR.into(),
+        purchase_limit: 60,
+        bump: 255,
+        version: 2,
+        _padding: [0; 6],
+        receipt_count: 0,
+        referral_rewards: 0,
+        referrer: Pubkey::default(),
+        registered_slot: 0,
+        last_purchase_slot: 0,
+        total_purchased: 40,
+        _reserved: [0; 56],
+    };
+    let decoded = decode::buyer_facts(&address, &borsh::to_vec(&v2).unwrap()).unwrap();
+    assert_eq!(decoded.version, 2);
+    assert_eq!(decoded.account.version, BuyerFacts::VERSION);
+    assert_eq!(decoded.account.total_purchased, 40);
+
+    let error = decode::buyer_facts(&address, bytemuck::bytes_of(&token_base)).unwrap_err();
+    assert!(error.to_string().contains("is not a BuyerFacts"));
+}
@@ -0,0 +1,53 @@
+/// Whitelist files
+///
+/// One buyer address per line, blank lines and lines starting with `#`
+/// are skipped. The Merkle tree is built in file order, so the same file
+/// always yields the same root.
+use merkle_whitelist_token_sale::merkle::{
+    convert_merkle_proof, pubkey_to_sha256_leaf, WhitelistProof, WhitelistRoot,
+};
+use merkletreers::tree::MerkleTree;
+use solana_sdk::pubkey::Pubkey;
+use std::{path::Path, str::FromStr};
+
+pub struct Whitelist {
+    buyers: Vec<Pubkey>,
+    tree: MerkleTree,
+}
+
+impl Whitelist {
+    pub fn new(buyers: Vec<Pubkey>) -> Self {
+        let tree = MerkleTree::new(buyers.iter().map(pubkey_to_sha256_leaf).collect());
+        Self { buyers, tree }
+    }
+
+    pub fn parse(contents: &str) -> Result<Self, String> {
+        contents
+            .lines()
+            .map(str::trim)
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .map(|(index, line)| {
+                Pubkey::from_str(line).map_err(|_| format!("line {}: invalid address", index + 1))
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self::new)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|error| format!("{}: {error}", path.display()))?;
+        Self::parse(&contents).map_err(|error| format!("{}: {error}", path.display()))
+    }
+
+    pub fn root(&self) -> WhitelistRoot {
+        WhitelistRoot(self.tree.root)
+    }
+
+    /// Proof of `buyer`, None if it isn't whitelisted
+    pub fn proof(&self, buyer: &Pubkey) -> Option<WhitelistProof> {
+        self.buyers
+            .contains(buyer)
+            .then(|| convert_merkle_proof(self.tree.make_proof(pubkey_to_sha256_leaf(buyer))))
+    }
+}
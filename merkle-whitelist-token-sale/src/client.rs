@@ -0,0 +1,149 @@
+/// Offchain helpers for reading program accounts
+///
+/// RPC agnostic, callers fetch accounts with their own client and pass
+/// the raw data in
+use crate::pda::{ReceiptPDA, RegistryPagePDA};
+use crate::state::{BuyerFacts, Receipt, Registry, RegistryPage};
+use borsh::BorshDeserialize;
+use solana_program::pubkey::Pubkey;
+use spl_discriminator::SplDiscriminate;
+
+/// Addresses of every Receipt issued to `buyer` in a sale
+///
+/// `receipt_count` is the buyer's BuyerFacts `receipt_count`, closed
+/// receipts keep their address so the caller should expect missing accounts.
+/// Fetch them with `getMultipleAccounts`
+pub fn receipt_addresses(
+    program_id: &Pubkey,
+    token_base: &Pubkey,
+    buyer: &Pubkey,
+    receipt_count: u64,
+) -> Vec<Pubkey> {
+    (0..receipt_count)
+        .map(|nonce| ReceiptPDA::find_pda(program_id, token_base, buyer, nonce).0)
+        .collect()
+}
+
+/// `getProgramAccounts` memcmp filter `(offset, bytes)` matching
+/// the receipts of `buyer` across every sale
+pub fn receipt_buyer_filter(buyer: &Pubkey) -> (usize, [u8; 32]) {
+    (Receipt::BUYER_OFFSET, buyer.to_bytes())
+}
+
+/// Receipts of `buyer` among fetched `(address, data)` accounts,
+/// ordered by sale then nonce
+///
+/// Skips accounts that are not initialized receipts of `buyer`
+pub fn list_receipts<'a>(
+    buyer: &Pubkey,
+    accounts: impl IntoIterator<Item = (Pubkey, &'a [u8])>,
+) -> Vec<(Pubkey, Receipt)> {
+    let mut receipts = accounts
+        .into_iter()
+        .filter(|(_, data)| data.len() == Receipt::LEN)
+        .filter_map(|(address, data)| Some((address, Receipt::try_from_slice(data).ok()?)))
+        .filter(|(_, receipt)| receipt.is_initialized() && receipt.buyer == *buyer)
+        .collect::<Vec<_>>();
+    receipts.sort_by_key(|(_, receipt)| (receipt.token_base, receipt.nonce));
+    receipts
+}
+
+/// `getProgramAccounts` memcmp filters `(offset, bytes)` matching
+/// the BuyerFacts of every buyer of `token_base`
+///
+/// Use them with a `dataSize` filter of `BuyerFacts::LEN`. BuyerFacts
+/// in older layouts only match once Migrate recorded their sale
+pub fn buyer_facts_filters(token_base: &Pubkey) -> [(usize, Vec<u8>); 2] {
+    [
+        (0, BuyerFacts::SPL_DISCRIMINATOR_SLICE.to_vec()),
+        (
+            BuyerFacts::TOKEN_BASE_OFFSET,
+            token_base.to_bytes().to_vec(),
+        ),
+    ]
+}
+
+/// BuyerFacts of `token_base` among fetched `(address, data)` accounts,
+/// ordered by buyer
+///
+/// Skips accounts that are not initialized BuyerFacts of `token_base`
+pub fn list_buyer_facts<'a>(
+    token_base: &Pubkey,
+    accounts: impl IntoIterator<Item = (Pubkey, &'a [u8])>,
+) -> Vec<(Pubkey, BuyerFacts)> {
+    let mut buyer_facts = accounts
+        .into_iter()
+        .filter(|(_, data)| data.len() == BuyerFacts::LEN)
+        .filter_map(|(address, data)| Some((address, BuyerFacts::try_from_slice(data).ok()?)))
+        .filter(|(_, buyer_facts)| {
+            buyer_facts.is_initialized() && buyer_facts.token_base == *token_base
+        })
+        .collect::<Vec<_>>();
+    buyer_facts.sort_by_key(|(_, buyer_facts)| buyer_facts.buyer);
+    buyer_facts
+}
+
+/// Addresses of every page of `registry`
+///
+/// `page_count` is the Registry `page_count`. Fetch them with
+/// `getMultipleAccounts`
+pub fn registry_page_addresses(
+    program_id: &Pubkey,
+    registry: &Pubkey,
+    page_count: u64,
+) -> Vec<Pubkey> {
+    (0..page_count)
+        .map(|page| RegistryPagePDA::find_pda(program_id, registry, page).0)
+        .collect()
+}
+
+/// Registry decoded from fetched account `data`, None if the registry
+/// was not created yet
+pub fn decode_registry(data: &[u8]) -> Option<Registry> {
+    Registry::try_from_slice(data)
+        .ok()
+        .filter(Registry::is_initialized)
+}
+
+/// Page OpenSale lists the next sale on, given the fetched registry
+/// `data` or None if the registry does not exist yet
+pub fn registry_append_page(data: Option<&[u8]>) -> u64 {
+    data.and_then(decode_registry)
+        .map_or(0, |registry| registry.append_page())
+}
+
+/// Sales listed by fetched page accounts, ordered by page then position
+///
+/// Skips accounts that are not initialized pages
+pub fn list_registry_sales<'a>(pages: impl IntoIterator<Item = &'a [u8]>) -> Vec<Pubkey> {
+    let mut pages = pages
+        .into_iter()
+        .filter_map(decode_page)
+        .collect::<Vec<_>>();
+    pages.sort_by_key(|(header, _)| header.page);
+    pages
+        .into_iter()
+        .flat_map(|(_, entries)| entries.iter().copied())
+        .collect()
+}
+
+/// Page among fetched page accounts listing `token_base`, passed to
+/// CloseSale
+pub fn find_registry_page<'a>(
+    token_base: &Pubkey,
+    pages: impl IntoIterator<Item = &'a [u8]>,
+) -> Option<u64> {
+    pages
+        .into_iter()
+        .filter_map(decode_page)
+        .find(|(_, entries)| entries.contains(token_base))
+        .map(|(header, _)| header.page)
+}
+
+/// Header and entries of an initialized page account
+fn decode_page(data: &[u8]) -> Option<(RegistryPage, &[Pubkey])> {
+    let header = RegistryPage::try_from_slice(data.get(..RegistryPage::LEN)?)
+        .ok()
+        .filter(RegistryPage::is_initialized)?;
+    (data.len() == RegistryPage::space(header.len)).then(|| (header, RegistryPage::entries(data)))
+}
@@ -0,0 +1,506 @@
+/// Cross-program invocation helpers
+///
+/// One typed helper per [`TokenSaleInstruction`], taking the accounts in a
+/// struct named after the instruction and the instruction data as arguments.
+/// `signers_seeds` sign for PDA accounts of the calling program, e.g. a PDA
+/// buyer, and may be empty.
+///
+/// Skipped optional accounts are replaced by `program`, the token sale
+/// program account, which must be passed to the caller as well. The token
+/// sale only reads an optional account as skipped when its key is
+/// [`crate::ID`], so the helpers only invoke the program deployed there.
+///
+/// PDA buyers pay for their tokens through the System Program, so they must
+/// hold no data and be owned by the System Program.
+use crate::instruction::{SaleSettings, TokenSaleInstruction};
+use crate::merkle::{WhitelistProof, WhitelistRoot};
+use crate::processor::quote::Quote;
+use crate::require;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::{get_return_data, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// Account passed to the token sale, with its privileges
+struct CpiAccount<'a, 'info> {
+    info: &'a AccountInfo<'info>,
+    is_writable: bool,
+    is_signer: bool,
+}
+
+impl<'a, 'info> CpiAccount<'a, 'info> {
+    fn readonly(info: &'a AccountInfo<'info>) -> Self {
+        Self {
+            info,
+            is_writable: false,
+            is_signer: false,
+        }
+    }
+
+    fn writable(info: &'a AccountInfo<'info>) -> Self {
+        Self {
+            info,
+            is_writable: true,
+            is_signer: false,
+        }
+    }
+
+    fn signer(info: &'a AccountInfo<'info>) -> Self {
+        Self {
+            info,
+            is_writable: false,
+            is_signer: true,
+        }
+    }
+
+    fn writable_signer(info: &'a AccountInfo<'info>) -> Self {
+        Self {
+            info,
+            is_writable: true,
+            is_signer: true,
+        }
+    }
+}
+
+/// Invoke the token sale `program` with `instruction`
+///
+/// - program is deployed at crate::ID
+fn invoke_token_sale<'info>(
+    program: &AccountInfo<'info>,
+    instruction: &TokenSaleInstruction,
+    accounts: &[CpiAccount<'_, 'info>],
+    signers_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    // - skipped optional accounts are only read as skipped at crate::ID
+    require!(
+        *program.key == crate::ID,
+        ProgramError::IncorrectProgramId,
+        "program"
+    );
+
+    let instruction = Instruction {
+        program_id: *program.key,
+        accounts: accounts
+            .iter()
+            .map(|account| AccountMeta {
+                pubkey: *account.info.key,
+                is_signer: account.is_signer,
+                is_writable: account.is_writable,
+            })
+            .collect(),
+        data: borsh::to_vec(instruction)?,
+    };
+
+    let account_infos = accounts
+        .iter()
+        .map(|account| account.info.clone())
+        .chain(std::iter::once(program.clone()))
+        .collect::<Vec<_>>();
+
+    invoke_signed(&instruction, &account_infos, signers_seeds)
+}
+
+/// Accounts struct and invoke helper of an instruction
+///
+/// Accounts are listed in instruction order with their privileges,
+/// optional accounts last
+macro_rules! cpi {
+    (
+        $(#[$doc:meta])*
+        $name:ident, $accounts:ident, $variant:ident { $($arg:ident: $ty:ty),* $(,)? },
+        [$($account:ident: $privilege:ident),* $(,)?]
+        $(, optional [$($optional:ident: $optional_privilege:ident),* $(,)?])?
+    ) => {
+        #[doc = concat!("Accounts of [`TokenSaleInstruction::", stringify!($variant), "`]")]
+        pub struct $accounts<'a, 'info> {
+            $(pub $account: &'a AccountInfo<'info>,)*
+            $($(pub $optional: Option<&'a AccountInfo<'info>>,)*)?
+        }
+
+        $(#[$doc])*
+        #[allow(clippy::too_many_arguments)] // one argument per instruction field
+        pub fn $name<'a, 'info>(
+            program: &'a AccountInfo<'info>,
+            accounts: $accounts<'a, 'info>,
+            $($arg: $ty,)*
+            signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            invoke_token_sale(
+                program,
+                &TokenSaleInstruction::$variant { $($arg),* },
+                &[
+                    $(CpiAccount::$privilege(accounts.$account),)*
+                    $($(match accounts.$optional {
+                        Some(info) => CpiAccount::$optional_privilege(info),
+                        None => CpiAccount::readonly(program),
+                    },)*)?
+                ],
+                signers_seeds,
+            )
+        }
+    };
+}
+
+cpi!(
+    /// Invoke OpenSale, the sale authority pays the TokenBase and registry rent
+    /// and hands the mint authority to the TokenBase PDA
+    open_sale,
+    OpenSaleCpiAccounts,
+    OpenSale {
+        price: u64,
+        purchase_limit: u64,
+        whitelist_root: WhitelistRoot,
+    },
+    [
+        token_base: writable,
+        mint: writable,
+        vault: readonly,
+        sale_authority: writable_signer,
+        system_program: readonly,
+        registry: writable,
+        registry_page: writable,
+        authority_registry: writable,
+        authority_registry_page: writable,
+        token_program: readonly,
+    ]
+);
+
+cpi!(
+    /// Invoke ToggleRunning
+    toggle_running,
+    ToggleRunningCpiAccounts,
+    ToggleRunning {},
+    [
+        token_base: writable,
+        mint: readonly,
+        sale_authority: signer,
+    ]
+);
+
+cpi!(
+    /// Invoke ConfigureSale
+    configure_sale,
+    ConfigureSaleCpiAccounts,
+    ConfigureSale {
+        price: Option<u64>,
+        default_purchase_limit: Option<u64>,
+        whitelist_root: Option<WhitelistRoot>,
+    },
+    [
+        token_base: writable,
+        mint: readonly,
+        sale_authority: signer,
+    ]
+);
+
+cpi!(
+    /// Invoke CloseSale, registered sales pass their registry pages
+    close_sale,
+    CloseSaleCpiAccounts,
+    CloseSale {},
+    [
+        token_base: writable,
+        mint: readonly,
+        sale_authority: writable_signer,
+    ],
+    optional [
+        registry: writable,
+        registry_page: writable,
+        authority_registry: writable,
+        authority_registry_page: writable,
+        escrow: writable,
+    ]
+);
+
+cpi!(
+    /// Invoke AssignLimit
+    assign_limit,
+    AssignLimitCpiAccounts,
+    AssignLimit {
+        new_purchase_limit: u64,
+    },
+    [
+        token_base: readonly,
+        buyer_facts: writable,
+        buyer: readonly,
+        sale_authority: signer,
+    ]
+);
+
+cpi!(
+    /// Invoke RegisterBuyer, the buyer may be a PDA signer
+    register_buyer,
+    RegisterBuyerCpiAccounts,
+    RegisterBuyer {},
+    [
+        token_base: readonly,
+        buyer_facts: writable,
+        buyer: signer,
+        payer: writable_signer,
+        system_program: readonly,
+    ],
+    optional [
+        holder_token_account: readonly,
+        holder_metadata: readonly,
+        holder_mint: readonly,
+    ]
+);
+
+cpi!(
+    /// Invoke DeregisterBuyer
+    deregister_buyer,
+    DeregisterBuyerCpiAccounts,
+    DeregisterBuyer {},
+    [
+        token_base: readonly,
+        buyer_facts: writable,
+        buyer: signer,
+        payer: writable,
+    ]
+);
+
+cpi!(
+    /// Invoke BuyToken, the buyer may be a PDA signer
+    buy_token,
+    BuyTokenCpiAccounts,
+    BuyToken {
+        amount: u64,
+        proof: WhitelistProof,
+    },
+    [
+        token_base: readonly,
+        mint: writable,
+        vault: writable,
+        sale_authority: readonly,
+        buyer_token_account: writable,
+        buyer_facts: writable,
+        buyer: writable_signer,
+        payer: writable_signer,
+        system_program: readonly,
+        token_program: readonly,
+        associated_token_program: readonly,
+    ],
+    optional [
+        receipt: writable,
+        referrer_facts: writable,
+        referrer: readonly,
+        instructions_sysvar: readonly,
+        holder_token_account: readonly,
+        holder_metadata: readonly,
+        holder_mint: readonly,
+    ]
+);
+
+cpi!(
+    /// Invoke ReclaimBuyerFacts
+    reclaim_buyer_facts,
+    ReclaimBuyerFactsCpiAccounts,
+    ReclaimBuyerFacts {},
+    [
+        token_base: readonly,
+        buyer_facts: writable,
+        buyer: writable,
+        payer: writable,
+    ]
+);
+
+cpi!(
+    /// Invoke Migrate, BuyerFacts pass their sale and buyer
+    migrate,
+    MigrateCpiAccounts,
+    Migrate {},
+    [
+        account: writable,
+        payer: writable_signer,
+        system_program: readonly,
+    ],
+    optional [
+        token_base: readonly,
+        buyer: readonly,
+    ]
+);
+
+cpi!(
+    /// Invoke CloseReceipt
+    close_receipt,
+    CloseReceiptCpiAccounts,
+    CloseReceipt {},
+    [
+        receipt: writable,
+        buyer: signer,
+        payer: writable,
+    ]
+);
+
+cpi!(
+    /// Invoke ClaimReferral
+    claim_referral,
+    ClaimReferralCpiAccounts,
+    ClaimReferral {},
+    [
+        token_base: readonly,
+        buyer_facts: writable,
+        referrer: writable_signer,
+    ]
+);
+
+cpi!(
+    /// Invoke Quote, see [`quote`] to also read the result
+    invoke_quote,
+    QuoteCpiAccounts,
+    Quote {
+        amount: u64,
+        proof: WhitelistProof,
+    },
+    [
+        token_base: readonly,
+        mint: readonly,
+        buyer_facts: readonly,
+        buyer: readonly,
+    ],
+    optional [
+        instructions_sysvar: readonly,
+        holder_token_account: readonly,
+        holder_metadata: readonly,
+        escrow: readonly,
+        holder_mint: readonly,
+    ]
+);
+
+cpi!(
+    /// Invoke RegisterAndBuy, the buyer may be a PDA signer
+    register_and_buy,
+    RegisterAndBuyCpiAccounts,
+    RegisterAndBuy {
+        amount: u64,
+        proof: WhitelistProof,
+    },
+    [
+        token_base: readonly,
+        mint: writable,
+        vault: writable,
+        sale_authority: readonly,
+        buyer_token_account: writable,
+        buyer_facts: writable,
+        buyer: writable_signer,
+        payer: writable_signer,
+        system_program: readonly,
+        token_program: readonly,
+        associated_token_program: readonly,
+    ],
+    optional [
+        receipt: writable,
+        referrer_facts: writable,
+        referrer: readonly,
+        instructions_sysvar: readonly,
+        holder_token_account: readonly,
+        holder_metadata: readonly,
+        holder_mint: readonly,
+    ]
+);
+
+cpi!(
+    /// Invoke InitializeEscrow
+    initialize_escrow,
+    InitializeEscrowCpiAccounts,
+    InitializeEscrow {
+        team: Pubkey,
+        approver: Option<Pubkey>,
+        milestone_bps: Vec<u16>,
+        vote_threshold_bps: u16,
+    },
+    [
+        token_base: writable,
+        mint: readonly,
+        sale_authority: writable_signer,
+        escrow: writable,
+        system_program: readonly,
+    ]
+);
+
+cpi!(
+    /// Invoke VoteMilestone, the buyer may be a PDA signer
+    vote_milestone,
+    VoteMilestoneCpiAccounts,
+    VoteMilestone {},
+    [
+        token_base: readonly,
+        escrow: writable,
+        buyer_facts: writable,
+        buyer: signer,
+    ]
+);
+
+cpi!(
+    /// Invoke ReleaseMilestone, the approver may be a PDA signer
+    release_milestone,
+    ReleaseMilestoneCpiAccounts,
+    ReleaseMilestone {},
+    [
+        token_base: writable,
+        escrow: writable,
+        team: writable,
+        authority: signer,
+    ]
+);
+
+cpi!(
+    /// Invoke CancelEscrow
+    cancel_escrow,
+    CancelEscrowCpiAccounts,
+    CancelEscrow {},
+    [
+        token_base: writable,
+        escrow: writable,
+        authority: signer,
+    ]
+);
+
+cpi!(
+    /// Invoke RefundEscrow
+    refund_escrow,
+    RefundEscrowCpiAccounts,
+    RefundEscrow {},
+    [
+        token_base: writable,
+        escrow: writable,
+        buyer_facts: writable,
+        buyer: writable,
+    ]
+);
+
+cpi!(
+    /// Invoke UpdateSaleSettings
+    update_sale_settings,
+    UpdateSaleSettingsCpiAccounts,
+    UpdateSaleSettings {
+        settings: SaleSettings,
+    },
+    [
+        token_base: writable,
+        mint: readonly,
+        sale_authority: signer,
+    ]
+);
+
+/// Invoke Quote and read the [`Quote`] it returned
+pub fn quote<'a, 'info>(
+    program: &'a AccountInfo<'info>,
+    accounts: QuoteCpiAccounts<'a, 'info>,
+    amount: u64,
+    proof: WhitelistProof,
+) -> Result<Quote, ProgramError> {
+    invoke_quote(program, accounts, amount, proof, &[])?;
+
+    let (program_id, data) = get_return_data().ok_or(ProgramError::InvalidInstructionData)?;
+    require!(
+        program_id == *program.key,
+        ProgramError::IncorrectProgramId,
+        "program"
+    );
+    Quote::from_return_data(&data).ok_or(ProgramError::InvalidInstructionData)
+}
@@ -1,4 +1,5 @@
 use num_derive::FromPrimitive;
+use num_traits::FromPrimitive as _;
 use solana_program::{
     decode_error::DecodeError,
     msg,
@@ -6,7 +7,7 @@ use solana_program::{
 };
 use thiserror::Error;
 
-#[derive(Debug, Error, FromPrimitive)]
+#[derive(Clone, Copy, Debug, Error, FromPrimitive, PartialEq, Eq)]
 pub enum TokenSaleError {
     #[error("Invalid account data length")]
     InvalidAccountDataLength, // 0
@@ -32,7 +33,7 @@ pub enum TokenSaleError {
     #[error("Invalid SPL Token Program")]
     InvalidTokenProgramID, // 7
 
-    #[error("Mint and Sale authority don't match")]
+    #[error("Account doesn't match TokenBase")]
     AccountsAndTokenBaseMismatch, // 8
 
     #[error("Not whitelisted")]
@@ -40,6 +41,269 @@ pub enum TokenSaleError {
 
     #[error("Incompatible Proof Format")]
     IncompatibleProof, // 10
+
+    #[error("Token sale is not running")]
+    SaleNotRunning, // 11
+
+    #[error("Purchase limit exceeded")]
+    LimitExceeded, // 12
+
+    #[error("Token sale must be stopped first")]
+    SaleStillRunning, // 13
+
+    #[error("Token sale is still open")]
+    SaleNotClosed, // 14
+
+    #[error("Token sale has ended")]
+    SaleEnded, // 15
+
+    #[error("Mint has no mint authority")]
+    MintAuthorityMissing, // 16
+
+    #[error("Mint doesn't match TokenBase")]
+    MintMismatch, // 17
+
+    #[error("Vault doesn't match TokenBase")]
+    VaultMismatch, // 18
+
+    #[error("Sale authority doesn't match TokenBase")]
+    SaleAuthorityMismatch, // 19
+
+    #[error("TokenBase seeds don't match")]
+    InvalidTokenBaseSeeds, // 20
+
+    #[error("BuyerFacts seeds don't match")]
+    InvalidBuyerFactsSeeds, // 21
+
+    #[error("Not the buyer's associated token account")]
+    InvalidBuyerTokenAccount, // 22
+
+    #[error("Token sale is already open")]
+    SaleAlreadyOpened, // 23
+
+    #[error("Buyer is already registered")]
+    BuyerAlreadyRegistered, // 24
+
+    #[error("Nothing to configure")]
+    NothingToConfigure, // 25
+
+    #[error("Mint is not owned by the SPL Token Program")]
+    InvalidMint, // 26
+
+    #[error("Math overflow")]
+    MathOverflow, // 27
+
+    #[error("Receipt seeds don't match")]
+    InvalidReceiptSeeds, // 28
+
+    #[error("Buyer can't refer itself")]
+    SelfReferral, // 29
+
+    #[error("Referrer was referred by the buyer, longer referral cycles are allowed")]
+    CircularReferral, // 30
+
+    #[error("Buyer is already attributed to another referrer")]
+    ReferrerMismatch, // 31
+
+    #[error("No referral rewards to claim")]
+    NoReferralRewards, // 32
+
+    #[error("Basis points exceed 10000")]
+    InvalidBasisPoints, // 33
+
+    #[error("Buyer registered too recently to purchase")]
+    RegistrationTooRecent, // 34
+
+    #[error("Buyer purchased too recently")]
+    PurchaseCooldown, // 35
+
+    #[error("Missing or invalid whitelist voucher")]
+    InvalidVoucher, // 36
+
+    #[error("Whitelist voucher expired")]
+    VoucherExpired, // 37
+
+    #[error("Purchase exceeds voucher allocation")]
+    AllocationExceeded, // 38
+
+    #[error("Voucher whitelist needs a whitelist signer")]
+    WhitelistSignerMissing, // 39
+
+    #[error("Holder gating needs a gate key")]
+    GateKeyMissing, // 40
+
+    #[error("Buyer does not hold the gating token")]
+    NotHolder, // 41
+
+    #[error("Held NFT is not in the verified gating collection")]
+    NotInCollection, // 42
+
+    #[error("Root activation slot is in the past or has no whitelist root")]
+    InvalidRootActivation, // 43
+
+    #[error("Registry account has unexpected seeds or is missing")]
+    InvalidRegistry, // 44
+
+    #[error("Registry page is not the expected page or does not list the sale")]
+    InvalidRegistryPage, // 45
+
+    #[error("Escrow account has unexpected seeds or belongs to another sale")]
+    InvalidEscrowSeeds, // 46
+
+    #[error("Milestone shares must sum to 10000 bps over 1 to 4 milestones, with an approver or a vote threshold")]
+    InvalidMilestones, // 47
+
+    #[error("Escrow was cancelled")]
+    EscrowCancelled, // 48
+
+    #[error("Escrow was not cancelled")]
+    EscrowNotCancelled, // 49
+
+    #[error("Escrow released every milestone")]
+    EscrowSettled, // 50
+
+    #[error("Escrow still holds unreleased funds")]
+    EscrowNotSettled, // 51
+
+    #[error("Milestone is not approved by the approver nor by the buyers' vote")]
+    MilestoneNotApproved, // 52
+
+    #[error("Buyer already voted on this milestone")]
+    AlreadyVoted, // 53
+
+    #[error("Buyer has no escrowed deposit")]
+    NoEscrowDeposit, // 54
+
+    #[error("Team is not the escrow's team")]
+    TeamMismatch, // 55
+
+    #[error("Referral rewards must be claimed first")]
+    UnclaimedReferralRewards, // 56
+
+    #[error("Payer is not the account that paid the rent")]
+    PayerMismatch, // 57
+
+    #[error("Buyer has purchased, its BuyerFacts is kept until the sale closes")]
+    BuyerHasPurchases, // 58
+
+    #[error("Sale has not reached its end slot")]
+    SaleNotEnded, // 59
+
+    #[error("End slot must be in the future and can only be extended")]
+    InvalidEndSlot, // 60
+
+    #[error("Held token is not an NFT, its mint must have a supply of 1 and no decimals")]
+    HolderNotNft, // 61
+}
+
+impl TokenSaleError {
+    /// Every error, ordered by code
+    pub const ALL: [TokenSaleError; 62] = [
+        Self::InvalidAccountDataLength,
+        Self::MintAndSaleAuthorityMismatch,
+        Self::MustBeNonExecutable,
+        Self::NeedSigner,
+        Self::UnexpectedPDASeeds,
+        Self::AccountUninitialized,
+        Self::FailedToDecodeSha256Hash,
+        Self::InvalidTokenProgramID,
+        Self::AccountsAndTokenBaseMismatch,
+        Self::NotWhitelisted,
+        Self::IncompatibleProof,
+        Self::SaleNotRunning,
+        Self::LimitExceeded,
+        Self::SaleStillRunning,
+        Self::SaleNotClosed,
+        Self::SaleEnded,
+        Self::MintAuthorityMissing,
+        Self::MintMismatch,
+        Self::VaultMismatch,
+        Self::SaleAuthorityMismatch,
+        Self::InvalidTokenBaseSeeds,
+        Self::InvalidBuyerFactsSeeds,
+        Self::InvalidBuyerTokenAccount,
+        Self::SaleAlreadyOpened,
+        Self::BuyerAlreadyRegistered,
+        Self::NothingToConfigure,
+        Self::InvalidMint,
+        Self::MathOverflow,
+        Self::InvalidReceiptSeeds,
+        Self::SelfReferral,
+        Self::CircularReferral,
+        Self::ReferrerMismatch,
+        Self::NoReferralRewards,
+        Self::InvalidBasisPoints,
+        Self::RegistrationTooRecent,
+        Self::PurchaseCooldown,
+        Self::InvalidVoucher,
+        Self::VoucherExpired,
+        Self::AllocationExceeded,
+        Self::WhitelistSignerMissing,
+        Self::GateKeyMissing,
+        Self::NotHolder,
+        Self::NotInCollection,
+        Self::InvalidRootActivation,
+        Self::InvalidRegistry,
+        Self::InvalidRegistryPage,
+        Self::InvalidEscrowSeeds,
+        Self::InvalidMilestones,
+        Self::EscrowCancelled,
+        Self::EscrowNotCancelled,
+        Self::EscrowSettled,
+        Self::EscrowNotSettled,
+        Self::MilestoneNotApproved,
+        Self::AlreadyVoted,
+        Self::NoEscrowDeposit,
+        Self::TeamMismatch,
+        Self::UnclaimedReferralRewards,
+        Self::PayerMismatch,
+        Self::BuyerHasPurchases,
+        Self::SaleNotEnded,
+        Self::InvalidEndSlot,
+        Self::HolderNotNft,
+    ];
+
+    /// Code carried by `ProgramError::Custom`
+    pub fn code(self) -> u32 {
+        self as u32
+    }
+
+    /// Variant name, e.g. `"SaleNotRunning"`
+    pub fn name(&self) -> String {
+        format!("{self:?}")
+    }
+
+    pub fn from_code(code: u32) -> Option<Self> {
+        Self::from_u32(code)
+    }
+
+    /// Map a failed instruction's error back to a TokenSaleError
+    pub fn from_program_error(error: &ProgramError) -> Option<Self> {
+        match error {
+            ProgramError::Custom(code) => Self::from_code(*code),
+            _ => None,
+        }
+    }
+}
+
+/// Entry of the published error-code table
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorCode {
+    pub code: u32,
+    pub name: String,
+    pub message: String,
+}
+
+/// Error-code table for clients mapping `Custom(code)` errors
+pub fn error_codes() -> Vec<ErrorCode> {
+    TokenSaleError::ALL
+        .iter()
+        .map(|e| ErrorCode {
+            code: e.code(),
+            name: e.name(),
+            message: e.to_string(),
+        })
+        .collect()
 }
 
 // allow .into() for Custom Error to ProgramError conversion
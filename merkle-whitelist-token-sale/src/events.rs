@@ -0,0 +1,406 @@
+/// Onchain event utils
+///
+/// Every state-changing instruction emits exactly one event through
+/// `sol_log_data`. The logged payload is the 8 byte SPL discriminator
+/// of the event followed by its borsh encoding, so indexers can
+/// reconstruct sale activity from transaction logs alone.
+use base64::{engine::general_purpose::STANDARD, Engine};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{log::sol_log_data, program_error::ProgramError, pubkey::Pubkey};
+use spl_discriminator::SplDiscriminate;
+
+/// Prefix the runtime puts in front of `sol_log_data` logs
+pub const PROGRAM_DATA_LOG_PREFIX: &str = "Program data: ";
+
+/// Prefix of the runtime's `Program <id> invoke [n]` and
+/// `Program <id> success` logs framing each program invocation
+const PROGRAM_LOG_PREFIX: &str = "Program ";
+
+/// Borsh encoded, discriminator prefixed event
+pub trait Event: BorshSerialize + SplDiscriminate {
+    /// Encode as `discriminator || borsh(event)`
+    fn to_bytes(&self) -> Result<Vec<u8>, ProgramError> {
+        let mut data = Self::SPL_DISCRIMINATOR_SLICE.to_vec();
+        self.serialize(&mut data)?;
+        Ok(data)
+    }
+
+    /// Log the encoded event via `sol_log_data`
+    fn emit(&self) -> Result<(), ProgramError> {
+        sol_log_data(&[&self.to_bytes()?]);
+        Ok(())
+    }
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::sale_opened")]
+/// Emitted by OpenSale
+pub struct SaleOpened {
+    pub token_base: Pubkey,
+    pub sale_authority: Pubkey,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub whitelist_root: [u8; 32],
+    pub price: u64,
+    pub default_purchase_limit: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::sale_configured")]
+/// Emitted by ConfigureSale and UpdateSaleSettings with the resulting configuration
+pub struct SaleConfigured {
+    pub token_base: Pubkey,
+    pub whitelist_root: [u8; 32],
+    pub price: u64,
+    pub default_purchase_limit: u64,
+    pub phase: u32,
+    pub referral_bps: u16,
+    pub min_registration_slots: u64,
+    pub purchase_cooldown_slots: u64,
+    /// [`WhitelistMode`](crate::state::WhitelistMode) as `u8`
+    pub whitelist_mode: u8,
+    pub whitelist_signer: Pubkey,
+    pub gate_key: Pubkey,
+    pub gate_min_amount: u64,
+    /// Slot the latest configured whitelist root activates at
+    pub root_activation_slot: u64,
+    pub root_grace_slots: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::toggled")]
+/// Emitted by ToggleRunning with the new `is_running` value
+pub struct Toggled {
+    pub token_base: Pubkey,
+    pub is_running: bool,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::buyer_registered")]
+/// Emitted by RegisterBuyer
+pub struct BuyerRegistered {
+    pub token_base: Pubkey,
+    pub buyer: Pubkey,
+    pub buyer_facts: Pubkey,
+    pub purchase_limit: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::buyer_deregistered")]
+/// Emitted by DeregisterBuyer
+pub struct BuyerDeregistered {
+    pub token_base: Pubkey,
+    pub buyer: Pubkey,
+    pub buyer_facts: Pubkey,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::limit_assigned")]
+/// Emitted by AssignLimit
+pub struct LimitAssigned {
+    pub token_base: Pubkey,
+    pub buyer: Pubkey,
+    pub buyer_facts: Pubkey,
+    pub purchase_limit: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::purchased")]
+/// Emitted by BuyToken
+pub struct Purchased {
+    pub token_base: Pubkey,
+    pub buyer: Pubkey,
+    pub buyer_token_account: Pubkey,
+    /// Amount of tokens minted to the buyer
+    pub amount: u64,
+    /// Lamports transferred from the buyer to the vault
+    pub lamports_paid: u64,
+    /// Purchase limit left for the buyer after this purchase
+    pub remaining_limit: u64,
+    /// Receipt issued for this purchase, if one was requested
+    pub receipt: Option<Pubkey>,
+    /// Referrer credited for this purchase, if one was passed
+    pub referrer: Option<Pubkey>,
+    /// Part of `lamports_paid` accrued to the referrer instead of the vault
+    pub referral_reward: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::sale_closed")]
+/// Emitted by CloseSale
+pub struct SaleClosed {
+    pub token_base: Pubkey,
+    pub sale_authority: Pubkey,
+    /// Rent lamports returned to the sale authority, with the escrow's
+    /// rent and dust of escrowed sales
+    pub reclaimed_lamports: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::buyer_facts_reclaimed")]
+/// Emitted by ReclaimBuyerFacts
+pub struct BuyerFactsReclaimed {
+    pub token_base: Pubkey,
+    pub buyer: Pubkey,
+    pub buyer_facts: Pubkey,
+    /// Rent lamports returned to the buyer
+    pub reclaimed_lamports: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::account_migrated")]
+/// Emitted by Migrate
+pub struct AccountMigrated {
+    /// TokenBase or BuyerFacts that was rewritten
+    pub account: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::receipt_closed")]
+/// Emitted by CloseReceipt
+pub struct ReceiptClosed {
+    pub token_base: Pubkey,
+    pub buyer: Pubkey,
+    pub receipt: Pubkey,
+    pub nonce: u64,
+    /// Rent lamports returned to the payer
+    pub reclaimed_lamports: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::referral_claimed")]
+/// Emitted by ClaimReferral
+pub struct ReferralClaimed {
+    pub token_base: Pubkey,
+    pub referrer: Pubkey,
+    pub buyer_facts: Pubkey,
+    /// Reward lamports paid to the referrer
+    pub claimed_lamports: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::escrow_initialized")]
+/// Emitted by InitializeEscrow
+pub struct EscrowInitialized {
+    pub token_base: Pubkey,
+    /// New vault of the sale
+    pub escrow: Pubkey,
+    pub team: Pubkey,
+    /// `Pubkey::default()` if milestones are only approved by vote
+    pub approver: Pubkey,
+    pub milestone_bps: Vec<u16>,
+    pub vote_threshold_bps: u16,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::milestone_voted")]
+/// Emitted by VoteMilestone
+pub struct MilestoneVoted {
+    pub token_base: Pubkey,
+    pub buyer: Pubkey,
+    /// Index of the approved milestone
+    pub milestone: u8,
+    /// Escrowed lamports of the buyer
+    pub weight: u64,
+    /// Escrowed lamports of every buyer approving the milestone so far
+    pub approve_weight: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::milestone_released")]
+/// Emitted by ReleaseMilestone
+pub struct MilestoneReleased {
+    pub token_base: Pubkey,
+    pub team: Pubkey,
+    /// Index of the released milestone
+    pub milestone: u8,
+    /// `true` if released by the buyers' vote rather than the approver
+    pub by_vote: bool,
+    /// Lamports paid to the team
+    pub released_lamports: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::escrow_cancelled")]
+/// Emitted by CancelEscrow
+pub struct EscrowCancelled {
+    pub token_base: Pubkey,
+    pub authority: Pubkey,
+    /// Unreleased lamports refunded to buyers pro rata
+    pub refund_pool: u64,
+}
+
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::events::escrow_refunded")]
+/// Emitted by RefundEscrow
+pub struct EscrowRefunded {
+    pub token_base: Pubkey,
+    pub buyer: Pubkey,
+    /// Lamports paid back to the buyer
+    pub refunded_lamports: u64,
+}
+
+impl Event for SaleOpened {}
+impl Event for SaleConfigured {}
+impl Event for Toggled {}
+impl Event for BuyerRegistered {}
+impl Event for BuyerDeregistered {}
+impl Event for LimitAssigned {}
+impl Event for Purchased {}
+impl Event for SaleClosed {}
+impl Event for BuyerFactsReclaimed {}
+impl Event for AccountMigrated {}
+impl Event for ReceiptClosed {}
+impl Event for ReferralClaimed {}
+impl Event for EscrowInitialized {}
+impl Event for MilestoneVoted {}
+impl Event for MilestoneReleased {}
+impl Event for EscrowCancelled {}
+impl Event for EscrowRefunded {}
+
+/// Decoded TokenSale event
+///
+/// Used offchain to parse `Program data:` logs
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenSaleEvent {
+    SaleOpened(SaleOpened),
+    SaleConfigured(SaleConfigured),
+    Toggled(Toggled),
+    BuyerRegistered(BuyerRegistered),
+    BuyerDeregistered(BuyerDeregistered),
+    LimitAssigned(LimitAssigned),
+    Purchased(Purchased),
+    SaleClosed(SaleClosed),
+    BuyerFactsReclaimed(BuyerFactsReclaimed),
+    AccountMigrated(AccountMigrated),
+    ReceiptClosed(ReceiptClosed),
+    ReferralClaimed(ReferralClaimed),
+    EscrowInitialized(EscrowInitialized),
+    MilestoneVoted(MilestoneVoted),
+    MilestoneReleased(MilestoneReleased),
+    EscrowCancelled(EscrowCancelled),
+    EscrowRefunded(EscrowRefunded),
+}
+
+impl TokenSaleEvent {
+    /// Decode `discriminator || borsh(event)` bytes
+    ///
+    /// Returns `Ok(None)` if the discriminator is not a TokenSale event
+    pub fn try_from_bytes(data: &[u8]) -> Result<Option<Self>, ProgramError> {
+        if data.len() < 8 {
+            return Ok(None);
+        }
+        let (discriminator, mut payload) = data.split_at(8);
+
+        let event = match discriminator {
+            d if d == SaleOpened::SPL_DISCRIMINATOR_SLICE => {
+                Self::SaleOpened(SaleOpened::deserialize(&mut payload)?)
+            }
+            d if d == SaleConfigured::SPL_DISCRIMINATOR_SLICE => {
+                Self::SaleConfigured(SaleConfigured::deserialize(&mut payload)?)
+            }
+            d if d == Toggled::SPL_DISCRIMINATOR_SLICE => {
+                Self::Toggled(Toggled::deserialize(&mut payload)?)
+            }
+            d if d == BuyerRegistered::SPL_DISCRIMINATOR_SLICE => {
+                Self::BuyerRegistered(BuyerRegistered::deserialize(&mut payload)?)
+            }
+            d if d == BuyerDeregistered::SPL_DISCRIMINATOR_SLICE => {
+                Self::BuyerDeregistered(BuyerDeregistered::deserialize(&mut payload)?)
+            }
+            d if d == LimitAssigned::SPL_DISCRIMINATOR_SLICE => {
+                Self::LimitAssigned(LimitAssigned::deserialize(&mut payload)?)
+            }
+            d if d == Purchased::SPL_DISCRIMINATOR_SLICE => {
+                Self::Purchased(Purchased::deserialize(&mut payload)?)
+            }
+            d if d == SaleClosed::SPL_DISCRIMINATOR_SLICE => {
+                Self::SaleClosed(SaleClosed::deserialize(&mut payload)?)
+            }
+            d if d == BuyerFactsReclaimed::SPL_DISCRIMINATOR_SLICE => {
+                Self::BuyerFactsReclaimed(BuyerFactsReclaimed::deserialize(&mut payload)?)
+            }
+            d if d == AccountMigrated::SPL_DISCRIMINATOR_SLICE => {
+                Self::AccountMigrated(AccountMigrated::deserialize(&mut payload)?)
+            }
+            d if d == ReceiptClosed::SPL_DISCRIMINATOR_SLICE => {
+                Self::ReceiptClosed(ReceiptClosed::deserialize(&mut payload)?)
+            }
+            d if d == ReferralClaimed::SPL_DISCRIMINATOR_SLICE => {
+                Self::ReferralClaimed(ReferralClaimed::deserialize(&mut payload)?)
+            }
+            d if d == EscrowInitialized::SPL_DISCRIMINATOR_SLICE => {
+                Self::EscrowInitialized(EscrowInitialized::deserialize(&mut payload)?)
+            }
+            d if d == MilestoneVoted::SPL_DISCRIMINATOR_SLICE => {
+                Self::MilestoneVoted(MilestoneVoted::deserialize(&mut payload)?)
+            }
+            d if d == MilestoneReleased::SPL_DISCRIMINATOR_SLICE => {
+                Self::MilestoneReleased(MilestoneReleased::deserialize(&mut payload)?)
+            }
+            d if d == EscrowCancelled::SPL_DISCRIMINATOR_SLICE => {
+                Self::EscrowCancelled(EscrowCancelled::deserialize(&mut payload)?)
+            }
+            d if d == EscrowRefunded::SPL_DISCRIMINATOR_SLICE => {
+                Self::EscrowRefunded(EscrowRefunded::deserialize(&mut payload)?)
+            }
+            _ => return Ok(None),
+        };
+
+        // trailing bytes mean this is not one of our events
+        if !payload.is_empty() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Ok(Some(event))
+    }
+
+    /// Decode a single `Program data: <base64>` log line
+    ///
+    /// Returns `None` for any other log line. A single line doesn't tell
+    /// which program logged it, use [`Self::from_logs`] for transactions
+    pub fn from_log(log: &str) -> Option<Self> {
+        let encoded = log.strip_prefix(PROGRAM_DATA_LOG_PREFIX)?;
+        // TokenSale events are always logged as a single field
+        if encoded.contains(' ') {
+            return None;
+        }
+        let data = STANDARD.decode(encoded).ok()?;
+        Self::try_from_bytes(&data).ok().flatten()
+    }
+
+    /// Decode every TokenSale event from a transaction's log messages
+    ///
+    /// Only `Program data:` lines logged while `program_id` is the
+    /// innermost invoked program are decoded, so other programs can't
+    /// forge events by logging matching bytes
+    pub fn from_logs<S: AsRef<str>>(program_id: &Pubkey, logs: &[S]) -> Vec<Self> {
+        let program_id = program_id.to_string();
+        let mut invoked: Vec<&str> = Vec::new();
+        let mut events = Vec::new();
+
+        for log in logs {
+            let log = log.as_ref();
+            if log.starts_with(PROGRAM_DATA_LOG_PREFIX) {
+                if invoked.last() == Some(&program_id.as_str()) {
+                    events.extend(Self::from_log(log));
+                }
+            } else if let Some(frame) = log.strip_prefix(PROGRAM_LOG_PREFIX) {
+                let mut words = frame.split(' ');
+                match (words.next(), words.next()) {
+                    (Some(program), Some("invoke")) => invoked.push(program),
+                    (Some(program), Some("success" | "failed:"))
+                        if invoked.last() == Some(&program) =>
+                    {
+                        invoked.pop();
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        events
+    }
+}
@@ -0,0 +1,165 @@
+/// Token and NFT holder gating
+///
+/// In [`WhitelistMode::TokenHolder`] sales a buyer is eligible while holding
+/// at least `gate_min_amount` of the `gate_key` mint. In
+/// [`WhitelistMode::CollectionHolder`] sales a buyer is eligible while holding
+/// an NFT, a mint with a supply of 1 and no decimals, whose Metaplex metadata
+/// has `gate_key` as its verified collection.
+use crate::error::TokenSaleError;
+use crate::require;
+use crate::state::{TokenBase, WhitelistMode};
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program_pack::Pack, pubkey,
+    pubkey::Pubkey,
+};
+use spl_token::state::{Account, Mint};
+
+/// Metaplex Token Metadata program
+pub const TOKEN_METADATA_PROGRAM_ID: Pubkey =
+    pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+/// `Key::MetadataV1` account tag of the Token Metadata program
+pub const METADATA_V1_KEY: u8 = 4;
+
+/// Verified creator of a Metaplex NFT
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq)]
+pub struct Creator {
+    pub address: Pubkey,
+    pub verified: bool,
+    pub share: u8,
+}
+
+/// Collection a Metaplex NFT belongs to
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq)]
+pub struct Collection {
+    pub verified: bool,
+    pub key: Pubkey,
+}
+
+/// Leading fields of a Metaplex `Metadata` account, up to its collection
+///
+/// Trailing fields are ignored, so later metadata versions still parse
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq)]
+pub struct MetadataPrefix {
+    pub key: u8,
+    pub update_authority: Pubkey,
+    pub mint: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub seller_fee_basis_points: u16,
+    pub creators: Option<Vec<Creator>>,
+    pub primary_sale_happened: bool,
+    pub is_mutable: bool,
+    pub edition_nonce: Option<u8>,
+    pub token_standard: Option<u8>,
+    pub collection: Option<Collection>,
+}
+
+impl MetadataPrefix {
+    /// Parse the leading fields of a `Metadata` account's data
+    pub fn parse(mut data: &[u8]) -> Option<Self> {
+        let metadata = Self::deserialize(&mut data).ok()?;
+        (metadata.key == METADATA_V1_KEY).then_some(metadata)
+    }
+
+    /// Collection key if the collection is verified
+    pub fn verified_collection(&self) -> Option<Pubkey> {
+        self.collection
+            .as_ref()
+            .filter(|collection| collection.verified)
+            .map(|collection| collection.key)
+    }
+}
+
+/// Check `holder` satisfies the sale's holder gate
+///
+/// No-op unless the sale is in a holder [`WhitelistMode`]
+///
+/// - token account is an SPL Token account owned by `holder`
+/// - TokenHolder: mint is `gate_key`, amount is at least `gate_min_amount`
+/// - CollectionHolder: amount is at least 1, the token account's mint has a
+///   supply of 1 and no decimals, metadata belongs to that mint and verifies
+///   `gate_key` as collection
+pub fn check_holder(
+    token_base: &TokenBase,
+    holder: &Pubkey,
+    holder_token_account: Option<&AccountInfo>,
+    holder_metadata: Option<&AccountInfo>,
+    holder_mint: Option<&AccountInfo>,
+) -> ProgramResult {
+    let mode = token_base.whitelist_mode()?;
+    if !matches!(
+        mode,
+        WhitelistMode::TokenHolder | WhitelistMode::CollectionHolder
+    ) {
+        return Ok(());
+    }
+
+    // - token account is an SPL Token account owned by `holder`
+    let holder_token_account = holder_token_account.ok_or(TokenSaleError::NotHolder)?;
+    require!(
+        *holder_token_account.owner == spl_token::ID,
+        TokenSaleError::NotHolder,
+        "holder_token_account"
+    );
+    let token_account = Account::unpack(&holder_token_account.try_borrow_data()?)?;
+    require!(
+        token_account.owner == *holder,
+        TokenSaleError::NotHolder,
+        "holder_token_account"
+    );
+
+    if mode == WhitelistMode::TokenHolder {
+        // - TokenHolder: mint is `gate_key`, amount is at least `gate_min_amount`
+        require!(
+            token_account.mint == token_base.gate_key
+                && token_account.amount > 0
+                && token_account.amount >= token_base.gate_min_amount,
+            TokenSaleError::NotHolder,
+            "holder_token_account"
+        );
+        return Ok(());
+    }
+
+    // - CollectionHolder: amount is at least 1
+    require!(
+        token_account.amount > 0,
+        TokenSaleError::NotHolder,
+        "holder_token_account"
+    );
+
+    // - the token account's mint has a supply of 1 and no decimals
+    // a fungible token could otherwise share the NFT's verified collection
+    let holder_mint = holder_mint.ok_or(TokenSaleError::HolderNotNft)?;
+    require!(
+        *holder_mint.owner == spl_token::ID && *holder_mint.key == token_account.mint,
+        TokenSaleError::HolderNotNft,
+        "holder_mint"
+    );
+    let mint = Mint::unpack(&holder_mint.try_borrow_data()?)?;
+    require!(
+        mint.supply == 1 && mint.decimals == 0,
+        TokenSaleError::HolderNotNft,
+        "holder_mint"
+    );
+
+    // - metadata belongs to the token account's mint and verifies `gate_key`
+    let holder_metadata = holder_metadata.ok_or(TokenSaleError::NotInCollection)?;
+    require!(
+        *holder_metadata.owner == TOKEN_METADATA_PROGRAM_ID,
+        TokenSaleError::NotInCollection,
+        "holder_metadata"
+    );
+    let metadata = MetadataPrefix::parse(&holder_metadata.try_borrow_data()?)
+        .ok_or(TokenSaleError::NotInCollection)?;
+    require!(
+        metadata.mint == token_account.mint
+            && metadata.verified_collection() == Some(token_base.gate_key),
+        TokenSaleError::NotInCollection,
+        "holder_metadata"
+    );
+
+    Ok(())
+}
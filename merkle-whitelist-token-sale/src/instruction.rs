@@ -1,38 +1,59 @@
 use crate::merkle::{WhitelistProof, WhitelistRoot};
+use crate::state::WhitelistMode;
 use borsh::{BorshDeserialize, BorshSerialize};
 use shank::{ShankContext, ShankInstruction};
+use solana_program::pubkey::Pubkey;
 
 /// TokenSale Instruction List
 ///
 /// For Token Sale Authority:
 /// - OpenSale (Initialize)
 /// - ConfigureSale
+/// - UpdateSaleSettings
 /// - CloseSale
 /// - ToggleRunning
 /// - AssignLimit
+/// - InitializeEscrow
 ///
 /// For Buyer:
 /// - RegisterBuyer (Initialize)
 /// - BuyToken
+/// - RegisterAndBuy
 /// - DeregisterBuyer
+/// - CloseReceipt
+/// - ClaimReferral
+/// - VoteMilestone
+///
+/// For Escrow Approver:
+/// - ReleaseMilestone (permissionless once the buyers' vote passed)
+/// - CancelEscrow (or Token Sale Authority)
+///
+/// Permissionless:
+/// - ReclaimBuyerFacts
+/// - Migrate
+/// - Quote (read-only)
+/// - RefundEscrow
 ///
 #[derive(BorshDeserialize, BorshSerialize, Debug, ShankContext, ShankInstruction)]
 pub enum TokenSaleInstruction {
     /// Open a Token Sale with the given config
     ///
     /// - Initializes the [`TokenBase`] PDA account (config)
+    /// - Lists the sale in the global and sale authority [`Registry`]
+    /// - Hands the mint authority to the TokenBase PDA
     ///
     /// For Token Sale Authority
     #[account(
         0,
         writable,
         name = "token_base",
-        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base',  `pubkey(sale_authority)`, `pubkey(mint)`]"
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
     )]
     #[account(
         1,
+        writable,
         name = "mint",
-        desc = "Account for holding the mint details of the token being sold"
+        desc = "Account for holding the mint details of the token being sold, its mint authority moves from the sale authority to the TokenBase PDA"
     )]
     #[account(
         2,
@@ -41,13 +62,39 @@ pub enum TokenSaleInstruction {
     )]
     #[account(
         3,
+        writable,
         signer,
         name = "sale_authority",
-        desc = "Account who has authority to manage the token sale"
+        desc = "Account who has authority to manage the token sale, pays the TokenBase rent"
     )]
     #[account(4, name = "system_program", desc = "System Program")]
+    #[account(
+        5,
+        writable,
+        name = "registry",
+        desc = "Account (Registry PDA) listing every sale. Seeds ['registry']"
+    )]
+    #[account(
+        6,
+        writable,
+        name = "registry_page",
+        desc = "Append page (RegistryPage PDA) of the registry. Seeds ['registry_page', `pubkey(registry)`, `registry.append_page()`]"
+    )]
+    #[account(
+        7,
+        writable,
+        name = "authority_registry",
+        desc = "Account (Registry PDA) listing the sale authority's sales. Seeds ['registry', `pubkey(sale_authority)`]"
+    )]
+    #[account(
+        8,
+        writable,
+        name = "authority_registry_page",
+        desc = "Append page (RegistryPage PDA) of the authority registry. Seeds ['registry_page', `pubkey(authority_registry)`, `authority_registry.append_page()`]"
+    )]
+    #[account(9, name = "token_program", desc = "Official SPL Token Program")]
     OpenSale {
-        /// Price of token
+        /// Price of token, lamports per whole token
         price: u64,
         /// Amount of tokens allowed per buyer wallet
         purchase_limit: u64,
@@ -82,7 +129,11 @@ pub enum TokenSaleInstruction {
     /// Update your Token Sale configuration
     ///
     /// - Modifies one or more than from: price, default_purchase_limit, whitelist_root
-    /// of [`TokenBase`]
+    ///   of [`TokenBase`]
+    /// - Records a new whitelist_root in the root history, active right away
+    /// - Bumps the sale phase
+    ///
+    /// Later settings are set by UpdateSaleSettings, keeping this encoding
     ///
     /// For Token Sale Authority
     #[account(
@@ -110,15 +161,18 @@ pub enum TokenSaleInstruction {
 
     /// Close the token sale
     ///
-    /// - Closes the [`TokenBase`] account
-    /// - Relinquishes rent lamports
+    /// - Closes the [`TokenBase`] account into a [`ClosedTokenBase`] tombstone
+    /// - Relinquishes rent lamports above the tombstone's rent exemption
+    /// - Only allowed once the sale is no longer running and reached its end slot, if any
+    /// - Removes a registered sale from the [`Registry`] accounts
+    /// - Closes the sale's [`Escrow`], sweeping its rounding dust to the sale authority
     ///
     /// For Token Sale Authority
     #[account(
         0,
         writable,
         name = "token_base",
-        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', pubkey(sale_authority), pubkey(mint)]"
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
     )]
     #[account(
         1,
@@ -127,28 +181,64 @@ pub enum TokenSaleInstruction {
     )]
     #[account(
         2,
+        writable,
         signer,
         name = "sale_authority",
-        desc = "Account who has authority to manage the token sale"
+        desc = "Account who has authority to manage the token sale, receives the reclaimed rent"
+    )]
+    #[account(
+        3,
+        optional,
+        writable,
+        name = "registry",
+        desc = "Account (Registry PDA) listing every sale, required by registered sales. Seeds ['registry']"
+    )]
+    #[account(
+        4,
+        optional,
+        writable,
+        name = "registry_page",
+        desc = "Page (RegistryPage PDA) of the registry listing the sale. Seeds ['registry_page', `pubkey(registry)`, `registry_page.page`]"
+    )]
+    #[account(
+        5,
+        optional,
+        writable,
+        name = "authority_registry",
+        desc = "Account (Registry PDA) listing the sale authority's sales, required by registered sales. Seeds ['registry', `pubkey(sale_authority)`]"
+    )]
+    #[account(
+        6,
+        optional,
+        writable,
+        name = "authority_registry_page",
+        desc = "Page (RegistryPage PDA) of the authority registry listing the sale. Seeds ['registry_page', `pubkey(authority_registry)`, `authority_registry_page.page`]"
+    )]
+    #[account(
+        7,
+        optional,
+        writable,
+        name = "escrow",
+        desc = "Account (Escrow PDA) of the sale, required by escrowed sales, closed along with it. Seeds ['escrow', `pubkey(token_base)`]"
     )]
     CloseSale,
 
     /// Assign a user's purchase limit
     ///
     /// - Changes the `purchase_limit` of a certain buyer's
-    /// BuyerFacts
+    ///   BuyerFacts
     ///
     /// For Token Sale Authority
     #[account(
         0,
         name = "token_base",
-        desc = "Account (BuyerFacts PDA) holding a buyer's configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
     )]
     #[account(
         1,
         writable,
         name = "buyer_facts",
-        desc = "Account (BuyerFacts PDA) holding a buyer's configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+        desc = "Account (BuyerFacts PDA) holding a buyer's configuration. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(buyer)`]"
     )]
     #[account(
         2,
@@ -166,67 +256,110 @@ pub enum TokenSaleInstruction {
     /// Register as a Buyer
     ///
     /// - Generates the buyer's BuyerFacts
+    /// - Rent is paid by `payer`, letting a relayer sponsor registration
+    /// - Holder-gated sales require proof of holding the gating token
     ///
     /// For Buyer
     #[account(
         0,
         name = "token_base",
-        desc = "Account (BuyerFacts PDA) holding a buyer's configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
     )]
     #[account(
         1,
         writable,
         name = "buyer_facts",
-        desc = "Account (BuyerFacts PDA) holding a buyer's configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+        desc = "Account (BuyerFacts PDA) holding a buyer's configuration. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(buyer)`]"
     )]
     #[account(
         2,
         signer,
         name = "buyer",
-        desc = "Account who owns the BuyerFacts PDA to be assigned a new purchase limit to"
+        desc = "Account registering as a buyer, owner of the new BuyerFacts PDA"
+    )]
+    #[account(
+        3,
+        writable,
+        signer,
+        name = "payer",
+        desc = "Account paying the rent for the BuyerFacts PDA, may be the buyer or a relayer"
+    )]
+    #[account(4, name = "system_program", desc = "System Program")]
+    #[account(
+        5,
+        optional,
+        name = "holder_token_account",
+        desc = "Buyer's token account of the gating mint or NFT, required by holder-gated sales"
+    )]
+    #[account(
+        6,
+        optional,
+        name = "holder_metadata",
+        desc = "Metaplex metadata of the held NFT, required by collection-gated sales"
+    )]
+    #[account(
+        7,
+        optional,
+        name = "holder_mint",
+        desc = "Mint of the held NFT, required by collection-gated sales"
     )]
-    #[account(3, name = "system_program", desc = "System_program")]
     RegisterBuyer,
 
     /// Close a buyer's BuyerFacts
     ///
     /// - Closes the [`BuyerFacts`] account
-    /// - Relinquishes rent lamports
+    /// - Returns rent lamports to the account that paid them
+    ///
+    /// Refused once the buyer purchased, ReclaimBuyerFacts closes it
+    /// after the sale instead
     ///
     /// For Buyers
     #[account(
         0,
         name = "token_base",
-        desc = "Account (BuyerFacts PDA) holding a buyer's configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
     )]
     #[account(
         1,
         writable,
         name = "buyer_facts",
-        desc = "Account (BuyerFacts PDA) holding a buyer's configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+        desc = "Account (BuyerFacts PDA) holding a buyer's configuration. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(buyer)`]"
     )]
     #[account(
         2,
         signer,
         name = "buyer",
-        desc = "Account who owns the BuyerFacts PDA to be assigned a new purchase limit to"
+        desc = "Account who owns the BuyerFacts PDA"
+    )]
+    #[account(
+        3,
+        writable,
+        name = "payer",
+        desc = "Account that paid the BuyerFacts rent on RegisterBuyer, receives its rent lamports"
     )]
     DeregisterBuyer,
 
     /// Buy N amount of Tokens
     ///
-    /// - Initializes Associated Token Account for Buyer
+    /// - Initializes Associated Token Account for Buyer (idempotent)
     /// - Transfers SOL (lamports) from Buyer to Vault
-    /// - Mints Token to Buyer account
+    /// - Mints Token to Buyer account, signed by the TokenBase PDA
+    /// - Deducts the bought amount from the Buyer's purchase limit
+    /// - Issues a [`Receipt`] if a receipt account is passed
+    /// - Rewards the referrer if referrer accounts are passed
+    ///
+    /// Voucher whitelists ignore `proof` and read the voucher from an
+    /// Ed25519 instruction right before this one
     ///
     /// For Buyers
     #[account(
         0,
         name = "token_base",
-        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `token_base::mint`]"
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
     )]
     #[account(
         1,
+        writable,
         name = "mint",
         desc = "Account for holding the mint details of the token being sold"
     )]
@@ -239,24 +372,625 @@ pub enum TokenSaleInstruction {
     #[account(
         3,
         name = "sale_authority",
-        desc = "Account who has authority to manage the token sale"
+        desc = "Account who has authority to manage the token sale, need not sign, the TokenBase PDA mints the token being sold"
     )]
     #[account(
         4,
+        writable,
         name = "buyer_token_account",
-        desc = "Account owned by the buyer where newly bought tokens get transferred to"
+        desc = "Buyer's associated token account for mint, created if missing. Newly bought tokens get minted to it"
     )]
     #[account(
         5,
+        writable,
         name = "buyer_facts",
-        desc = "Account (BuyerFacts PDA) holding user specific statistics. Seeds ['buyer_facts', `pubkey(buyer)`, `pubkey(mint)`]"
+        desc = "Account (BuyerFacts PDA) holding user specific statistics. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(buyer)`]"
     )]
     #[account(
         6,
+        writable,
         signer,
         name = "buyer",
-        desc = "Account who is buying from token sale and will pay for the fees"
+        desc = "Account who is buying from token sale and pays for the tokens"
+    )]
+    #[account(
+        7,
+        writable,
+        signer,
+        name = "payer",
+        desc = "Account paying the rent for the buyer's associated token account, may be the buyer or a relayer"
+    )]
+    #[account(8, name = "system_program", desc = "System Program")]
+    #[account(9, name = "token_program", desc = "Official SPL Token Program")]
+    #[account(
+        10,
+        name = "associated_token_program",
+        desc = "Official SPL Associated Token Account Program"
+    )]
+    #[account(
+        11,
+        optional,
+        writable,
+        name = "receipt",
+        desc = "Account (Receipt PDA) to record this purchase in, pass the program id to skip. Seeds ['receipt', `pubkey(token_base)`, `pubkey(buyer)`, `u64_le(buyer_facts.receipt_count)`]"
+    )]
+    #[account(
+        12,
+        optional,
+        writable,
+        name = "referrer_facts",
+        desc = "Account (BuyerFacts PDA) of the referrer, accrues the referral reward, pass the program id to skip. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(referrer)`]"
+    )]
+    #[account(
+        13,
+        optional,
+        name = "referrer",
+        desc = "Account who referred the buyer, required with referrer_facts"
+    )]
+    #[account(
+        14,
+        optional,
+        name = "instructions_sysvar",
+        desc = "Instructions sysvar, required by voucher whitelists to read the preceding Ed25519 instruction"
+    )]
+    #[account(
+        15,
+        optional,
+        name = "holder_token_account",
+        desc = "Buyer's token account of the gating mint or NFT, required by holder-gated sales"
+    )]
+    #[account(
+        16,
+        optional,
+        name = "holder_metadata",
+        desc = "Metaplex metadata of the held NFT, required by collection-gated sales"
+    )]
+    #[account(
+        17,
+        optional,
+        name = "holder_mint",
+        desc = "Mint of the held NFT, required by collection-gated sales"
     )]
-    #[account(7, name = "token_program", desc = "Official SPL Token Program")]
     BuyToken { amount: u64, proof: WhitelistProof },
+
+    /// Close a buyer's BuyerFacts left behind by a closed sale
+    ///
+    /// - Closes the [`BuyerFacts`] account
+    /// - Returns rent lamports to the account that paid them
+    /// - Pays unclaimed referral rewards to the Buyer
+    ///
+    /// Permissionless
+    #[account(
+        0,
+        name = "token_base",
+        desc = "Closed TokenBase PDA address. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+    )]
+    #[account(
+        1,
+        writable,
+        name = "buyer_facts",
+        desc = "Account (BuyerFacts PDA) holding a buyer's configuration. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(buyer)`]"
+    )]
+    #[account(
+        2,
+        writable,
+        name = "buyer",
+        desc = "Account who owns the BuyerFacts PDA and receives its unclaimed referral rewards"
+    )]
+    #[account(
+        3,
+        writable,
+        name = "payer",
+        desc = "Account that paid the BuyerFacts rent on RegisterBuyer, receives its rent lamports"
+    )]
+    ReclaimBuyerFacts,
+
+    /// Migrate a TokenBase or BuyerFacts to the current layout version
+    ///
+    /// - Reallocs the account to the current size
+    /// - Rewrites the account in the current format
+    /// - Records the sale and buyer of a BuyerFacts, checked against its seeds
+    /// - Tops up rent from `payer`
+    ///
+    /// Permissionless
+    #[account(
+        0,
+        writable,
+        name = "account",
+        desc = "Account (TokenBase or BuyerFacts PDA) to migrate"
+    )]
+    #[account(
+        1,
+        writable,
+        signer,
+        name = "payer",
+        desc = "Account paying the rent difference of the bigger layout"
+    )]
+    #[account(2, name = "system_program", desc = "System Program")]
+    #[account(
+        3,
+        optional,
+        name = "token_base",
+        desc = "Account (TokenBase PDA) of the sale, required by BuyerFacts"
+    )]
+    #[account(
+        4,
+        optional,
+        name = "buyer",
+        desc = "Account of the buyer, required by BuyerFacts"
+    )]
+    Migrate,
+
+    /// Close a purchase Receipt
+    ///
+    /// - Closes the [`Receipt`] account
+    /// - Returns rent lamports to the Payer of the Receipt rent
+    ///
+    /// For Buyers
+    #[account(
+        0,
+        writable,
+        name = "receipt",
+        desc = "Account (Receipt PDA) recording a single purchase. Seeds ['receipt', `pubkey(token_base)`, `pubkey(buyer)`, `u64_le(nonce)`]"
+    )]
+    #[account(1, signer, name = "buyer", desc = "Account who made the purchase")]
+    #[account(
+        2,
+        writable,
+        name = "payer",
+        desc = "Account that paid the Receipt rent on BuyToken, receives the rent lamports"
+    )]
+    CloseReceipt,
+
+    /// Claim accrued referral rewards
+    ///
+    /// - Transfers the referrer's unclaimed rewards out of its [`BuyerFacts`]
+    ///
+    /// For Buyers
+    #[account(
+        0,
+        name = "token_base",
+        desc = "TokenBase PDA address, open or closed. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+    )]
+    #[account(
+        1,
+        writable,
+        name = "buyer_facts",
+        desc = "Account (BuyerFacts PDA) of the referrer holding the rewards. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(referrer)`]"
+    )]
+    #[account(
+        2,
+        writable,
+        signer,
+        name = "referrer",
+        desc = "Account who referred buyers and receives the rewards"
+    )]
+    ClaimReferral,
+
+    /// Quote a purchase without executing it
+    ///
+    /// - Runs BuyToken's purchase checks and pricing
+    /// - Returns a [`Quote`](crate::processor::quote::Quote) via return data
+    /// - Never mutates state, meant for `simulateTransaction`
+    ///
+    /// Buyers without a BuyerFacts are quoted as RegisterAndBuy registers them
+    ///
+    /// Permissionless
+    #[account(
+        0,
+        name = "token_base",
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+    )]
+    #[account(
+        1,
+        name = "mint",
+        desc = "Account for holding the mint details of the token being sold"
+    )]
+    #[account(
+        2,
+        name = "buyer_facts",
+        desc = "Account (BuyerFacts PDA) holding a buyer's configuration, may be uninitialized. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(buyer)`]"
+    )]
+    #[account(3, name = "buyer", desc = "Account the purchase is quoted for")]
+    #[account(
+        4,
+        optional,
+        name = "instructions_sysvar",
+        desc = "Instructions sysvar, required by voucher whitelists to read the preceding Ed25519 instruction"
+    )]
+    #[account(
+        5,
+        optional,
+        name = "holder_token_account",
+        desc = "Buyer's token account of the gating mint or NFT, required by holder-gated sales"
+    )]
+    #[account(
+        6,
+        optional,
+        name = "holder_metadata",
+        desc = "Metaplex metadata of the held NFT, required by collection-gated sales"
+    )]
+    #[account(
+        7,
+        optional,
+        name = "escrow",
+        desc = "Account (Escrow PDA) of the sale, required by escrowed sales. Seeds ['escrow', `pubkey(token_base)`]"
+    )]
+    #[account(
+        8,
+        optional,
+        name = "holder_mint",
+        desc = "Mint of the held NFT, required by collection-gated sales"
+    )]
+    Quote { amount: u64, proof: WhitelistProof },
+
+    /// Register as a Buyer if needed and buy N amount of Tokens
+    ///
+    /// - Initializes the Buyer's [`BuyerFacts`] if missing, like RegisterBuyer
+    /// - Buys like BuyToken, with the same accounts
+    ///
+    /// An existing BuyerFacts is used as is, never initialized twice
+    ///
+    /// For Buyers
+    #[account(
+        0,
+        name = "token_base",
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+    )]
+    #[account(
+        1,
+        writable,
+        name = "mint",
+        desc = "Account for holding the mint details of the token being sold"
+    )]
+    #[account(
+        2,
+        writable,
+        name = "vault",
+        desc = "Account for holding the funds raised from token sale"
+    )]
+    #[account(
+        3,
+        name = "sale_authority",
+        desc = "Account who has authority to manage the token sale, need not sign, the TokenBase PDA mints the token being sold"
+    )]
+    #[account(
+        4,
+        writable,
+        name = "buyer_token_account",
+        desc = "Buyer's associated token account for mint, created if missing. Newly bought tokens get minted to it"
+    )]
+    #[account(
+        5,
+        writable,
+        name = "buyer_facts",
+        desc = "Account (BuyerFacts PDA) holding user specific statistics, created if missing. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(buyer)`]"
+    )]
+    #[account(
+        6,
+        writable,
+        signer,
+        name = "buyer",
+        desc = "Account who is buying from token sale and pays for the tokens"
+    )]
+    #[account(
+        7,
+        writable,
+        signer,
+        name = "payer",
+        desc = "Account paying the rent for the buyer's BuyerFacts and associated token account, may be the buyer or a relayer"
+    )]
+    #[account(8, name = "system_program", desc = "System Program")]
+    #[account(9, name = "token_program", desc = "Official SPL Token Program")]
+    #[account(
+        10,
+        name = "associated_token_program",
+        desc = "Official SPL Associated Token Account Program"
+    )]
+    #[account(
+        11,
+        optional,
+        writable,
+        name = "receipt",
+        desc = "Account (Receipt PDA) to record this purchase in, pass the program id to skip. Seeds ['receipt', `pubkey(token_base)`, `pubkey(buyer)`, `u64_le(buyer_facts.receipt_count)`]"
+    )]
+    #[account(
+        12,
+        optional,
+        writable,
+        name = "referrer_facts",
+        desc = "Account (BuyerFacts PDA) of the referrer, accrues the referral reward, pass the program id to skip. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(referrer)`]"
+    )]
+    #[account(
+        13,
+        optional,
+        name = "referrer",
+        desc = "Account who referred the buyer, required with referrer_facts"
+    )]
+    #[account(
+        14,
+        optional,
+        name = "instructions_sysvar",
+        desc = "Instructions sysvar, required by voucher whitelists to read the preceding Ed25519 instruction"
+    )]
+    #[account(
+        15,
+        optional,
+        name = "holder_token_account",
+        desc = "Buyer's token account of the gating mint or NFT, required by holder-gated sales"
+    )]
+    #[account(
+        16,
+        optional,
+        name = "holder_metadata",
+        desc = "Metaplex metadata of the held NFT, required by collection-gated sales"
+    )]
+    #[account(
+        17,
+        optional,
+        name = "holder_mint",
+        desc = "Mint of the held NFT, required by collection-gated sales"
+    )]
+    RegisterAndBuy { amount: u64, proof: WhitelistProof },
+
+    /// Escrow the raise of a sale, released to the team by milestones
+    ///
+    /// - Initializes the [`Escrow`] PDA account and makes it the vault
+    /// - Milestones are approved by `approver`, if any, or by a vote of
+    ///   the buyers holding `vote_threshold_bps` of the escrowed lamports
+    ///
+    /// For Token Sale Authority
+    #[account(
+        0,
+        writable,
+        name = "token_base",
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+    )]
+    #[account(
+        1,
+        name = "mint",
+        desc = "Account for holding the mint details of the token being sold"
+    )]
+    #[account(
+        2,
+        writable,
+        signer,
+        name = "sale_authority",
+        desc = "Account who has authority to manage the token sale, pays the Escrow rent"
+    )]
+    #[account(
+        3,
+        writable,
+        name = "escrow",
+        desc = "Account (Escrow PDA) to initialize, becomes the sale's vault. Seeds ['escrow', `pubkey(token_base)`]"
+    )]
+    #[account(4, name = "system_program", desc = "System Program")]
+    InitializeEscrow {
+        /// Account receiving the released tranches
+        team: Pubkey,
+        /// Key signing off milestones and allowed to cancel, None to
+        /// approve milestones by vote only
+        approver: Option<Pubkey>,
+        /// Share of the raise released by each milestone, in basis points
+        milestone_bps: Vec<u16>,
+        /// Share of the escrowed lamports approving a milestone, in
+        /// basis points, 0 disables voting
+        vote_threshold_bps: u16,
+    },
+
+    /// Approve the escrow's next milestone
+    ///
+    /// - Adds the buyer's escrowed lamports to the milestone's approvals
+    ///
+    /// For Buyer
+    #[account(
+        0,
+        name = "token_base",
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+    )]
+    #[account(
+        1,
+        writable,
+        name = "escrow",
+        desc = "Account (Escrow PDA) holding the raised lamports, the sale's vault. Seeds ['escrow', `pubkey(token_base)`]"
+    )]
+    #[account(
+        2,
+        writable,
+        name = "buyer_facts",
+        desc = "Account (BuyerFacts PDA) holding the buyer's escrowed lamports. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(buyer)`]"
+    )]
+    #[account(
+        3,
+        signer,
+        name = "buyer",
+        desc = "Account who owns the BuyerFacts PDA"
+    )]
+    VoteMilestone,
+
+    /// Release the escrow's next milestone to the team
+    ///
+    /// - Transfers the milestone's share of the raise to the team
+    /// - Signed by the approver, or by anyone once the buyers' vote passed
+    ///
+    /// For Escrow Approver
+    #[account(
+        0,
+        writable,
+        name = "token_base",
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+    )]
+    #[account(
+        1,
+        writable,
+        name = "escrow",
+        desc = "Account (Escrow PDA) holding the raised lamports, the sale's vault. Seeds ['escrow', `pubkey(token_base)`]"
+    )]
+    #[account(
+        2,
+        writable,
+        name = "team",
+        desc = "Account receiving the released tranche, the escrow's team"
+    )]
+    #[account(
+        3,
+        signer,
+        name = "authority",
+        desc = "Escrow approver, or any account once the buyers' vote passed"
+    )]
+    ReleaseMilestone,
+
+    /// Cancel the escrow
+    ///
+    /// - Freezes the unreleased lamports for pro rata refunds to buyers
+    /// - Settles the sale right away if nothing was deposited
+    ///
+    /// For Escrow Approver or Token Sale Authority
+    #[account(
+        0,
+        writable,
+        name = "token_base",
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+    )]
+    #[account(
+        1,
+        writable,
+        name = "escrow",
+        desc = "Account (Escrow PDA) holding the raised lamports, the sale's vault. Seeds ['escrow', `pubkey(token_base)`]"
+    )]
+    #[account(
+        2,
+        signer,
+        name = "authority",
+        desc = "Escrow approver or the sale authority"
+    )]
+    CancelEscrow,
+
+    /// Refund a buyer's share of a cancelled escrow
+    ///
+    /// - Transfers the buyer's pro rata share of the unreleased lamports
+    /// - Settles the sale once every deposit is refunded, so it can close
+    ///
+    /// Permissionless
+    #[account(
+        0,
+        writable,
+        name = "token_base",
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+    )]
+    #[account(
+        1,
+        writable,
+        name = "escrow",
+        desc = "Account (Escrow PDA) holding the raised lamports, the sale's vault. Seeds ['escrow', `pubkey(token_base)`]"
+    )]
+    #[account(
+        2,
+        writable,
+        name = "buyer_facts",
+        desc = "Account (BuyerFacts PDA) holding the buyer's escrowed lamports. Seeds ['buyer_facts', `pubkey(token_base)`, `pubkey(buyer)`]"
+    )]
+    #[account(
+        3,
+        writable,
+        name = "buyer",
+        desc = "Account who owns the BuyerFacts PDA, receives the refund"
+    )]
+    RefundEscrow,
+
+    /// Update the Token Sale settings beyond ConfigureSale
+    ///
+    /// - Modifies the settings set in `settings` of [`TokenBase`]
+    /// - Records a new whitelist_root in the root history, activating at
+    ///   root_activation_slot or right away
+    ///
+    /// New settings are added as a new [`SaleSettings`] version
+    ///
+    /// For Token Sale Authority
+    #[account(
+        0,
+        writable,
+        name = "token_base",
+        desc = "Account (TokenBase PDA) holding token sale configuration. Seeds ['token_base', `pubkey(sale_authority)`, `pubkey(mint)`]"
+    )]
+    #[account(
+        1,
+        name = "mint",
+        desc = "Account for holding the mint details of the token being sold"
+    )]
+    #[account(
+        2,
+        signer,
+        name = "sale_authority",
+        desc = "Account who has authority to manage the token sale"
+    )]
+    UpdateSaleSettings { settings: SaleSettings },
+}
+
+/// Settings of UpdateSaleSettings
+///
+/// Each version is a variant, so adding settings never changes the
+/// encoding of the existing ones
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug)]
+pub enum SaleSettings {
+    V1(SaleSettingsV1),
+    V2(SaleSettingsV2),
+}
+
+impl SaleSettings {
+    /// Settings in the latest version, settings added later are `None`
+    pub fn into_latest(self) -> SaleSettingsV2 {
+        match self {
+            Self::V1(v1) => SaleSettingsV2 { v1, end_slot: None },
+            Self::V2(v2) => v2,
+        }
+    }
+}
+
+/// Settings introduced with UpdateSaleSettings, `None` keeps the current value
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Default)]
+pub struct SaleSettingsV1 {
+    pub referral_bps: Option<u16>,
+    pub min_registration_slots: Option<u64>,
+    pub purchase_cooldown_slots: Option<u64>,
+    pub whitelist_mode: Option<WhitelistMode>,
+    pub whitelist_signer: Option<Pubkey>,
+    pub gate_key: Option<Pubkey>,
+    pub gate_min_amount: Option<u64>,
+    /// Whitelist root to rotate to, bumps the sale phase like ConfigureSale
+    pub whitelist_root: Option<WhitelistRoot>,
+    /// Slot `whitelist_root` activates at, right away if `None`
+    pub root_activation_slot: Option<u64>,
+    pub root_grace_slots: Option<u64>,
+}
+
+impl SaleSettingsV1 {
+    /// Is `true` if no setting is set, `root_activation_slot` alone sets nothing
+    pub fn is_empty(&self) -> bool {
+        self.referral_bps.is_none()
+            && self.min_registration_slots.is_none()
+            && self.purchase_cooldown_slots.is_none()
+            && self.whitelist_mode.is_none()
+            && self.whitelist_signer.is_none()
+            && self.gate_key.is_none()
+            && self.gate_min_amount.is_none()
+            && self.whitelist_root.is_none()
+            && self.root_grace_slots.is_none()
+    }
+}
+
+/// Adds the sale's `end_slot` to [`SaleSettingsV1`]
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, Default)]
+pub struct SaleSettingsV2 {
+    pub v1: SaleSettingsV1,
+    /// Slot purchases end at and CloseSale is allowed from,
+    /// in the future and never earlier than the current one
+    pub end_slot: Option<u64>,
+}
+
+impl SaleSettingsV2 {
+    /// Is `true` if no setting is set
+    pub fn is_empty(&self) -> bool {
+        self.v1.is_empty() && self.end_slot.is_none()
+    }
 }
@@ -1,13 +1,22 @@
 #![forbid(unsafe_code)]
 
+pub mod client;
+pub mod cpi;
+#[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 pub mod error;
+pub mod events;
+pub mod holder;
 pub mod instruction;
 pub mod macros;
+pub mod math;
 pub mod merkle;
 pub mod pda;
 pub mod processor;
+pub mod registry;
 pub mod state;
+pub mod validation;
+pub mod voucher;
 pub mod wasm;
 
 // make sure tests don't affect binary
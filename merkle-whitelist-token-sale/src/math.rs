@@ -0,0 +1,84 @@
+/// Overflow-safe pricing math
+///
+/// Intermediate products are computed in u128 and every division names
+/// its [`Rounding`]. Amounts owed to the protocol (purchase costs, fees)
+/// round up, amounts paid out by the protocol (tokens, shares) round down.
+use crate::error::TokenSaleError;
+use solana_program::program_error::ProgramError;
+
+/// Basis points in 100%
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Largest supported mint decimals, 10^19 is the last power of ten in u64
+pub const MAX_DECIMALS: u8 = 19;
+
+/// Direction of a division remainder
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    /// Towards zero, for payouts
+    Down,
+    /// Away from zero, for charges
+    Up,
+}
+
+/// `a * b / denominator` without intermediate overflow
+pub fn mul_div(a: u64, b: u64, denominator: u64, rounding: Rounding) -> Result<u64, ProgramError> {
+    if denominator == 0 {
+        return Err(TokenSaleError::MathOverflow.into());
+    }
+
+    let product = (a as u128) * (b as u128);
+    let denominator = denominator as u128;
+    let (quotient, remainder) = (product / denominator, product % denominator);
+    let quotient = match rounding {
+        Rounding::Up if remainder != 0 => quotient + 1,
+        _ => quotient,
+    };
+
+    u64::try_from(quotient).map_err(|_| TokenSaleError::MathOverflow.into())
+}
+
+/// Base units in one whole token, `10^decimals`
+pub fn decimals_factor(decimals: u8) -> Result<u64, ProgramError> {
+    10u64
+        .checked_pow(decimals as u32)
+        .ok_or_else(|| TokenSaleError::MathOverflow.into())
+}
+
+/// Lamports charged for `amount` base units at `price` lamports per whole token
+///
+/// Rounds up, a buyer is never undercharged
+pub fn purchase_cost(price: u64, amount: u64, decimals: u8) -> Result<u64, ProgramError> {
+    mul_div(price, amount, decimals_factor(decimals)?, Rounding::Up)
+}
+
+/// Base units `lamports` can buy at `price` lamports per whole token
+///
+/// Rounds down, so `purchase_cost` of the result never exceeds `lamports`
+pub fn tokens_for_lamports(lamports: u64, price: u64, decimals: u8) -> Result<u64, ProgramError> {
+    if price == 0 {
+        return Err(TokenSaleError::MathOverflow.into());
+    }
+    mul_div(lamports, decimals_factor(decimals)?, price, Rounding::Down)
+}
+
+/// Split `amount` into `(fee, remainder)` for a `fee_bps` fee
+///
+/// The fee rounds up, the remainder gets what is left
+pub fn fee_split(amount: u64, fee_bps: u16) -> Result<(u64, u64), ProgramError> {
+    if fee_bps as u64 > BPS_DENOMINATOR {
+        return Err(TokenSaleError::MathOverflow.into());
+    }
+    let fee = mul_div(amount, fee_bps as u64, BPS_DENOMINATOR, Rounding::Up)?;
+    Ok((fee, amount - fee))
+}
+
+/// `share / whole` of `total`, paid out by the protocol
+///
+/// Rounds down, the shares of all holders never sum past `total`
+pub fn pro_rata(total: u64, share: u64, whole: u64) -> Result<u64, ProgramError> {
+    if share > whole {
+        return Err(TokenSaleError::MathOverflow.into());
+    }
+    mul_div(total, share, whole, Rounding::Down)
+}
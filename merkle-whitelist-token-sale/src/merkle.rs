@@ -1,5 +1,6 @@
 /// Onchain Merkle Tree utils
 use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
 use merkletreers::{
     node::{Node, Side},
     {merkle_proof_check::merkle_proof_check, Leaf, Proof, Root},
@@ -24,12 +25,18 @@ pub struct WhitelistNode {
 }
 
 /// borsh de/serializable Merkle Root primitive
-#[derive(BorshSerialize, BorshDeserialize, Clone, Debug)]
+#[repr(transparent)]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Pod, Zeroable)]
 pub struct WhitelistRoot(pub Root);
 
 /// Verify membership
 pub fn verify_membership(root: Root, proof: Proof, member: Leaf) -> bool {
-    root == merkle_proof_check(proof, member)
+    root == proof_root(proof, member)
+}
+
+/// Root the Merkle proof of `member` resolves to
+pub fn proof_root(proof: Proof, member: Leaf) -> Root {
+    merkle_proof_check(proof, member)
 }
 
 /// Converts a Solana Pubkey into a Merkle Tree Leaf
@@ -60,3 +67,19 @@ pub fn convert_whitelist_proof(w_proof: WhitelistProof) -> Proof {
 
     merkle_proof
 }
+
+/// Converts merkletreers::Proof into WhitelistProof
+///
+/// Used offchain to build BuyToken instruction data
+pub fn convert_merkle_proof(proof: Proof) -> WhitelistProof {
+    proof
+        .into_iter()
+        .map(|node| WhitelistNode {
+            data: node.data,
+            side: match node.side {
+                Side::LEFT => WhitelistSide::LEFT,
+                Side::RIGHT => WhitelistSide::RIGHT,
+            },
+        })
+        .collect()
+}
@@ -1,4 +1,26 @@
-use solana_program::pubkey::Pubkey;
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+    pubkey::{Pubkey, PubkeyError},
+    rent::Rent,
+    system_instruction,
+    sysvar::Sysvar,
+};
+
+/// Component of a PDA's seeds
+///
+/// Published as seed metadata in the IDL in place of shank's
+/// `#[seeds()]` helper attribute
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Seed {
+    /// Constant UTF-8 bytes
+    Literal(&'static str),
+    /// Public key of the named instruction account
+    Account(&'static str),
+    /// Little-endian `u64` read from the named account field
+    Nonce(&'static str),
+}
 
 /// Finds the [`TokenBase`] PDA with canonical bump
 ///
@@ -8,6 +30,11 @@ pub struct TokenBasePDA {}
 
 impl TokenBasePDA {
     pub const NAME: &'static str = "token_base";
+    pub const SEEDS: [Seed; 3] = [
+        Seed::Literal(Self::NAME),
+        Seed::Account("sale_authority"),
+        Seed::Account("mint"),
+    ];
 
     pub fn find_pda(program_id: &Pubkey, sale_authority: &Pubkey, mint: &Pubkey) -> (Pubkey, u8) {
         Pubkey::find_program_address(
@@ -19,6 +46,24 @@ impl TokenBasePDA {
             program_id,
         )
     }
+
+    /// Recreates the PDA from a stored bump, cheaper than [`Self::find_pda`]
+    pub fn create_pda(
+        program_id: &Pubkey,
+        sale_authority: &Pubkey,
+        mint: &Pubkey,
+        bump: u8,
+    ) -> Result<Pubkey, PubkeyError> {
+        Pubkey::create_program_address(
+            &[
+                Self::NAME.as_bytes(),
+                sale_authority.as_ref(),
+                mint.as_ref(),
+                &[bump],
+            ],
+            program_id,
+        )
+    }
 }
 
 /// Finds the [`BuyerFacts`] PDA with canonical bump
@@ -29,6 +74,11 @@ pub struct BuyerFactsPDA {}
 
 impl BuyerFactsPDA {
     pub const NAME: &'static str = "buyer_facts";
+    pub const SEEDS: [Seed; 3] = [
+        Seed::Literal(Self::NAME),
+        Seed::Account("token_base"),
+        Seed::Account("buyer"),
+    ];
 
     pub fn find_pda(program_id: &Pubkey, token_base: &Pubkey, buyer: &Pubkey) -> (Pubkey, u8) {
         Pubkey::find_program_address(
@@ -36,4 +86,236 @@ impl BuyerFactsPDA {
             program_id,
         )
     }
+
+    /// Recreates the PDA from a stored bump, cheaper than [`Self::find_pda`]
+    pub fn create_pda(
+        program_id: &Pubkey,
+        token_base: &Pubkey,
+        buyer: &Pubkey,
+        bump: u8,
+    ) -> Result<Pubkey, PubkeyError> {
+        Pubkey::create_program_address(
+            &[
+                Self::NAME.as_bytes(),
+                token_base.as_ref(),
+                buyer.as_ref(),
+                &[bump],
+            ],
+            program_id,
+        )
+    }
+}
+
+/// Finds the [`Receipt`] PDA with canonical bump
+///
+/// - Used for validating Receipt seeds
+/// - Used for listing a buyer's receipts offchain
+pub struct ReceiptPDA {}
+
+impl ReceiptPDA {
+    pub const NAME: &'static str = "receipt";
+    pub const SEEDS: [Seed; 4] = [
+        Seed::Literal(Self::NAME),
+        Seed::Account("token_base"),
+        Seed::Account("buyer"),
+        Seed::Nonce("buyer_facts.receipt_count"),
+    ];
+
+    pub fn find_pda(
+        program_id: &Pubkey,
+        token_base: &Pubkey,
+        buyer: &Pubkey,
+        nonce: u64,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                Self::NAME.as_bytes(),
+                token_base.as_ref(),
+                buyer.as_ref(),
+                &nonce.to_le_bytes(),
+            ],
+            program_id,
+        )
+    }
+
+    /// Recreates the PDA from a stored bump, cheaper than [`Self::find_pda`]
+    pub fn create_pda(
+        program_id: &Pubkey,
+        token_base: &Pubkey,
+        buyer: &Pubkey,
+        nonce: u64,
+        bump: u8,
+    ) -> Result<Pubkey, PubkeyError> {
+        Pubkey::create_program_address(
+            &[
+                Self::NAME.as_bytes(),
+                token_base.as_ref(),
+                buyer.as_ref(),
+                &nonce.to_le_bytes(),
+                &[bump],
+            ],
+            program_id,
+        )
+    }
+}
+
+/// Finds the [`Registry`] PDAs with canonical bump
+///
+/// - Global registry of every sale: ["registry"]
+/// - Registry of a sale authority's sales: ["registry", pubkey(sale_authority)]
+pub struct RegistryPDA {}
+
+impl RegistryPDA {
+    pub const NAME: &'static str = "registry";
+    pub const SEEDS: [Seed; 1] = [Seed::Literal(Self::NAME)];
+    pub const AUTHORITY_SEEDS: [Seed; 2] =
+        [Seed::Literal(Self::NAME), Seed::Account("sale_authority")];
+
+    /// Registry of `authority`'s sales, the global registry if None
+    pub fn find_pda(program_id: &Pubkey, authority: Option<&Pubkey>) -> (Pubkey, u8) {
+        match authority {
+            Some(authority) => Pubkey::find_program_address(
+                &[Self::NAME.as_bytes(), authority.as_ref()],
+                program_id,
+            ),
+            None => Pubkey::find_program_address(&[Self::NAME.as_bytes()], program_id),
+        }
+    }
+
+    /// Recreates the PDA from a stored bump, cheaper than [`Self::find_pda`]
+    pub fn create_pda(
+        program_id: &Pubkey,
+        authority: Option<&Pubkey>,
+        bump: u8,
+    ) -> Result<Pubkey, PubkeyError> {
+        match authority {
+            Some(authority) => Pubkey::create_program_address(
+                &[Self::NAME.as_bytes(), authority.as_ref(), &[bump]],
+                program_id,
+            ),
+            None => Pubkey::create_program_address(&[Self::NAME.as_bytes(), &[bump]], program_id),
+        }
+    }
+}
+
+/// Finds the [`RegistryPage`] PDA with canonical bump
+///
+/// - Used for validating RegistryPage seeds
+/// - Used for listing a registry's sales offchain
+pub struct RegistryPagePDA {}
+
+impl RegistryPagePDA {
+    pub const NAME: &'static str = "registry_page";
+
+    /// Seeds of a page of the registry account named `registry`
+    pub const fn seeds(registry: &'static str, page: &'static str) -> [Seed; 3] {
+        [
+            Seed::Literal(Self::NAME),
+            Seed::Account(registry),
+            Seed::Nonce(page),
+        ]
+    }
+
+    pub fn find_pda(program_id: &Pubkey, registry: &Pubkey, page: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                Self::NAME.as_bytes(),
+                registry.as_ref(),
+                &page.to_le_bytes(),
+            ],
+            program_id,
+        )
+    }
+
+    /// Recreates the PDA from a stored bump, cheaper than [`Self::find_pda`]
+    pub fn create_pda(
+        program_id: &Pubkey,
+        registry: &Pubkey,
+        page: u64,
+        bump: u8,
+    ) -> Result<Pubkey, PubkeyError> {
+        Pubkey::create_program_address(
+            &[
+                Self::NAME.as_bytes(),
+                registry.as_ref(),
+                &page.to_le_bytes(),
+                &[bump],
+            ],
+            program_id,
+        )
+    }
+}
+
+/// Finds the [`Escrow`] PDA with canonical bump
+///
+/// - Used for validating Escrow seeds
+/// - Used as the vault of escrowed sales
+pub struct EscrowPDA {}
+
+impl EscrowPDA {
+    pub const NAME: &'static str = "escrow";
+    pub const SEEDS: [Seed; 2] = [Seed::Literal(Self::NAME), Seed::Account("token_base")];
+
+    pub fn find_pda(program_id: &Pubkey, token_base: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[Self::NAME.as_bytes(), token_base.as_ref()], program_id)
+    }
+
+    /// Recreates the PDA from a stored bump, cheaper than [`Self::find_pda`]
+    pub fn create_pda(
+        program_id: &Pubkey,
+        token_base: &Pubkey,
+        bump: u8,
+    ) -> Result<Pubkey, PubkeyError> {
+        Pubkey::create_program_address(
+            &[Self::NAME.as_bytes(), token_base.as_ref(), &[bump]],
+            program_id,
+        )
+    }
+}
+
+/// Creates the PDA `account` with `space` bytes, `payer` funds its rent
+///
+/// Lamports sent to the address beforehand are kept, so prefunding a
+/// PDA address can't block its creation
+pub(crate) fn create_pda_account<'info>(
+    program_id: &Pubkey,
+    account: &AccountInfo<'info>,
+    space: usize,
+    seeds: &[&[u8]],
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    let rent = Rent::get()?.minimum_balance(space);
+
+    if account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                rent,
+                space as u64,
+                program_id,
+            ),
+            &[payer.clone(), account.clone(), system_program.clone()],
+            &[seeds],
+        );
+    }
+
+    let rent_due = rent.saturating_sub(account.lamports());
+    if rent_due > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, rent_due),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, program_id),
+        &[account.clone(), system_program.clone()],
+        &[seeds],
+    )
 }
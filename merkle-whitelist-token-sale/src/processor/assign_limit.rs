@@ -1,15 +1,16 @@
 use super::AssignLimitAccounts;
-use crate::error::TokenSaleError;
-use crate::pda::BuyerFactsPDA;
-use crate::state::BuyerFacts;
-use crate::{instruction::accounts::*, require};
-use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+use crate::events::{Event, LimitAssigned};
+use crate::instruction::accounts::*;
+use crate::validation::{
+    check_not_executable, SaleAuthoritySigner, ValidatedBuyerFacts, ValidatedTokenBase,
+};
+use solana_program::{entrypoint::ProgramResult, pubkey::Pubkey};
 
 /// Assign a user's purchase limit
 ///
 /// - Changes the `purchase_limit` of a certain buyer's
-/// BuyerFacts
+///   BuyerFacts
+/// - Records the sale and buyer in BuyerFacts, backfilling migrated accounts
 ///
 /// For Token Sale Authority
 ///
@@ -23,7 +24,7 @@ use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pub
 /// - new_purchase_limit: u64,
 ///
 /// Data Validations
-/// - (None)
+/// - token_base belongs to sale_authority
 pub fn process_assign_limit(
     program_id: &Pubkey,
     ctx: Context<AssignLimitAccounts>,
@@ -31,72 +32,54 @@ pub fn process_assign_limit(
 ) -> ProgramResult {
     //---------- Account Validations ----------
 
-    // 0. buyer_facts
+    // 3. sale_authority
     //
-    // - account is initialized
-    // - buyer_facts seeds must be ["token_base", pubkey(token_base), pubkey(buyer)]
+    // - not executable
+    // - must be signer
+    let sale_authority = SaleAuthoritySigner::new(ctx.accounts.sale_authority)?;
 
+    // 0. token_base
+    //
+    // - owner is token_sale (this) program
     // - account is initialized
-    let mut buyer_facts_data = ctx.accounts.buyer_facts.try_borrow_mut_data()?;
-    let mut buyer_facts = BuyerFacts::try_from_slice(&buyer_facts_data)?;
-    require!(
-        buyer_facts.is_initialized(),
-        ProgramError::UninitializedAccount,
-        "buyer_facts"
-    );
+    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    // - token_base sale_authority is sale_authority
+    let token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+    token_base.check_sale_authority(&sale_authority)?;
 
-    // - buyer_facts seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
-    let (buyer_facts_pda, _) = BuyerFactsPDA::find_pda(
-        program_id,
-        ctx.accounts.token_base.key,
-        ctx.accounts.buyer.key,
-    );
-    require!(
-        *ctx.accounts.buyer_facts.key == buyer_facts_pda,
-        ProgramError::InvalidSeeds,
-        "buyer_facts"
-    );
-
-    // 1. buyer
+    // 2. buyer
     //
     // - not executable
+    check_not_executable(ctx.accounts.buyer, "buyer")?;
 
-    // - not executable
-    require!(
-        !ctx.accounts.buyer.executable,
-        TokenSaleError::MustBeNonExecutable,
-        "buyer"
-    );
-
-    // 3. sale_authority
+    // 1. buyer_facts
     //
-    // - not executable
-    // - must be signer
-    let sale_authority = ctx.accounts.sale_authority;
-
-    // - not executable
-    require!(
-        !sale_authority.executable,
-        TokenSaleError::MustBeNonExecutable,
-        "sale_authority"
-    );
-
-    // - must be signer
-    require!(
-        sale_authority.is_signer,
-        TokenSaleError::NeedSigner,
-        "sale_authority"
-    );
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - buyer_facts seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+    let mut buyer_facts = ValidatedBuyerFacts::new(
+        program_id,
+        ctx.accounts.buyer_facts,
+        ctx.accounts.token_base.key,
+        ctx.accounts.buyer.key,
+    )?;
 
     //---------- Data Validations (if any) ----------
 
     //---------- Executing Instruction ----------
     buyer_facts.purchase_limit = new_purchase_limit;
 
-    // store new values
-    buyer_facts
-        .serialize(&mut &mut buyer_facts_data[..])
-        .unwrap();
+    // - Records the sale and buyer, backfilling migrated accounts
+    buyer_facts.token_base = *ctx.accounts.token_base.key;
+    buyer_facts.buyer = *ctx.accounts.buyer.key;
+
+    LimitAssigned {
+        token_base: *ctx.accounts.token_base.key,
+        buyer: *ctx.accounts.buyer.key,
+        buyer_facts: *ctx.accounts.buyer_facts.key,
+        purchase_limit: buyer_facts.purchase_limit,
+    }
+    .emit()?;
 
     Ok(())
 }
@@ -1,42 +1,87 @@
 use crate::error::TokenSaleError;
+use crate::events::{Event, Purchased};
+use crate::holder::check_holder;
+use crate::math::{mul_div, purchase_cost, Rounding, BPS_DENOMINATOR};
 use crate::merkle::WhitelistProof;
-use crate::pda::{BuyerFactsPDA, TokenBasePDA};
-use crate::state::{BuyerFacts, TokenBase};
+use crate::pda::{create_pda_account, ReceiptPDA, TokenBasePDA};
+use crate::state::{BuyerFacts, Escrow, Receipt, WhitelistMode, ZeroCopyAccount};
+use crate::validation::{
+    check_program_id, BuyerSigner, PayerSigner, ValidatedBuyerFacts, ValidatedEscrow,
+    ValidatedMint, ValidatedTokenBase,
+};
+use crate::voucher::Voucher;
 use crate::{
     instruction::accounts::{BuyTokenAccounts, Context},
     require,
 };
-use borsh::BorshDeserialize;
-use solana_program::program::invoke_signed;
 use solana_program::{
-    entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+    account_info::AccountInfo,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
     system_instruction,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+    sysvar::Sysvar,
 };
-use spl_token::{
-    error::TokenError,
-    instruction,
-    state::{Account, AccountState, Mint},
+use spl_associated_token_account::{
+    get_associated_token_address, instruction::create_associated_token_account_idempotent,
 };
+use spl_discriminator::SplDiscriminate;
+use spl_token::instruction;
 
 /// Buy N amount of Tokens
 ///
-/// - Transfers SOL (lamports) from Buyer to Vault
-/// - Mints Token to Buyer account
-/// - Creates Buyer Facts
+/// - Initializes Associated Token Account for Buyer (idempotent)
+/// - Transfers SOL (lamports) from Buyer to Vault, `price` per whole token
+///   less the referral reward, which goes to the referrer's BuyerFacts
+/// - Mints Token to Buyer account, signed by the TokenBase PDA as mint authority
+/// - Deducts the bought amount from the Buyer's purchase limit
+/// - Issues a Receipt if a receipt account is passed, rent paid by `payer`
+/// - Attributes the Buyer to the referrer on its first referred purchase
+/// - Records the purchase slot for the sale's purchase cooldown
+/// - Records the sale and buyer in BuyerFacts, backfilling migrated accounts
+/// - Records the Vault's share in BuyerFacts if the Vault is the sale's
+///   [`Escrow`](crate::state::Escrow)
+///
+/// For Buyer
 ///
 /// Accounts
 /// 0. `[]`         `Token Base` config account, PDA generated offchain
-/// 1. `[]`         `Mint` account, generated offchain
-/// 2. `[WRITE]`    `Vault` account
-/// 3. `[]`         `Sale Authority` account
-/// 4. `[WRITE]`    `Buyer Token Account` account, PDA (ATA) generated offchain
-/// 5. `[WRITE]`    `Buyer Facts` account, PDA generated offchain
-/// 6. `[SIGNER]`   `Buyer` account
-/// 7. `[]`         `Token Program`
+/// 1. `[WRITE]`    `Mint` account
+/// 2. `[WRITE]`    `Vault` account, the Escrow of escrowed sales
+/// 3. `[]`         `Sale Authority` account, seed of the TokenBase PDA
+/// 4. `[WRITE]`    `Buyer Token Account` account, Buyer's ATA for `Mint`
+/// 5. `[WRITE]`    `Buyer Facts` buyer config account, PDA generated offchain
+/// 6. `[WRITE, SIGNER]` `Buyer` account
+/// 7. `[WRITE, SIGNER]` `Payer` account, funds the ATA rent if created
+/// 8. `[]`         `System Program`
+/// 9. `[]`         `Token Program`
+/// 10. `[]`        `Associated Token Program`
+/// 11. `[WRITE]`   `Receipt` (optional) purchase record account, PDA generated offchain
+/// 12. `[WRITE]`   `Referrer Facts` (optional) referrer's BuyerFacts, PDA generated offchain
+/// 13. `[]`        `Referrer` (optional) account, required with `Referrer Facts`
+/// 14. `[]`        `Instructions Sysvar` (optional), required by voucher whitelists
+/// 15. `[]`        `Holder Token Account` (optional), required by holder-gated sales
+/// 16. `[]`        `Holder Metadata` (optional), required by collection-gated sales
+/// 17. `[]`        `Holder Mint` (optional), required by collection-gated sales
 ///
 /// Instruction Data
 /// - amount: u64,
 /// - proof: WhitelistProof
+///
+/// Data Validations
+/// - sale is running and has not reached its end slot
+/// - buyer is whitelisted, by Merkle proof, signed voucher or holding
+///   the gating token or NFT
+/// - amount does not exceed buyer's purchase limit, nor its voucher allocation
+/// - referrer is not the buyer, nor referred by the buyer, only 2-cycles
+///   are rejected since longer referral chains aren't stored
+/// - referrer is the buyer's attributed referrer, if any
+/// - buyer registered at least `min_registration_slots` ago
+/// - buyer's last purchase was at least `purchase_cooldown_slots` ago
+/// - escrow is not cancelled nor settled, if escrowed
 pub fn process_buy_token(
     program_id: &Pubkey,
     ctx: Context<BuyTokenAccounts>,
@@ -45,225 +90,535 @@ pub fn process_buy_token(
 ) -> ProgramResult {
     //---------- Account Validations ----------
 
+    // 1. mint
+    //
+    // - is_initialized is true
+    // - mint_authority is token_base
+    let mint = ValidatedMint::new(ctx.accounts.mint, ctx.accounts.token_base.key)?;
+
     // 0. token_base
     //
     // - owner is token_sale (this) program
     // - correct allocation length (TokenBase::LEN)
     // - account is initialized
     // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+    token_base.check_mint(&mint)?;
 
-    // - owner is token_sale (this) program
-    require!(
-        ctx.accounts.token_base.owner == program_id,
-        ProgramError::InvalidAccountOwner,
-        "token_base"
-    );
-
-    // - correct allocation length (TokenBase::LEN)
-    let token_base_data = ctx.accounts.token_base.try_borrow_mut_data()?;
-    require!(
-        token_base_data.len() == TokenBase::LEN,
-        TokenSaleError::InvalidAccountDataLength,
-        "token_base"
-    );
-
-    // - account is initialized
-    let token_base = TokenBase::try_from_slice(&token_base_data)?;
-    require!(
-        token_base.is_initialized(),
-        ProgramError::UninitializedAccount,
-        "token_base"
-    );
-
-    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
-    let (token_base_pda, _) = find_token_base_pda(
-        program_id,
-        ctx.accounts.sale_authority.key,
-        ctx.accounts.mint.key,
-    );
-    require!(
-        *ctx.accounts.token_base.key == token_base_pda,
-        TokenSaleError::UnexpectedPDASeeds,
-        "token_base"
-    );
-
-    // 1. mint
+    // 3. sale_authority
     //
-    // - token_base mint is mint
-    require!(
-        token_base.mint == *ctx.accounts.mint.key,
-        TokenSaleError::AccountsAndTokenBaseMismatch,
-        "mint"
-    );
+    // - token_base sale_authority is sale_authority, signs nothing
+    token_base.check_sale_authority(ctx.accounts.sale_authority)?;
 
     // 2. vault
     //
     // - token_base vault is vault
-    require!(
-        token_base.vault == *ctx.accounts.vault.key,
-        TokenSaleError::AccountsAndTokenBaseMismatch,
-        "vault"
-    );
-
-    // 3. sale_authority
+    // - escrow of the sale, if escrowed
+    token_base.check_vault(ctx.accounts.vault)?;
+    let escrow = if token_base.is_escrow() {
+        Some(ValidatedEscrow::new(
+            program_id,
+            ctx.accounts.vault,
+            token_base.info.key,
+        )?)
+    } else {
+        None
+    };
+
+    // 6. buyer
     //
-    // - sale_authority must also be mint_authority
-    let mint_data = ctx.accounts.mint.try_borrow_data()?;
-    let mint = Mint::unpack(&mint_data)?;
-    require!(
-        token_base.sale_authority == mint.mint_authority.unwrap(),
-        TokenSaleError::AccountsAndTokenBaseMismatch,
-        "vault"
-    );
+    // - not executable
+    // - must be signer
+    let buyer = BuyerSigner::new(ctx.accounts.buyer)?;
 
-    // 1. buyer_token_account
+    // 4. buyer_token_account
     //
-    // - must be initialized
-    // - mint must be token_base mint
-    // - owner must be buyer
-    let buyer_token_account_data = ctx.accounts.buyer_token_account.try_borrow_data()?;
-    let buyer_token_account = Account::unpack(&buyer_token_account_data)?;
-
-    // - must be initialized
-    require!(
-        buyer_token_account.state == AccountState::Initialized,
-        TokenError::UninitializedState,
-        "buyer_token_account"
-    );
-
-    // - mint must be token_base mint
-    require!(
-        buyer_token_account.mint == token_base.mint,
-        TokenError::InvalidMint,
-        "buyer_token_account"
-    );
-
-    // - owner must be buyer
-    let buyer = *ctx.accounts.buyer.key;
+    // - must be the buyer's associated token account for token_base mint
     require!(
-        buyer_token_account.owner == buyer,
-        TokenError::OwnerMismatch,
+        *ctx.accounts.buyer_token_account.key
+            == get_associated_token_address(buyer.key, &token_base.mint),
+        TokenSaleError::InvalidBuyerTokenAccount,
         "buyer_token_account"
     );
 
-    // 2. buyer_facts
+    // 5. buyer_facts
     //
     // - owner is token_sale (this) program
     // - correct allocation length (BuyerFacts::LEN)
-    // - buyer_facts seeds must be ['buyer_facts', `pubkey(buyer)`, `pubkey(mint)`]
-
-    // - owner is token_sale (this) program
-    require!(
-        ctx.accounts.buyer_facts.owner == program_id,
-        ProgramError::InvalidAccountOwner,
-        "buyer_facts"
-    );
-
-    // - correct allocation length (BuyerFacts::LEN)
-    let buyer_facts_data = ctx.accounts.buyer_facts.try_borrow_mut_data()?;
-    require!(
-        buyer_facts_data.len() == BuyerFacts::LEN,
-        TokenSaleError::InvalidAccountDataLength,
-        "buyer_facts"
-    );
-
-    // - buyer_facts seeds must be ['buyer_facts', `pubkey(buyer)`, `pubkey(mint)`]
-    let (buyer_facts_pda, bump) =
-        find_buyer_facts_pda(program_id, ctx.accounts.buyer.key, &token_base.mint);
-
-    require!(
-        *ctx.accounts.buyer_facts.key == buyer_facts_pda,
-        TokenSaleError::UnexpectedPDASeeds,
-        "buyer_facts"
-    );
+    // - account is initialized
+    // - buyer_facts seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+    let mut buyer_facts = ValidatedBuyerFacts::new(
+        program_id,
+        ctx.accounts.buyer_facts,
+        token_base.info.key,
+        buyer.key,
+    )?;
 
-    // 3. buyer
+    // 7. payer
     //
-    // - not executable
-    // - must be signer
-    let buyer = ctx.accounts.buyer;
-
-    // - not executable
-    require!(
-        !buyer.executable,
-        TokenSaleError::MustBeNonExecutable,
-        "buyer"
-    );
-
     // - must be signer
-    require!(buyer.is_signer, TokenSaleError::NeedSigner, "buyer");
+    let payer = PayerSigner::new(ctx.accounts.payer)?;
 
-    // 4. token_program
+    // 8. system_program
     //
-    // - key must be the same as official
+    // - must be official system program
+    check_program_id(
+        ctx.accounts.system_program,
+        &SYSTEM_PROGRAM_ID,
+        "system_program",
+    )?;
 
+    // 9. token_program
+    //
     // - key must be the same as official SPL Token Program ID
-    let token_program = ctx.accounts.token_program;
     require!(
         // HOHOHOHO! No doppelganger programs here.
-        *token_program.key == spl_token::ID,
+        *ctx.accounts.token_program.key == spl_token::ID,
         TokenSaleError::InvalidTokenProgramID,
         "token_program"
     );
 
-    //---------- Data Validations (if any) ----------
+    // 10. associated_token_program
+    //
+    // - key must be the same as official Associated Token Program ID
+    check_program_id(
+        ctx.accounts.associated_token_program,
+        &spl_associated_token_account::ID,
+        "associated_token_program",
+    )?;
 
-    // Whitelist Gate
-    match token_base.is_whitelisted(buyer.key, proof) {
-        Ok(whitelisted) => {
-            if !whitelisted {
-                return Err(TokenSaleError::NotWhitelisted.into());
-            }
+    // 11. receipt (optional)
+    //
+    // - seeds must be ["receipt", pubkey(token_base), pubkey(buyer), buyer_facts.receipt_count]
+    let receipt = match ctx.accounts.receipt {
+        Some(receipt) => {
+            let (receipt_pda, receipt_canonical_bump) = ReceiptPDA::find_pda(
+                program_id,
+                token_base.info.key,
+                buyer.key,
+                buyer_facts.receipt_count,
+            );
+            require!(
+                *receipt.key == receipt_pda,
+                TokenSaleError::InvalidReceiptSeeds,
+                "receipt"
+            );
+            Some((receipt, receipt_canonical_bump))
         }
-        Err(e) => return Err(e),
-    }
+        None => None,
+    };
 
-    //---------- Executing Instruction ----------
+    // 13. referrer (optional)
+    //
+    // - required with referrer_facts
+    // - not the buyer
+    //
+    // 12. referrer_facts (optional)
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - seeds must be ["buyer_facts", pubkey(token_base), pubkey(referrer)]
+    let referral = match ctx.accounts.referrer_facts {
+        Some(referrer_facts) => {
+            let referrer = ctx
+                .accounts
+                .referrer
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+            require!(
+                referrer.key != buyer.key,
+                TokenSaleError::SelfReferral,
+                "referrer"
+            );
+
+            // rewards are credited through a CPI, release the borrow until then
+            let referred_by = ValidatedBuyerFacts::new(
+                program_id,
+                referrer_facts,
+                token_base.info.key,
+                referrer.key,
+            )?
+            .referrer;
+            Some((referrer_facts, referrer, referred_by))
+        }
+        None => None,
+    };
+
+    // 14. instructions_sysvar (optional)
+    //
+    // - key is checked when loading the voucher
+
+    // 15. holder_token_account (optional)
+    // 16. holder_metadata (optional)
+    // 17. holder_mint (optional)
+    //
+    // - checked against the holder gate
 
-    // build transfer instruction
-    let payment_ix = system_instruction::transfer(buyer.key, &token_base.vault, token_base.price);
-
-    // build mint_to instruction
-    let buyer_pubkey = ctx.accounts.buyer.key;
-    let buyer_account_pubkey = ctx.accounts.buyer_token_account.key;
-    let mint_to_ix = instruction::mint_to(
-        &spl_token::ID,
-        &token_base.mint,
-        buyer_account_pubkey,
-        buyer_pubkey,
-        &[buyer_pubkey],
+    //---------- Data Validations (if any) ----------
+
+    let clock = Clock::get()?;
+
+    // - sale is running and has not reached its end slot
+    // - escrow is not cancelled nor settled, if escrowed
+    // - buyer is whitelisted, by Merkle proof, signed voucher or holding
+    //   the gating token or NFT
+    // - amount does not exceed buyer's purchase limit, nor its voucher allocation
+    // - buyer registered at least `min_registration_slots` ago
+    // - buyer's last purchase was at least `purchase_cooldown_slots` ago
+    check_purchase(
+        &token_base,
+        escrow.as_deref(),
+        &buyer_facts,
+        buyer.key,
         amount,
+        proof,
+        WhitelistAccounts {
+            instructions_sysvar: ctx.accounts.instructions_sysvar,
+            holder_token_account: ctx.accounts.holder_token_account,
+            holder_metadata: ctx.accounts.holder_metadata,
+            holder_mint: ctx.accounts.holder_mint,
+        },
+        &clock,
     )?;
+    // the purchase pays into the escrow through the System Program
+    drop(escrow);
+
+    if let Some((_, referrer, referred_by)) = referral {
+        // - referrer is not referred by the buyer
+        require!(
+            referred_by != *buyer.key,
+            TokenSaleError::CircularReferral,
+            "referrer"
+        );
+
+        // - referrer is the buyer's attributed referrer, if any
+        require!(
+            !buyer_facts.is_referred() || buyer_facts.referrer == *referrer.key,
+            TokenSaleError::ReferrerMismatch,
+            "referrer"
+        );
+    }
 
-    // invoke instructions
+    let slot = clock.slot;
+
+    //---------- Executing Instruction ----------
+
+    // rounds up, a buyer is never undercharged
+    let lamports_paid = purchase_cost(token_base.price, amount, mint.state.decimals)?;
+
+    // rounds down, paid out of the vault's share
+    let referral_reward = match referral {
+        Some(_) => mul_div(
+            lamports_paid,
+            token_base.referral_bps as u64,
+            BPS_DENOMINATOR,
+            Rounding::Down,
+        )?,
+        None => 0,
+    };
+
+    // - Initializes Associated Token Account for Buyer (idempotent)
+    // the ATA program rejects an existing account with the wrong owner or mint
+    invoke(
+        &create_associated_token_account_idempotent(
+            payer.key,
+            buyer.key,
+            &token_base.mint,
+            &spl_token::ID,
+        ),
+        &[
+            payer.0.clone(),
+            ctx.accounts.buyer_token_account.clone(),
+            buyer.0.clone(),
+            ctx.accounts.mint.clone(),
+            ctx.accounts.system_program.clone(),
+            ctx.accounts.token_program.clone(),
+            ctx.accounts.associated_token_program.clone(),
+        ],
+    )?;
 
     // - Transfers SOL (lamports) from Buyer to Vault
-    let vault = ctx.accounts.vault;
-    invoke_signed(
-        &payment_ix,
-        &[buyer.clone(), vault.clone()],
-        // signer seeds = PDA seeds
-        &[&["token_base".as_bytes(), token_base.mint.as_ref()]],
+    invoke(
+        &system_instruction::transfer(
+            buyer.key,
+            &token_base.vault,
+            lamports_paid - referral_reward,
+        ),
+        &[
+            buyer.0.clone(),
+            ctx.accounts.vault.clone(),
+            ctx.accounts.system_program.clone(),
+        ],
     )?;
 
-    // - Mints Token to Buyer account
-    let mint = ctx.accounts.mint;
-    // mint_authority == sale_authority
-    let mint_authority = ctx.accounts.sale_authority;
+    // - Records the Vault's share if the Vault is the sale's Escrow
+    if token_base.is_escrow() {
+        let deposited = lamports_paid - referral_reward;
+
+        // seeds were checked above
+        let mut escrow_data = ctx.accounts.vault.try_borrow_mut_data()?;
+        let escrow = Escrow::load_mut(&mut escrow_data)?;
+        escrow.total_deposited = escrow
+            .total_deposited
+            .checked_add(deposited)
+            .ok_or(TokenSaleError::MathOverflow)?;
+
+        buyer_facts.escrow_deposited = buyer_facts
+            .escrow_deposited
+            .checked_add(deposited)
+            .ok_or(TokenSaleError::MathOverflow)?;
+    }
+
+    // - Deducts the bought amount from the Buyer's purchase limit
+    buyer_facts.purchase_limit -= amount;
+    buyer_facts.total_purchased = buyer_facts
+        .total_purchased
+        .checked_add(amount)
+        .ok_or(TokenSaleError::MathOverflow)?;
+
+    // - Records the purchase slot
+    buyer_facts.last_purchase_slot = slot;
+
+    // - Records the sale and buyer, backfilling migrated accounts
+    buyer_facts.token_base = *token_base.info.key;
+    buyer_facts.buyer = *buyer.key;
+
+    // - Attributes the Buyer to the referrer and accrues its reward
+    if let Some((referrer_facts, referrer, _)) = referral {
+        if referral_reward > 0 {
+            invoke(
+                &system_instruction::transfer(buyer.key, referrer_facts.key, referral_reward),
+                &[
+                    buyer.0.clone(),
+                    referrer_facts.clone(),
+                    ctx.accounts.system_program.clone(),
+                ],
+            )?;
+        }
+
+        // seeds were checked above
+        let mut referrer_facts_data = referrer_facts.try_borrow_mut_data()?;
+        let referrer_facts = BuyerFacts::load_mut(&mut referrer_facts_data)?;
+        referrer_facts.referral_rewards = referrer_facts
+            .referral_rewards
+            .checked_add(referral_reward)
+            .ok_or(TokenSaleError::MathOverflow)?;
+
+        buyer_facts.referrer = *referrer.key;
+    }
+
+    // - Issues a Receipt if a receipt account is passed
+    if let Some((receipt_account_info, receipt_bump)) = receipt {
+        let nonce = buyer_facts.receipt_count;
+
+        create_pda_account(
+            program_id,
+            receipt_account_info,
+            Receipt::LEN,
+            &[
+                ReceiptPDA::NAME.as_bytes(),
+                token_base.info.key.as_ref(),
+                buyer.key.as_ref(),
+                &nonce.to_le_bytes(),
+                &[receipt_bump],
+            ],
+            payer.0,
+            ctx.accounts.system_program,
+        )?;
+
+        let mut receipt_data = receipt_account_info.try_borrow_mut_data()?;
+        let receipt = Receipt::load_uninitialized_mut(&mut receipt_data)?;
+
+        receipt.discriminator = Receipt::SPL_DISCRIMINATOR.into();
+        receipt.token_base = *token_base.info.key;
+        receipt.buyer = *buyer.key;
+        receipt.payer = *payer.key;
+        receipt.nonce = nonce;
+        receipt.amount = amount;
+        receipt.lamports_paid = lamports_paid;
+        receipt.price = token_base.price;
+        receipt.slot = slot;
+        receipt.phase = token_base.phase;
+        receipt.bump = receipt_bump;
+
+        buyer_facts.receipt_count = nonce.checked_add(1).ok_or(TokenSaleError::MathOverflow)?;
+    }
+
+    // - Mints Token to Buyer account, signed by the TokenBase PDA
+    // token_base signs the CPI, release its borrow first
+    let token_base_bump = token_base.bump;
+    drop(token_base);
     invoke_signed(
-        &mint_to_ix,
-        &[mint.clone(), buyer.clone(), mint_authority.clone()],
-        // signer seeds = PDA seeds
-        &[&["token_base".as_bytes(), token_base.mint.as_ref()]],
+        &instruction::mint_to(
+            &spl_token::ID,
+            ctx.accounts.mint.key,
+            ctx.accounts.buyer_token_account.key,
+            ctx.accounts.token_base.key,
+            &[],
+            amount,
+        )?,
+        &[
+            ctx.accounts.mint.clone(),
+            ctx.accounts.buyer_token_account.clone(),
+            ctx.accounts.token_base.clone(),
+            ctx.accounts.token_program.clone(),
+        ],
+        &[&[
+            TokenBasePDA::NAME.as_bytes(),
+            ctx.accounts.sale_authority.key.as_ref(),
+            ctx.accounts.mint.key.as_ref(),
+            &[token_base_bump],
+        ]],
     )?;
 
-    // - Creates Buyer Facts
-    let buyer_facts_data = ctx.accounts.buyer_facts.try_borrow_mut_data()?;
-    let mut buyer_facts = BuyerFacts::try_from_slice(&buyer_facts_data)?;
+    Purchased {
+        token_base: *ctx.accounts.token_base.key,
+        buyer: *buyer.key,
+        buyer_token_account: *ctx.accounts.buyer_token_account.key,
+        amount,
+        lamports_paid,
+        remaining_limit: buyer_facts.purchase_limit,
+        receipt: ctx.accounts.receipt.map(|receipt| *receipt.key),
+        referrer: referral.map(|(_, referrer, _)| *referrer.key),
+        referral_reward,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Optional accounts proving eligibility outside Merkle whitelists
+pub(crate) struct WhitelistAccounts<'a, 'info> {
+    pub instructions_sysvar: Option<&'a AccountInfo<'info>>,
+    pub holder_token_account: Option<&'a AccountInfo<'info>>,
+    pub holder_metadata: Option<&'a AccountInfo<'info>>,
+    pub holder_mint: Option<&'a AccountInfo<'info>>,
+}
+
+/// Purchase checks of BuyToken, shared with Quote
+///
+/// - sale is running and has not reached its end slot
+/// - escrow is not cancelled nor settled, if escrowed
+/// - buyer is whitelisted, by Merkle proof, signed voucher or holding
+///   the gating token or NFT
+/// - amount does not exceed buyer's purchase limit, nor its voucher allocation
+/// - buyer registered at least `min_registration_slots` ago
+/// - buyer's last purchase was at least `purchase_cooldown_slots` ago
+#[allow(clippy::too_many_arguments)] // BuyToken and Quote pass the same accounts
+pub(crate) fn check_purchase(
+    token_base: &ValidatedTokenBase,
+    escrow: Option<&Escrow>,
+    buyer_facts: &BuyerFacts,
+    buyer: &Pubkey,
+    amount: u64,
+    proof: WhitelistProof,
+    whitelist: WhitelistAccounts,
+    clock: &Clock,
+) -> ProgramResult {
+    // - sale is running
+    require!(
+        token_base.is_running(),
+        TokenSaleError::SaleNotRunning,
+        "token_base"
+    );
+
+    // - sale has not reached its end slot
+    require!(
+        !token_base.is_ended(clock.slot),
+        TokenSaleError::SaleEnded,
+        "token_base"
+    );
 
-    buyer_facts.token_account = *ctx.accounts.buyer_token_account.key;
-    buyer_facts.purchase_limit = token_base.default_purchase_limit;
-    buyer_facts.bump = bump;
+    // - escrow is not cancelled nor settled, if escrowed
+    if let Some(escrow) = escrow {
+        require!(
+            !escrow.is_cancelled(),
+            TokenSaleError::EscrowCancelled,
+            "escrow"
+        );
+        require!(
+            escrow.has_pending_milestone(),
+            TokenSaleError::EscrowSettled,
+            "escrow"
+        );
+    }
+
+    // - buyer is whitelisted, by Merkle proof, signed voucher or holding
+    //   the gating token or NFT
+    match token_base.whitelist_mode()? {
+        WhitelistMode::Merkle => {
+            require!(
+                token_base.is_whitelisted(buyer, proof, clock.slot)?,
+                TokenSaleError::NotWhitelisted,
+                "buyer"
+            );
+        }
+        WhitelistMode::Voucher => {
+            let instructions_sysvar = whitelist
+                .instructions_sysvar
+                .ok_or(TokenSaleError::InvalidVoucher)?;
+            let voucher =
+                Voucher::load_verified(instructions_sysvar, &token_base.whitelist_signer)?;
+
+            require!(
+                voucher.buyer == *buyer && voucher.token_base == *token_base.info.key,
+                TokenSaleError::InvalidVoucher,
+                "buyer"
+            );
+            require!(
+                clock.unix_timestamp < voucher.expiry,
+                TokenSaleError::VoucherExpired,
+                "buyer"
+            );
+
+            // - amount does not exceed its voucher allocation
+            require!(
+                buyer_facts
+                    .total_purchased
+                    .checked_add(amount)
+                    .is_some_and(|total| total <= voucher.allocation),
+                TokenSaleError::AllocationExceeded,
+                "amount"
+            );
+        }
+        WhitelistMode::TokenHolder | WhitelistMode::CollectionHolder => {
+            check_holder(
+                token_base,
+                buyer,
+                whitelist.holder_token_account,
+                whitelist.holder_metadata,
+                whitelist.holder_mint,
+            )?;
+        }
+    }
+
+    // - amount does not exceed buyer's purchase limit
+    require!(
+        amount <= buyer_facts.purchase_limit,
+        TokenSaleError::LimitExceeded,
+        "amount"
+    );
+
+    let slot = clock.slot;
+
+    // - buyer registered at least `min_registration_slots` ago
+    require!(
+        slot >= buyer_facts
+            .registered_slot
+            .saturating_add(token_base.min_registration_slots),
+        TokenSaleError::RegistrationTooRecent,
+        "buyer_facts"
+    );
+
+    // - buyer's last purchase was at least `purchase_cooldown_slots` ago
+    require!(
+        buyer_facts.last_purchase_slot == 0
+            || slot
+                >= buyer_facts
+                    .last_purchase_slot
+                    .saturating_add(token_base.purchase_cooldown_slots),
+        TokenSaleError::PurchaseCooldown,
+        "buyer_facts"
+    );
 
     Ok(())
 }
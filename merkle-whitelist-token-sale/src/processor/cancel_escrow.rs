@@ -0,0 +1,101 @@
+use crate::error::TokenSaleError;
+use crate::events::{EscrowCancelled, Event};
+use crate::validation::{EscrowAuthoritySigner, ValidatedEscrow, ValidatedTokenBase};
+use crate::{instruction::accounts::*, require};
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+
+/// Cancel the escrow
+///
+/// - Freezes the unreleased lamports as the refund pool, refunded pro
+///   rata to buyers through the permissionless RefundEscrow
+/// - Stops purchases, milestone votes and releases
+/// - Settles the sale right away if nothing was deposited, RefundEscrow
+///   settles it after the last refund otherwise
+///
+/// For Escrow Approver or Token Sale Authority
+///
+/// Accounts
+/// 0. `[WRITE]`    `Token Base` config account, PDA generated offchain
+/// 1. `[WRITE]`    `Escrow` account, PDA generated offchain
+/// 2. `[SIGNER]`   `Authority` account, approver or sale authority
+///
+/// Instruction Data
+/// - (None)
+///
+/// Data Validations
+/// - authority is the approver or the sale authority
+/// - escrow is not cancelled
+/// - escrow has a milestone left to release
+pub fn process_cancel_escrow(
+    program_id: &Pubkey,
+    ctx: Context<CancelEscrowAccounts>,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 0. token_base
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let mut token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+
+    // 1. escrow
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - escrow token_base is token_base
+    // - seeds must be ["escrow", pubkey(token_base)]
+    let mut escrow = ValidatedEscrow::new(program_id, ctx.accounts.escrow, token_base.info.key)?;
+
+    // 2. authority
+    //
+    // - not executable
+    // - must be signer
+    let authority = EscrowAuthoritySigner::new(ctx.accounts.authority)?;
+
+    //---------- Data Validations (if any) ----------
+
+    // - authority is the approver or the sale authority
+    require!(
+        escrow.is_approver(authority.key) || token_base.sale_authority == *authority.key,
+        TokenSaleError::SaleAuthorityMismatch,
+        "authority"
+    );
+
+    // - escrow is not cancelled
+    require!(
+        !escrow.is_cancelled(),
+        TokenSaleError::EscrowCancelled,
+        "escrow"
+    );
+
+    // - escrow has a milestone left to release
+    require!(
+        escrow.has_pending_milestone(),
+        TokenSaleError::EscrowSettled,
+        "escrow"
+    );
+
+    //---------- Executing Instruction ----------
+
+    // - Freezes the unreleased lamports as the refund pool
+    escrow.refund_pool = escrow
+        .total_deposited
+        .checked_sub(escrow.total_released)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    escrow.is_cancelled = true.into();
+
+    // - Settles the sale right away if nothing was deposited
+    if escrow.is_fully_refunded() {
+        token_base.is_escrow_settled = true.into();
+    }
+
+    EscrowCancelled {
+        token_base: *token_base.info.key,
+        authority: *authority.key,
+        refund_pool: escrow.refund_pool,
+    }
+    .emit()?;
+
+    Ok(())
+}
@@ -0,0 +1,87 @@
+use crate::error::TokenSaleError;
+use crate::events::{Event, ReferralClaimed};
+use crate::validation::{ReferrerSigner, ValidatedBuyerFacts};
+use crate::{instruction::accounts::*, require};
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+
+/// Claim accrued referral rewards
+///
+/// - Transfers the referrer's unclaimed rewards out of its
+///   [`BuyerFacts`](crate::state::BuyerFacts), leaving the rent
+///
+/// Rewards are escrowed in the BuyerFacts by BuyToken, next to its rent,
+/// since the vault can't be debited by this program. DeregisterBuyer is
+/// refused while rewards are unclaimed, and ReclaimBuyerFacts pays them
+/// to the referrer with the rent once the sale is closed
+///
+/// For Buyer
+///
+/// Accounts
+/// 0. `[]`         `Token Base` config account, open or closed, PDA generated offchain
+/// 1. `[WRITE]`    `Buyer Facts` referrer's buyer config account, PDA generated offchain
+/// 2. `[WRITE, SIGNER]` `Referrer` account, receives the rewards
+///
+/// Instruction Data
+/// - (None)
+///
+/// Data Validations
+/// - referrer has unclaimed rewards
+pub fn process_claim_referral(
+    program_id: &Pubkey,
+    ctx: Context<ClaimReferralAccounts>,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 2. referrer
+    //
+    // - not executable
+    // - must be signer
+    let referrer = ReferrerSigner::new(ctx.accounts.referrer)?;
+
+    // 1. buyer_facts
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - seeds must be ["buyer_facts", pubkey(token_base), pubkey(referrer)]
+    let mut buyer_facts = ValidatedBuyerFacts::new(
+        program_id,
+        ctx.accounts.buyer_facts,
+        ctx.accounts.token_base.key,
+        referrer.key,
+    )?;
+
+    //---------- Data Validations (if any) ----------
+
+    // - referrer has unclaimed rewards
+    let claimed_lamports = buyer_facts.referral_rewards;
+    require!(
+        claimed_lamports > 0,
+        TokenSaleError::NoReferralRewards,
+        "buyer_facts"
+    );
+
+    //---------- Executing Instruction ----------
+
+    buyer_facts.referral_rewards = 0;
+
+    // - Transfers the referrer's unclaimed rewards
+    let buyer_facts_lamports = buyer_facts.info.lamports();
+    **buyer_facts.info.try_borrow_mut_lamports()? = buyer_facts_lamports
+        .checked_sub(claimed_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    let referrer_lamports = referrer.lamports();
+    **referrer.try_borrow_mut_lamports()? = referrer_lamports
+        .checked_add(claimed_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    ReferralClaimed {
+        token_base: *ctx.accounts.token_base.key,
+        referrer: *referrer.key,
+        buyer_facts: *buyer_facts.info.key,
+        claimed_lamports,
+    }
+    .emit()?;
+
+    Ok(())
+}
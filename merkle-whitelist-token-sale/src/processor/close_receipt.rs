@@ -0,0 +1,101 @@
+use crate::error::TokenSaleError;
+use crate::events::{Event, ReceiptClosed};
+use crate::validation::{BuyerSigner, ValidatedReceipt};
+use crate::{instruction::accounts::*, require};
+use solana_program::{
+    entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+};
+
+/// Close a purchase Receipt
+///
+/// - Closes the [`Receipt`](crate::state::Receipt) account
+/// - Returns rent lamports to the account that paid them
+///
+/// Receipts are the buyer's own records, only the buyer can close
+/// them and the sale may be in any state, even closed
+///
+/// For Buyer
+///
+/// Accounts
+/// 0. `[WRITE]`    `Receipt` purchase record account, PDA generated offchain
+/// 1. `[SIGNER]`   `Buyer` account
+/// 2. `[WRITE]`    `Payer` account that paid the rent on BuyToken, receives it
+///
+/// Instruction Data
+/// - (None)
+///
+/// Data Validations
+/// - (None)
+pub fn process_close_receipt(
+    program_id: &Pubkey,
+    ctx: Context<CloseReceiptAccounts>,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 1. buyer
+    //
+    // - not executable
+    // - must be signer
+    let buyer = BuyerSigner::new(ctx.accounts.buyer)?;
+
+    // 0. receipt
+    //
+    // - owner is token_sale (this) program
+    // - correct allocation length (Receipt::LEN)
+    // - account is initialized
+    // - seeds must be ["receipt", receipt.token_base, pubkey(buyer), receipt.nonce]
+    let receipt = ValidatedReceipt::new(program_id, ctx.accounts.receipt, buyer.key)?;
+    let (token_base, nonce, rent_payer) = (receipt.token_base, receipt.nonce, receipt.payer);
+    drop(receipt);
+
+    // 2. payer
+    //
+    // - must be the account that paid the rent
+    require!(
+        *ctx.accounts.payer.key == rent_payer,
+        TokenSaleError::PayerMismatch,
+        "payer"
+    );
+
+    //---------- Data Validations (if any) ----------
+
+    //---------- Executing Instruction ----------
+
+    // receipt
+    let receipt_account_info = ctx.accounts.receipt;
+    let receipt_lamports = receipt_account_info.lamports();
+
+    // payer
+    let payer_account_info = ctx.accounts.payer;
+    let payer_lamports = payer_account_info.lamports();
+
+    // - Returns rent lamports to the account that paid them
+
+    // direct transfer receipt (PDA) lamports into payer
+    // NOTE: Direct transfer is okay since receipt is a PDA owned by this program
+    **payer_account_info.try_borrow_mut_lamports()? = payer_lamports
+        .checked_add(receipt_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    // zero out receipt (PDA) lamports
+    **receipt_account_info.try_borrow_mut_lamports()? = 0;
+
+    // - Closes the [`Receipt`] account
+    // fill with 0s = no data
+    receipt_account_info.try_borrow_mut_data()?.fill(0);
+
+    receipt_account_info.realloc(0, false)?;
+    receipt_account_info.assign(&SYSTEM_PROGRAM_ID);
+
+    ReceiptClosed {
+        token_base,
+        buyer: *buyer.key,
+        receipt: *receipt_account_info.key,
+        nonce,
+        reclaimed_lamports: receipt_lamports,
+    }
+    .emit()?;
+
+    Ok(())
+}
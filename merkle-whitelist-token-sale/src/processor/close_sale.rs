@@ -1,123 +1,230 @@
 use super::CloseSaleAccounts;
 use crate::error::TokenSaleError;
-use crate::pda::TokenBasePDA;
-use crate::state::TokenBase;
+use crate::events::{Event, SaleClosed};
+use crate::registry::remove_sale;
+use crate::state::{ClosedTokenBase, ZeroCopyAccount};
+use crate::validation::{SaleAuthoritySigner, ValidatedEscrow, ValidatedMint, ValidatedTokenBase};
 use crate::{instruction::accounts::*, require};
-use borsh::BorshDeserialize;
 use solana_program::{
-    entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+    clock::Clock, entrypoint::ProgramResult, msg, program_error::ProgramError, pubkey::Pubkey,
+    rent::Rent, system_program::ID as SYSTEM_PROGRAM_ID, sysvar::Sysvar,
 };
-use spl_token::{error::TokenError, state::Mint};
+use spl_discriminator::SplDiscriminate;
 
 /// Close the token sale
 ///
-/// - Relinquishes rent lamports
-/// - Closes the [`TokenBase`] account
+/// - Relinquishes rent lamports above the tombstone's rent exemption
+/// - Closes the [`TokenBase`](crate::state::TokenBase) account into a
+///   [`ClosedTokenBase`] tombstone, so OpenSale can never reuse the address
+///   while its [`BuyerFacts`](crate::state::BuyerFacts) may still exist
+///
+/// - Removes a registered sale from the global and sale authority
+///   [`Registry`](crate::state::Registry), refunding the freed page rent
+///
+/// - Closes the sale's [`Escrow`](crate::state::Escrow), sweeping the
+///   rounding dust of releases or refunds to the sale authority
+///
+/// Registered [`BuyerFacts`](crate::state::BuyerFacts) are reclaimed
+/// afterwards through the permissionless ReclaimBuyerFacts
 ///
 /// Accounts
 /// 0. `[WRITE]`    `Token Base` config account, PDA generated offchain
 /// 1. `[]`         `Mint` account
-/// 2. `[SIGNER]`   `Sale Authority` account
+/// 2. `[WRITE, SIGNER]` `Sale Authority` account
+/// 3. `[WRITE]`    (optional) `Registry` account, required by registered sales
+/// 4. `[WRITE]`    (optional) `Registry Page` account listing the sale
+/// 5. `[WRITE]`    (optional) `Authority Registry` account, required by registered sales
+/// 6. `[WRITE]`    (optional) `Authority Registry Page` account listing the sale
+/// 7. `[WRITE]`    (optional) `Escrow` account, required by escrowed sales
 ///
 /// Instruction Data
 /// - (Empty, None, Nada! HAHAHA)
+///
+/// Data Validations
+/// - sale is not running
+/// - sale reached its end slot, if it has one
+/// - escrow released its last milestone or refunded every deposit, if escrowed
 pub fn process_close_sale(program_id: &Pubkey, ctx: Context<CloseSaleAccounts>) -> ProgramResult {
     //---------- Account Validations ----------
 
+    // 2. sale_authority
+    //
+    // - not executable
+    // - must be signer
+    let sale_authority = SaleAuthoritySigner::new(ctx.accounts.sale_authority)?;
+
+    // 1. mint
+    //
+    // - is_initialized is true
+    // - mint_authority is token_base
+    let mint = ValidatedMint::new(ctx.accounts.mint, ctx.accounts.token_base.key)?;
+
     // 0. token_base
     //
+    // - owner is token_sale (this) program
     // - account is initialized
     // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+    token_base.check_sale_authority(&sale_authority)?;
+    token_base.check_mint(&mint)?;
 
-    // - account is initialized
-    let mut token_base_data = ctx.accounts.token_base.try_borrow_mut_data()?;
-    let token_base = TokenBase::try_from_slice(&token_base_data)?;
-    require!(
-        token_base.is_initialized(),
-        ProgramError::UninitializedAccount,
-        "token_base"
-    );
-
-    // - token_base seeds must be ["token_base", pubkey(mint)]
-    let (token_base_pda, _) = TokenBasePDA::find_pda(
-        program_id,
-        ctx.accounts.sale_authority.key,
-        ctx.accounts.mint.key,
-    );
-    require!(
-        *ctx.accounts.token_base.key == token_base_pda,
-        ProgramError::InvalidSeeds,
-        "token_base"
-    );
-
-    // 1. mint
+    // 3. - 6. registry, registry_page, authority_registry, authority_registry_page
     //
-    // - is_initialized is true
-    // - mint_authority is token_base sale_authority
-    let mint = ctx.accounts.mint;
-    let mint_data = mint.try_borrow_data()?;
-    let mint_state = Mint::unpack(&mint_data)?;
+    // - required if the sale is registered
+    // - registry seeds must be ["registry"]
+    // - authority_registry seeds must be ["registry", pubkey(sale_authority)]
+    // - page seeds must be ["registry_page", pubkey(registry), registry_page.page]
+    // - page lists the sale
+    // seeds and listing checked while removing the sale
+    let registries = if token_base.is_registered() {
+        let (
+            Some(registry),
+            Some(registry_page),
+            Some(authority_registry),
+            Some(authority_registry_page),
+        ) = (
+            ctx.accounts.registry,
+            ctx.accounts.registry_page,
+            ctx.accounts.authority_registry,
+            ctx.accounts.authority_registry_page,
+        )
+        else {
+            msg!("Constraint failed: registry");
+            return Err(TokenSaleError::InvalidRegistry.into());
+        };
+        vec![
+            (registry, registry_page, None),
+            (
+                authority_registry,
+                authority_registry_page,
+                Some(sale_authority.key),
+            ),
+        ]
+    } else {
+        vec![]
+    };
+
+    // 7. escrow
+    //
+    // - required if the sale is escrowed
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - escrow token_base is token_base
+    // - seeds must be ["escrow", pubkey(token_base)]
+    let escrow = if token_base.is_escrow() {
+        let Some(escrow) = ctx.accounts.escrow else {
+            msg!("Constraint failed: escrow");
+            return Err(TokenSaleError::InvalidEscrowSeeds.into());
+        };
+        drop(ValidatedEscrow::new(
+            program_id,
+            escrow,
+            token_base.info.key,
+        )?);
+        Some(escrow)
+    } else {
+        None
+    };
 
-    // - is_initialized is true
-    require!(
-        mint_state.is_initialized,
-        TokenError::UninitializedState,
-        "mint"
-    );
+    //---------- Data Validations (if any) ----------
 
-    // - mint_authority is token_base sale_authority
+    // - sale is not running
     require!(
-        mint_state.mint_authority.unwrap() == *ctx.accounts.sale_authority.key,
-        TokenSaleError::MintAndSaleAuthorityMismatch,
-        "mint"
+        !token_base.is_running(),
+        TokenSaleError::SaleStillRunning,
+        "token_base"
     );
 
-    // 2. sale_authority
-    //
-    // - not executable
-    // - must be signer
-    let sale_authority = ctx.accounts.sale_authority;
-
-    // - not executable
+    // - sale reached its end slot, if it has one
+    // buyers can rely on a sale with an end slot not closing before it
+    let clock = Clock::get()?;
     require!(
-        !sale_authority.executable,
-        TokenSaleError::MustBeNonExecutable,
-        "sale_authority"
+        token_base.end_slot == 0 || token_base.is_ended(clock.slot),
+        TokenSaleError::SaleNotEnded,
+        "token_base"
     );
 
-    // - must be signer
+    // - escrow released its last milestone or refunded every deposit, if escrowed
+    // a cancelled escrow stays open until the last refund
     require!(
-        sale_authority.is_signer,
-        TokenSaleError::NeedSigner,
-        "sale_authority"
+        token_base.is_escrow_settled(),
+        TokenSaleError::EscrowNotSettled,
+        "token_base"
     );
-
-    //---------- Data Validations (if any) ----------
+    drop(token_base);
 
     //---------- Executing Instruction ----------
 
+    // - Removes a registered sale from the registries
+    for (registry, registry_page, authority) in registries {
+        remove_sale(
+            program_id,
+            registry,
+            registry_page,
+            authority,
+            ctx.accounts.token_base.key,
+            ctx.accounts.sale_authority,
+        )?;
+    }
+
     // token_base
     let token_base_account_info = ctx.accounts.token_base;
-    let token_base_lamports = token_base_account_info.lamports();
 
     // sale_authority
     let sale_authority_account_info = ctx.accounts.sale_authority;
     let sale_authority_lamports = sale_authority_account_info.lamports();
 
-    // - Relinquishes rent lamports
+    // - Closes the [`TokenBase`] account into a [`ClosedTokenBase`] tombstone
+    // fill with 0s first so no sale configuration survives the shrink
+    token_base_account_info.try_borrow_mut_data()?.fill(0);
+    token_base_account_info.realloc(ClosedTokenBase::LEN, false)?;
+    {
+        let mut token_base_data = token_base_account_info.try_borrow_mut_data()?;
+        let closed = ClosedTokenBase::load_uninitialized_mut(&mut token_base_data)?;
+        closed.discriminator = ClosedTokenBase::SPL_DISCRIMINATOR.into();
+        closed.sale_authority = *sale_authority_account_info.key;
+        closed.mint = *ctx.accounts.mint.key;
+        closed.closed_slot = clock.slot;
+    }
+
+    // - Relinquishes rent lamports above the tombstone's rent exemption
+    // NOTE: Direct transfer is okay since token_base is a PDA owned by this program
+    let token_base_lamports = token_base_account_info.lamports();
+    let reclaimed_lamports =
+        token_base_lamports.saturating_sub(Rent::get()?.minimum_balance(ClosedTokenBase::LEN));
 
-    // direct transfer token_base (PDA) lamports into sale_authority
-    // NOTE: Direct transfer is okay since token_base is a PDA owned by sale_authority
     **sale_authority_account_info.try_borrow_mut_lamports()? = sale_authority_lamports
-        .checked_add(token_base_lamports) // None if overflow
-        .unwrap();
-
-    // zero out token_base (PDA) lamports
-    **token_base_account_info.try_borrow_mut_lamports()? = 0;
-
-    // - Closes the [`TokenBase`] account
-    // fill with 0s = no data
-    token_base_data.fill(0);
+        .checked_add(reclaimed_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **token_base_account_info.try_borrow_mut_lamports()? = token_base_lamports - reclaimed_lamports;
+
+    // - Closes the sale's Escrow, sweeping its rent and dust to the sale authority
+    // NOTE: Direct transfer is okay since escrow is a PDA owned by this program
+    let escrow_lamports = match escrow {
+        Some(escrow) => {
+            let escrow_lamports = escrow.lamports();
+            let sale_authority_lamports = sale_authority_account_info.lamports();
+            **sale_authority_account_info.try_borrow_mut_lamports()? = sale_authority_lamports
+                .checked_add(escrow_lamports)
+                .ok_or(ProgramError::ArithmeticOverflow)?;
+            **escrow.try_borrow_mut_lamports()? = 0;
+
+            escrow.try_borrow_mut_data()?.fill(0);
+            escrow.realloc(0, false)?;
+            escrow.assign(&SYSTEM_PROGRAM_ID);
+            escrow_lamports
+        }
+        None => 0,
+    };
+
+    SaleClosed {
+        token_base: *token_base_account_info.key,
+        sale_authority: *sale_authority_account_info.key,
+        reclaimed_lamports: reclaimed_lamports
+            .checked_add(escrow_lamports)
+            .ok_or(ProgramError::ArithmeticOverflow)?,
+    }
+    .emit()?;
 
     Ok(())
 }
@@ -1,19 +1,19 @@
 use super::ConfigureSaleAccounts;
 use crate::error::TokenSaleError;
+use crate::events::{Event, SaleConfigured};
 use crate::merkle::WhitelistRoot;
-use crate::pda::TokenBasePDA;
 use crate::state::TokenBase;
+use crate::validation::{SaleAuthoritySigner, ValidatedMint, ValidatedTokenBase};
 use crate::{instruction::accounts::*, require};
-use borsh::{BorshDeserialize, BorshSerialize};
-use solana_program::{
-    entrypoint::ProgramResult, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
-};
-use spl_token::{error::TokenError, state::Mint};
+use solana_program::{clock::Clock, entrypoint::ProgramResult, pubkey::Pubkey, sysvar::Sysvar};
 
 /// Update your Token Sale configuration
 ///
 /// - Modifies one or more than from: price, default_purchase_limit, whitelist_root
-/// of [`TokenBase`]
+///   of [`TokenBase`]
+/// - Records a new whitelist_root in the root history, active right away.
+///   Proofs against the replaced root keep verifying for root_grace_slots
+/// - Bumps the sale phase if price or whitelist_root is set
 ///
 /// For Token Sale Authority
 ///
@@ -38,84 +38,49 @@ pub fn process_configure_sale(
 ) -> ProgramResult {
     //---------- Account Validations ----------
 
-    // 0. token_base
+    // 2. sale_authority
     //
-    // - account is initialized
-    // - seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
-
-    // - account is initialized
-    let mut token_base_data = ctx.accounts.token_base.try_borrow_mut_data()?;
-    let mut token_base = TokenBase::try_from_slice(&token_base_data)?;
-    require!(
-        token_base.is_initialized(),
-        ProgramError::UninitializedAccount,
-        "token_base"
-    );
-
-    // - token_base seeds must be ["token_base", pubkey(mint)]
-    let (token_base_pda, _) = TokenBasePDA::find_pda(
-        program_id,
-        ctx.accounts.sale_authority.key,
-        ctx.accounts.mint.key,
-    );
-    require!(
-        *ctx.accounts.token_base.key == token_base_pda,
-        ProgramError::InvalidSeeds,
-        "token_base"
-    );
+    // - not executable
+    // - must be signer
+    let sale_authority = SaleAuthoritySigner::new(ctx.accounts.sale_authority)?;
 
     // 1. mint
     //
     // - is_initialized is true
-    // - mint_authority is token_base sale_authority
-    let mint = ctx.accounts.mint;
-    let mint_data = mint.try_borrow_data()?;
-    let mint_state = Mint::unpack(&mint_data)?;
-
-    // - is_initialized is true
-    require!(
-        mint_state.is_initialized,
-        TokenError::UninitializedState,
-        "mint"
-    );
-
-    // - mint_authority is token_base sale_authority
-    require!(
-        mint_state.mint_authority.unwrap() == *ctx.accounts.sale_authority.key,
-        TokenSaleError::MintAndSaleAuthorityMismatch,
-        "mint"
-    );
+    // - mint_authority is token_base
+    let mint = ValidatedMint::new(ctx.accounts.mint, ctx.accounts.token_base.key)?;
 
-    // 2. sale_authority
+    // 0. token_base
     //
-    // - not executable
-    // - must be signer
-    let sale_authority = ctx.accounts.sale_authority;
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let mut token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+    token_base.check_sale_authority(&sale_authority)?;
+    token_base.check_mint(&mint)?;
 
-    // - not executable
-    require!(
-        !sale_authority.executable,
-        TokenSaleError::MustBeNonExecutable,
-        "sale_authority"
-    );
+    //---------- Data Validations (if any) ----------
 
-    // - must be signer
+    // ensure fail if price, default_purchase_limit and whitelist_root are
+    // all None. It means it'll be a wasteful tx. No state change needed
     require!(
-        sale_authority.is_signer,
-        TokenSaleError::NeedSigner,
-        "sale_authority"
+        price.is_some() || default_purchase_limit.is_some() || whitelist_root.is_some(),
+        TokenSaleError::NothingToConfigure,
+        "price, default_purchase_limit, whitelist_root"
     );
 
-    //---------- Data Validations (if any) ----------
-
-    // ensure fail if none of price, default_purchase_limit, and whitelist_root
-    // is Some(). It means it'll be a wasteful tx. No state change needed
-    assert!(price.is_some() || default_purchase_limit.is_some() || whitelist_root.is_some());
-
     //---------- Executing Instruction ----------
 
     // configure sale
 
+    // - a new price or whitelist starts a new phase
+    if price.is_some() || whitelist_root.is_some() {
+        token_base.phase = token_base
+            .phase
+            .checked_add(1)
+            .ok_or(TokenSaleError::MathOverflow)?;
+    }
+
     if let Some(price) = price {
         token_base.price = price;
     }
@@ -124,12 +89,34 @@ pub fn process_configure_sale(
         token_base.default_purchase_limit = dpl;
     }
 
+    // - a new whitelist root is recorded, the replaced one stays in the history
     if let Some(root) = whitelist_root {
-        token_base.whitelist_root = root;
+        let slot = Clock::get()?.slot;
+        token_base.rotate_whitelist_root(root, slot, slot);
     }
 
-    // store new values
-    token_base.serialize(&mut &mut token_base_data[..]).unwrap();
+    sale_configured(ctx.accounts.token_base.key, &token_base).emit()?;
 
     Ok(())
 }
+
+/// SaleConfigured event of `token_base` with its resulting configuration,
+/// shared with UpdateSaleSettings
+pub(crate) fn sale_configured(token_base_key: &Pubkey, token_base: &TokenBase) -> SaleConfigured {
+    SaleConfigured {
+        token_base: *token_base_key,
+        whitelist_root: token_base.whitelist_root.0,
+        price: token_base.price,
+        default_purchase_limit: token_base.default_purchase_limit,
+        phase: token_base.phase,
+        referral_bps: token_base.referral_bps,
+        min_registration_slots: token_base.min_registration_slots,
+        purchase_cooldown_slots: token_base.purchase_cooldown_slots,
+        whitelist_mode: token_base.whitelist_mode,
+        whitelist_signer: token_base.whitelist_signer,
+        gate_key: token_base.gate_key,
+        gate_min_amount: token_base.gate_min_amount,
+        root_activation_slot: token_base.latest_root_activation_slot(),
+        root_grace_slots: token_base.root_grace_slots,
+    }
+}
@@ -1,14 +1,13 @@
 use crate::error::TokenSaleError;
-use crate::pda::BuyerFactsPDA;
-use crate::state::{BuyerFacts, TokenBase};
+use crate::events::{BuyerDeregistered, Event};
+use crate::validation::{BuyerSigner, ValidatedBuyerFacts, ValidatedTokenBase};
 use crate::{instruction::accounts::*, require};
-use borsh::BorshDeserialize;
 use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
 
 /// Close a buyer's BuyerFacts
 ///
-/// - Closes the [`BuyerFacts`] account
-/// - Relinquishes rent lamports
+/// - Closes the [`BuyerFacts`](crate::state::BuyerFacts) account
+/// - Returns rent lamports to the account that paid them
 ///
 /// For Buyer
 ///
@@ -16,12 +15,16 @@ use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pub
 /// 0. `[]`         `Token Base` config account, PDA generated offchain
 /// 1. `[WRITE]`    `Buyer Facts` buyer config account, PDA generated offchain
 /// 2. `[SIGNER]`   `Buyer` account
+/// 3. `[WRITE]`    `Payer` account that paid the rent on RegisterBuyer, receives it
 ///
 /// Instruction Data
 /// - (None)
 ///
 /// Data Validations
-/// - (None)
+/// - buyer never purchased, so re-registering can't reset `total_purchased`
+///   and reuse a voucher allocation
+/// - buyer has no unclaimed referral rewards, they are held by its BuyerFacts
+/// - buyer has no escrowed lamports while the escrow is not settled
 pub fn process_deregister_buyer(
     program_id: &Pubkey,
     ctx: Context<DeregisterBuyerAccounts>,
@@ -30,63 +33,63 @@ pub fn process_deregister_buyer(
 
     // 0. token_base
     //
+    // - owner is token_sale (this) program
     // - account is initialized
+    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
 
-    // - account is initialized
-    let token_base_data = ctx.accounts.token_base.try_borrow_mut_data()?;
-    let token_base = TokenBase::try_from_slice(&token_base_data)?;
-    require!(
-        token_base.is_initialized(),
-        ProgramError::UninitializedAccount,
-        "token_base"
-    );
+    // 2. buyer
+    //
+    // - not executable
+    // - must be signer
+    let buyer = BuyerSigner::new(ctx.accounts.buyer)?;
 
     // 1. buyer_facts
     //
+    // - owner is token_sale (this) program
     // - account is initialized
     // - seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+    let buyer_facts = ValidatedBuyerFacts::new(
+        program_id,
+        ctx.accounts.buyer_facts,
+        token_base.info.key,
+        buyer.key,
+    )?;
 
-    // - account is initialized
-    let mut buyer_facts_data = ctx.accounts.buyer_facts.try_borrow_mut_data()?;
-    let buyer_facts = BuyerFacts::try_from_slice(&buyer_facts_data)?;
+    // 3. payer
+    //
+    // - must be the account that paid the rent, the buyer for older BuyerFacts
     require!(
-        buyer_facts.is_initialized(),
-        ProgramError::UninitializedAccount,
-        "buyer_facts"
+        *ctx.accounts.payer.key == buyer_facts.rent_payer(buyer.key),
+        TokenSaleError::PayerMismatch,
+        "payer"
     );
 
-    // - seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
-    let (buyer_facts_pda, _) = BuyerFactsPDA::find_pda(
-        program_id,
-        ctx.accounts.token_base.key,
-        ctx.accounts.buyer.key,
-    );
+    //---------- Data Validations (if any) ----------
+
+    // - buyer never purchased
+    // BuyerFacts of buyers who purchased are closed by ReclaimBuyerFacts
     require!(
-        *ctx.accounts.buyer_facts.key == buyer_facts_pda,
-        ProgramError::InvalidSeeds,
+        buyer_facts.total_purchased == 0,
+        TokenSaleError::BuyerHasPurchases,
         "buyer_facts"
     );
 
-    // 2. buyer
-    //
-    // - not executable
-    // - must be signer
-
-    // - not executable
+    // - buyer has no unclaimed referral rewards
     require!(
-        !ctx.accounts.buyer.executable,
-        TokenSaleError::MustBeNonExecutable,
-        "buyer"
+        buyer_facts.referral_rewards == 0,
+        TokenSaleError::UnclaimedReferralRewards,
+        "buyer_facts"
     );
 
-    // - must be signer
+    // - buyer has no escrowed lamports while the escrow is not settled
+    // its deposit backs the milestone vote and the refund
     require!(
-        ctx.accounts.buyer.is_signer,
-        TokenSaleError::NeedSigner,
-        "buyer"
+        token_base.is_escrow_settled() || !buyer_facts.has_escrow_deposit(),
+        TokenSaleError::EscrowNotSettled,
+        "buyer_facts"
     );
-
-    //---------- Data Validations (if any) ----------
+    drop(buyer_facts);
 
     //---------- Executing Instruction ----------
 
@@ -94,24 +97,31 @@ pub fn process_deregister_buyer(
     let buyer_facts_account_info = ctx.accounts.buyer_facts;
     let buyer_facts_lamports = buyer_facts_account_info.lamports();
 
-    // buyer
-    let buyer_account_info = ctx.accounts.buyer;
-    let buyer_lamports = buyer_account_info.lamports();
+    // payer
+    let payer_account_info = ctx.accounts.payer;
+    let payer_lamports = payer_account_info.lamports();
 
-    // - Relinquishes rent lamports
+    // - Returns rent lamports to the account that paid them
 
-    // direct transfer buyer_facts (PDA) lamports into buyer
-    // NOTE: Direct transfer is okay since token_base is a PDA owned by buyer
-    **buyer_account_info.try_borrow_mut_lamports()? = buyer_lamports
-        .checked_add(buyer_facts_lamports) // None if overflow
-        .unwrap();
+    // direct transfer buyer_facts (PDA) lamports into payer
+    // NOTE: Direct transfer is okay since buyer_facts is a PDA owned by this program
+    **payer_account_info.try_borrow_mut_lamports()? = payer_lamports
+        .checked_add(buyer_facts_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
 
     // zero out token_base (PDA) lamports
     **buyer_facts_account_info.try_borrow_mut_lamports()? = 0;
 
     // - Closes the [`BuyerFacts`] account
     // fill with 0s = no data
-    buyer_facts_data.fill(0);
+    buyer_facts_account_info.try_borrow_mut_data()?.fill(0);
+
+    BuyerDeregistered {
+        token_base: *ctx.accounts.token_base.key,
+        buyer: *ctx.accounts.buyer.key,
+        buyer_facts: *ctx.accounts.buyer_facts.key,
+    }
+    .emit()?;
 
     Ok(())
 }
@@ -0,0 +1,176 @@
+use crate::error::TokenSaleError;
+use crate::events::{EscrowInitialized, Event};
+use crate::math::BPS_DENOMINATOR;
+use crate::pda::{create_pda_account, EscrowPDA};
+use crate::state::{Escrow, ZeroCopyAccount, MAX_MILESTONES};
+use crate::validation::{check_program_id, SaleAuthoritySigner, ValidatedMint, ValidatedTokenBase};
+use crate::{
+    instruction::accounts::{Context, InitializeEscrowAccounts},
+    require,
+};
+use solana_program::{
+    entrypoint::ProgramResult, pubkey::Pubkey, system_program::ID as SYSTEM_PROGRAM_ID,
+};
+use spl_discriminator::SplDiscriminate;
+
+/// Escrow the raise of a sale, released to the team by milestones
+///
+/// - Initializes the [`Escrow`] PDA account
+/// - Makes the escrow the sale's vault, later purchases pay into it
+///
+/// For Token Sale Authority
+///
+/// Accounts
+/// 0. `[WRITE]`    `Token Base` config account, PDA generated offchain
+/// 1. `[]`         `Mint` account
+/// 2. `[WRITE, SIGNER]` `Sale Authority` account, pays the Escrow rent
+/// 3. `[WRITE]`    `Escrow` account, PDA generated offchain
+/// 4. `[]`         `System Program`
+///
+/// Instruction Data
+/// - team: Pubkey,
+/// - approver: Option<Pubkey>,
+/// - milestone_bps: Vec<u16>,
+/// - vote_threshold_bps: u16
+///
+/// Data Validations
+/// - sale is not running
+/// - sale is not escrowed yet
+/// - 1 to 4 milestones, each releasing a share, summing to 10000 bps
+/// - vote_threshold_bps is at most 10000
+/// - milestones are approved by an approver or by vote
+pub fn process_initialize_escrow(
+    program_id: &Pubkey,
+    ctx: Context<InitializeEscrowAccounts>,
+    team: Pubkey,
+    approver: Option<Pubkey>,
+    milestone_bps: Vec<u16>,
+    vote_threshold_bps: u16,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 2. sale_authority
+    //
+    // - not executable
+    // - must be signer
+    let sale_authority = SaleAuthoritySigner::new(ctx.accounts.sale_authority)?;
+
+    // 1. mint
+    //
+    // - is_initialized is true
+    // - mint_authority is token_base
+    let mint = ValidatedMint::new(ctx.accounts.mint, ctx.accounts.token_base.key)?;
+
+    // 0. token_base
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let mut token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+    token_base.check_sale_authority(&sale_authority)?;
+    token_base.check_mint(&mint)?;
+
+    // 3. escrow
+    //
+    // - account is uninitialized
+    // - escrow seeds must be ["escrow", pubkey(token_base)]
+    require!(
+        ctx.accounts.escrow.data_is_empty(),
+        TokenSaleError::InvalidEscrowSeeds,
+        "escrow"
+    );
+    let (escrow_pda, escrow_bump) = EscrowPDA::find_pda(program_id, token_base.info.key);
+    require!(
+        *ctx.accounts.escrow.key == escrow_pda,
+        TokenSaleError::InvalidEscrowSeeds,
+        "escrow"
+    );
+
+    // 4. system_program
+    //
+    // - must be official system program
+    check_program_id(
+        ctx.accounts.system_program,
+        &SYSTEM_PROGRAM_ID,
+        "system_program",
+    )?;
+
+    //---------- Data Validations (if any) ----------
+
+    // - sale is not running
+    require!(
+        !token_base.is_running(),
+        TokenSaleError::SaleStillRunning,
+        "token_base"
+    );
+
+    // - sale is not escrowed yet
+    require!(
+        !token_base.is_escrow(),
+        TokenSaleError::InvalidEscrowSeeds,
+        "token_base"
+    );
+
+    // - 1 to 4 milestones, each releasing a share, summing to 10000 bps
+    require!(
+        (1..=MAX_MILESTONES).contains(&milestone_bps.len())
+            && milestone_bps.iter().all(|bps| *bps > 0)
+            && milestone_bps.iter().map(|bps| *bps as u64).sum::<u64>() == BPS_DENOMINATOR,
+        TokenSaleError::InvalidMilestones,
+        "milestone_bps"
+    );
+
+    // - vote_threshold_bps is at most 10000
+    // - milestones are approved by an approver or by vote
+    require!(
+        vote_threshold_bps as u64 <= BPS_DENOMINATOR
+            && (approver.is_some() || vote_threshold_bps > 0),
+        TokenSaleError::InvalidMilestones,
+        "vote_threshold_bps"
+    );
+
+    //---------- Executing Instruction ----------
+
+    // - Initializes the Escrow PDA account
+    // a prefunded address is topped up and allocated instead
+    create_pda_account(
+        program_id,
+        ctx.accounts.escrow,
+        Escrow::LEN,
+        &[
+            EscrowPDA::NAME.as_bytes(),
+            token_base.info.key.as_ref(),
+            &[escrow_bump],
+        ],
+        ctx.accounts.sale_authority,
+        ctx.accounts.system_program,
+    )?;
+
+    let mut escrow_data = ctx.accounts.escrow.try_borrow_mut_data()?;
+    let escrow = Escrow::load_uninitialized_mut(&mut escrow_data)?;
+
+    escrow.discriminator = Escrow::SPL_DISCRIMINATOR.into();
+    escrow.token_base = *token_base.info.key;
+    escrow.team = team;
+    escrow.approver = approver.unwrap_or_default();
+    escrow.milestone_bps[..milestone_bps.len()].copy_from_slice(&milestone_bps);
+    escrow.vote_threshold_bps = vote_threshold_bps;
+    escrow.milestone_count = milestone_bps.len() as u8;
+    escrow.bump = escrow_bump; // store canonical bump
+
+    // - Makes the escrow the sale's vault
+    token_base.vault = escrow_pda;
+    token_base.is_escrow = true.into();
+
+    EscrowInitialized {
+        token_base: *token_base.info.key,
+        escrow: escrow_pda,
+        team,
+        approver: escrow.approver,
+        milestone_bps,
+        vote_threshold_bps,
+    }
+    .emit()?;
+
+    Ok(())
+}
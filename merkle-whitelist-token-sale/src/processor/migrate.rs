@@ -0,0 +1,183 @@
+use crate::error::TokenSaleError;
+use crate::events::{AccountMigrated, Event};
+use crate::pda::BuyerFactsPDA;
+use crate::state::{BuyerFacts, BuyerFactsV1, BuyerFactsV2, TokenBase, TokenBaseV1};
+use crate::validation::{check_program_id, PayerSigner};
+use crate::{instruction::accounts::*, require};
+use borsh::BorshDeserialize;
+use solana_program::{
+    entrypoint::ProgramResult, msg, program::invoke, program_error::ProgramError, pubkey::Pubkey,
+    rent::Rent, system_instruction, system_program::ID as SYSTEM_PROGRAM_ID, sysvar::Sysvar,
+};
+use spl_discriminator::SplDiscriminate;
+
+/// Migrate a TokenBase or BuyerFacts to the current layout version
+///
+/// - Reallocs the account to the current size
+/// - Rewrites the account in the current format
+/// - Records the sale and buyer of a BuyerFacts, so snapshots find it
+/// - Tops up rent from `payer`
+///
+/// Permissionless, the layout change never alters the account's values.
+/// Other BuyerFacts fields added by the layout are zeroed.
+/// Accounts already at the current version are left untouched
+///
+/// Accounts
+/// 0. `[WRITE]`    `Account` TokenBase or BuyerFacts to migrate
+/// 1. `[WRITE, SIGNER]` `Payer` account, funds the extra rent
+/// 2. `[]`         `System Program`
+/// 3. `[]`         (optional) `Token Base` account of the sale, required by BuyerFacts
+/// 4. `[]`         (optional) `Buyer` account, required by BuyerFacts
+///
+/// Instruction Data
+/// - (None)
+///
+/// Data Validations
+/// - BuyerFacts seeds are ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+pub fn process_migrate(program_id: &Pubkey, ctx: Context<MigrateAccounts>) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 0. account
+    //
+    // - owner is token_sale (this) program
+    // - discriminator is TokenBase or BuyerFacts
+    let account = ctx.accounts.account;
+
+    // - owner is token_sale (this) program
+    require!(
+        account.owner == program_id,
+        ProgramError::InvalidAccountOwner,
+        "account"
+    );
+
+    // 1. payer
+    //
+    // - must be signer
+    let payer = PayerSigner::new(ctx.accounts.payer)?;
+
+    // 2. system_program
+    //
+    // - must be official system program
+    check_program_id(
+        ctx.accounts.system_program,
+        &SYSTEM_PROGRAM_ID,
+        "system_program",
+    )?;
+
+    //---------- Data Validations (if any) ----------
+
+    // - discriminator is TokenBase or BuyerFacts
+    // all versions share the discriminator, length tells them apart
+    let data = account.try_borrow_data()?;
+    let token_base_discriminator =
+        data.get(TokenBase::DISCRIMINATOR_OFFSET..TokenBase::DISCRIMINATOR_OFFSET + 8);
+    let buyer_facts_discriminator = data.get(..8);
+
+    let (migrated, from_version, to_version) =
+        if token_base_discriminator == Some(TokenBase::SPL_DISCRIMINATOR_SLICE) {
+            match data.len() {
+                TokenBase::LEN => return Ok(()),
+                TokenBaseV1::LEN => {
+                    let v1 = TokenBaseV1::try_from_slice(&data)?;
+                    (
+                        bytemuck::bytes_of(&TokenBase::from(v1)).to_vec(),
+                        1,
+                        TokenBase::VERSION,
+                    )
+                }
+                _ => return Err(TokenSaleError::InvalidAccountDataLength.into()),
+            }
+        } else if buyer_facts_discriminator == Some(BuyerFacts::SPL_DISCRIMINATOR_SLICE) {
+            match data.len() {
+                BuyerFacts::LEN => return Ok(()),
+                BuyerFactsV1::LEN => {
+                    let v1 = BuyerFactsV1::try_from_slice(&data)?;
+                    let buyer_facts = record_sale(program_id, &ctx, BuyerFacts::from(v1))?;
+                    (
+                        bytemuck::bytes_of(&buyer_facts).to_vec(),
+                        1,
+                        BuyerFacts::VERSION,
+                    )
+                }
+                BuyerFactsV2::LEN => {
+                    let v2 = BuyerFactsV2::try_from_slice(&data)?;
+                    let buyer_facts = record_sale(program_id, &ctx, BuyerFacts::from(v2))?;
+                    (
+                        bytemuck::bytes_of(&buyer_facts).to_vec(),
+                        2,
+                        BuyerFacts::VERSION,
+                    )
+                }
+                _ => return Err(TokenSaleError::InvalidAccountDataLength.into()),
+            }
+        } else {
+            return Err(ProgramError::InvalidAccountData);
+        };
+    drop(data);
+
+    //---------- Executing Instruction ----------
+
+    // - Tops up rent from `payer`
+    let rent_sysvar = &Rent::get()?;
+    let rent_due = rent_sysvar
+        .minimum_balance(migrated.len())
+        .saturating_sub(account.lamports());
+
+    if rent_due > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, rent_due),
+            &[
+                payer.0.clone(),
+                account.clone(),
+                ctx.accounts.system_program.clone(),
+            ],
+        )?;
+    }
+
+    // - Reallocs the account to the current size
+    account.realloc(migrated.len(), true)?;
+
+    // - Rewrites the account in the current format
+    account.try_borrow_mut_data()?.copy_from_slice(&migrated);
+
+    AccountMigrated {
+        account: *account.key,
+        from_version,
+        to_version,
+    }
+    .emit()?;
+
+    Ok(())
+}
+
+/// Fill in the sale and buyer older BuyerFacts layouts didn't store
+///
+/// - token_base and buyer are passed
+/// - BuyerFacts seeds are ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+fn record_sale(
+    program_id: &Pubkey,
+    ctx: &Context<MigrateAccounts>,
+    mut buyer_facts: BuyerFacts,
+) -> Result<BuyerFacts, ProgramError> {
+    // 3. - 4. token_base, buyer
+    //
+    // - token_base and buyer are passed
+    let (Some(token_base), Some(buyer)) = (ctx.accounts.token_base, ctx.accounts.buyer) else {
+        msg!("Constraint failed: token_base, buyer");
+        return Err(TokenSaleError::InvalidBuyerFactsSeeds.into());
+    };
+
+    // - BuyerFacts seeds are ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+    let expected =
+        BuyerFactsPDA::create_pda(program_id, token_base.key, buyer.key, buyer_facts.bump)
+            .map_err(|_| TokenSaleError::InvalidBuyerFactsSeeds)?;
+    require!(
+        *ctx.accounts.account.key == expected,
+        TokenSaleError::InvalidBuyerFactsSeeds,
+        "account"
+    );
+
+    buyer_facts.token_base = *token_base.key;
+    buyer_facts.buyer = *buyer.key;
+    Ok(buyer_facts)
+}
@@ -23,8 +23,44 @@ use register_buyer::*;
 pub mod deregister_buyer;
 use deregister_buyer::*;
 
-// pub mod buy_token;
-// use buy_token::*;
+pub mod buy_token;
+use buy_token::*;
+
+pub mod reclaim_buyer_facts;
+use reclaim_buyer_facts::*;
+
+pub mod migrate;
+use migrate::*;
+
+pub mod close_receipt;
+use close_receipt::*;
+
+pub mod claim_referral;
+use claim_referral::*;
+
+pub mod quote;
+use quote::*;
+
+pub mod register_and_buy;
+use register_and_buy::*;
+
+pub mod initialize_escrow;
+use initialize_escrow::*;
+
+pub mod vote_milestone;
+use vote_milestone::*;
+
+pub mod release_milestone;
+use release_milestone::*;
+
+pub mod cancel_escrow;
+use cancel_escrow::*;
+
+pub mod refund_escrow;
+use refund_escrow::*;
+
+pub mod update_sale_settings;
+use update_sale_settings::*;
 
 /// Program state processor
 pub struct Processor {}
@@ -93,6 +129,90 @@ impl<'a> Processor {
             TokenSaleInstruction::DeregisterBuyer => {
                 process_deregister_buyer(program_id, DeregisterBuyerAccounts::context(accounts)?)?;
             }
+
+            TokenSaleInstruction::BuyToken { amount, proof } => {
+                process_buy_token(
+                    program_id,
+                    BuyTokenAccounts::context(accounts)?,
+                    amount,
+                    proof,
+                )?;
+            }
+
+            TokenSaleInstruction::ReclaimBuyerFacts => {
+                process_reclaim_buyer_facts(
+                    program_id,
+                    ReclaimBuyerFactsAccounts::context(accounts)?,
+                )?;
+            }
+
+            TokenSaleInstruction::Migrate => {
+                process_migrate(program_id, MigrateAccounts::context(accounts)?)?;
+            }
+
+            TokenSaleInstruction::CloseReceipt => {
+                process_close_receipt(program_id, CloseReceiptAccounts::context(accounts)?)?;
+            }
+
+            TokenSaleInstruction::ClaimReferral => {
+                process_claim_referral(program_id, ClaimReferralAccounts::context(accounts)?)?;
+            }
+
+            TokenSaleInstruction::Quote { amount, proof } => {
+                process_quote(program_id, QuoteAccounts::context(accounts)?, amount, proof)?;
+            }
+
+            TokenSaleInstruction::RegisterAndBuy { amount, proof } => {
+                process_register_and_buy(
+                    program_id,
+                    RegisterAndBuyAccounts::context(accounts)?,
+                    amount,
+                    proof,
+                )?;
+            }
+
+            TokenSaleInstruction::InitializeEscrow {
+                team,
+                approver,
+                milestone_bps,
+                vote_threshold_bps,
+            } => {
+                process_initialize_escrow(
+                    program_id,
+                    InitializeEscrowAccounts::context(accounts)?,
+                    team,
+                    approver,
+                    milestone_bps,
+                    vote_threshold_bps,
+                )?;
+            }
+
+            TokenSaleInstruction::VoteMilestone => {
+                process_vote_milestone(program_id, VoteMilestoneAccounts::context(accounts)?)?;
+            }
+
+            TokenSaleInstruction::ReleaseMilestone => {
+                process_release_milestone(
+                    program_id,
+                    ReleaseMilestoneAccounts::context(accounts)?,
+                )?;
+            }
+
+            TokenSaleInstruction::CancelEscrow => {
+                process_cancel_escrow(program_id, CancelEscrowAccounts::context(accounts)?)?;
+            }
+
+            TokenSaleInstruction::RefundEscrow => {
+                process_refund_escrow(program_id, RefundEscrowAccounts::context(accounts)?)?;
+            }
+
+            TokenSaleInstruction::UpdateSaleSettings { settings } => {
+                process_update_sale_settings(
+                    program_id,
+                    UpdateSaleSettingsAccounts::context(accounts)?,
+                    settings,
+                )?;
+            }
         }
 
         Ok(())
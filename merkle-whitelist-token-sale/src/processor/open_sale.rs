@@ -1,30 +1,46 @@
 use crate::error::TokenSaleError;
+use crate::events::{Event, SaleOpened};
 use crate::merkle::WhitelistRoot;
-use crate::pda::TokenBasePDA;
-use crate::state::TokenBase;
+use crate::pda::{create_pda_account, TokenBasePDA};
+use crate::registry::append_sale;
+use crate::state::{RootRecord, TokenBase, ZeroCopyAccount};
+use crate::validation::{
+    check_not_executable, check_program_id, is_closed_sale, SaleAuthoritySigner, ValidatedMint,
+};
 use crate::{
     instruction::accounts::{Context, OpenSaleAccounts},
     require,
 };
-use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    entrypoint::ProgramResult, program::invoke_signed, program_error::ProgramError,
-    program_pack::Pack, pubkey::Pubkey, rent::Rent, system_instruction,
+    clock::Clock, entrypoint::ProgramResult, program::invoke, pubkey::Pubkey,
     system_program::ID as SYSTEM_PROGRAM_ID, sysvar::Sysvar,
 };
 use spl_discriminator::SplDiscriminate;
-use spl_token::{error::TokenError, state::Mint};
+use spl_token::instruction::{set_authority, AuthorityType};
 
 /// Open a Token Sale with the given config
 ///
+/// - Records whitelist_root as the first root of the root history,
+///   active from this slot
+/// - Lists the sale in the global and sale authority [`Registry`](crate::state::Registry),
+///   creating the registries and their append pages on first use
+/// - Hands the mint authority to the TokenBase PDA, so purchases mint
+///   without the sale authority's signature. It stays with the sale once
+///   closed, fixing the supply
+///
 /// Initializes the [`TokenBase`] PDA account (config)
 ///
 /// Accounts
 /// 0. `[WRITE]`    `Token Base` config account, PDA generated offchain
-/// 1. `[]`         `Mint` account
+/// 1. `[WRITE]`    `Mint` account, mint authority is the sale authority
 /// 2. `[]`         `Vault` account
-/// 3. `[SIGNER]`   `Sale Authority` account
+/// 3. `[WRITE, SIGNER]` `Sale Authority` account, pays the TokenBase rent
 /// 4. `[]`         `System Program`
+/// 5. `[WRITE]`    `Registry` account, PDA generated offchain
+/// 6. `[WRITE]`    `Registry Page` account, append page of the registry
+/// 7. `[WRITE]`    `Authority Registry` account, PDA generated offchain
+/// 8. `[WRITE]`    `Authority Registry Page` account, append page of the authority registry
+/// 9. `[]`         `Token Program`
 ///
 /// Instruction Data
 /// - price: u64,
@@ -44,14 +60,23 @@ pub fn process_open_sale(
 
     // 0. token_base
     //
+    // - sale was never closed
     // - account is uninitialized
     // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
 
+    // - sale was never closed, its BuyerFacts may still exist
+    require!(
+        !(ctx.accounts.token_base.owner == program_id
+            && is_closed_sale(program_id, ctx.accounts.token_base)),
+        TokenSaleError::SaleEnded,
+        "token_base"
+    );
+
     // - account is uninitialized
     let token_base_data = ctx.accounts.token_base.try_borrow_data()?;
     require!(
         token_base_data.len() == 0,
-        ProgramError::AccountAlreadyInitialized,
+        TokenSaleError::SaleAlreadyOpened,
         "token_base"
     );
     drop(token_base_data);
@@ -64,116 +89,140 @@ pub fn process_open_sale(
     );
     require!(
         *ctx.accounts.token_base.key == token_base_pda,
-        ProgramError::InvalidSeeds,
+        TokenSaleError::InvalidTokenBaseSeeds,
         "token_base"
     );
 
-    // 1. mint
+    // 3. sale_authority
     //
-    // - is_initialized is true
-    // - mint_authority is token_base sale_authority
-    let mint = ctx.accounts.mint;
-    let mint_data = mint.try_borrow_data()?;
-    let mint_state = Mint::unpack(&mint_data)?;
+    // - not executable
+    // - must be signer
+    let sale_authority = SaleAuthoritySigner::new(ctx.accounts.sale_authority)?;
 
+    // 1. mint
+    //
     // - is_initialized is true
-    require!(
-        mint_state.is_initialized,
-        TokenError::UninitializedState,
-        "mint"
-    );
-
-    // - mint_authority is token_base sale_authority
-    require!(
-        mint_state.mint_authority.unwrap() == *ctx.accounts.sale_authority.key,
-        TokenSaleError::MintAndSaleAuthorityMismatch,
-        "mint"
-    );
+    // - mint_authority is sale_authority
+    let mint = ValidatedMint::new(ctx.accounts.mint, sale_authority.key)?;
 
     // 2. vault
     //
     // - not executable
     let vault = ctx.accounts.vault;
+    check_not_executable(vault, "vault")?;
 
-    // - not executable
-    require!(
-        !vault.executable,
-        TokenSaleError::MustBeNonExecutable,
-        "vault"
-    );
-
-    // 3. sale_authority
+    // 4. system_program
     //
-    // - not executable
-    // - must be signer
-    let sale_authority = ctx.accounts.sale_authority;
-
-    // - not executable
-    require!(
-        !sale_authority.executable,
-        TokenSaleError::MustBeNonExecutable,
-        "sale_authority"
-    );
+    // - must be official system program
+    check_program_id(
+        ctx.accounts.system_program,
+        &SYSTEM_PROGRAM_ID,
+        "system_program",
+    )?;
 
-    // - must be signer
+    // 9. token_program
+    //
+    // - key must be the same as official SPL Token Program ID
     require!(
-        sale_authority.is_signer,
-        TokenSaleError::NeedSigner,
-        "sale_authority"
+        *ctx.accounts.token_program.key == spl_token::ID,
+        TokenSaleError::InvalidTokenProgramID,
+        "token_program"
     );
 
-    // 4. system_program
+    // 5. - 8. registry, registry_page, authority_registry, authority_registry_page
     //
-    // - must be official system program
-    require!(
-        *ctx.accounts.system_program.key == SYSTEM_PROGRAM_ID,
-        ProgramError::IncorrectProgramId,
-        "system_program"
-    );
+    // - registry seeds must be ["registry"]
+    // - authority_registry seeds must be ["registry", pubkey(sale_authority)]
+    // - page seeds must be ["registry_page", pubkey(registry), registry.append_page()]
+    // checked while listing the sale
 
     //---------- Data Validations (if any) ----------
 
     //---------- Executing Instruction ----------
 
     // inititalize token_base
-    let rent_sysvar = &Rent::get()?;
-
-    invoke_signed(
-        &system_instruction::create_account(
-            ctx.accounts.sale_authority.key,
-            ctx.accounts.token_base.key,
-            rent_sysvar.minimum_balance(TokenBase::LEN),
-            TokenBase::LEN as u64,
-            program_id,
-        ),
+    create_pda_account(
+        program_id,
+        ctx.accounts.token_base,
+        TokenBase::LEN,
         &[
-            ctx.accounts.sale_authority.clone(),
-            ctx.accounts.token_base.clone(),
-        ],
-        &[&[
             TokenBasePDA::NAME.as_bytes(),
             ctx.accounts.sale_authority.key.as_ref(),
             ctx.accounts.mint.key.as_ref(),
             &[token_base_bump],
-        ]],
+        ],
+        ctx.accounts.sale_authority,
+        ctx.accounts.system_program,
     )?;
 
     let mut token_base_data = ctx.accounts.token_base.try_borrow_mut_data()?;
-    let mut token_base = TokenBase::try_from_slice(&token_base_data)?;
+    let token_base = TokenBase::load_uninitialized_mut(&mut token_base_data)?;
 
     // update values
     token_base.discriminator = TokenBase::SPL_DISCRIMINATOR.into();
-    token_base.mint = *mint.key;
+    token_base.mint = *mint.info.key;
     token_base.vault = *vault.key;
     token_base.sale_authority = *sale_authority.key;
     token_base.whitelist_root = whitelist_root;
+    token_base.root_history[0] = RootRecord {
+        root: whitelist_root,
+        activation_slot: Clock::get()?.slot,
+    };
     token_base.price = price;
     token_base.default_purchase_limit = purchase_limit;
-    token_base.is_running = false;
+    token_base.is_running = false.into();
     token_base.bump = token_base_bump; // store canonical bump
+    token_base.version = TokenBase::VERSION;
+    token_base.is_registered = true.into();
+
+    SaleOpened {
+        token_base: *ctx.accounts.token_base.key,
+        sale_authority: token_base.sale_authority,
+        mint: token_base.mint,
+        vault: token_base.vault,
+        whitelist_root: token_base.whitelist_root.0,
+        price: token_base.price,
+        default_purchase_limit: token_base.default_purchase_limit,
+    }
+    .emit()?;
+    drop(token_base_data);
 
-    // store new values
-    token_base.serialize(&mut &mut token_base_data[..]).unwrap();
+    // hand the mint authority to token_base
+    invoke(
+        &set_authority(
+            &spl_token::ID,
+            ctx.accounts.mint.key,
+            Some(ctx.accounts.token_base.key),
+            AuthorityType::MintTokens,
+            sale_authority.key,
+            &[],
+        )?,
+        &[
+            ctx.accounts.mint.clone(),
+            ctx.accounts.sale_authority.clone(),
+            ctx.accounts.token_program.clone(),
+        ],
+    )?;
+
+    // list the sale in the registries
+    for (registry, registry_page, authority) in [
+        (ctx.accounts.registry, ctx.accounts.registry_page, None),
+        (
+            ctx.accounts.authority_registry,
+            ctx.accounts.authority_registry_page,
+            Some(sale_authority.key),
+        ),
+    ] {
+        append_sale(
+            program_id,
+            registry,
+            registry_page,
+            authority,
+            ctx.accounts.token_base.key,
+            ctx.accounts.sale_authority,
+            ctx.accounts.system_program,
+        )?;
+    }
 
     Ok(())
 }
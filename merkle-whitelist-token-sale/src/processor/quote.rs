@@ -0,0 +1,200 @@
+use super::buy_token::{check_purchase, WhitelistAccounts};
+use crate::error::TokenSaleError;
+use crate::math::purchase_cost;
+use crate::merkle::WhitelistProof;
+use crate::pda::BuyerFactsPDA;
+use crate::state::BuyerFacts;
+use crate::validation::{ValidatedBuyerFacts, ValidatedEscrow, ValidatedTokenBase};
+use crate::{instruction::accounts::*, require};
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::Zeroable;
+use solana_program::{
+    clock::Clock, entrypoint::ProgramResult, msg, program::set_return_data,
+    program_error::ProgramError, program_pack::Pack, pubkey::Pubkey, sysvar::Sysvar,
+};
+use spl_token::state::Mint;
+
+/// Quoted purchase, returned by Quote via return data
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq)]
+pub struct Quote {
+    /// Quoted amount of tokens, in base units
+    pub amount: u64,
+    /// Lamports BuyToken would charge, 0 if the cost overflows
+    pub lamports_cost: u64,
+    /// Buyer's purchase limit before the purchase
+    pub purchase_limit: u64,
+    /// Buyer's purchase limit left after the purchase, 0 if exceeded
+    pub remaining_limit: u64,
+    /// [`TokenSaleError`] code BuyToken would fail with, None if eligible
+    pub error: Option<u32>,
+}
+
+impl Quote {
+    /// Is `true` if BuyToken would accept the purchase
+    pub fn is_eligible(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Decode the return data of a simulated Quote
+    pub fn from_return_data(data: &[u8]) -> Option<Self> {
+        Self::try_from_slice(data).ok()
+    }
+}
+
+/// Quote a purchase without executing it
+///
+/// - Runs BuyToken's purchase checks and pricing
+/// - Returns a [`Quote`] via return data
+/// - Never mutates state, meant for `simulateTransaction`
+///
+/// Failed purchase checks are reported in [`Quote::error`] rather than
+/// failing the instruction, invalid accounts still fail it
+///
+/// Buyers without a BuyerFacts are quoted as RegisterAndBuy would register
+/// them, with the sale's default purchase limit and no purchase cooldown
+///
+/// Permissionless
+///
+/// Accounts
+/// 0. `[]`         `Token Base` config account, PDA generated offchain
+/// 1. `[]`         `Mint` account
+/// 2. `[]`         `Buyer Facts` buyer config account, PDA generated offchain, may be uninitialized
+/// 3. `[]`         `Buyer` account
+/// 4. `[]`         `Instructions Sysvar` (optional), required by voucher whitelists
+/// 5. `[]`         `Holder Token Account` (optional), required by holder-gated sales
+/// 6. `[]`         `Holder Metadata` (optional), required by collection-gated sales
+/// 7. `[]`         `Escrow` (optional), required by escrowed sales
+/// 8. `[]`         `Holder Mint` (optional), required by collection-gated sales
+///
+/// Instruction Data
+/// - amount: u64,
+/// - proof: WhitelistProof
+///
+/// Data Validations
+/// - (None), BuyToken's are reported in the quote
+pub fn process_quote(
+    program_id: &Pubkey,
+    ctx: Context<QuoteAccounts>,
+    amount: u64,
+    proof: WhitelistProof,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 0. token_base
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+
+    // 1. mint
+    //
+    // - token_base mint is mint
+    // - is_initialized is true
+    require!(
+        token_base.mint == *ctx.accounts.mint.key,
+        TokenSaleError::MintMismatch,
+        "mint"
+    );
+    let mint = Mint::unpack(&ctx.accounts.mint.try_borrow_data()?)?;
+
+    // 2. buyer_facts
+    //
+    // - seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+    // - initialized accounts are owned by token_sale (this) program
+    let buyer_facts = if ctx.accounts.buyer_facts.data_is_empty() {
+        let (buyer_facts_pda, buyer_facts_canonical_bump) = BuyerFactsPDA::find_pda(
+            program_id,
+            ctx.accounts.token_base.key,
+            ctx.accounts.buyer.key,
+        );
+        require!(
+            *ctx.accounts.buyer_facts.key == buyer_facts_pda,
+            TokenSaleError::InvalidBuyerFactsSeeds,
+            "buyer_facts"
+        );
+
+        // registered in this slot like RegisterAndBuy, never purchased
+        let mut buyer_facts = BuyerFacts::zeroed();
+        buyer_facts.purchase_limit = token_base.default_purchase_limit;
+        buyer_facts.bump = buyer_facts_canonical_bump;
+        buyer_facts.registered_slot = Clock::get()?.slot;
+        buyer_facts.token_base = *token_base.info.key;
+        buyer_facts.buyer = *ctx.accounts.buyer.key;
+        buyer_facts
+    } else {
+        *ValidatedBuyerFacts::new(
+            program_id,
+            ctx.accounts.buyer_facts,
+            ctx.accounts.token_base.key,
+            ctx.accounts.buyer.key,
+        )?
+    };
+
+    // 4. instructions_sysvar (optional)
+    // 5. holder_token_account (optional)
+    // 6. holder_metadata (optional)
+    // 8. holder_mint (optional)
+    //
+    // - checked like in BuyToken
+
+    // 7. escrow (optional)
+    //
+    // - required if the sale is escrowed
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - escrow token_base is token_base
+    // - seeds must be ["escrow", pubkey(token_base)]
+    let escrow = match (token_base.is_escrow(), ctx.accounts.escrow) {
+        (true, Some(escrow)) => Some(ValidatedEscrow::new(
+            program_id,
+            escrow,
+            token_base.info.key,
+        )?),
+        (true, None) => {
+            msg!("Constraint failed: escrow");
+            return Err(TokenSaleError::InvalidEscrowSeeds.into());
+        }
+        (false, _) => None,
+    };
+
+    //---------- Executing Instruction ----------
+
+    let eligibility = check_purchase(
+        &token_base,
+        escrow.as_deref(),
+        &buyer_facts,
+        ctx.accounts.buyer.key,
+        amount,
+        proof,
+        WhitelistAccounts {
+            instructions_sysvar: ctx.accounts.instructions_sysvar,
+            holder_token_account: ctx.accounts.holder_token_account,
+            holder_metadata: ctx.accounts.holder_metadata,
+            holder_mint: ctx.accounts.holder_mint,
+        },
+        &Clock::get()?,
+    );
+    let lamports_cost = purchase_cost(token_base.price, amount, mint.decimals);
+
+    // only TokenSaleErrors describe the purchase, anything else fails the quote
+    let error = match eligibility.err().or(lamports_cost.clone().err()) {
+        Some(error) => Some(
+            TokenSaleError::from_program_error(&error)
+                .ok_or::<ProgramError>(error)?
+                .code(),
+        ),
+        None => None,
+    };
+
+    let quote = Quote {
+        amount,
+        lamports_cost: lamports_cost.unwrap_or(0),
+        purchase_limit: buyer_facts.purchase_limit,
+        remaining_limit: buyer_facts.purchase_limit.saturating_sub(amount),
+        error,
+    };
+    set_return_data(&borsh::to_vec(&quote)?);
+
+    Ok(())
+}
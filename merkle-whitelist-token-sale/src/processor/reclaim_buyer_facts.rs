@@ -0,0 +1,116 @@
+use crate::error::TokenSaleError;
+use crate::events::{BuyerFactsReclaimed, Event};
+use crate::validation::{check_not_executable, is_closed_sale, ValidatedBuyerFacts};
+use crate::{instruction::accounts::*, require};
+use solana_program::{
+    entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+};
+
+/// Close a buyer's BuyerFacts orphaned by a closed sale
+///
+/// - Closes the [`BuyerFacts`](crate::state::BuyerFacts) account
+/// - Returns rent lamports to the account that paid them
+/// - Pays unclaimed referral rewards to the Buyer
+///
+/// Permissionless, anyone can crank this once the sale is closed
+///
+/// Accounts
+/// 0. `[]`         `Token Base` closed config account, PDA generated offchain
+/// 1. `[WRITE]`    `Buyer Facts` buyer config account, PDA generated offchain
+/// 2. `[WRITE]`    `Buyer` account, receives the unclaimed referral rewards
+/// 3. `[WRITE]`    `Payer` account that paid the rent on RegisterBuyer, receives it
+///
+/// Instruction Data
+/// - (None)
+///
+/// Data Validations
+/// - token_base sale is closed
+pub fn process_reclaim_buyer_facts(
+    program_id: &Pubkey,
+    ctx: Context<ReclaimBuyerFactsAccounts>,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 0. token_base
+    //
+    // - account is closed (ClosedTokenBase tombstone, or owned by System Program with no data)
+    require!(
+        is_closed_sale(program_id, ctx.accounts.token_base),
+        TokenSaleError::SaleNotClosed,
+        "token_base"
+    );
+
+    // 2. buyer
+    //
+    // - not executable
+    check_not_executable(ctx.accounts.buyer, "buyer")?;
+
+    // 1. buyer_facts
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+    let buyer_facts = ValidatedBuyerFacts::new(
+        program_id,
+        ctx.accounts.buyer_facts,
+        ctx.accounts.token_base.key,
+        ctx.accounts.buyer.key,
+    )?;
+    let referral_rewards = buyer_facts.referral_rewards;
+
+    // 3. payer
+    //
+    // - must be the account that paid the rent, the buyer for older BuyerFacts
+    require!(
+        *ctx.accounts.payer.key == buyer_facts.rent_payer(ctx.accounts.buyer.key),
+        TokenSaleError::PayerMismatch,
+        "payer"
+    );
+    drop(buyer_facts);
+
+    //---------- Data Validations (if any) ----------
+
+    //---------- Executing Instruction ----------
+
+    // buyer_facts
+    let buyer_facts_account_info = ctx.accounts.buyer_facts;
+    let buyer_facts_lamports = buyer_facts_account_info.lamports();
+
+    // - Pays unclaimed referral rewards to the Buyer
+    let buyer_account_info = ctx.accounts.buyer;
+    let buyer_lamports = buyer_account_info.lamports();
+    **buyer_account_info.try_borrow_mut_lamports()? = buyer_lamports
+        .checked_add(referral_rewards)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    // - Returns rent lamports to the account that paid them
+    let rent_lamports = buyer_facts_lamports
+        .checked_sub(referral_rewards)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    let payer_account_info = ctx.accounts.payer;
+    let payer_lamports = payer_account_info.lamports();
+    **payer_account_info.try_borrow_mut_lamports()? = payer_lamports
+        .checked_add(rent_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    // zero out buyer_facts (PDA) lamports
+    **buyer_facts_account_info.try_borrow_mut_lamports()? = 0;
+
+    // - Closes the [`BuyerFacts`] account
+    // fill with 0s = no data
+    buyer_facts_account_info.try_borrow_mut_data()?.fill(0);
+
+    buyer_facts_account_info.realloc(0, false)?;
+    buyer_facts_account_info.assign(&SYSTEM_PROGRAM_ID);
+
+    BuyerFactsReclaimed {
+        token_base: *ctx.accounts.token_base.key,
+        buyer: *buyer_account_info.key,
+        buyer_facts: *buyer_facts_account_info.key,
+        reclaimed_lamports: buyer_facts_lamports,
+    }
+    .emit()?;
+
+    Ok(())
+}
@@ -0,0 +1,122 @@
+use crate::error::TokenSaleError;
+use crate::events::{EscrowRefunded, Event};
+use crate::validation::{
+    check_not_executable, ValidatedBuyerFacts, ValidatedEscrow, ValidatedTokenBase,
+};
+use crate::{instruction::accounts::*, require};
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+
+/// Refund a buyer's share of a cancelled escrow
+///
+/// - Transfers the buyer's pro rata share of the refund pool, rounded
+///   down so every refund stays payable
+/// - Marks the buyer refunded
+/// - Settles the sale once every deposit is refunded, so it can close
+///
+/// Permissionless, the refund always goes to the buyer
+///
+/// Accounts
+/// 0. `[WRITE]`    `Token Base` config account, PDA generated offchain
+/// 1. `[WRITE]`    `Escrow` account, PDA generated offchain
+/// 2. `[WRITE]`    `Buyer Facts` buyer config account, PDA generated offchain
+/// 3. `[WRITE]`    `Buyer` account, receives the refund
+///
+/// Instruction Data
+/// - (None)
+///
+/// Data Validations
+/// - escrow is cancelled
+/// - buyer has escrowed lamports not refunded yet
+pub fn process_refund_escrow(
+    program_id: &Pubkey,
+    ctx: Context<RefundEscrowAccounts>,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 0. token_base
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let mut token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+
+    // 1. escrow
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - escrow token_base is token_base
+    // - seeds must be ["escrow", pubkey(token_base)]
+    let mut escrow = ValidatedEscrow::new(program_id, ctx.accounts.escrow, token_base.info.key)?;
+
+    // 3. buyer
+    //
+    // - not executable
+    let buyer = ctx.accounts.buyer;
+    check_not_executable(buyer, "buyer")?;
+
+    // 2. buyer_facts
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+    let mut buyer_facts = ValidatedBuyerFacts::new(
+        program_id,
+        ctx.accounts.buyer_facts,
+        token_base.info.key,
+        buyer.key,
+    )?;
+
+    //---------- Data Validations (if any) ----------
+
+    // - escrow is cancelled
+    require!(
+        escrow.is_cancelled(),
+        TokenSaleError::EscrowNotCancelled,
+        "escrow"
+    );
+
+    // - buyer has escrowed lamports not refunded yet
+    require!(
+        buyer_facts.has_escrow_deposit(),
+        TokenSaleError::NoEscrowDeposit,
+        "buyer_facts"
+    );
+
+    //---------- Executing Instruction ----------
+
+    let refunded_lamports = escrow.refund(buyer_facts.escrow_deposited)?;
+
+    // - Marks the buyer refunded
+    buyer_facts.escrow_refunded = true.into();
+    escrow.refunded_deposits = escrow
+        .refunded_deposits
+        .checked_add(buyer_facts.escrow_deposited)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    // - Settles the sale once every deposit is refunded
+    if escrow.is_fully_refunded() {
+        token_base.is_escrow_settled = true.into();
+    }
+
+    // - Transfers the buyer's share of the refund pool
+    let escrow_lamports = escrow.info.lamports();
+    let buyer_lamports = buyer.lamports();
+
+    // direct transfer escrow (PDA) lamports into buyer
+    // NOTE: Direct transfer is okay since escrow is a PDA owned by this program
+    **escrow.info.try_borrow_mut_lamports()? = escrow_lamports
+        .checked_sub(refunded_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **buyer.try_borrow_mut_lamports()? = buyer_lamports
+        .checked_add(refunded_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    EscrowRefunded {
+        token_base: *token_base.info.key,
+        buyer: *buyer.key,
+        refunded_lamports,
+    }
+    .emit()?;
+
+    Ok(())
+}
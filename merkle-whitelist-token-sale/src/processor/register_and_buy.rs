@@ -0,0 +1,133 @@
+use super::buy_token::process_buy_token;
+use super::register_buyer::initialize_buyer_facts;
+use crate::error::TokenSaleError;
+use crate::merkle::WhitelistProof;
+use crate::pda::BuyerFactsPDA;
+use crate::validation::{check_program_id, BuyerSigner, PayerSigner, ValidatedTokenBase};
+use crate::{
+    instruction::accounts::{BuyTokenAccounts, Context, RegisterAndBuyAccounts},
+    require,
+};
+use solana_program::{
+    entrypoint::ProgramResult, pubkey::Pubkey, system_program::ID as SYSTEM_PROGRAM_ID,
+};
+
+/// Register as a Buyer if needed and buy N amount of Tokens
+///
+/// - Generates the buyer's BuyerFacts if missing, rent paid by `payer`
+/// - Buys like BuyToken, see [`process_buy_token`]
+///
+/// An existing BuyerFacts is used as is. New buyers are registered in this
+/// slot, so sales with a minimum registration age reject their purchase.
+///
+/// For Buyer
+///
+/// Accounts
+/// - Same as BuyToken, `Buyer Facts` may be uninitialized
+///
+/// Instruction Data
+/// - amount: u64,
+/// - proof: WhitelistProof
+///
+/// Data Validations
+/// - Same as BuyToken
+pub fn process_register_and_buy(
+    program_id: &Pubkey,
+    ctx: Context<RegisterAndBuyAccounts>,
+    amount: u64,
+    proof: WhitelistProof,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 5. buyer_facts
+    //
+    // - initialized accounts are validated by BuyToken
+    if ctx.accounts.buyer_facts.data_is_empty() {
+        // 0. token_base
+        //
+        // - owner is token_sale (this) program
+        // - account is initialized
+        // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+        let token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+
+        // 5. buyer_facts
+        //
+        // - seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+        let (buyer_facts_pda, buyer_facts_canonical_bump) = BuyerFactsPDA::find_pda(
+            program_id,
+            ctx.accounts.token_base.key,
+            ctx.accounts.buyer.key,
+        );
+        require!(
+            *ctx.accounts.buyer_facts.key == buyer_facts_pda,
+            TokenSaleError::InvalidBuyerFactsSeeds,
+            "buyer_facts"
+        );
+
+        // 6. buyer
+        //
+        // - not executable
+        // - must be signer
+        let buyer = BuyerSigner::new(ctx.accounts.buyer)?;
+
+        // 7. payer
+        //
+        // - must be signer
+        let payer = PayerSigner::new(ctx.accounts.payer)?;
+
+        // 8. system_program
+        //
+        // - must be official system program
+        check_program_id(
+            ctx.accounts.system_program,
+            &SYSTEM_PROGRAM_ID,
+            "system_program",
+        )?;
+
+        //---------- Executing Instruction ----------
+
+        // - Generates the buyer's BuyerFacts if missing
+        // holder gates are checked by the purchase below
+        initialize_buyer_facts(
+            program_id,
+            &token_base,
+            ctx.accounts.buyer_facts,
+            buyer.key,
+            &payer,
+            ctx.accounts.system_program,
+            buyer_facts_canonical_bump,
+        )?;
+
+        // token_base borrow is released here, BuyToken validates it again
+    }
+
+    // - Buys like BuyToken
+    process_buy_token(
+        program_id,
+        Context {
+            accounts: BuyTokenAccounts {
+                token_base: ctx.accounts.token_base,
+                mint: ctx.accounts.mint,
+                vault: ctx.accounts.vault,
+                sale_authority: ctx.accounts.sale_authority,
+                buyer_token_account: ctx.accounts.buyer_token_account,
+                buyer_facts: ctx.accounts.buyer_facts,
+                buyer: ctx.accounts.buyer,
+                payer: ctx.accounts.payer,
+                system_program: ctx.accounts.system_program,
+                token_program: ctx.accounts.token_program,
+                associated_token_program: ctx.accounts.associated_token_program,
+                receipt: ctx.accounts.receipt,
+                referrer_facts: ctx.accounts.referrer_facts,
+                referrer: ctx.accounts.referrer,
+                instructions_sysvar: ctx.accounts.instructions_sysvar,
+                holder_token_account: ctx.accounts.holder_token_account,
+                holder_metadata: ctx.accounts.holder_metadata,
+                holder_mint: ctx.accounts.holder_mint,
+            },
+            remaining_accounts: ctx.remaining_accounts,
+        },
+        amount,
+        proof,
+    )
+}
@@ -1,17 +1,23 @@
 use crate::error::TokenSaleError;
-use crate::pda::BuyerFactsPDA;
-use crate::state::{BuyerFacts, TokenBase};
+use crate::events::{BuyerRegistered, Event};
+use crate::holder::check_holder;
+use crate::pda::{create_pda_account, BuyerFactsPDA};
+use crate::state::{BuyerFacts, ZeroCopyAccount};
+use crate::validation::{check_program_id, BuyerSigner, PayerSigner, ValidatedTokenBase};
 use crate::{instruction::accounts::*, require};
-use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    entrypoint::ProgramResult, program::invoke_signed, program_error::ProgramError, pubkey::Pubkey,
-    rent::Rent, system_instruction, system_program::ID as SYSTEM_PROGRAM_ID, sysvar::Sysvar,
+    account_info::AccountInfo, clock::Clock, entrypoint::ProgramResult, pubkey::Pubkey,
+    system_program::ID as SYSTEM_PROGRAM_ID, sysvar::Sysvar,
 };
 use spl_discriminator::SplDiscriminate;
 
 /// Register as a Buyer
 ///
 /// - Generates the buyer's BuyerFacts
+/// - Records the registration slot for the sale's minimum registration age
+/// - Rent is paid by `payer`, letting a relayer sponsor registration,
+///   and is returned to it when the BuyerFacts is closed
+/// - Holder-gated sales require proof of holding the gating token or NFT
 ///
 /// For Buyer
 ///
@@ -19,13 +25,17 @@ use spl_discriminator::SplDiscriminate;
 /// 0. `[]`         `Token Base` config account, PDA generated offchain
 /// 1. `[WRITE]`    `Buyer Facts` buyer config account, PDA generated offchain
 /// 2. `[SIGNER]`   `Buyer` account
-/// 3. `[]`         `System Program`
+/// 3. `[WRITE, SIGNER]` `Payer` account, funds the BuyerFacts rent
+/// 4. `[]`         `System Program`
+/// 5. `[]`         `Holder Token Account` (optional), required by holder-gated sales
+/// 6. `[]`         `Holder Metadata` (optional), required by collection-gated sales
+/// 7. `[]`         `Holder Mint` (optional), required by collection-gated sales
 ///
 /// Instruction Data
 /// - (None)
 ///
 /// Data Validations
-/// - (None)
+/// - buyer holds the gating token or NFT, in holder-gated sales
 pub fn process_register_buyer(
     program_id: &Pubkey,
     ctx: Context<RegisterBuyerAccounts>,
@@ -34,16 +44,10 @@ pub fn process_register_buyer(
 
     // 0. token_base
     //
+    // - owner is token_sale (this) program
     // - account is initialized
-
-    // - account is initialized
-    let token_base_data = ctx.accounts.token_base.try_borrow_mut_data()?;
-    let token_base = TokenBase::try_from_slice(&token_base_data)?;
-    require!(
-        token_base.is_initialized(),
-        ProgramError::UninitializedAccount,
-        "token_base"
-    );
+    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
 
     // 1. buyer_facts
     //
@@ -54,7 +58,7 @@ pub fn process_register_buyer(
     let buyer_facts_data = ctx.accounts.buyer_facts.try_borrow_data()?;
     require!(
         buyer_facts_data.len() == 0,
-        ProgramError::AccountAlreadyInitialized,
+        TokenSaleError::BuyerAlreadyRegistered,
         "buyer_facts"
     );
     drop(buyer_facts_data);
@@ -67,7 +71,7 @@ pub fn process_register_buyer(
     );
     require!(
         *ctx.accounts.buyer_facts.key == buyer_facts_pda,
-        ProgramError::InvalidSeeds,
+        TokenSaleError::InvalidBuyerFactsSeeds,
         "buyer_facts"
     );
 
@@ -75,66 +79,99 @@ pub fn process_register_buyer(
     //
     // - not executable
     // - must be signer
+    let buyer = BuyerSigner::new(ctx.accounts.buyer)?;
 
-    // - not executable
-    require!(
-        !ctx.accounts.buyer.executable,
-        TokenSaleError::MustBeNonExecutable,
-        "buyer"
-    );
-
+    // 3. payer
+    //
     // - must be signer
-    require!(
-        ctx.accounts.buyer.is_signer,
-        TokenSaleError::NeedSigner,
-        "buyer"
-    );
+    let payer = PayerSigner::new(ctx.accounts.payer)?;
 
-    // 3. system_program
+    // 4. system_program
     //
     // - must be official system program
-    require!(
-        *ctx.accounts.system_program.key == SYSTEM_PROGRAM_ID,
-        ProgramError::IncorrectProgramId,
-        "system_program"
-    );
+    check_program_id(
+        ctx.accounts.system_program,
+        &SYSTEM_PROGRAM_ID,
+        "system_program",
+    )?;
+
+    // 5. holder_token_account (optional)
+    // 6. holder_metadata (optional)
+    // 7. holder_mint (optional)
+    //
+    // - checked against the holder gate
 
     //---------- Data Validations (if any) ----------
 
+    // - buyer holds the gating token or NFT, in holder-gated sales
+    check_holder(
+        &token_base,
+        buyer.key,
+        ctx.accounts.holder_token_account,
+        ctx.accounts.holder_metadata,
+        ctx.accounts.holder_mint,
+    )?;
+
     //---------- Executing Instruction ----------
 
-    // inititalize token_base
-    let rent_sysvar = &Rent::get()?;
-
-    invoke_signed(
-        &system_instruction::create_account(
-            ctx.accounts.buyer.key,
-            ctx.accounts.buyer_facts.key,
-            rent_sysvar.minimum_balance(BuyerFacts::LEN),
-            BuyerFacts::LEN as u64,
-            program_id,
-        ),
-        &[ctx.accounts.buyer.clone(), ctx.accounts.buyer_facts.clone()],
-        &[&[
+    initialize_buyer_facts(
+        program_id,
+        &token_base,
+        ctx.accounts.buyer_facts,
+        buyer.key,
+        &payer,
+        ctx.accounts.system_program,
+        buyer_facts_canonical_bump,
+    )?;
+
+    Ok(())
+}
+
+/// Create and initialize a buyer's BuyerFacts, shared with RegisterAndBuy
+///
+/// `buyer_facts` must be the uninitialized BuyerFacts PDA of `buyer`,
+/// `bump` its canonical bump. `payer` is recorded to get the rent back
+pub(crate) fn initialize_buyer_facts<'info>(
+    program_id: &Pubkey,
+    token_base: &ValidatedTokenBase<'_, 'info>,
+    buyer_facts_info: &AccountInfo<'info>,
+    buyer: &Pubkey,
+    payer: &PayerSigner<'_, 'info>,
+    system_program: &AccountInfo<'info>,
+    bump: u8,
+) -> ProgramResult {
+    create_pda_account(
+        program_id,
+        buyer_facts_info,
+        BuyerFacts::LEN,
+        &[
             BuyerFactsPDA::NAME.as_bytes(),
-            ctx.accounts.token_base.key.as_ref(),
-            ctx.accounts.buyer.key.as_ref(),
-            &[buyer_facts_canonical_bump],
-        ]],
+            token_base.info.key.as_ref(),
+            buyer.as_ref(),
+            &[bump],
+        ],
+        payer.0,
+        system_program,
     )?;
 
-    let mut buyer_facts_data = ctx.accounts.buyer_facts.try_borrow_mut_data()?;
-    let mut buyer_facts = BuyerFacts::try_from_slice(&buyer_facts_data)?;
+    let mut buyer_facts_data = buyer_facts_info.try_borrow_mut_data()?;
+    let buyer_facts = BuyerFacts::load_uninitialized_mut(&mut buyer_facts_data)?;
 
     // update values
     buyer_facts.discriminator = BuyerFacts::SPL_DISCRIMINATOR.into();
     buyer_facts.purchase_limit = token_base.default_purchase_limit;
-    buyer_facts.bump = buyer_facts_canonical_bump;
-
-    // store new values
-    buyer_facts
-        .serialize(&mut &mut buyer_facts_data[..])
-        .unwrap();
-
-    Ok(())
+    buyer_facts.bump = bump;
+    buyer_facts.version = BuyerFacts::VERSION;
+    buyer_facts.registered_slot = Clock::get()?.slot;
+    buyer_facts.token_base = *token_base.info.key;
+    buyer_facts.buyer = *buyer;
+    buyer_facts.payer = *payer.key;
+
+    BuyerRegistered {
+        token_base: *token_base.info.key,
+        buyer: *buyer,
+        buyer_facts: *buyer_facts_info.key,
+        purchase_limit: buyer_facts.purchase_limit,
+    }
+    .emit()
 }
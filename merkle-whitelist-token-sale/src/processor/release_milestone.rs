@@ -0,0 +1,140 @@
+use crate::error::TokenSaleError;
+use crate::events::{Event, MilestoneReleased};
+use crate::validation::{EscrowAuthoritySigner, ValidatedEscrow, ValidatedTokenBase};
+use crate::{instruction::accounts::*, require};
+use solana_program::{entrypoint::ProgramResult, program_error::ProgramError, pubkey::Pubkey};
+
+/// Release the escrow's next milestone to the team
+///
+/// - Transfers the milestone's share of the escrowed lamports to the team,
+///   with the share of earlier milestones in lamports deposited since
+/// - Clears the approvals for the following milestone
+/// - Settles the escrow once the last milestone is released
+///
+/// For Escrow Approver, or anyone once the buyers' vote passed
+///
+/// Accounts
+/// 0. `[WRITE]`    `Token Base` config account, PDA generated offchain
+/// 1. `[WRITE]`    `Escrow` account, PDA generated offchain
+/// 2. `[WRITE]`    `Team` account, receives the tranche
+/// 3. `[SIGNER]`   `Authority` account, approver or any account
+///
+/// Instruction Data
+/// - (None)
+///
+/// Data Validations
+/// - sale is not running
+/// - escrow is not cancelled
+/// - escrow has a milestone left to release
+/// - milestone is signed off by the approver or approved by vote
+pub fn process_release_milestone(
+    program_id: &Pubkey,
+    ctx: Context<ReleaseMilestoneAccounts>,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 0. token_base
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let mut token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+
+    // 1. escrow
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - escrow token_base is token_base
+    // - seeds must be ["escrow", pubkey(token_base)]
+    let mut escrow = ValidatedEscrow::new(program_id, ctx.accounts.escrow, token_base.info.key)?;
+
+    // 2. team
+    //
+    // - escrow team is team
+    let team = ctx.accounts.team;
+    require!(
+        escrow.team == *team.key,
+        TokenSaleError::TeamMismatch,
+        "team"
+    );
+
+    // 3. authority
+    //
+    // - not executable
+    // - must be signer
+    let authority = EscrowAuthoritySigner::new(ctx.accounts.authority)?;
+
+    //---------- Data Validations (if any) ----------
+
+    // - sale is not running
+    require!(
+        !token_base.is_running(),
+        TokenSaleError::SaleStillRunning,
+        "token_base"
+    );
+
+    // - escrow is not cancelled
+    require!(
+        !escrow.is_cancelled(),
+        TokenSaleError::EscrowCancelled,
+        "escrow"
+    );
+
+    // - escrow has a milestone left to release
+    require!(
+        escrow.has_pending_milestone(),
+        TokenSaleError::EscrowSettled,
+        "escrow"
+    );
+
+    // - milestone is signed off by the approver or approved by vote
+    let by_vote = !escrow.is_approver(authority.key);
+    require!(
+        !by_vote || escrow.is_vote_passed(),
+        TokenSaleError::MilestoneNotApproved,
+        "authority"
+    );
+
+    //---------- Executing Instruction ----------
+
+    let milestone = escrow.milestones_released;
+    let release_target = escrow.release_target()?;
+    let released_lamports = release_target
+        .checked_sub(escrow.total_released)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    // - Transfers the milestone's share to the team
+    let escrow_lamports = escrow.info.lamports();
+    let team_lamports = team.lamports();
+
+    // direct transfer escrow (PDA) lamports into team
+    // NOTE: Direct transfer is okay since escrow is a PDA owned by this program
+    **escrow.info.try_borrow_mut_lamports()? = escrow_lamports
+        .checked_sub(released_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+    **team.try_borrow_mut_lamports()? = team_lamports
+        .checked_add(released_lamports)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    escrow.total_released = release_target;
+    escrow.milestones_released = milestone + 1;
+
+    // - Clears the approvals for the following milestone
+    escrow.approve_weight = 0;
+
+    // - Settles the escrow once the last milestone is released
+    if !escrow.has_pending_milestone() {
+        token_base.is_escrow_settled = true.into();
+    }
+
+    MilestoneReleased {
+        token_base: *token_base.info.key,
+        team: *team.key,
+        milestone,
+        by_vote,
+        released_lamports,
+    }
+    .emit()?;
+
+    Ok(())
+}
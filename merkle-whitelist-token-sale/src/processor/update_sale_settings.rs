@@ -0,0 +1,199 @@
+use super::configure_sale::sale_configured;
+use crate::error::TokenSaleError;
+use crate::events::Event;
+use crate::instruction::{SaleSettings, SaleSettingsV1, SaleSettingsV2};
+use crate::math::BPS_DENOMINATOR;
+use crate::state::WhitelistMode;
+use crate::validation::{SaleAuthoritySigner, ValidatedMint, ValidatedTokenBase};
+use crate::{instruction::accounts::*, require};
+use solana_program::{clock::Clock, entrypoint::ProgramResult, pubkey::Pubkey, sysvar::Sysvar};
+
+/// Update the Token Sale settings beyond ConfigureSale
+///
+/// - Modifies one or more than from: referral_bps, min_registration_slots,
+///   purchase_cooldown_slots, whitelist_mode, whitelist_signer, gate_key,
+///   gate_min_amount, whitelist_root, root_grace_slots, end_slot of
+///   [`TokenBase`](crate::state::TokenBase)
+/// - Records a new whitelist_root in the root history, activating at
+///   root_activation_slot or right away. Proofs against the replaced
+///   root keep verifying for root_grace_slots after it activates
+/// - Bumps the sale phase if whitelist_root is set
+///
+/// For Token Sale Authority
+///
+/// Accounts
+/// 0. `[WRITE]`    `Token Base` config account, PDA generated offchain
+/// 1. `[]`         `Mint` account
+/// 2. `[SIGNER]`   `Sale Authority` account
+///
+/// Instruction Data
+/// - settings: SaleSettings, versioned, see [`SaleSettingsV2`]
+///
+/// Data Validations
+/// - at least one of the settings is Some()
+/// - referral_bps is at most 10000
+/// - voucher whitelists have a whitelist_signer
+/// - holder-gated sales have a gate_key
+/// - root_activation_slot comes with a whitelist_root and is not in the past
+/// - sale has not reached its end slot
+/// - end_slot is in the future and not earlier than the current end_slot
+pub fn process_update_sale_settings(
+    program_id: &Pubkey,
+    ctx: Context<UpdateSaleSettingsAccounts>,
+    settings: SaleSettings,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 2. sale_authority
+    //
+    // - not executable
+    // - must be signer
+    let sale_authority = SaleAuthoritySigner::new(ctx.accounts.sale_authority)?;
+
+    // 1. mint
+    //
+    // - is_initialized is true
+    // - mint_authority is token_base
+    let mint = ValidatedMint::new(ctx.accounts.mint, ctx.accounts.token_base.key)?;
+
+    // 0. token_base
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - token_base seeds must be ["token_base", pubkey(sale_authority), pubkey(mint)]
+    let mut token_base = ValidatedTokenBase::new(program_id, ctx.accounts.token_base)?;
+    token_base.check_sale_authority(&sale_authority)?;
+    token_base.check_mint(&mint)?;
+
+    let settings = settings.into_latest();
+
+    //---------- Data Validations (if any) ----------
+
+    // ensure fail if none of the settings is Some(). It means it'll
+    // be a wasteful tx. No state change needed
+    require!(
+        !settings.is_empty(),
+        TokenSaleError::NothingToConfigure,
+        "settings"
+    );
+
+    let SaleSettingsV2 {
+        v1:
+            SaleSettingsV1 {
+                referral_bps,
+                min_registration_slots,
+                purchase_cooldown_slots,
+                whitelist_mode,
+                whitelist_signer,
+                gate_key,
+                gate_min_amount,
+                whitelist_root,
+                root_activation_slot,
+                root_grace_slots,
+            },
+        end_slot,
+    } = settings;
+
+    // - referral_bps is at most 10000
+    require!(
+        !matches!(referral_bps, Some(bps) if bps as u64 > BPS_DENOMINATOR),
+        TokenSaleError::InvalidBasisPoints,
+        "referral_bps"
+    );
+
+    // - root_activation_slot comes with a whitelist_root and is not in the past
+    let slot = Clock::get()?.slot;
+    require!(
+        root_activation_slot.is_none()
+            || (whitelist_root.is_some() && root_activation_slot >= Some(slot)),
+        TokenSaleError::InvalidRootActivation,
+        "root_activation_slot"
+    );
+
+    // - sale has not reached its end slot
+    // an ended sale is final, it only awaits CloseSale
+    require!(
+        !token_base.is_ended(slot),
+        TokenSaleError::SaleEnded,
+        "token_base"
+    );
+
+    // - end_slot is in the future and not earlier than the current end_slot
+    // buyers can rely on the sale not closing before it
+    require!(
+        end_slot.map_or(true, |end_slot| end_slot > slot
+            && end_slot >= token_base.end_slot),
+        TokenSaleError::InvalidEndSlot,
+        "end_slot"
+    );
+
+    //---------- Executing Instruction ----------
+
+    // - a new whitelist root is recorded, the replaced one stays in the history
+    // and starts a new phase
+    if let Some(root) = whitelist_root {
+        token_base.phase = token_base
+            .phase
+            .checked_add(1)
+            .ok_or(TokenSaleError::MathOverflow)?;
+        token_base.rotate_whitelist_root(root, root_activation_slot.unwrap_or(slot), slot);
+    }
+
+    if let Some(end_slot) = end_slot {
+        token_base.end_slot = end_slot;
+    }
+
+    if let Some(slots) = root_grace_slots {
+        token_base.root_grace_slots = slots;
+    }
+
+    if let Some(bps) = referral_bps {
+        token_base.referral_bps = bps;
+    }
+
+    if let Some(slots) = min_registration_slots {
+        token_base.min_registration_slots = slots;
+    }
+
+    if let Some(slots) = purchase_cooldown_slots {
+        token_base.purchase_cooldown_slots = slots;
+    }
+
+    if let Some(mode) = whitelist_mode {
+        token_base.whitelist_mode = mode as u8;
+    }
+
+    if let Some(signer) = whitelist_signer {
+        token_base.whitelist_signer = signer;
+    }
+
+    // - voucher whitelists have a whitelist_signer
+    require!(
+        token_base.whitelist_mode()? != WhitelistMode::Voucher
+            || token_base.whitelist_signer != Pubkey::default(),
+        TokenSaleError::WhitelistSignerMissing,
+        "whitelist_signer"
+    );
+
+    if let Some(key) = gate_key {
+        token_base.gate_key = key;
+    }
+
+    if let Some(min_amount) = gate_min_amount {
+        token_base.gate_min_amount = min_amount;
+    }
+
+    // - holder-gated sales have a gate_key
+    require!(
+        !matches!(
+            token_base.whitelist_mode()?,
+            WhitelistMode::TokenHolder | WhitelistMode::CollectionHolder
+        ) || token_base.gate_key != Pubkey::default(),
+        TokenSaleError::GateKeyMissing,
+        "gate_key"
+    );
+
+    sale_configured(ctx.accounts.token_base.key, &token_base).emit()?;
+
+    Ok(())
+}
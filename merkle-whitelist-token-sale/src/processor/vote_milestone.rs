@@ -0,0 +1,117 @@
+use crate::error::TokenSaleError;
+use crate::events::{Event, MilestoneVoted};
+use crate::validation::{BuyerSigner, ValidatedBuyerFacts, ValidatedEscrow};
+use crate::{instruction::accounts::*, require};
+use solana_program::{entrypoint::ProgramResult, pubkey::Pubkey};
+
+/// Approve the escrow's next milestone
+///
+/// - Adds the buyer's escrowed lamports to the milestone's approvals
+/// - Once approvals reach `vote_threshold_bps` of the escrowed lamports,
+///   anyone may release the milestone
+///
+/// For Buyer
+///
+/// Accounts
+/// 0. `[]`         `Token Base` config account, PDA generated offchain
+/// 1. `[WRITE]`    `Escrow` account, PDA generated offchain
+/// 2. `[WRITE]`    `Buyer Facts` buyer config account, PDA generated offchain
+/// 3. `[SIGNER]`   `Buyer` account
+///
+/// Instruction Data
+/// - (None)
+///
+/// Data Validations
+/// - escrow is not cancelled
+/// - escrow has a milestone left to release
+/// - escrow approves milestones by vote
+/// - buyer has escrowed lamports
+/// - buyer did not approve this milestone yet
+pub fn process_vote_milestone(
+    program_id: &Pubkey,
+    ctx: Context<VoteMilestoneAccounts>,
+) -> ProgramResult {
+    //---------- Account Validations ----------
+
+    // 0. token_base
+    //
+    // - checked through the escrow and buyer_facts seeds
+    let token_base = ctx.accounts.token_base.key;
+
+    // 1. escrow
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - escrow token_base is token_base
+    // - seeds must be ["escrow", pubkey(token_base)]
+    let mut escrow = ValidatedEscrow::new(program_id, ctx.accounts.escrow, token_base)?;
+
+    // 3. buyer
+    //
+    // - not executable
+    // - must be signer
+    let buyer = BuyerSigner::new(ctx.accounts.buyer)?;
+
+    // 2. buyer_facts
+    //
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+    let mut buyer_facts =
+        ValidatedBuyerFacts::new(program_id, ctx.accounts.buyer_facts, token_base, buyer.key)?;
+
+    //---------- Data Validations (if any) ----------
+
+    // - escrow is not cancelled
+    require!(
+        !escrow.is_cancelled(),
+        TokenSaleError::EscrowCancelled,
+        "escrow"
+    );
+
+    // - escrow has a milestone left to release
+    require!(
+        escrow.has_pending_milestone(),
+        TokenSaleError::EscrowSettled,
+        "escrow"
+    );
+
+    // - escrow approves milestones by vote
+    require!(
+        escrow.vote_threshold_bps > 0,
+        TokenSaleError::MilestoneNotApproved,
+        "escrow"
+    );
+
+    // - buyer has escrowed lamports
+    let weight = buyer_facts.escrow_deposited;
+    require!(weight > 0, TokenSaleError::NoEscrowDeposit, "buyer_facts");
+
+    // - buyer did not approve this milestone yet
+    let milestone = escrow.milestones_released;
+    require!(
+        buyer_facts.escrow_voted != milestone + 1,
+        TokenSaleError::AlreadyVoted,
+        "buyer_facts"
+    );
+
+    //---------- Executing Instruction ----------
+
+    // - Adds the buyer's escrowed lamports to the milestone's approvals
+    escrow.approve_weight = escrow
+        .approve_weight
+        .checked_add(weight)
+        .ok_or(TokenSaleError::MathOverflow)?;
+    buyer_facts.escrow_voted = milestone + 1;
+
+    MilestoneVoted {
+        token_base: *token_base,
+        buyer: *buyer.key,
+        milestone,
+        weight,
+        approve_weight: escrow.approve_weight,
+    }
+    .emit()?;
+
+    Ok(())
+}
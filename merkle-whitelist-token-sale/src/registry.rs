@@ -0,0 +1,262 @@
+/// Launchpad registry bookkeeping
+///
+/// OpenSale lists every sale in the global [`Registry`] and in the
+/// registry of its sale authority, CloseSale removes it from both.
+/// Sales are stored across [`RegistryPage`] accounts so UIs can list
+/// them with `getMultipleAccounts` instead of `getProgramAccounts`.
+use crate::error::TokenSaleError;
+use crate::pda::{create_pda_account, RegistryPDA, RegistryPagePDA};
+use crate::require;
+use crate::state::{Registry, RegistryPage, ZeroCopyAccount};
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, program::invoke,
+    program_error::ProgramError, pubkey::Pubkey, rent::Rent, system_instruction, sysvar::Sysvar,
+};
+use spl_discriminator::SplDiscriminate;
+
+/// Lists `token_base` on the append page of the registry of `authority`,
+/// the global registry if None
+///
+/// - Creates the registry and the page on first use
+/// - Grows the page by one entry, `payer` tops up its rent
+///
+/// Returns the page the sale was listed on
+pub fn append_sale<'info>(
+    program_id: &Pubkey,
+    registry_info: &AccountInfo<'info>,
+    page_info: &AccountInfo<'info>,
+    authority: Option<&Pubkey>,
+    token_base: &Pubkey,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<u64, ProgramError> {
+    // - registry seeds must be ["registry"] or ["registry", pubkey(sale_authority)]
+    let (registry_pda, registry_bump) = RegistryPDA::find_pda(program_id, authority);
+    require!(
+        *registry_info.key == registry_pda,
+        TokenSaleError::InvalidRegistry,
+        "registry"
+    );
+
+    // - creates the registry on first use
+    if registry_info.data_is_empty() {
+        let authority_seed = authority.map(|authority| authority.as_ref());
+        let bump_seed = [registry_bump];
+        let seeds: Vec<&[u8]> = [RegistryPDA::NAME.as_bytes()]
+            .into_iter()
+            .chain(authority_seed)
+            .chain([bump_seed.as_slice()])
+            .collect();
+        create_pda_account(
+            program_id,
+            registry_info,
+            Registry::LEN,
+            &seeds,
+            payer,
+            system_program,
+        )?;
+
+        let mut registry_data = registry_info.try_borrow_mut_data()?;
+        let registry = Registry::load_uninitialized_mut(&mut registry_data)?;
+        registry.discriminator = Registry::SPL_DISCRIMINATOR.into();
+        registry.authority = authority.copied().unwrap_or_default();
+        registry.bump = registry_bump;
+    }
+
+    // - owner is token_sale (this) program
+    // - account is initialized
+    require!(
+        registry_info.owner == program_id,
+        ProgramError::InvalidAccountOwner,
+        "registry"
+    );
+    let page = Registry::load(&registry_info.try_borrow_data()?)?.append_page();
+
+    // - page seeds must be ["registry_page", pubkey(registry), registry.append_page()]
+    let (page_pda, page_bump) = RegistryPagePDA::find_pda(program_id, registry_info.key, page);
+    require!(
+        *page_info.key == page_pda,
+        TokenSaleError::InvalidRegistryPage,
+        "registry_page"
+    );
+
+    let len = if page_info.data_is_empty() {
+        // - creates the page on first use
+        create_pda_account(
+            program_id,
+            page_info,
+            RegistryPage::space(1),
+            &[
+                RegistryPagePDA::NAME.as_bytes(),
+                registry_info.key.as_ref(),
+                &page.to_le_bytes(),
+                &[page_bump],
+            ],
+            payer,
+            system_program,
+        )?;
+
+        let mut page_data = page_info.try_borrow_mut_data()?;
+        let header = RegistryPage::load_uninitialized_mut(&mut page_data[..RegistryPage::LEN])?;
+        header.discriminator = RegistryPage::SPL_DISCRIMINATOR.into();
+        header.registry = *registry_info.key;
+        header.page = page;
+        header.bump = page_bump;
+        0
+    } else {
+        // - owner is token_sale (this) program
+        // - account is initialized
+        let len = load_page(program_id, page_info)?.len;
+
+        // - grows the page by one entry
+        grow(
+            page_info,
+            RegistryPage::space(len + 1),
+            payer,
+            system_program,
+        )?;
+        len
+    };
+
+    let mut page_data = page_info.try_borrow_mut_data()?;
+    RegistryPage::entries_mut(&mut page_data)[len as usize] = *token_base;
+    RegistryPage::load_mut(&mut page_data[..RegistryPage::LEN])?.len = len + 1;
+
+    let mut registry_data = registry_info.try_borrow_mut_data()?;
+    let registry = Registry::load_mut(&mut registry_data)?;
+    if page == registry.page_count {
+        registry.page_count += 1;
+        registry.tail_len = 0;
+    }
+    registry.tail_len += 1;
+    registry.sale_count += 1;
+
+    Ok(page)
+}
+
+/// Removes `token_base` from the page of the registry of `authority`
+/// listing it, the global registry if None
+///
+/// - Moves the page's last entry into the freed slot
+/// - Shrinks the page by one entry, excess rent goes to `refund`
+pub fn remove_sale<'info>(
+    program_id: &Pubkey,
+    registry_info: &AccountInfo<'info>,
+    page_info: &AccountInfo<'info>,
+    authority: Option<&Pubkey>,
+    token_base: &Pubkey,
+    refund: &AccountInfo<'info>,
+) -> ProgramResult {
+    // - registry seeds must be ["registry"] or ["registry", pubkey(sale_authority)]
+    require!(
+        *registry_info.key == RegistryPDA::find_pda(program_id, authority).0,
+        TokenSaleError::InvalidRegistry,
+        "registry"
+    );
+
+    // - owner is token_sale (this) program
+    // - account is initialized
+    require!(
+        registry_info.owner == program_id,
+        ProgramError::InvalidAccountOwner,
+        "registry"
+    );
+    let mut registry_data = registry_info.try_borrow_mut_data()?;
+    let registry = Registry::load_mut(&mut registry_data)?;
+
+    // - owner is token_sale (this) program
+    // - account is initialized
+    // - page seeds must be ["registry_page", pubkey(registry), registry_page.page]
+    let header = load_page(program_id, page_info)?;
+    let page_pda =
+        RegistryPagePDA::create_pda(program_id, registry_info.key, header.page, header.bump)
+            .map_err(|_| TokenSaleError::InvalidRegistryPage)?;
+    require!(
+        header.registry == *registry_info.key && *page_info.key == page_pda,
+        TokenSaleError::InvalidRegistryPage,
+        "registry_page"
+    );
+
+    // - page lists token_base
+    let len = header.len;
+    let mut page_data = page_info.try_borrow_mut_data()?;
+    let entries = RegistryPage::entries_mut(&mut page_data);
+    let index = entries
+        .iter()
+        .position(|entry| entry == token_base)
+        .ok_or(TokenSaleError::InvalidRegistryPage)?;
+
+    // - moves the page's last entry into the freed slot
+    entries[index] = entries[len as usize - 1];
+    RegistryPage::load_mut(&mut page_data[..RegistryPage::LEN])?.len = len - 1;
+    drop(page_data);
+
+    // - shrinks the page by one entry
+    let space = RegistryPage::space(len - 1);
+    page_info.realloc(space, false)?;
+    let excess = page_info
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(space));
+
+    let refund_lamports = refund
+        .lamports()
+        .checked_add(excess)
+        .ok_or(ProgramError::ArithmeticOverflow)?;
+
+    // direct transfer page (PDA) lamports into refund
+    // NOTE: Direct transfer is okay since the page is a PDA owned by this program
+    **page_info.try_borrow_mut_lamports()? -= excess;
+    **refund.try_borrow_mut_lamports()? = refund_lamports;
+
+    if header.page + 1 == registry.page_count {
+        registry.tail_len -= 1;
+    }
+    registry.sale_count -= 1;
+
+    Ok(())
+}
+
+/// Header of an initialized page, its data holding exactly `len` entries
+fn load_page(program_id: &Pubkey, page_info: &AccountInfo) -> Result<RegistryPage, ProgramError> {
+    require!(
+        page_info.owner == program_id,
+        ProgramError::InvalidAccountOwner,
+        "registry_page"
+    );
+
+    let page_data = page_info.try_borrow_data()?;
+    require!(
+        page_data.len() >= RegistryPage::LEN,
+        TokenSaleError::InvalidAccountDataLength,
+        "registry_page"
+    );
+    let header = *RegistryPage::load(&page_data[..RegistryPage::LEN])?;
+    require!(
+        page_data.len() == RegistryPage::space(header.len),
+        TokenSaleError::InvalidAccountDataLength,
+        "registry_page"
+    );
+
+    Ok(header)
+}
+
+/// Reallocs `account` to `space` bytes, `payer` tops up its rent
+fn grow<'info>(
+    account: &AccountInfo<'info>,
+    space: usize,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> ProgramResult {
+    let rent_due = Rent::get()?
+        .minimum_balance(space)
+        .saturating_sub(account.lamports());
+
+    if rent_due > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, rent_due),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+
+    account.realloc(space, true)
+}
@@ -1,16 +1,20 @@
+use super::{BuyerFactsV1, BuyerFactsV2, PodBool, ZeroCopyAccount};
 use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
 use shank::ShankAccount;
+use solana_program::pubkey::Pubkey;
 use spl_discriminator::{ArrayDiscriminator, SplDiscriminate};
 
 // OPT-OUT: didn't use #[seeds()] because ShankAccount seeds
 // helper attribute is buggy. PDA is generated offchain
 // instead and seeds are validated on OpenSale
+// Seeds are published in the IDL through `crate::pda::Seed`
 
 // TODO: Cache-line optimization (if I have time left)
 
 #[repr(C)]
 #[rustfmt::skip] // ensure manual struct ordering
-#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, ShankAccount, SplDiscriminate)]
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, Debug, Pod, Zeroable, ShankAccount, SplDiscriminate)]
 #[discriminator_hash_input("token_sale::state::buyer_facts")]
 /// BuyerFacts holding per wallet buyer stats
 pub struct BuyerFacts {
@@ -20,16 +24,88 @@ pub struct BuyerFacts {
     pub purchase_limit: u64,
     /// Canonical bump for BuyerFacts
     pub bump: u8,
+    /// Account layout version, see [`BuyerFacts::VERSION`]
+    pub version: u8,
 
     /// Padding to remove SLOP in C memory layout alignment
     /// Widest scalar = 8bytes
-    _padding: [u8; 7]
+    _padding: [u8; 6],
+    /// Number of receipts issued to this buyer, nonce of the next Receipt
+    pub receipt_count: u64,
+    /// Unclaimed referral rewards, lamports held by this account
+    /// on top of its rent until ClaimReferral
+    pub referral_rewards: u64,
+    /// Wallet that referred this buyer, set by the first referred purchase
+    /// 
+    /// `Pubkey::default()` if never referred
+    pub referrer: Pubkey,
+    /// Slot of RegisterBuyer, 0 for buyers registered before it was tracked
+    pub registered_slot: u64,
+    /// Slot of the latest purchase, 0 if the buyer never purchased
+    pub last_purchase_slot: u64,
+    /// Tokens bought so far, checked against voucher allocations
+    ///
+    /// Never reset, DeregisterBuyer is refused once the buyer purchased
+    pub total_purchased: u64,
+    /// Sale this BuyerFacts belongs to, for `getProgramAccounts` filters
+    ///
+    /// Recorded by Migrate for accounts of older layouts
+    pub token_base: Pubkey,
+    /// Wallet this BuyerFacts belongs to, set along with `token_base`
+    pub buyer: Pubkey,
+    /// Lamports paid into the sale's escrow, voting weight and
+    /// basis of the refund
+    pub escrow_deposited: u64,
+    /// Milestone the buyer last approved, plus one, 0 if never voted
+    pub escrow_voted: u8,
+    /// Is `true` once refunded by a cancelled escrow
+    pub escrow_refunded: PodBool,
+
+    /// Padding to align the following fields
+    _padding2: [u8; 6],
+    /// Account that paid the rent on RegisterBuyer, gets it back when closed
+    ///
+    /// `Pubkey::default()` for buyers registered before it was tracked,
+    /// the buyer paid their rent
+    pub payer: Pubkey,
+    /// Zeroed space for future fields, shrinks as fields are added
+    /// so new fields don't change the account size
+    _reserved: [u8; 8]
 }
 
 impl BuyerFacts {
     /// Get known size of BuyerFacts
     pub const LEN: usize = std::mem::size_of::<BuyerFacts>();
 
+    /// Current account layout version
+    ///
+    /// - 1: [`BuyerFactsV1`], no version field
+    /// - 2: adds `version` and reserved space
+    /// - 3: adds `token_base` and `buyer`
+    pub const VERSION: u8 = 3;
+
+    /// Offset of `token_base`, for `memcmp` filters listing a sale's buyers
+    pub const TOKEN_BASE_OFFSET: usize = 8 + 8 + 1 + 1 + 6 + 8 + 8 + 32 + 8 + 8 + 8;
+
+    /// Is `true` if escrowed lamports of this buyer were not refunded
+    pub fn has_escrow_deposit(&self) -> bool {
+        self.escrow_deposited > 0 && !bool::from(self.escrow_refunded)
+    }
+
+    /// Account refunded with the rent once this BuyerFacts is closed
+    pub fn rent_payer(&self, buyer: &Pubkey) -> Pubkey {
+        if self.payer == Pubkey::default() {
+            *buyer
+        } else {
+            self.payer
+        }
+    }
+
+    /// Is `true` if a referrer is attributed to this buyer
+    pub fn is_referred(&self) -> bool {
+        self.referrer != Pubkey::default()
+    }
+
     /// Is `true` if BuyerFacts is initialized
     pub fn is_initialized(&self) -> bool {
         self.discriminator.as_slice() == BuyerFacts::SPL_DISCRIMINATOR_SLICE
@@ -40,3 +116,61 @@ impl BuyerFacts {
         self.discriminator.as_slice() == ArrayDiscriminator::UNINITIALIZED.as_slice()
     }
 }
+
+impl ZeroCopyAccount for BuyerFacts {
+    fn discriminator(&self) -> &[u8; 8] {
+        &self.discriminator
+    }
+}
+
+impl From<BuyerFactsV1> for BuyerFacts {
+    fn from(v1: BuyerFactsV1) -> Self {
+        BuyerFacts {
+            discriminator: v1.discriminator,
+            purchase_limit: v1.purchase_limit,
+            bump: v1.bump,
+            version: BuyerFacts::VERSION,
+            _padding: [0; 6],
+            receipt_count: 0,
+            referral_rewards: 0,
+            referrer: Pubkey::default(),
+            registered_slot: 0,
+            last_purchase_slot: 0,
+            total_purchased: 0,
+            token_base: Pubkey::default(),
+            buyer: Pubkey::default(),
+            escrow_deposited: 0,
+            escrow_voted: 0,
+            escrow_refunded: false.into(),
+            _padding2: [0; 6],
+            payer: Pubkey::default(),
+            _reserved: [0; 8],
+        }
+    }
+}
+
+impl From<BuyerFactsV2> for BuyerFacts {
+    fn from(v2: BuyerFactsV2) -> Self {
+        BuyerFacts {
+            discriminator: v2.discriminator,
+            purchase_limit: v2.purchase_limit,
+            bump: v2.bump,
+            version: BuyerFacts::VERSION,
+            _padding: [0; 6],
+            receipt_count: v2.receipt_count,
+            referral_rewards: v2.referral_rewards,
+            referrer: v2.referrer,
+            registered_slot: v2.registered_slot,
+            last_purchase_slot: v2.last_purchase_slot,
+            total_purchased: v2.total_purchased,
+            token_base: Pubkey::default(),
+            buyer: Pubkey::default(),
+            escrow_deposited: 0,
+            escrow_voted: 0,
+            escrow_refunded: false.into(),
+            _padding2: [0; 6],
+            payer: Pubkey::default(),
+            _reserved: [0; 8],
+        }
+    }
+}
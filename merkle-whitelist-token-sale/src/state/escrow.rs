@@ -0,0 +1,136 @@
+use super::{PodBool, ZeroCopyAccount};
+use crate::math::{mul_div, Rounding, BPS_DENOMINATOR};
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use shank::ShankAccount;
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use spl_discriminator::SplDiscriminate;
+
+// Seeds are published in the IDL through `crate::pda::Seed`
+
+/// Most milestones an [`Escrow`] releases the raise in
+pub const MAX_MILESTONES: usize = 4;
+
+#[repr(C)]
+#[rustfmt::skip] // ensure manual struct ordering
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, Debug, Pod, Zeroable, ShankAccount, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::state::escrow")]
+/// Escrow holding the lamports raised by a sale, the sale's vault
+///
+/// Released to the team in tranches, one per milestone signed off by the
+/// approver or by a vote of the buyers weighted by their escrowed
+/// lamports. Once cancelled the unreleased lamports are refunded to the
+/// buyers pro rata
+pub struct Escrow {
+    /// Identifier for this specific structure
+    pub discriminator: [u8; 8],
+    /// Sale whose raise is escrowed
+    pub token_base: Pubkey,
+    /// Account receiving the released tranches
+    pub team: Pubkey,
+    /// Key signing off milestones and allowed to cancel
+    ///
+    /// `Pubkey::default()` if milestones are only approved by vote
+    pub approver: Pubkey,
+    /// Lamports paid into the escrow by buyers
+    pub total_deposited: u64,
+    /// Lamports released to the team
+    pub total_released: u64,
+    /// Lamports unreleased at cancellation, refunded pro rata
+    pub refund_pool: u64,
+    /// Escrowed lamports of the buyers approving the next milestone
+    pub approve_weight: u64,
+    /// Share of the raise released by each milestone, in basis points,
+    /// summing to 10_000 over the first `milestone_count`
+    pub milestone_bps: [u16; 4], // MAX_MILESTONES, shank needs a literal
+    /// Share of the escrowed lamports whose buyers must approve a
+    /// milestone, in basis points, 0 disables voting
+    pub vote_threshold_bps: u16,
+    /// Number of milestones
+    pub milestone_count: u8,
+    /// Number of milestones released, index of the next milestone
+    pub milestones_released: u8,
+    /// Is `true` once cancelled, refunds replace releases
+    pub is_cancelled: PodBool,
+    /// Canonical bump for Escrow PDA
+    pub bump: u8,
+
+    /// Padding to remove SLOP in C memory layout alignment
+    /// Widest scalar = 8bytes
+    _padding: [u8; 2],
+    /// Escrowed lamports of the buyers refunded so far, the escrow
+    /// settles once every deposit is refunded
+    pub refunded_deposits: u64,
+    /// Zeroed space for future fields
+    _reserved: [u8; 56]
+}
+
+impl Escrow {
+    /// Get known size of Escrow
+    pub const LEN: usize = std::mem::size_of::<Escrow>();
+
+    /// Is `true` if Escrow is initialized
+    pub fn is_initialized(&self) -> bool {
+        self.discriminator.as_slice() == Escrow::SPL_DISCRIMINATOR_SLICE
+    }
+
+    /// Is `true` if the escrow was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.is_cancelled.into()
+    }
+
+    /// Is `true` if a milestone is left to release
+    pub fn has_pending_milestone(&self) -> bool {
+        self.milestones_released < self.milestone_count
+    }
+
+    /// Is `true` if `approver` may sign off milestones
+    pub fn is_approver(&self, approver: &Pubkey) -> bool {
+        self.approver != Pubkey::default() && self.approver == *approver
+    }
+
+    /// Is `true` if the buyers' vote approves the next milestone
+    pub fn is_vote_passed(&self) -> bool {
+        self.vote_threshold_bps > 0
+            && self.approve_weight > 0
+            && self.approve_weight as u128 * BPS_DENOMINATOR as u128
+                >= self.vote_threshold_bps as u128 * self.total_deposited as u128
+    }
+
+    /// Lamports released to the team once the next milestone is released,
+    /// every deposit after the last one
+    pub fn release_target(&self) -> Result<u64, ProgramError> {
+        let released_bps = self.milestone_bps[..=self.milestones_released as usize]
+            .iter()
+            .map(|bps| *bps as u64)
+            .sum();
+        mul_div(
+            self.total_deposited,
+            released_bps,
+            BPS_DENOMINATOR,
+            Rounding::Down,
+        )
+    }
+
+    /// Pro rata refund of a buyer who paid `deposited` lamports in,
+    /// rounds down so refunds never exceed the pool
+    pub fn refund(&self, deposited: u64) -> Result<u64, ProgramError> {
+        mul_div(
+            deposited,
+            self.refund_pool,
+            self.total_deposited,
+            Rounding::Down,
+        )
+    }
+
+    /// Is `true` once cancelled and every escrowed deposit was refunded
+    pub fn is_fully_refunded(&self) -> bool {
+        self.is_cancelled() && self.refunded_deposits == self.total_deposited
+    }
+}
+
+impl ZeroCopyAccount for Escrow {
+    fn discriminator(&self) -> &[u8; 8] {
+        &self.discriminator
+    }
+}
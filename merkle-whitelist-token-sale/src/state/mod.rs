@@ -1,5 +1,23 @@
+pub mod zero_copy;
+pub use zero_copy::*;
+
 pub mod token_base;
 pub use token_base::*;
 
 pub mod buyer_facts;
 pub use buyer_facts::*;
+
+pub mod v1;
+pub use v1::*;
+
+pub mod v2;
+pub use v2::*;
+
+pub mod receipt;
+pub use receipt::*;
+
+pub mod registry;
+pub use registry::*;
+
+pub mod escrow;
+pub use escrow::*;
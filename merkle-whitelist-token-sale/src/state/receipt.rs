@@ -0,0 +1,64 @@
+use super::ZeroCopyAccount;
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use shank::ShankAccount;
+use solana_program::pubkey::Pubkey;
+use spl_discriminator::SplDiscriminate;
+
+// Seeds are published in the IDL through `crate::pda::Seed`
+
+#[repr(C)]
+#[rustfmt::skip] // ensure manual struct ordering
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, Debug, Pod, Zeroable, ShankAccount, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::state::receipt")]
+/// Receipt recording a single purchase
+///
+/// Optionally issued by BuyToken, one per purchase, numbered by the
+/// buyer's BuyerFacts `receipt_count`
+pub struct Receipt {
+    /// Identifier for this specific structure
+    pub discriminator: [u8; 8],
+    /// Sale the purchase was made in
+    pub token_base: Pubkey,
+    /// Wallet that made the purchase
+    pub buyer: Pubkey,
+    /// Account that paid the rent on BuyToken, gets it back when closed
+    pub payer: Pubkey,
+    /// Position of this purchase among the buyer's receipts for the sale
+    pub nonce: u64,
+    /// Amount of tokens minted to the buyer
+    pub amount: u64,
+    /// Lamports transferred from the buyer to the vault
+    pub lamports_paid: u64,
+    /// TokenBase price at the time of purchase, lamports per whole token
+    pub price: u64,
+    /// Slot the purchase landed in
+    pub slot: u64,
+    /// TokenBase phase at the time of purchase
+    pub phase: u32,
+    /// Canonical bump for Receipt PDA
+    pub bump: u8,
+
+    /// Padding to remove SLOP in C memory layout alignment
+    /// Widest scalar = 8bytes
+    _padding: [u8; 3]
+}
+
+impl Receipt {
+    /// Get known size of Receipt
+    pub const LEN: usize = std::mem::size_of::<Receipt>();
+
+    /// Offset of `buyer`, for `memcmp` filters listing a wallet's receipts
+    pub const BUYER_OFFSET: usize = 8 + 32; // discriminator, token_base
+
+    /// Is `true` if Receipt is initialized
+    pub fn is_initialized(&self) -> bool {
+        self.discriminator.as_slice() == Receipt::SPL_DISCRIMINATOR_SLICE
+    }
+}
+
+impl ZeroCopyAccount for Receipt {
+    fn discriminator(&self) -> &[u8; 8] {
+        &self.discriminator
+    }
+}
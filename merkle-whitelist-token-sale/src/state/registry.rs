@@ -0,0 +1,130 @@
+use super::ZeroCopyAccount;
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use shank::ShankAccount;
+use solana_program::pubkey::Pubkey;
+use spl_discriminator::SplDiscriminate;
+
+// Seeds are published in the IDL through `crate::pda::Seed`
+
+#[repr(C)]
+#[rustfmt::skip] // ensure manual struct ordering
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, Debug, Pod, Zeroable, ShankAccount, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::state::registry")]
+/// Registry listing open sales across [`RegistryPage`] accounts
+///
+/// The global registry lists every sale, each sale authority also gets
+/// a registry listing its own sales. Created by the first OpenSale
+pub struct Registry {
+    /// Identifier for this specific structure
+    pub discriminator: [u8; 8],
+    /// Sale authority whose sales are listed
+    ///
+    /// `Pubkey::default()` for the global registry
+    pub authority: Pubkey,
+    /// Sales listed across all pages
+    pub sale_count: u64,
+    /// Number of pages, created in order from page 0
+    pub page_count: u64,
+    /// Sales listed by the last page, OpenSale starts a new page
+    /// once it holds [`RegistryPage::CAPACITY`]
+    pub tail_len: u64,
+    /// Canonical bump for Registry PDA
+    pub bump: u8,
+
+    /// Padding to remove SLOP in C memory layout alignment
+    /// Widest scalar = 8bytes
+    _padding: [u8; 7],
+    /// Zeroed space for future fields
+    _reserved: [u8; 64]
+}
+
+impl Registry {
+    /// Get known size of Registry
+    pub const LEN: usize = std::mem::size_of::<Registry>();
+
+    /// Sale authority whose sales are listed, None for the global registry
+    pub fn authority(&self) -> Option<Pubkey> {
+        (self.authority != Pubkey::default()).then_some(self.authority)
+    }
+
+    /// Is `true` if Registry is initialized
+    pub fn is_initialized(&self) -> bool {
+        self.discriminator.as_slice() == Registry::SPL_DISCRIMINATOR_SLICE
+    }
+
+    /// Page OpenSale appends the next sale to, a new page if the last is full
+    pub fn append_page(&self) -> u64 {
+        if self.page_count == 0 || self.tail_len >= RegistryPage::CAPACITY {
+            self.page_count
+        } else {
+            self.page_count - 1
+        }
+    }
+}
+
+impl ZeroCopyAccount for Registry {
+    fn discriminator(&self) -> &[u8; 8] {
+        &self.discriminator
+    }
+}
+
+#[repr(C)]
+#[rustfmt::skip] // ensure manual struct ordering
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, Debug, Pod, Zeroable, ShankAccount, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::state::registry_page")]
+/// Page of a [`Registry`], header of the account
+///
+/// Followed by `len` TokenBase addresses. The account grows by one
+/// address per OpenSale and shrinks by one per CloseSale, which moves
+/// the page's last address into the freed slot
+pub struct RegistryPage {
+    /// Identifier for this specific structure
+    pub discriminator: [u8; 8],
+    /// Registry this page belongs to
+    pub registry: Pubkey,
+    /// Position of this page in the registry
+    pub page: u64,
+    /// Number of TokenBase addresses following the header
+    pub len: u64,
+    /// Canonical bump for RegistryPage PDA
+    pub bump: u8,
+
+    /// Padding to remove SLOP in C memory layout alignment
+    /// Widest scalar = 8bytes
+    _padding: [u8; 7]
+}
+
+impl RegistryPage {
+    /// Get known size of the RegistryPage header
+    pub const LEN: usize = std::mem::size_of::<RegistryPage>();
+
+    /// Most sales listed by a page
+    pub const CAPACITY: u64 = 256;
+
+    /// Account size of a page listing `len` sales
+    pub fn space(len: u64) -> usize {
+        RegistryPage::LEN + len as usize * 32
+    }
+
+    /// Is `true` if RegistryPage is initialized
+    pub fn is_initialized(&self) -> bool {
+        self.discriminator.as_slice() == RegistryPage::SPL_DISCRIMINATOR_SLICE
+    }
+
+    /// TokenBase addresses listed by the page account `data`
+    pub fn entries(data: &[u8]) -> &[Pubkey] {
+        bytemuck::cast_slice(&data[RegistryPage::LEN..])
+    }
+
+    /// Mutable TokenBase addresses listed by the page account `data`
+    pub fn entries_mut(data: &mut [u8]) -> &mut [Pubkey] {
+        bytemuck::cast_slice_mut(&mut data[RegistryPage::LEN..])
+    }
+}
+
+impl ZeroCopyAccount for RegistryPage {
+    fn discriminator(&self) -> &[u8; 8] {
+        &self.discriminator
+    }
+}
@@ -1,8 +1,10 @@
+use super::{PodBool, TokenBaseV1, ZeroCopyAccount};
 use crate::merkle::{
-    convert_whitelist_proof, pubkey_to_sha256_leaf, verify_membership, WhitelistProof,
-    WhitelistRoot,
+    convert_whitelist_proof, proof_root, pubkey_to_sha256_leaf, WhitelistProof, WhitelistRoot,
 };
 use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use merkletreers::Root;
 use shank::ShankAccount;
 use solana_program::{program_error::ProgramError, pubkey::Pubkey};
 use spl_discriminator::{ArrayDiscriminator, SplDiscriminate};
@@ -10,12 +12,13 @@ use spl_discriminator::{ArrayDiscriminator, SplDiscriminate};
 // OPT-OUT: didn't use #[seeds()] because ShankAccount seeds
 // helper attribute is buggy. PDA is generated offchain
 // instead and seeds are validated on OpenSale
+// Seeds are published in the IDL through `crate::pda::Seed`
 
 // TODO: Cache-line optimization (if I have time left)
 
 #[repr(C)]
 #[rustfmt::skip] // ensure manual struct ordering
-#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, ShankAccount, SplDiscriminate)]
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, Debug, Pod, Zeroable, ShankAccount, SplDiscriminate)]
 #[discriminator_hash_input("token_sale::state::token_base")]
 /// TokenBase holding the token sale configuraiton
 pub struct TokenBase {
@@ -25,13 +28,14 @@ pub struct TokenBase {
     pub mint: Pubkey,
     /// Account holding the SOL from token sale
     pub vault: Pubkey,
-    /// Merkle root hash used to verify passed Merkle proof
-    /// for whitelist gating
+    /// Latest configured Merkle root hash used to verify passed
+    /// Merkle proof for whitelist gating, it may activate in a
+    /// future slot, see `root_history`
     pub whitelist_root: WhitelistRoot, // [u8; 32]
     /// Identifier for this specific structure
     pub discriminator: [u8; 8],
     /// Amount of lamports to transfer from Buyer to Vault 
-    /// when purchasing tokens
+    /// per whole token (10^decimals base units) purchased
     pub price: u64,
     /// Purchase limit set per user upon RegisterBuyer
     /// 
@@ -39,19 +43,160 @@ pub struct TokenBase {
     /// per wallet via AssignLimit
     pub default_purchase_limit: u64,
     /// Determines if the sale is already open for buyers
-    pub is_running: bool,
+    pub is_running: PodBool,
     /// Canonical bump for TokenBase PDA
     pub bump: u8,
+    /// Account layout version, see [`TokenBase::VERSION`]
+    pub version: u8,
 
     /// Padding to remove SLOP in C memory layout alignment
     /// Widest scalar = 32bytes
-    _padding: [u8; 6]
+    _padding: [u8; 5],
+    /// Pricing phase, starts at 0 and is bumped by ConfigureSale
+    /// whenever the price or whitelist root changes
+    pub phase: u32,
+    /// Share of each referred purchase rewarded to the referrer,
+    /// in basis points, 0 disables referrals
+    pub referral_bps: u16,
+
+    /// Padding to align the following u64 fields
+    _padding2: [u8; 2],
+    /// Slots a buyer must wait after RegisterBuyer before buying,
+    /// 0 disables the check
+    pub min_registration_slots: u64,
+    /// Slots a buyer must wait between purchases, 0 disables the check
+    pub purchase_cooldown_slots: u64,
+    /// Ed25519 key signing vouchers in [`WhitelistMode::Voucher`] sales
+    pub whitelist_signer: Pubkey,
+    /// How buyers prove eligibility, see [`WhitelistMode`]
+    pub whitelist_mode: u8,
+
+    /// Padding to align the following fields
+    _padding3: [u8; 7],
+    /// Gating mint in [`WhitelistMode::TokenHolder`] sales,
+    /// verified collection in [`WhitelistMode::CollectionHolder`] sales
+    pub gate_key: Pubkey,
+    /// Minimum balance of the gating mint in
+    /// [`WhitelistMode::TokenHolder`] sales
+    pub gate_min_amount: u64,
+    /// Slots a replaced whitelist root keeps verifying proofs after
+    /// its successor activates, 0 rejects it right away
+    pub root_grace_slots: u64,
+    /// Recently configured whitelist roots with their activation slot,
+    /// ring buffer whose latest entry is at `root_history_head`
+    pub root_history: [RootRecord; 3], // ROOT_HISTORY_LEN, shank needs a literal
+    /// Index of the latest configured root in `root_history`
+    pub root_history_head: u8,
+    /// Is `true` if OpenSale listed the sale in the launchpad
+    /// [`Registry`](crate::state::Registry) accounts
+    pub is_registered: PodBool,
+    /// Is `true` if `vault` is the sale's [`Escrow`](crate::state::Escrow)
+    pub is_escrow: PodBool,
+    /// Is `true` once the escrow released its last milestone
+    pub is_escrow_settled: PodBool,
+
+    /// Padding to align the following fields
+    _padding4: [u8; 4],
+    /// Slot purchases end at and CloseSale is allowed from,
+    /// 0 until the sale authority sets it
+    pub end_slot: u64,
+    /// Zeroed space for future fields, shrinks as fields are added
+    /// so new fields don't change the account size
+    _reserved: [u8; 8]
+}
+
+/// Number of whitelist roots kept in [`TokenBase::root_history`]
+pub const ROOT_HISTORY_LEN: usize = 3;
+
+/// Whitelist root and the slot it starts verifying proofs at
+#[repr(C)]
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, Debug, Pod, Zeroable)]
+pub struct RootRecord {
+    pub root: WhitelistRoot,
+    pub activation_slot: u64,
+}
+
+impl RootRecord {
+    /// Is `true` for never written entries
+    pub fn is_empty(&self) -> bool {
+        self.root.0 == [0; 32] && self.activation_slot == 0
+    }
+}
+
+/// How buyers prove they are whitelisted
+#[repr(u8)]
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq)]
+pub enum WhitelistMode {
+    /// Merkle proof against `whitelist_root`
+    Merkle,
+    /// Voucher signed by `whitelist_signer`, see [`crate::voucher`]
+    Voucher,
+    /// Holding at least `gate_min_amount` of the `gate_key` mint,
+    /// see [`crate::holder`]
+    TokenHolder,
+    /// Holding an NFT of the verified `gate_key` collection,
+    /// see [`crate::holder`]
+    CollectionHolder,
+}
+
+impl TryFrom<u8> for WhitelistMode {
+    type Error = ProgramError;
+
+    fn try_from(mode: u8) -> Result<Self, Self::Error> {
+        match mode {
+            0 => Ok(Self::Merkle),
+            1 => Ok(Self::Voucher),
+            2 => Ok(Self::TokenHolder),
+            3 => Ok(Self::CollectionHolder),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
 }
 
 impl TokenBase {
     /// Get known size of TokenBase
     pub const LEN: usize = std::mem::size_of::<TokenBase>();
 
+    /// Current account layout version
+    ///
+    /// - 1: [`TokenBaseV1`], no version field
+    /// - 2: adds `version` and reserved space
+    pub const VERSION: u8 = 2;
+
+    /// Offset of `discriminator`, after the four leading keys, in every version
+    pub const DISCRIMINATOR_OFFSET: usize = 32 + 32 + 32 + 32;
+
+    /// Is `true` if the sale is already open for buyers
+    pub fn is_running(&self) -> bool {
+        self.is_running.into()
+    }
+
+    /// Is `true` if the sale is listed in the launchpad registries
+    pub fn is_registered(&self) -> bool {
+        self.is_registered.into()
+    }
+
+    /// Is `true` if the raise is held by the sale's escrow
+    pub fn is_escrow(&self) -> bool {
+        self.is_escrow.into()
+    }
+
+    /// Is `true` if the escrow holds no unreleased raise, always for
+    /// sales without escrow
+    pub fn is_escrow_settled(&self) -> bool {
+        !self.is_escrow() || self.is_escrow_settled.into()
+    }
+
+    /// Is `true` once `end_slot` is set and reached at `slot`
+    pub fn is_ended(&self, slot: u64) -> bool {
+        self.end_slot != 0 && slot >= self.end_slot
+    }
+
+    /// How buyers prove they are whitelisted
+    pub fn whitelist_mode(&self) -> Result<WhitelistMode, ProgramError> {
+        self.whitelist_mode.try_into()
+    }
+
     /// Is `true` if TokenBase is initialized
     pub fn is_initialized(&self) -> bool {
         self.discriminator.as_slice() == TokenBase::SPL_DISCRIMINATOR_SLICE
@@ -62,18 +207,159 @@ impl TokenBase {
         self.discriminator.as_slice() == ArrayDiscriminator::UNINITIALIZED.as_slice()
     }
 
-    /// Is `true` if buyer is in Merkle Tree whitelist.
+    /// Is `true` if buyer is in a Merkle Tree whitelist accepted at `slot`,
+    /// see [`TokenBase::is_root_accepted`]
     pub fn is_whitelisted(
         &self,
         buyer: &Pubkey,
         proof: WhitelistProof,
+        slot: u64,
     ) -> Result<bool, ProgramError> {
         let member = pubkey_to_sha256_leaf(buyer);
         let merkle_proof = convert_whitelist_proof(proof);
-        Ok(verify_membership(
-            self.whitelist_root.0,
-            merkle_proof,
-            member,
-        ))
+        Ok(self.is_root_accepted(&proof_root(merkle_proof, member), slot))
+    }
+
+    /// Recorded whitelist roots, latest configured first
+    ///
+    /// Empty for sales opened before roots were recorded
+    pub fn root_records(&self) -> impl Iterator<Item = &RootRecord> {
+        let head = self.root_history_head as usize;
+        (0..ROOT_HISTORY_LEN)
+            .map(move |age| &self.root_history[(head + ROOT_HISTORY_LEN - age) % ROOT_HISTORY_LEN])
+            .take_while(|record| !record.is_empty())
+    }
+
+    /// Is `true` if proofs against `root` verify at `slot`
+    ///
+    /// - the latest root activated by `slot` is accepted
+    /// - a replaced root is accepted until `root_grace_slots` after
+    ///   the root replacing it activated
+    /// - scheduled roots are accepted from their activation slot
+    pub fn is_root_accepted(&self, root: &Root, slot: u64) -> bool {
+        // sales without recorded roots only know `whitelist_root`
+        if self.root_records().next().is_none() {
+            return self.whitelist_root.0 == *root;
+        }
+
+        // activation slot of the next newer root
+        let mut replaced_slot = u64::MAX;
+        for record in self.root_records() {
+            if record.activation_slot <= slot
+                && slot < replaced_slot.saturating_add(self.root_grace_slots)
+                && record.root.0 == *root
+            {
+                return true;
+            }
+            replaced_slot = record.activation_slot;
+        }
+        false
+    }
+
+    /// Activation slot of the latest configured root, 0 if unrecorded
+    pub fn latest_root_activation_slot(&self) -> u64 {
+        self.root_records()
+            .next()
+            .map_or(0, |record| record.activation_slot)
+    }
+
+    /// Record `root` as the latest whitelist root, verifying proofs from
+    /// `activation_slot` on
+    ///
+    /// - a scheduled root that is still pending at `slot` is replaced
+    /// - otherwise the oldest recorded root is dropped when the history is full
+    pub fn rotate_whitelist_root(&mut self, root: WhitelistRoot, activation_slot: u64, slot: u64) {
+        if self.root_records().next().is_none() {
+            // sales opened before roots were recorded start their
+            // history with the root they were verifying so far
+            self.root_history[0] = RootRecord {
+                root: self.whitelist_root,
+                activation_slot: 0,
+            };
+            self.root_history_head = 0;
+        }
+
+        let head = self.root_history_head as usize;
+        if self.root_history[head].activation_slot <= slot {
+            self.root_history_head = ((head + 1) % ROOT_HISTORY_LEN) as u8;
+        }
+        self.root_history[self.root_history_head as usize] = RootRecord {
+            root,
+            activation_slot,
+        };
+        self.whitelist_root = root;
+    }
+}
+
+impl ZeroCopyAccount for TokenBase {
+    fn discriminator(&self) -> &[u8; 8] {
+        &self.discriminator
+    }
+}
+
+impl From<TokenBaseV1> for TokenBase {
+    fn from(v1: TokenBaseV1) -> Self {
+        TokenBase {
+            sale_authority: v1.sale_authority,
+            mint: v1.mint,
+            vault: v1.vault,
+            whitelist_root: v1.whitelist_root,
+            discriminator: v1.discriminator,
+            price: v1.price,
+            default_purchase_limit: v1.default_purchase_limit,
+            is_running: v1.is_running.into(),
+            bump: v1.bump,
+            version: TokenBase::VERSION,
+            _padding: [0; 5],
+            phase: 0,
+            referral_bps: 0,
+            _padding2: [0; 2],
+            min_registration_slots: 0,
+            purchase_cooldown_slots: 0,
+            whitelist_signer: Pubkey::default(),
+            whitelist_mode: WhitelistMode::Merkle as u8,
+            _padding3: [0; 7],
+            gate_key: Pubkey::default(),
+            gate_min_amount: 0,
+            root_grace_slots: 0,
+            root_history: [RootRecord::zeroed(); ROOT_HISTORY_LEN],
+            root_history_head: 0,
+            is_registered: false.into(),
+            is_escrow: false.into(),
+            is_escrow_settled: false.into(),
+            _padding4: [0; 4],
+            end_slot: 0,
+            _reserved: [0; 8],
+        }
+    }
+}
+
+#[repr(C)]
+#[rustfmt::skip] // ensure manual struct ordering
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, Debug, Pod, Zeroable, ShankAccount, SplDiscriminate)]
+#[discriminator_hash_input("token_sale::state::closed_token_base")]
+/// Tombstone CloseSale leaves at the TokenBase address
+///
+/// Keeps the address owned by this program, so OpenSale can't open the
+/// sale again over the BuyerFacts it left behind
+pub struct ClosedTokenBase {
+    /// Identifier for this specific structure
+    pub discriminator: [u8; 8],
+    /// Sale authority of the closed sale
+    pub sale_authority: Pubkey,
+    /// Mint of the closed sale
+    pub mint: Pubkey,
+    /// Slot CloseSale landed in
+    pub closed_slot: u64,
+}
+
+impl ClosedTokenBase {
+    /// Get known size of ClosedTokenBase
+    pub const LEN: usize = std::mem::size_of::<ClosedTokenBase>();
+}
+
+impl ZeroCopyAccount for ClosedTokenBase {
+    fn discriminator(&self) -> &[u8; 8] {
+        &self.discriminator
     }
 }
@@ -0,0 +1,45 @@
+//! Legacy (version 1) account layouts
+//!
+//! Only kept around to read accounts created before layout
+//! versioning so Migrate can rewrite them in the current format
+use crate::merkle::WhitelistRoot;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+#[repr(C)]
+#[rustfmt::skip] // ensure manual struct ordering
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug)]
+/// TokenBase before layout versioning
+pub struct TokenBaseV1 {
+    pub sale_authority: Pubkey,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub whitelist_root: WhitelistRoot, // [u8; 32]
+    pub discriminator: [u8; 8],
+    pub price: u64,
+    pub default_purchase_limit: u64,
+    pub is_running: bool,
+    pub bump: u8,
+    pub _padding: [u8; 6]
+}
+
+impl TokenBaseV1 {
+    /// Get known size of TokenBaseV1
+    pub const LEN: usize = std::mem::size_of::<TokenBaseV1>();
+}
+
+#[repr(C)]
+#[rustfmt::skip] // ensure manual struct ordering
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug)]
+/// BuyerFacts before layout versioning
+pub struct BuyerFactsV1 {
+    pub discriminator: [u8; 8],
+    pub purchase_limit: u64,
+    pub bump: u8,
+    pub _padding: [u8; 7]
+}
+
+impl BuyerFactsV1 {
+    /// Get known size of BuyerFactsV1
+    pub const LEN: usize = std::mem::size_of::<BuyerFactsV1>();
+}
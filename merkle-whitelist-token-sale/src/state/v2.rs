@@ -0,0 +1,30 @@
+//! Legacy (version 2) account layouts
+//!
+//! Only kept around to read accounts created before they grew
+//! so Migrate can rewrite them in the current format
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+#[repr(C)]
+#[rustfmt::skip] // ensure manual struct ordering
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug)]
+/// BuyerFacts before storing its token_base and buyer
+pub struct BuyerFactsV2 {
+    pub discriminator: [u8; 8],
+    pub purchase_limit: u64,
+    pub bump: u8,
+    pub version: u8,
+    pub _padding: [u8; 6],
+    pub receipt_count: u64,
+    pub referral_rewards: u64,
+    pub referrer: Pubkey,
+    pub registered_slot: u64,
+    pub last_purchase_slot: u64,
+    pub total_purchased: u64,
+    pub _reserved: [u8; 56]
+}
+
+impl BuyerFactsV2 {
+    /// Get known size of BuyerFactsV2
+    pub const LEN: usize = std::mem::size_of::<BuyerFactsV2>();
+}
@@ -0,0 +1,67 @@
+use crate::error::TokenSaleError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, PodCastError, Zeroable};
+use solana_program::program_error::ProgramError;
+use spl_discriminator::{ArrayDiscriminator, SplDiscriminate};
+
+/// `bool` replacement that is [`Pod`], borsh encoded like `bool`
+#[repr(transparent)]
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Pod, Zeroable, BorshSerialize, BorshDeserialize,
+)]
+pub struct PodBool(pub u8);
+
+impl From<bool> for PodBool {
+    fn from(b: bool) -> Self {
+        Self(b as u8)
+    }
+}
+
+impl From<PodBool> for bool {
+    fn from(b: PodBool) -> Self {
+        b.0 != 0
+    }
+}
+
+/// Zero-copy access to program accounts
+///
+/// Casts account data in place instead of a borsh round-trip,
+/// checking the size and discriminator on the way
+pub trait ZeroCopyAccount: Pod + SplDiscriminate {
+    /// Stored discriminator of this account
+    fn discriminator(&self) -> &[u8; 8];
+
+    /// Load an initialized account
+    fn load(data: &[u8]) -> Result<&Self, ProgramError> {
+        let account: &Self = bytemuck::try_from_bytes(data).map_err(cast_error)?;
+        if account.discriminator() != Self::SPL_DISCRIMINATOR.as_slice() {
+            return Err(TokenSaleError::AccountUninitialized.into());
+        }
+        Ok(account)
+    }
+
+    /// Mutably load an initialized account
+    fn load_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        let account: &mut Self = bytemuck::try_from_bytes_mut(data).map_err(cast_error)?;
+        if account.discriminator() != Self::SPL_DISCRIMINATOR.as_slice() {
+            return Err(TokenSaleError::AccountUninitialized.into());
+        }
+        Ok(account)
+    }
+
+    /// Mutably load a freshly allocated account for initialization
+    fn load_uninitialized_mut(data: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        let account: &mut Self = bytemuck::try_from_bytes_mut(data).map_err(cast_error)?;
+        if account.discriminator() != ArrayDiscriminator::UNINITIALIZED.as_slice() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        Ok(account)
+    }
+}
+
+fn cast_error(e: PodCastError) -> ProgramError {
+    match e {
+        PodCastError::SizeMismatch => TokenSaleError::InvalidAccountDataLength.into(),
+        _ => ProgramError::InvalidAccountData,
+    }
+}
@@ -1,14 +1,15 @@
 use super::utils::TestHelper;
 use crate::*;
+use assert_matches::assert_matches;
 use borsh::BorshSerialize;
 use merkletreers::tree::MerkleTree;
 use solana_program_test::*;
 use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
+    instruction::{AccountMeta, Instruction, InstructionError},
     pubkey::Pubkey,
     signature::Keypair,
     signature::Signer,
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 
 /// Test Happy Path
@@ -84,3 +85,145 @@ async fn test_assign_limit() {
 
     assert_eq!(buyer_facts.purchase_limit, new_purchase_limit);
 }
+
+/// Test a TokenBase copy owned by another program is rejected
+#[tokio::test]
+async fn test_assign_limit_rejects_foreign_token_base() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let (buyer, buyer_facts_pda, _) =
+        TestHelper::initialize_buyer_facts(token_base_pda, program_id, &mut ctx).await;
+
+    let spoofed_token_base = TestHelper::spoof_account(token_base_pda, &mut ctx).await;
+
+    let result = assign_limit(
+        spoofed_token_base,
+        buyer_facts_pda,
+        buyer.pubkey(),
+        &ctx.payer.insecure_clone(),
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidAccountOwner)
+    );
+}
+
+/// Test only the TokenBase's own sale authority can assign limits
+#[tokio::test]
+async fn test_assign_limit_rejects_other_sale_authority() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let (buyer, buyer_facts_pda, _) =
+        TestHelper::initialize_buyer_facts(token_base_pda, program_id, &mut ctx).await;
+
+    // signs, but is not the sale authority of token_base
+    let intruder = Keypair::new();
+
+    let result = assign_limit(
+        token_base_pda,
+        buyer_facts_pda,
+        buyer.pubkey(),
+        &intruder,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::SaleAuthorityMismatch as u32
+    );
+}
+
+async fn assign_limit(
+    token_base: Pubkey,
+    buyer_facts: Pubkey,
+    buyer: Pubkey,
+    sale_authority: &Keypair,
+    program_id: Pubkey,
+    ctx: &mut ProgramTestContext,
+) -> Result<(), BanksClientError> {
+    let instruction = crate::instruction::TokenSaleInstruction::AssignLimit {
+        new_purchase_limit: 143,
+    };
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(buyer_facts, false),
+                AccountMeta::new_readonly(buyer, false),
+                AccountMeta::new_readonly(sale_authority.pubkey(), true),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), sale_authority],
+        ctx.last_blockhash,
+    );
+
+    ctx.banks_client.process_transaction(transaction).await
+}
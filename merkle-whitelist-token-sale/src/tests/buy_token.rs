@@ -0,0 +1,1247 @@
+use super::utils::TestHelper;
+use crate::events::{Purchased, TokenSaleEvent};
+use crate::merkle::{convert_merkle_proof, pubkey_to_sha256_leaf, WhitelistProof};
+use crate::pda::ReceiptPDA;
+use crate::voucher::Voucher;
+use crate::*;
+use assert_matches::assert_matches;
+use borsh::BorshSerialize;
+use merkletreers::{tree::MerkleTree, Leaf};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signature::Signer,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+    sysvar,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account;
+
+/// Running sale with a whitelisted and registered buyer and referrer
+struct Sale {
+    program_id: Pubkey,
+    mint: Pubkey,
+    vault: Pubkey,
+    token_base: Pubkey,
+    buyer: Keypair,
+    buyer_facts: Pubkey,
+    referrer: Keypair,
+    referrer_facts: Pubkey,
+    whitelist: Vec<Leaf>,
+}
+
+impl Sale {
+    fn proof(&self, buyer: &Pubkey) -> WhitelistProof {
+        let tree = MerkleTree::new(self.whitelist.clone());
+        convert_merkle_proof(tree.make_proof(pubkey_to_sha256_leaf(buyer)))
+    }
+}
+
+/// Optional BuyToken accounts, the program id in place of one skips it
+struct OptionalAccounts {
+    receipt: Pubkey,
+    referrer_facts: Pubkey,
+    referrer: Pubkey,
+    instructions_sysvar: Pubkey,
+    holder_token_account: Pubkey,
+    holder_metadata: Pubkey,
+    holder_mint: Pubkey,
+}
+
+impl Default for OptionalAccounts {
+    fn default() -> Self {
+        Self {
+            receipt: crate::ID,
+            referrer_facts: crate::ID,
+            referrer: crate::ID,
+            instructions_sysvar: crate::ID,
+            holder_token_account: crate::ID,
+            holder_metadata: crate::ID,
+            holder_mint: crate::ID,
+        }
+    }
+}
+
+const PRICE: u64 = 1000;
+const DEFAULT_PURCHASE_LIMIT: u64 = 100;
+const REFERRAL_BPS: u16 = 500;
+
+async fn setup_running_sale(ctx: &mut ProgramTestContext, program_id: Pubkey) -> Sale {
+    // create Mint
+    let mint = TestHelper::new_mint(0, ctx).await;
+
+    let vault = Keypair::new();
+
+    // the buyer is only known after RegisterBuyer, so open the sale with
+    // an empty whitelist and configure the root afterwards
+    let empty_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        PRICE,
+        DEFAULT_PURCHASE_LIMIT,
+        mint,
+        vault.pubkey(),
+        &empty_root,
+        program_id,
+        ctx,
+    )
+    .await;
+
+    let (buyer, buyer_facts_pda, _) =
+        TestHelper::initialize_buyer_facts(token_base_pda, program_id, ctx).await;
+    let (referrer, referrer_facts_pda, _) =
+        TestHelper::initialize_buyer_facts(token_base_pda, program_id, ctx).await;
+
+    // whitelist buyer and referrer
+    let whitelist = vec![
+        pubkey_to_sha256_leaf(&buyer.pubkey()),
+        pubkey_to_sha256_leaf(&referrer.pubkey()),
+    ];
+    let tree = MerkleTree::new(whitelist.clone());
+    let whitelist_root = crate::merkle::WhitelistRoot(tree.root);
+
+    let mut instruction_data = Vec::new();
+    crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+        settings: crate::instruction::SaleSettings::V1(crate::instruction::SaleSettingsV1 {
+            whitelist_root: Some(whitelist_root),
+            referral_bps: Some(REFERRAL_BPS),
+            ..Default::default()
+        }),
+    }
+    .serialize(&mut instruction_data)
+    .unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base_pda, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // is_running: false -> true
+    TestHelper::toggle_running(token_base_pda, mint, program_id, ctx).await;
+
+    Sale {
+        program_id,
+        mint,
+        vault: vault.pubkey(),
+        token_base: token_base_pda,
+        buyer,
+        buyer_facts: buyer_facts_pda,
+        referrer,
+        referrer_facts: referrer_facts_pda,
+        whitelist,
+    }
+}
+
+fn buy_token_transaction(
+    sale: Sale,
+    buyer_token_account: Pubkey,
+    amount: u64,
+    ctx: &ProgramTestContext,
+) -> Transaction {
+    buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        amount,
+        OptionalAccounts::default(),
+        ctx,
+    )
+}
+
+fn buy_token_with_optional_transaction(
+    sale: &Sale,
+    buyer: &Keypair,
+    buyer_facts: Pubkey,
+    buyer_token_account: Pubkey,
+    amount: u64,
+    optional: OptionalAccounts,
+    ctx: &ProgramTestContext,
+) -> Transaction {
+    // buyer only pays for the tokens, payer covers fees and ATA rent
+    Transaction::new_signed_with_payer(
+        &[buy_token_instruction(
+            sale,
+            &buyer.pubkey(),
+            buyer_facts,
+            buyer_token_account,
+            amount,
+            optional,
+            ctx,
+        )],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    )
+}
+
+fn buy_token_instruction(
+    sale: &Sale,
+    buyer: &Pubkey,
+    buyer_facts: Pubkey,
+    buyer_token_account: Pubkey,
+    amount: u64,
+    optional: OptionalAccounts,
+    ctx: &ProgramTestContext,
+) -> Instruction {
+    let instruction = crate::instruction::TokenSaleInstruction::BuyToken {
+        amount,
+        proof: sale.proof(buyer),
+    };
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    Instruction {
+        program_id: sale.program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(sale.token_base, false),
+            AccountMeta::new(sale.mint, false),
+            AccountMeta::new(sale.vault, false),
+            AccountMeta::new_readonly(ctx.payer.pubkey(), false),
+            AccountMeta::new(buyer_token_account, false),
+            AccountMeta::new(buyer_facts, false),
+            AccountMeta::new(*buyer, true),
+            AccountMeta::new(ctx.payer.pubkey(), true),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+            AccountMeta::new(optional.receipt, false),
+            AccountMeta::new(optional.referrer_facts, false),
+            AccountMeta::new_readonly(optional.referrer, false),
+            AccountMeta::new_readonly(optional.instructions_sysvar, false),
+            AccountMeta::new_readonly(optional.holder_token_account, false),
+            AccountMeta::new_readonly(optional.holder_metadata, false),
+            AccountMeta::new_readonly(optional.holder_mint, false),
+        ],
+        data: instruction_data,
+    }
+}
+
+/// Test Happy Path, first-time buyer without a token account
+#[tokio::test]
+async fn test_buy_token() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    let (token_base, buyer, buyer_facts_pda, vault) = (
+        sale.token_base,
+        sale.buyer.pubkey(),
+        sale.buyer_facts,
+        sale.vault,
+    );
+
+    // ATA does not exist yet
+    let buyer_token_account = get_associated_token_address(&buyer, &sale.mint);
+    assert!(ctx
+        .banks_client
+        .get_account(buyer_token_account)
+        .await
+        .unwrap()
+        .is_none());
+
+    let amount: u64 = 40;
+    let transaction = buy_token_transaction(sale, buyer_token_account, amount, &ctx);
+
+    let result = ctx
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert!(result.result.is_ok());
+
+    // confirm state
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(buyer_facts_pda)
+        .await
+        .unwrap();
+    assert_eq!(buyer_facts.purchase_limit, DEFAULT_PURCHASE_LIMIT - amount);
+
+    let token_account = ctx
+        .banks_client
+        .get_account(buyer_token_account)
+        .await
+        .unwrap()
+        .unwrap();
+    let token_account = Account::unpack(&token_account.data).unwrap();
+    assert_eq!(token_account.owner, buyer);
+    assert_eq!(token_account.amount, amount);
+
+    assert_eq!(
+        ctx.banks_client.get_balance(vault).await.unwrap(),
+        PRICE * amount
+    );
+
+    // Purchased event is decodable from logs
+    let events = TokenSaleEvent::from_logs(&program_id, &result.metadata.unwrap().log_messages);
+    assert_eq!(
+        events,
+        vec![TokenSaleEvent::Purchased(Purchased {
+            token_base,
+            buyer,
+            buyer_token_account,
+            amount,
+            lamports_paid: PRICE * amount,
+            remaining_limit: DEFAULT_PURCHASE_LIMIT - amount,
+            receipt: None,
+            referrer: None,
+            referral_reward: 0,
+        })]
+    );
+}
+
+/// Test the buyer alone signs, the TokenBase PDA mints
+#[tokio::test]
+async fn test_buy_token_without_sale_authority_signature() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+
+    // OpenSale handed the mint authority to the TokenBase PDA
+    let mint = ctx
+        .banks_client
+        .get_account(sale.mint)
+        .await
+        .unwrap()
+        .unwrap();
+    let mint = spl_token::state::Mint::unpack(&mint.data).unwrap();
+    assert_eq!(
+        mint.mint_authority,
+        solana_sdk::program_option::COption::Some(sale.token_base)
+    );
+
+    // the buyer pays the fees and rent, the sale authority signs nothing
+    let buyer = sale.buyer.pubkey();
+    let buyer_token_account = get_associated_token_address(&buyer, &sale.mint);
+    let mut instruction = buy_token_instruction(
+        &sale,
+        &buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        40,
+        OptionalAccounts::default(),
+        &ctx,
+    );
+    instruction.accounts[7] = AccountMeta::new(buyer, true);
+    assert!(!instruction.accounts[3].is_signer);
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&buyer),
+        &[&sale.buyer],
+        ctx.last_blockhash,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let token_account = ctx
+        .banks_client
+        .get_account(buyer_token_account)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Account::unpack(&token_account.data).unwrap().amount, 40);
+}
+
+/// Test a non-ATA token account owned by the buyer is rejected
+#[tokio::test]
+async fn test_buy_token_rejects_non_ata() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+
+    let buyer_token_account =
+        TestHelper::new_token_account(sale.mint, sale.buyer.pubkey(), &mut ctx).await;
+
+    let transaction = buy_token_transaction(sale, buyer_token_account, 1, &ctx);
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::InvalidBuyerTokenAccount as u32
+    );
+}
+
+/// Test purchases are rejected once the sale reached its end slot
+#[tokio::test]
+async fn test_buy_token_after_end_slot_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    TestHelper::end_sale(sale.token_base, sale.mint, program_id, &mut ctx).await;
+
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+    let transaction = buy_token_transaction(sale, buyer_token_account, 1, &ctx);
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::SaleEnded as u32
+    );
+}
+
+/// Test purchases issue numbered receipts when a receipt account is passed
+#[tokio::test]
+async fn test_buy_token_with_receipt() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    let (token_base, buyer, buyer_facts_pda) =
+        (sale.token_base, sale.buyer.pubkey(), sale.buyer_facts);
+    let buyer_token_account = get_associated_token_address(&buyer, &sale.mint);
+
+    let amount: u64 = 40;
+    let (receipt_pda, _) = ReceiptPDA::find_pda(&program_id, &token_base, &buyer, 0);
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        amount,
+        OptionalAccounts {
+            receipt: receipt_pda,
+            ..Default::default()
+        },
+        &ctx,
+    );
+
+    let result = ctx
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert!(result.result.is_ok());
+
+    // confirm state
+    let receipt = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::Receipt>(receipt_pda)
+        .await
+        .unwrap();
+    assert!(receipt.is_initialized());
+    assert_eq!(receipt.token_base, token_base);
+    assert_eq!(receipt.buyer, buyer);
+    assert_eq!(receipt.payer, ctx.payer.pubkey());
+    assert_eq!(receipt.nonce, 0);
+    assert_eq!(receipt.amount, amount);
+    assert_eq!(receipt.lamports_paid, PRICE * amount);
+    assert_eq!(receipt.price, PRICE);
+    // the whitelist was configured once during setup
+    assert_eq!(receipt.phase, 1);
+
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(buyer_facts_pda)
+        .await
+        .unwrap();
+    assert_eq!(buyer_facts.receipt_count, 1);
+
+    let events = TokenSaleEvent::from_logs(&program_id, &result.metadata.unwrap().log_messages);
+    assert_matches!(
+        events.as_slice(),
+        [TokenSaleEvent::Purchased(Purchased { receipt: Some(receipt), .. })] if *receipt == receipt_pda
+    );
+}
+
+/// Test a receipt not at the buyer's next nonce is rejected
+#[tokio::test]
+async fn test_buy_token_rejects_stale_receipt_nonce() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+
+    // receipt_count is 0, nonce 1 is not next
+    let (receipt_pda, _) =
+        ReceiptPDA::find_pda(&program_id, &sale.token_base, &sale.buyer.pubkey(), 1);
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        1,
+        OptionalAccounts {
+            receipt: receipt_pda,
+            ..Default::default()
+        },
+        &ctx,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::InvalidReceiptSeeds as u32
+    );
+}
+
+/// Test a referred purchase splits the payment and attributes the buyer
+#[tokio::test]
+async fn test_buy_token_with_referrer() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+    let referrer_facts_lamports = ctx
+        .banks_client
+        .get_balance(sale.referrer_facts)
+        .await
+        .unwrap();
+
+    let amount: u64 = 40;
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        amount,
+        OptionalAccounts {
+            referrer_facts: sale.referrer_facts,
+            referrer: sale.referrer.pubkey(),
+            ..Default::default()
+        },
+        &ctx,
+    );
+
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // confirm state
+    let lamports_paid = PRICE * amount;
+    let referral_reward = lamports_paid * REFERRAL_BPS as u64 / 10_000;
+
+    assert_eq!(
+        ctx.banks_client.get_balance(sale.vault).await.unwrap(),
+        lamports_paid - referral_reward
+    );
+    assert_eq!(
+        ctx.banks_client
+            .get_balance(sale.referrer_facts)
+            .await
+            .unwrap(),
+        referrer_facts_lamports + referral_reward
+    );
+
+    let referrer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(sale.referrer_facts)
+        .await
+        .unwrap();
+    assert_eq!(referrer_facts.referral_rewards, referral_reward);
+
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(sale.buyer_facts)
+        .await
+        .unwrap();
+    assert_eq!(buyer_facts.referrer, sale.referrer.pubkey());
+}
+
+/// Test a buyer can't refer itself
+#[tokio::test]
+async fn test_buy_token_rejects_self_referral() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        1,
+        OptionalAccounts {
+            referrer_facts: sale.buyer_facts,
+            referrer: sale.buyer.pubkey(),
+            ..Default::default()
+        },
+        &ctx,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::SelfReferral as u32
+    );
+}
+
+/// Test a buyer can't be referred by a wallet it referred
+#[tokio::test]
+async fn test_buy_token_rejects_circular_referral() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+
+    // buyer refers referrer
+    let referrer_token_account = get_associated_token_address(&sale.referrer.pubkey(), &sale.mint);
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.referrer,
+        sale.referrer_facts,
+        referrer_token_account,
+        1,
+        OptionalAccounts {
+            referrer_facts: sale.buyer_facts,
+            referrer: sale.buyer.pubkey(),
+            ..Default::default()
+        },
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // referrer refers buyer back
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        1,
+        OptionalAccounts {
+            referrer_facts: sale.referrer_facts,
+            referrer: sale.referrer.pubkey(),
+            ..Default::default()
+        },
+        &ctx,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::CircularReferral as u32
+    );
+}
+
+/// Set the sale's anti-bot settings
+async fn configure_anti_bot(
+    sale: &Sale,
+    min_registration_slots: u64,
+    purchase_cooldown_slots: u64,
+    ctx: &mut ProgramTestContext,
+) {
+    TestHelper::configure_sale(
+        sale.token_base,
+        sale.mint,
+        crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+            settings: crate::instruction::SaleSettings::V1(crate::instruction::SaleSettingsV1 {
+                min_registration_slots: Some(min_registration_slots),
+                purchase_cooldown_slots: Some(purchase_cooldown_slots),
+                ..Default::default()
+            }),
+        },
+        sale.program_id,
+        ctx,
+    )
+    .await;
+}
+
+/// Test a buyer can't purchase until its registration is old enough
+#[tokio::test]
+async fn test_buy_token_min_registration_age() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    configure_anti_bot(&sale, 100, 0, &mut ctx).await;
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(sale.buyer_facts)
+        .await
+        .unwrap();
+
+    // same block as registration
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        1,
+        OptionalAccounts::default(),
+        &ctx,
+    );
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::RegistrationTooRecent as u32
+    );
+
+    // old enough
+    ctx.warp_to_slot(buyer_facts.registered_slot + 100).unwrap();
+    ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        1,
+        OptionalAccounts::default(),
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+}
+
+/// Test a buyer can't purchase again within the cooldown
+#[tokio::test]
+async fn test_buy_token_purchase_cooldown() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    configure_anti_bot(&sale, 0, 100, &mut ctx).await;
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        1,
+        OptionalAccounts::default(),
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(sale.buyer_facts)
+        .await
+        .unwrap();
+    assert_ne!(buyer_facts.last_purchase_slot, 0);
+
+    // different amount, not a duplicate transaction
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        2,
+        OptionalAccounts::default(),
+        &ctx,
+    );
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::PurchaseCooldown as u32
+    );
+}
+
+/// Switch the sale to vouchers signed by `whitelist_signer`
+async fn configure_voucher_whitelist(
+    sale: &Sale,
+    whitelist_signer: &Keypair,
+    ctx: &mut ProgramTestContext,
+) {
+    TestHelper::configure_sale(
+        sale.token_base,
+        sale.mint,
+        crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+            settings: crate::instruction::SaleSettings::V1(crate::instruction::SaleSettingsV1 {
+                whitelist_mode: Some(state::WhitelistMode::Voucher),
+                whitelist_signer: Some(whitelist_signer.pubkey()),
+                ..Default::default()
+            }),
+        },
+        sale.program_id,
+        ctx,
+    )
+    .await;
+}
+
+/// BuyToken preceded by the Ed25519 instruction verifying `voucher`
+fn buy_token_with_voucher_transaction(
+    sale: &Sale,
+    voucher: &Voucher,
+    whitelist_signer: &Keypair,
+    amount: u64,
+    ctx: &ProgramTestContext,
+) -> Transaction {
+    let signature = whitelist_signer.sign_message(&voucher.message());
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+
+    Transaction::new_signed_with_payer(
+        &[
+            voucher.ed25519_instruction(&whitelist_signer.pubkey(), &signature.into()),
+            buy_token_instruction(
+                sale,
+                &sale.buyer.pubkey(),
+                sale.buyer_facts,
+                buyer_token_account,
+                amount,
+                OptionalAccounts {
+                    instructions_sysvar: sysvar::instructions::ID,
+                    ..Default::default()
+                },
+                ctx,
+            ),
+        ],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &sale.buyer.insecure_clone()],
+        ctx.last_blockhash,
+    )
+}
+
+/// Test Happy Path, voucher signed by the whitelist signer
+#[tokio::test]
+async fn test_buy_token_with_voucher() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+
+    let whitelist_signer = Keypair::new();
+    configure_voucher_whitelist(&sale, &whitelist_signer, &mut ctx).await;
+
+    let amount: u64 = 40;
+    let voucher = Voucher {
+        buyer: sale.buyer.pubkey(),
+        token_base: sale.token_base,
+        allocation: amount,
+        expiry: i64::MAX,
+    };
+    let transaction =
+        buy_token_with_voucher_transaction(&sale, &voucher, &whitelist_signer, amount, &ctx);
+
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // confirm state
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(sale.buyer_facts)
+        .await
+        .unwrap();
+    assert_eq!(buyer_facts.total_purchased, amount);
+}
+
+/// Test purchases past the voucher allocation are rejected
+#[tokio::test]
+async fn test_buy_token_voucher_allocation_exceeded() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    let whitelist_signer = Keypair::new();
+    configure_voucher_whitelist(&sale, &whitelist_signer, &mut ctx).await;
+
+    let voucher = Voucher {
+        buyer: sale.buyer.pubkey(),
+        token_base: sale.token_base,
+        allocation: 10,
+        expiry: i64::MAX,
+    };
+    let transaction =
+        buy_token_with_voucher_transaction(&sale, &voucher, &whitelist_signer, 11, &ctx);
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::AllocationExceeded as u32
+    );
+}
+
+/// Write a token account of `gate_mint` held by `holder` straight into the bank
+fn set_holder_token_account(
+    gate_mint: Pubkey,
+    holder: Pubkey,
+    amount: u64,
+    ctx: &mut ProgramTestContext,
+) -> Pubkey {
+    let holder_token_account = Pubkey::new_unique();
+
+    let mut data = vec![0; Account::LEN];
+    Account {
+        mint: gate_mint,
+        owner: holder,
+        amount,
+        state: spl_token::state::AccountState::Initialized,
+        ..Default::default()
+    }
+    .pack_into_slice(&mut data);
+
+    let account = solana_sdk::account::Account {
+        lamports: solana_sdk::rent::Rent::default().minimum_balance(Account::LEN),
+        data,
+        owner: spl_token::ID,
+        executable: false,
+        rent_epoch: 0,
+    };
+    ctx.set_account(&holder_token_account, &account.into());
+
+    holder_token_account
+}
+
+/// Gate the sale on holding `gate_min_amount` of `gate_mint`
+async fn configure_token_holder_gate(
+    sale: &Sale,
+    gate_mint: Pubkey,
+    gate_min_amount: u64,
+    ctx: &mut ProgramTestContext,
+) {
+    TestHelper::configure_sale(
+        sale.token_base,
+        sale.mint,
+        crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+            settings: crate::instruction::SaleSettings::V1(crate::instruction::SaleSettingsV1 {
+                whitelist_mode: Some(state::WhitelistMode::TokenHolder),
+                gate_key: Some(gate_mint),
+                gate_min_amount: Some(gate_min_amount),
+                ..Default::default()
+            }),
+        },
+        sale.program_id,
+        ctx,
+    )
+    .await;
+}
+
+/// Test Happy Path, governance token holder
+#[tokio::test]
+async fn test_buy_token_token_holder() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    let gate_mint = Pubkey::new_unique();
+    configure_token_holder_gate(&sale, gate_mint, 10, &mut ctx).await;
+
+    let holder_token_account =
+        set_holder_token_account(gate_mint, sale.buyer.pubkey(), 10, &mut ctx);
+
+    let amount: u64 = 40;
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        amount,
+        OptionalAccounts {
+            holder_token_account,
+            ..Default::default()
+        },
+        &ctx,
+    );
+
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // confirm state
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(sale.buyer_facts)
+        .await
+        .unwrap();
+    assert_eq!(buyer_facts.purchase_limit, DEFAULT_PURCHASE_LIMIT - amount);
+}
+
+/// Test a balance below the gate's minimum is rejected
+#[tokio::test]
+async fn test_buy_token_token_holder_below_minimum_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    let gate_mint = Pubkey::new_unique();
+    configure_token_holder_gate(&sale, gate_mint, 10, &mut ctx).await;
+
+    let holder_token_account =
+        set_holder_token_account(gate_mint, sale.buyer.pubkey(), 9, &mut ctx);
+
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        40,
+        OptionalAccounts {
+            holder_token_account,
+            ..Default::default()
+        },
+        &ctx,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::NotHolder as u32
+    );
+}
+
+/// Test proofs against a replaced whitelist root verify during the grace period
+#[tokio::test]
+async fn test_buy_token_previous_root_grace_period() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    let buyer_token_account = get_associated_token_address(&sale.buyer.pubkey(), &sale.mint);
+
+    // rotate to a whitelist without the buyer, its proof is for the old root
+    let new_root = crate::merkle::WhitelistRoot(
+        MerkleTree::new(vec![pubkey_to_sha256_leaf(&sale.referrer.pubkey())]).root,
+    );
+    TestHelper::configure_sale(
+        sale.token_base,
+        sale.mint,
+        crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+            settings: crate::instruction::SaleSettings::V1(crate::instruction::SaleSettingsV1 {
+                whitelist_root: Some(new_root),
+                root_grace_slots: Some(100),
+                ..Default::default()
+            }),
+        },
+        sale.program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let token_base = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::TokenBase>(sale.token_base)
+        .await
+        .unwrap();
+    let rotated_slot = token_base.latest_root_activation_slot();
+
+    // within the grace period
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        1,
+        OptionalAccounts::default(),
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // grace period is over
+    ctx.warp_to_slot(rotated_slot + 100).unwrap();
+    ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+
+    let transaction = buy_token_with_optional_transaction(
+        &sale,
+        &sale.buyer,
+        sale.buyer_facts,
+        buyer_token_account,
+        1,
+        OptionalAccounts::default(),
+        &ctx,
+    );
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::NotWhitelisted as u32
+    );
+}
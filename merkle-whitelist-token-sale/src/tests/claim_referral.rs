@@ -0,0 +1,162 @@
+use crate::pda::BuyerFactsPDA;
+use crate::state::BuyerFacts;
+use crate::*;
+use assert_matches::assert_matches;
+use borsh::BorshSerialize;
+use bytemuck::Zeroable;
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Keypair,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+};
+use spl_discriminator::SplDiscriminate;
+
+/// Write a referrer's BuyerFacts holding `referral_rewards` straight into the bank
+fn set_referrer_facts(
+    token_base: &Pubkey,
+    referrer: &Pubkey,
+    referral_rewards: u64,
+    program_id: Pubkey,
+    ctx: &mut ProgramTestContext,
+) -> Pubkey {
+    let (buyer_facts_pda, buyer_facts_canonical_bump) =
+        BuyerFactsPDA::find_pda(&program_id, token_base, referrer);
+
+    let mut buyer_facts = BuyerFacts::zeroed();
+    buyer_facts.discriminator = BuyerFacts::SPL_DISCRIMINATOR.into();
+    buyer_facts.purchase_limit = 100;
+    buyer_facts.bump = buyer_facts_canonical_bump;
+    buyer_facts.version = BuyerFacts::VERSION;
+    buyer_facts.referral_rewards = referral_rewards;
+
+    let account = Account {
+        lamports: Rent::default().minimum_balance(BuyerFacts::LEN) + referral_rewards,
+        data: bytemuck::bytes_of(&buyer_facts).to_vec(),
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    ctx.set_account(&buyer_facts_pda, &account.into());
+
+    buyer_facts_pda
+}
+
+fn claim_referral_transaction(
+    token_base: Pubkey,
+    buyer_facts: Pubkey,
+    referrer: &Keypair,
+    program_id: Pubkey,
+    ctx: &ProgramTestContext,
+) -> Transaction {
+    let mut instruction_data = Vec::new();
+    crate::instruction::TokenSaleInstruction::ClaimReferral
+        .serialize(&mut instruction_data)
+        .unwrap();
+
+    Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(buyer_facts, false),
+                AccountMeta::new(referrer.pubkey(), true),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &referrer.insecure_clone()],
+        ctx.last_blockhash,
+    )
+}
+
+/// Test Happy Path
+#[tokio::test]
+async fn test_claim_referral() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let token_base = Pubkey::new_unique();
+    let referrer = Keypair::new();
+    let referral_rewards: u64 = 2_000;
+    let buyer_facts_pda = set_referrer_facts(
+        &token_base,
+        &referrer.pubkey(),
+        referral_rewards,
+        program_id,
+        &mut ctx,
+    );
+
+    let transaction =
+        claim_referral_transaction(token_base, buyer_facts_pda, &referrer, program_id, &ctx);
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // confirm state
+    assert_eq!(
+        ctx.banks_client
+            .get_balance(referrer.pubkey())
+            .await
+            .unwrap(),
+        referral_rewards
+    );
+
+    // the rent stays behind
+    assert_eq!(
+        ctx.banks_client.get_balance(buyer_facts_pda).await.unwrap(),
+        Rent::default().minimum_balance(BuyerFacts::LEN)
+    );
+
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<BuyerFacts>(buyer_facts_pda)
+        .await
+        .unwrap();
+    assert_eq!(buyer_facts.referral_rewards, 0);
+}
+
+/// Test claiming without rewards is rejected
+#[tokio::test]
+async fn test_claim_referral_without_rewards_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let token_base = Pubkey::new_unique();
+    let referrer = Keypair::new();
+    let buyer_facts_pda =
+        set_referrer_facts(&token_base, &referrer.pubkey(), 0, program_id, &mut ctx);
+
+    let transaction =
+        claim_referral_transaction(token_base, buyer_facts_pda, &referrer, program_id, &ctx);
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::NoReferralRewards as u32
+    );
+}
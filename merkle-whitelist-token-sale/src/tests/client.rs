@@ -0,0 +1,131 @@
+use crate::client::{
+    buyer_facts_filters, list_buyer_facts, list_receipts, receipt_addresses, receipt_buyer_filter,
+};
+use crate::pda::ReceiptPDA;
+use crate::state::{BuyerFacts, Receipt};
+use bytemuck::Zeroable;
+use solana_sdk::pubkey::Pubkey;
+use spl_discriminator::SplDiscriminate;
+
+fn receipt(token_base: Pubkey, buyer: Pubkey, nonce: u64) -> Vec<u8> {
+    let mut receipt = Receipt::zeroed();
+    receipt.discriminator = Receipt::SPL_DISCRIMINATOR.into();
+    receipt.token_base = token_base;
+    receipt.buyer = buyer;
+    receipt.nonce = nonce;
+    bytemuck::bytes_of(&receipt).to_vec()
+}
+
+fn buyer_facts(token_base: Pubkey, buyer: Pubkey, total_purchased: u64) -> Vec<u8> {
+    let mut buyer_facts = BuyerFacts::zeroed();
+    buyer_facts.discriminator = BuyerFacts::SPL_DISCRIMINATOR.into();
+    buyer_facts.token_base = token_base;
+    buyer_facts.buyer = buyer;
+    buyer_facts.total_purchased = total_purchased;
+    bytemuck::bytes_of(&buyer_facts).to_vec()
+}
+
+/// Receipt addresses follow the nonce
+#[test]
+fn test_receipt_addresses() {
+    let (program_id, token_base, buyer) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+
+    let addresses = receipt_addresses(&program_id, &token_base, &buyer, 3);
+    assert_eq!(addresses.len(), 3);
+    for (nonce, address) in addresses.iter().enumerate() {
+        let (pda, _) = ReceiptPDA::find_pda(&program_id, &token_base, &buyer, nonce as u64);
+        assert_eq!(*address, pda);
+    }
+}
+
+/// The memcmp filter matches the buyer field of a Receipt
+#[test]
+fn test_receipt_buyer_filter() {
+    let buyer = Pubkey::new_unique();
+    let data = receipt(Pubkey::new_unique(), buyer, 0);
+
+    let (offset, bytes) = receipt_buyer_filter(&buyer);
+    assert_eq!(&data[offset..offset + 32], bytes.as_slice());
+}
+
+/// Only the wallet's initialized receipts are listed, in order
+#[test]
+fn test_list_receipts() {
+    let (token_base, buyer, other) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+
+    let accounts = [
+        (Pubkey::new_unique(), receipt(token_base, buyer, 1)),
+        (Pubkey::new_unique(), receipt(token_base, other, 0)),
+        (Pubkey::new_unique(), vec![0; Receipt::LEN]),
+        (Pubkey::new_unique(), vec![1; 8]),
+        (Pubkey::new_unique(), receipt(token_base, buyer, 0)),
+    ];
+
+    let receipts = list_receipts(
+        &buyer,
+        accounts
+            .iter()
+            .map(|(address, data)| (*address, data.as_slice())),
+    );
+
+    let listed = receipts
+        .iter()
+        .map(|(address, receipt)| (*address, receipt.nonce))
+        .collect::<Vec<_>>();
+    assert_eq!(listed, vec![(accounts[4].0, 0), (accounts[0].0, 1)]);
+}
+
+/// The memcmp filters match the discriminator and token_base of a BuyerFacts
+#[test]
+fn test_buyer_facts_filters() {
+    let token_base = Pubkey::new_unique();
+    let data = buyer_facts(token_base, Pubkey::new_unique(), 0);
+
+    for (offset, bytes) in buyer_facts_filters(&token_base) {
+        assert_eq!(&data[offset..offset + bytes.len()], bytes.as_slice());
+    }
+    let (offset, bytes) = &buyer_facts_filters(&Pubkey::new_unique())[1];
+    assert_ne!(&data[*offset..offset + 32], bytes.as_slice());
+}
+
+/// Only the sale's initialized BuyerFacts are listed, by buyer
+#[test]
+fn test_list_buyer_facts() {
+    let token_base = Pubkey::new_unique();
+    let (first, second) = {
+        let (a, b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        (a.min(b), a.max(b))
+    };
+
+    let accounts = [
+        (Pubkey::new_unique(), buyer_facts(token_base, second, 2)),
+        (
+            Pubkey::new_unique(),
+            buyer_facts(Pubkey::new_unique(), first, 3),
+        ),
+        (Pubkey::new_unique(), vec![0; BuyerFacts::LEN]),
+        (Pubkey::new_unique(), vec![1; 8]),
+        (Pubkey::new_unique(), buyer_facts(token_base, first, 1)),
+    ];
+
+    let buyer_facts = list_buyer_facts(
+        &token_base,
+        accounts
+            .iter()
+            .map(|(address, data)| (*address, data.as_slice())),
+    );
+
+    let listed = buyer_facts
+        .iter()
+        .map(|(address, buyer_facts)| (*address, buyer_facts.total_purchased))
+        .collect::<Vec<_>>();
+    assert_eq!(listed, vec![(accounts[4].0, 1), (accounts[0].0, 2)]);
+}
@@ -0,0 +1,225 @@
+use crate::pda::ReceiptPDA;
+use crate::state::Receipt;
+use crate::*;
+use assert_matches::assert_matches;
+use borsh::BorshSerialize;
+use bytemuck::Zeroable;
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Keypair,
+    signature::Signer,
+    system_transaction,
+    transaction::{Transaction, TransactionError},
+};
+use spl_discriminator::SplDiscriminate;
+
+/// Write a Receipt for `buyer`, rent paid by `payer`, straight into the bank
+fn set_receipt(
+    buyer: &Pubkey,
+    payer: &Pubkey,
+    program_id: Pubkey,
+    ctx: &mut ProgramTestContext,
+) -> Pubkey {
+    let token_base = Pubkey::new_unique();
+    let (receipt_pda, receipt_canonical_bump) =
+        ReceiptPDA::find_pda(&program_id, &token_base, buyer, 0);
+
+    let mut receipt = Receipt::zeroed();
+    receipt.discriminator = Receipt::SPL_DISCRIMINATOR.into();
+    receipt.token_base = token_base;
+    receipt.buyer = *buyer;
+    receipt.payer = *payer;
+    receipt.amount = 40;
+    receipt.lamports_paid = 40000;
+    receipt.price = 1000;
+    receipt.bump = receipt_canonical_bump;
+
+    let account = Account {
+        lamports: Rent::default().minimum_balance(Receipt::LEN),
+        data: bytemuck::bytes_of(&receipt).to_vec(),
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    ctx.set_account(&receipt_pda, &account.into());
+
+    receipt_pda
+}
+
+async fn funded_buyer(ctx: &mut ProgramTestContext) -> Keypair {
+    let buyer = Keypair::new();
+    ctx.banks_client
+        .process_transaction(system_transaction::transfer(
+            &ctx.payer,
+            &buyer.pubkey(),
+            1000000000,
+            ctx.last_blockhash,
+        ))
+        .await
+        .unwrap();
+    buyer
+}
+
+fn close_receipt_transaction(
+    receipt: Pubkey,
+    buyer: &Keypair,
+    payer: &Pubkey,
+    program_id: Pubkey,
+    ctx: &ProgramTestContext,
+) -> Transaction {
+    let mut instruction_data = Vec::new();
+    crate::instruction::TokenSaleInstruction::CloseReceipt
+        .serialize(&mut instruction_data)
+        .unwrap();
+
+    Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(receipt, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(*payer, false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    )
+}
+
+/// Test Happy Path
+#[tokio::test]
+async fn test_close_receipt() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let buyer = funded_buyer(&mut ctx).await;
+    let receipt_pda = set_receipt(&buyer.pubkey(), &buyer.pubkey(), program_id, &mut ctx);
+
+    let receipt_lamports = ctx.banks_client.get_balance(receipt_pda).await.unwrap();
+    let buyer_lamports = ctx.banks_client.get_balance(buyer.pubkey()).await.unwrap();
+
+    let transaction =
+        close_receipt_transaction(receipt_pda, &buyer, &buyer.pubkey(), program_id, &ctx);
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // confirm state
+    assert!(ctx
+        .banks_client
+        .get_account(receipt_pda)
+        .await
+        .unwrap()
+        .is_none());
+
+    // rent goes back to the buyer
+    assert_eq!(
+        ctx.banks_client.get_balance(buyer.pubkey()).await.unwrap(),
+        buyer_lamports + receipt_lamports
+    );
+}
+
+/// Test only the receipt's buyer can close it
+#[tokio::test]
+async fn test_close_receipt_rejects_other_buyer() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let buyer = funded_buyer(&mut ctx).await;
+    let receipt_pda = set_receipt(&buyer.pubkey(), &buyer.pubkey(), program_id, &mut ctx);
+
+    let other = funded_buyer(&mut ctx).await;
+    let transaction =
+        close_receipt_transaction(receipt_pda, &other, &other.pubkey(), program_id, &ctx);
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::InvalidReceiptSeeds as u32
+    );
+}
+
+/// Test the rent goes back to a relayer that paid it, not the buyer
+#[tokio::test]
+async fn test_close_receipt_refunds_payer() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let buyer = funded_buyer(&mut ctx).await;
+    let relayer = funded_buyer(&mut ctx).await;
+    let receipt_pda = set_receipt(&buyer.pubkey(), &relayer.pubkey(), program_id, &mut ctx);
+
+    // the buyer can't claim the relayer's rent
+    let transaction =
+        close_receipt_transaction(receipt_pda, &buyer, &buyer.pubkey(), program_id, &ctx);
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::PayerMismatch as u32
+    );
+
+    let receipt_lamports = ctx.banks_client.get_balance(receipt_pda).await.unwrap();
+    let buyer_lamports = ctx.banks_client.get_balance(buyer.pubkey()).await.unwrap();
+    let relayer_lamports = ctx
+        .banks_client
+        .get_balance(relayer.pubkey())
+        .await
+        .unwrap();
+
+    let transaction =
+        close_receipt_transaction(receipt_pda, &buyer, &relayer.pubkey(), program_id, &ctx);
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // rent goes back to the relayer, the buyer keeps its lamports
+    assert_eq!(
+        ctx.banks_client
+            .get_balance(relayer.pubkey())
+            .await
+            .unwrap(),
+        relayer_lamports + receipt_lamports
+    );
+    assert_eq!(
+        ctx.banks_client.get_balance(buyer.pubkey()).await.unwrap(),
+        buyer_lamports
+    );
+}
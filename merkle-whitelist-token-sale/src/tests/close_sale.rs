@@ -5,11 +5,11 @@ use borsh::BorshSerialize;
 use merkletreers::{tree::MerkleTree, Leaf};
 use solana_program_test::*;
 use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
+    instruction::{AccountMeta, Instruction, InstructionError},
     pubkey::Pubkey,
     signature::Keypair,
     signature::Signer,
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 
 /// Test Happy Path
@@ -47,10 +47,16 @@ async fn test_close_sale() {
     )
     .await;
 
+    TestHelper::end_sale(token_base_pda, mint, program_id, &mut ctx).await;
+
     let instruction = crate::instruction::TokenSaleInstruction::CloseSale;
 
     let mut instruction_data = Vec::new();
     instruction.serialize(&mut instruction_data).unwrap();
+    let registry_accounts =
+        TestHelper::close_sale_registry_accounts(token_base_pda, program_id, &mut ctx).await;
+    let escrow_account =
+        TestHelper::close_sale_escrow_account(token_base_pda, program_id, &mut ctx).await;
 
     // CloseSale Transaction
     let transaction = Transaction::new_signed_with_payer(
@@ -60,7 +66,11 @@ async fn test_close_sale() {
                 AccountMeta::new(token_base_pda, false),
                 AccountMeta::new_readonly(mint, false),
                 AccountMeta::new(ctx.payer.pubkey(), true),
-            ],
+            ]
+            .into_iter()
+            .chain(registry_accounts)
+            .chain([escrow_account])
+            .collect(),
             data: instruction_data.clone(),
         }],
         Some(&ctx.payer.pubkey()),
@@ -74,14 +84,347 @@ async fn test_close_sale() {
         .unwrap();
 
     // confirm state
+    let account = ctx
+        .banks_client
+        .get_account(token_base_pda)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // must be a rent exempt tombstone
+    assert_eq!(account.owner, program_id);
+    assert_eq!(account.data.len(), state::ClosedTokenBase::LEN);
+    let closed: state::ClosedTokenBase = bytemuck::pod_read_unaligned(&account.data);
+    assert_eq!(closed.sale_authority, ctx.payer.pubkey());
+    assert_eq!(closed.mint, mint);
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    assert_eq!(
+        account.lamports,
+        rent.minimum_balance(state::ClosedTokenBase::LEN)
+    );
+
+    // OpenSale can't reuse the address while its BuyerFacts may exist
+    let instruction = crate::instruction::TokenSaleInstruction::OpenSale {
+        price,
+        purchase_limit: default_purchase_limit,
+        whitelist_root,
+    };
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+    let registry_accounts = TestHelper::open_sale_registry_accounts(program_id, &mut ctx).await;
+
+    let new_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base_pda, false),
+                AccountMeta::new(mint, false),
+                AccountMeta::new_readonly(vault.pubkey(), false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+                AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+            ]
+            .into_iter()
+            .chain(registry_accounts)
+            .chain([AccountMeta::new_readonly(spl_token::ID, false)])
+            .collect(),
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        new_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::SaleEnded as u32
+    );
+}
+
+/// Test a stopped sale without an end slot closes right away
+#[tokio::test]
+async fn test_close_stopped_sale_without_end_slot() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase, OpenSale leaves it stopped and without an end slot
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::CloseSale;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+    let registry_accounts =
+        TestHelper::close_sale_registry_accounts(token_base_pda, program_id, &mut ctx).await;
+    let escrow_account =
+        TestHelper::close_sale_escrow_account(token_base_pda, program_id, &mut ctx).await;
+
+    let new_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base_pda, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+            ]
+            .into_iter()
+            .chain(registry_accounts)
+            .chain([escrow_account])
+            .collect(),
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        new_blockhash,
+    );
+
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let account = ctx
+        .banks_client
+        .get_account(token_base_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.data.len(), state::ClosedTokenBase::LEN);
+}
+
+/// Test a running sale can't be closed
+#[tokio::test]
+async fn test_close_running_sale_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let price: u64 = 100000000000;
+    let default_purchase_limit: u64 = 100;
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        price,
+        default_purchase_limit,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    // is_running: false -> true
+    TestHelper::toggle_running(token_base_pda, mint, program_id, &mut ctx).await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::CloseSale;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+    let registry_accounts =
+        TestHelper::close_sale_registry_accounts(token_base_pda, program_id, &mut ctx).await;
+    let escrow_account =
+        TestHelper::close_sale_escrow_account(token_base_pda, program_id, &mut ctx).await;
+
+    let new_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+
+    // CloseSale Transaction
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base_pda, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+            ]
+            .into_iter()
+            .chain(registry_accounts)
+            .chain([escrow_account])
+            .collect(),
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        new_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::SaleStillRunning as u32
+    );
+
+    // TokenBase is untouched
     let token_base = ctx
         .banks_client
         .get_account_data_with_borsh::<state::TokenBase>(token_base_pda)
-        .await;
+        .await
+        .unwrap();
+    assert!(token_base.is_running());
+}
+
+/// Test a paused sale can't be closed before its end slot
+#[tokio::test]
+async fn test_close_sale_before_end_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    // end slot is set but not reached yet
+    TestHelper::configure_sale(
+        token_base_pda,
+        mint,
+        crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+            settings: crate::instruction::SaleSettings::V2(crate::instruction::SaleSettingsV2 {
+                end_slot: Some(1_000),
+                ..Default::default()
+            }),
+        },
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::CloseSale;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+    let registry_accounts =
+        TestHelper::close_sale_registry_accounts(token_base_pda, program_id, &mut ctx).await;
+    let escrow_account =
+        TestHelper::close_sale_escrow_account(token_base_pda, program_id, &mut ctx).await;
+
+    let new_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+
+    // CloseSale Transaction
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base_pda, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+            ]
+            .into_iter()
+            .chain(registry_accounts)
+            .chain([escrow_account])
+            .collect(),
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        new_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::SaleNotEnded as u32
+    );
+
+    // an end slot can't be moved earlier
+    let instruction = crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+        settings: crate::instruction::SaleSettings::V2(crate::instruction::SaleSettingsV2 {
+            end_slot: Some(500),
+            ..Default::default()
+        }),
+    };
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base_pda, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        new_blockhash,
+    );
 
-    // must be account not found
+    let result = ctx.banks_client.process_transaction(transaction).await;
     assert_matches!(
-        token_base,
-        Err(BanksClientError::ClientError("Account not found"))
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::InvalidEndSlot as u32
     );
 }
@@ -0,0 +1,889 @@
+//! Compute unit benchmarks
+//!
+//! Runs every TokenSaleInstruction against sales whitelisting the buyer at
+//! proof depths 1 to 24, writes the consumed compute units to a JSON report
+//! and fails if any instruction exceeds its budget in [`BUDGETS`], or if a
+//! transaction outgrows a packet without being marked unsendable.
+//!
+//! The report is written to `target/compute_units.json`, or to the path in
+//! `COMPUTE_UNITS_REPORT`.
+use crate::instruction::TokenSaleInstruction;
+use crate::merkle::{convert_merkle_proof, pubkey_to_sha256_leaf, WhitelistRoot};
+use crate::pda::{BuyerFactsPDA, EscrowPDA, ReceiptPDA, TokenBasePDA};
+use borsh::BorshSerialize;
+use merkletreers::{
+    merkle_proof_check::merkle_proof_check,
+    node::{Node, Side},
+    Proof,
+};
+use serde_json::json;
+use solana_program_test::*;
+use solana_sdk::{
+    compute_budget::ComputeBudgetInstruction,
+    hash::hash,
+    instruction::{AccountMeta, Instruction},
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signature::Signer,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+    system_transaction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::get_associated_token_address;
+
+const PRICE: u64 = 1000;
+const DEFAULT_PURCHASE_LIMIT: u64 = 100;
+const REFERRAL_BPS: u16 = 500;
+const MILESTONE_BPS: [u16; 2] = [5000, 5000];
+const VOTE_THRESHOLD_BPS: u16 = 5000;
+const PROOF_DEPTHS: std::ops::RangeInclusive<usize> = 1..=24;
+
+/// Compute unit limit of a single instruction without a ComputeBudget request
+const DEFAULT_INSTRUCTION_LIMIT: u64 = 200_000;
+
+/// Limits one instruction is held to
+struct Budget {
+    instruction: &'static str,
+    compute_units: u64,
+    /// Takes a whitelist proof, deep proofs outgrow a packet and are
+    /// reported unsendable
+    takes_proof: bool,
+}
+
+/// Budget of every measured instruction
+///
+/// Proof verification grows with the depth, so instructions taking a proof
+/// must fit the default instruction limit at the deepest proof. The others
+/// are held well below it so regressions show up early.
+#[rustfmt::skip]
+const BUDGETS: &[Budget] = &[
+    Budget { instruction: "OpenSale", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "ConfigureSale", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "UpdateSaleSettings", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "InitializeEscrow", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "ToggleRunning", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "RegisterBuyer", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "AssignLimit", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "Quote", compute_units: DEFAULT_INSTRUCTION_LIMIT, takes_proof: true },
+    Budget { instruction: "BuyToken", compute_units: DEFAULT_INSTRUCTION_LIMIT, takes_proof: true },
+    Budget { instruction: "RegisterAndBuy", compute_units: DEFAULT_INSTRUCTION_LIMIT, takes_proof: true },
+    Budget { instruction: "ClaimReferral", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "VoteMilestone", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "ReleaseMilestone", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "CancelEscrow", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "RefundEscrow", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "CloseReceipt", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "Migrate", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "DeregisterBuyer", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "CloseSale", compute_units: 50_000, takes_proof: false },
+    Budget { instruction: "ReclaimBuyerFacts", compute_units: 50_000, takes_proof: false },
+];
+
+/// Budget of `instruction`, every measured instruction must have one
+fn budget(instruction: &str) -> &'static Budget {
+    BUDGETS
+        .iter()
+        .find(|budget| budget.instruction == instruction)
+        .unwrap_or_else(|| panic!("{instruction} has no entry in BUDGETS"))
+}
+
+/// Compute units consumed by one instruction
+struct Measurement {
+    instruction: &'static str,
+    proof_depth: usize,
+    compute_units: u64,
+    transaction_size: usize,
+}
+
+impl Measurement {
+    /// Is `true` if the transaction fits a single packet
+    fn is_sendable(&self) -> bool {
+        self.transaction_size <= PACKET_DATA_SIZE
+    }
+}
+
+/// Whitelist of `buyers[0]` and `buyers[1]` at `depth`, with its root
+/// and the proof of each buyer
+///
+/// Building a real tree of 2^24 leaves is too slow, the buyers are
+/// siblings and the other nodes are arbitrary hashes instead
+fn synthetic_whitelist(buyers: [&Pubkey; 2], depth: usize) -> (WhitelistRoot, [Proof; 2]) {
+    let leaves = buyers.map(pubkey_to_sha256_leaf);
+    let path = (1..depth)
+        .map(|level| Node {
+            data: hash(&level.to_le_bytes()).to_bytes(),
+            side: match level % 2 {
+                0 => Side::LEFT,
+                _ => Side::RIGHT,
+            },
+        })
+        .collect::<Vec<_>>();
+
+    let proofs = [(leaves[1], Side::RIGHT), (leaves[0], Side::LEFT)]
+        .map(|(data, side)| [vec![Node { data, side }], path.clone()].concat());
+
+    let root = merkle_proof_check(proofs[0].clone(), leaves[0]);
+    (WhitelistRoot(root), proofs)
+}
+
+/// Units the top level invocation of `program_id` consumed, from its logs
+fn consumed_units(program_id: &Pubkey, log_messages: &[String]) -> u64 {
+    let prefix = format!("Program {program_id} consumed ");
+    log_messages
+        .iter()
+        .find_map(|log| log.strip_prefix(&prefix))
+        .and_then(|log| log.split_whitespace().next())
+        .and_then(|units| units.parse().ok())
+        .expect("program logs its consumed units")
+}
+
+/// Wire size of `transaction`, one byte encodes the signature count
+fn transaction_size(transaction: &Transaction) -> usize {
+    1 + transaction.signatures.len() * 64 + transaction.message.serialize().len()
+}
+
+struct Bench {
+    program_id: Pubkey,
+    proof_depth: usize,
+    measurements: Vec<Measurement>,
+}
+
+impl Bench {
+    /// Run `instruction`, returning its compute units and transaction size
+    ///
+    /// The transaction requests the maximum compute units, an instruction
+    /// over budget is still measured rather than aborted
+    async fn process(
+        &self,
+        instruction: TokenSaleInstruction,
+        accounts: Vec<AccountMeta>,
+        signers: &[&Keypair],
+        ctx: &mut ProgramTestContext,
+    ) -> (u64, usize) {
+        let name = format!("{instruction:?}");
+        let mut instruction_data = Vec::new();
+        instruction.serialize(&mut instruction_data).unwrap();
+
+        let payer = ctx.payer.insecure_clone();
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                ComputeBudgetInstruction::set_compute_unit_limit(1_400_000),
+                Instruction {
+                    program_id: self.program_id,
+                    accounts,
+                    data: instruction_data,
+                },
+            ],
+            Some(&payer.pubkey()),
+            &[&[&payer], signers].concat(),
+            ctx.last_blockhash,
+        );
+        let transaction_size = transaction_size(&transaction);
+
+        let processed = ctx
+            .banks_client
+            .process_transaction_with_metadata(transaction)
+            .await
+            .unwrap();
+        assert_eq!(processed.result, Ok(()), "{name} failed");
+
+        let compute_units =
+            consumed_units(&self.program_id, &processed.metadata.unwrap().log_messages);
+        (compute_units, transaction_size)
+    }
+
+    /// Run `instruction` and record its compute units
+    async fn measure(
+        &mut self,
+        name: &'static str,
+        instruction: TokenSaleInstruction,
+        accounts: Vec<AccountMeta>,
+        signers: &[&Keypair],
+        ctx: &mut ProgramTestContext,
+    ) {
+        let (compute_units, transaction_size) =
+            self.process(instruction, accounts, signers, ctx).await;
+
+        self.measurements.push(Measurement {
+            instruction: name,
+            proof_depth: self.proof_depth,
+            compute_units,
+            transaction_size,
+        });
+    }
+
+    /// Run every instruction once, in an order satisfying their preconditions
+    async fn run_sale(&mut self, ctx: &mut ProgramTestContext) {
+        let program_id = self.program_id;
+        let sale_authority = ctx.payer.insecure_clone();
+        let buyer = Keypair::new();
+        let referrer = Keypair::new();
+        let newcomer = Keypair::new();
+        // registers and deregisters without purchasing
+        let idle = Keypair::new();
+        let vault = Keypair::new().pubkey();
+        let team = Keypair::new().pubkey();
+
+        // the vault and team are funded so they stay rent exempt
+        for account in [
+            buyer.pubkey(),
+            referrer.pubkey(),
+            newcomer.pubkey(),
+            vault,
+            team,
+        ] {
+            ctx.banks_client
+                .process_transaction(system_transaction::transfer(
+                    &ctx.payer,
+                    &account,
+                    1_000_000_000,
+                    ctx.last_blockhash,
+                ))
+                .await
+                .unwrap();
+        }
+
+        let mint = super::utils::TestHelper::new_mint(0, ctx).await;
+        let (token_base, _) = TokenBasePDA::find_pda(&program_id, &sale_authority.pubkey(), &mint);
+        let (buyer_facts, _) = BuyerFactsPDA::find_pda(&program_id, &token_base, &buyer.pubkey());
+        let (referrer_facts, _) =
+            BuyerFactsPDA::find_pda(&program_id, &token_base, &referrer.pubkey());
+        let (receipt, _) = ReceiptPDA::find_pda(&program_id, &token_base, &buyer.pubkey(), 0);
+        let (escrow, _) = EscrowPDA::find_pda(&program_id, &token_base);
+        let (whitelist_root, [proof, newcomer_proof]) =
+            synthetic_whitelist([&buyer.pubkey(), &newcomer.pubkey()], self.proof_depth);
+
+        let sale_authority_accounts = vec![
+            AccountMeta::new(token_base, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new(sale_authority.pubkey(), true),
+        ];
+        let register_buyer_accounts = |buyer: &Pubkey, buyer_facts: Pubkey| {
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(buyer_facts, false),
+                AccountMeta::new_readonly(*buyer, true),
+                AccountMeta::new(sale_authority.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ]
+        };
+
+        let registry_accounts =
+            super::utils::TestHelper::open_sale_registry_accounts(program_id, ctx).await;
+        self.measure(
+            "OpenSale",
+            TokenSaleInstruction::OpenSale {
+                price: PRICE,
+                purchase_limit: DEFAULT_PURCHASE_LIMIT,
+                whitelist_root,
+            },
+            vec![
+                AccountMeta::new(token_base, false),
+                AccountMeta::new(mint, false),
+                AccountMeta::new_readonly(vault, false),
+                AccountMeta::new(sale_authority.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            ]
+            .into_iter()
+            .chain(registry_accounts)
+            .chain([AccountMeta::new_readonly(spl_token::ID, false)])
+            .collect(),
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "ConfigureSale",
+            TokenSaleInstruction::ConfigureSale {
+                price: None,
+                default_purchase_limit: Some(DEFAULT_PURCHASE_LIMIT),
+                whitelist_root: None,
+            },
+            sale_authority_accounts.clone(),
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "UpdateSaleSettings",
+            TokenSaleInstruction::UpdateSaleSettings {
+                settings: crate::instruction::SaleSettings::V1(
+                    crate::instruction::SaleSettingsV1 {
+                        referral_bps: Some(REFERRAL_BPS),
+                        ..Default::default()
+                    },
+                ),
+            },
+            sale_authority_accounts.clone(),
+            &[],
+            ctx,
+        )
+        .await;
+
+        // the sale authority signs off milestones, purchases pay into the escrow
+        self.measure(
+            "InitializeEscrow",
+            TokenSaleInstruction::InitializeEscrow {
+                team,
+                approver: Some(sale_authority.pubkey()),
+                milestone_bps: MILESTONE_BPS.to_vec(),
+                vote_threshold_bps: VOTE_THRESHOLD_BPS,
+            },
+            [
+                sale_authority_accounts.clone(),
+                vec![
+                    AccountMeta::new(escrow, false),
+                    AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                ],
+            ]
+            .concat(),
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "ToggleRunning",
+            TokenSaleInstruction::ToggleRunning,
+            sale_authority_accounts.clone(),
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "RegisterBuyer",
+            TokenSaleInstruction::RegisterBuyer,
+            register_buyer_accounts(&buyer.pubkey(), buyer_facts),
+            &[&buyer],
+            ctx,
+        )
+        .await;
+        self.process(
+            TokenSaleInstruction::RegisterBuyer,
+            register_buyer_accounts(&referrer.pubkey(), referrer_facts),
+            &[&referrer],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "AssignLimit",
+            TokenSaleInstruction::AssignLimit {
+                new_purchase_limit: DEFAULT_PURCHASE_LIMIT,
+            },
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(buyer_facts, false),
+                AccountMeta::new_readonly(buyer.pubkey(), false),
+                AccountMeta::new_readonly(sale_authority.pubkey(), true),
+            ],
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "Quote",
+            TokenSaleInstruction::Quote {
+                amount: DEFAULT_PURCHASE_LIMIT,
+                proof: convert_merkle_proof(proof.clone()),
+            },
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new_readonly(buyer_facts, false),
+                AccountMeta::new_readonly(buyer.pubkey(), false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(escrow, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            &[],
+            ctx,
+        )
+        .await;
+
+        // the most expensive path, creating the ATA, a receipt and a referral
+        self.measure(
+            "BuyToken",
+            TokenSaleInstruction::BuyToken {
+                amount: DEFAULT_PURCHASE_LIMIT,
+                proof: convert_merkle_proof(proof.clone()),
+            },
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(mint, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(sale_authority.pubkey(), false),
+                AccountMeta::new(get_associated_token_address(&buyer.pubkey(), &mint), false),
+                AccountMeta::new(buyer_facts, false),
+                AccountMeta::new(buyer.pubkey(), true),
+                AccountMeta::new(sale_authority.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+                AccountMeta::new(receipt, false),
+                AccountMeta::new(referrer_facts, false),
+                AccountMeta::new_readonly(referrer.pubkey(), false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            &[&buyer],
+            ctx,
+        )
+        .await;
+
+        // registers the newcomer on the way
+        self.measure(
+            "RegisterAndBuy",
+            TokenSaleInstruction::RegisterAndBuy {
+                amount: DEFAULT_PURCHASE_LIMIT,
+                proof: convert_merkle_proof(newcomer_proof),
+            },
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(mint, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(sale_authority.pubkey(), false),
+                AccountMeta::new(
+                    get_associated_token_address(&newcomer.pubkey(), &mint),
+                    false,
+                ),
+                AccountMeta::new(
+                    BuyerFactsPDA::find_pda(&program_id, &token_base, &newcomer.pubkey()).0,
+                    false,
+                ),
+                AccountMeta::new(newcomer.pubkey(), true),
+                AccountMeta::new(sale_authority.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+                AccountMeta::new(crate::ID, false),
+                AccountMeta::new(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            &[&newcomer],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "ClaimReferral",
+            TokenSaleInstruction::ClaimReferral,
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(referrer_facts, false),
+                AccountMeta::new(referrer.pubkey(), true),
+            ],
+            &[&referrer],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "VoteMilestone",
+            TokenSaleInstruction::VoteMilestone,
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(buyer_facts, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+            ],
+            &[&buyer],
+            ctx,
+        )
+        .await;
+
+        // milestones are released once the sale stopped
+        self.process(
+            TokenSaleInstruction::ToggleRunning,
+            sale_authority_accounts.clone(),
+            &[],
+            ctx,
+        )
+        .await;
+
+        let release_milestone_accounts = vec![
+            AccountMeta::new(token_base, false),
+            AccountMeta::new(escrow, false),
+            AccountMeta::new(team, false),
+            AccountMeta::new_readonly(sale_authority.pubkey(), true),
+        ];
+        self.measure(
+            "ReleaseMilestone",
+            TokenSaleInstruction::ReleaseMilestone,
+            release_milestone_accounts.clone(),
+            &[],
+            ctx,
+        )
+        .await;
+
+        // settles the escrow so the sale can be closed
+        self.process(
+            TokenSaleInstruction::ReleaseMilestone,
+            release_milestone_accounts,
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.run_cancelled_escrow(&buyer, whitelist_root, proof, ctx)
+            .await;
+
+        self.measure(
+            "CloseReceipt",
+            TokenSaleInstruction::CloseReceipt,
+            vec![
+                AccountMeta::new(receipt, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(sale_authority.pubkey(), false),
+            ],
+            &[&buyer],
+            ctx,
+        )
+        .await;
+
+        // current layout, measures the version check
+        self.measure(
+            "Migrate",
+            TokenSaleInstruction::Migrate,
+            vec![
+                AccountMeta::new(token_base, false),
+                AccountMeta::new(sale_authority.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            &[],
+            ctx,
+        )
+        .await;
+
+        let (idle_facts, _) = BuyerFactsPDA::find_pda(&program_id, &token_base, &idle.pubkey());
+        self.process(
+            TokenSaleInstruction::RegisterBuyer,
+            register_buyer_accounts(&idle.pubkey(), idle_facts),
+            &[&idle],
+            ctx,
+        )
+        .await;
+        self.measure(
+            "DeregisterBuyer",
+            TokenSaleInstruction::DeregisterBuyer,
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(idle_facts, false),
+                AccountMeta::new_readonly(idle.pubkey(), true),
+                AccountMeta::new(sale_authority.pubkey(), false),
+            ],
+            &[&idle],
+            ctx,
+        )
+        .await;
+
+        // only an ended sale can be closed
+        super::utils::TestHelper::end_sale(token_base, mint, program_id, ctx).await;
+
+        let registry_accounts =
+            super::utils::TestHelper::close_sale_registry_accounts(token_base, program_id, ctx)
+                .await;
+        self.measure(
+            "CloseSale",
+            TokenSaleInstruction::CloseSale,
+            [
+                sale_authority_accounts,
+                registry_accounts,
+                vec![AccountMeta::new(escrow, false)],
+            ]
+            .concat(),
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "ReclaimBuyerFacts",
+            TokenSaleInstruction::ReclaimBuyerFacts,
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(referrer_facts, false),
+                AccountMeta::new(referrer.pubkey(), false),
+                AccountMeta::new(sale_authority.pubkey(), false),
+            ],
+            &[],
+            ctx,
+        )
+        .await;
+    }
+
+    /// Cancel the escrow of a second sale the buyer paid into, measuring
+    /// CancelEscrow and the buyer's RefundEscrow
+    ///
+    /// The main sale releases its milestones instead, measuring both ways
+    /// an escrow settles
+    async fn run_cancelled_escrow(
+        &mut self,
+        buyer: &Keypair,
+        whitelist_root: WhitelistRoot,
+        proof: Proof,
+        ctx: &mut ProgramTestContext,
+    ) {
+        let program_id = self.program_id;
+        let sale_authority = ctx.payer.insecure_clone();
+
+        let mint = super::utils::TestHelper::new_mint(0, ctx).await;
+        let (token_base, _) = TokenBasePDA::find_pda(&program_id, &sale_authority.pubkey(), &mint);
+        let (buyer_facts, _) = BuyerFactsPDA::find_pda(&program_id, &token_base, &buyer.pubkey());
+        let (escrow, _) = EscrowPDA::find_pda(&program_id, &token_base);
+
+        let sale_authority_accounts = vec![
+            AccountMeta::new(token_base, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new(sale_authority.pubkey(), true),
+        ];
+
+        let registry_accounts =
+            super::utils::TestHelper::open_sale_registry_accounts(program_id, ctx).await;
+        self.process(
+            TokenSaleInstruction::OpenSale {
+                price: PRICE,
+                purchase_limit: DEFAULT_PURCHASE_LIMIT,
+                whitelist_root,
+            },
+            vec![
+                AccountMeta::new(token_base, false),
+                AccountMeta::new(mint, false),
+                AccountMeta::new_readonly(escrow, false),
+                AccountMeta::new(sale_authority.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            ]
+            .into_iter()
+            .chain(registry_accounts)
+            .chain([AccountMeta::new_readonly(spl_token::ID, false)])
+            .collect(),
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.process(
+            TokenSaleInstruction::InitializeEscrow {
+                team: sale_authority.pubkey(),
+                approver: None,
+                milestone_bps: MILESTONE_BPS.to_vec(),
+                vote_threshold_bps: VOTE_THRESHOLD_BPS,
+            },
+            [
+                sale_authority_accounts.clone(),
+                vec![
+                    AccountMeta::new(escrow, false),
+                    AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                ],
+            ]
+            .concat(),
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.process(
+            TokenSaleInstruction::ToggleRunning,
+            sale_authority_accounts,
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.process(
+            TokenSaleInstruction::RegisterBuyer,
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(buyer_facts, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(sale_authority.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            &[buyer],
+            ctx,
+        )
+        .await;
+
+        self.process(
+            TokenSaleInstruction::BuyToken {
+                amount: DEFAULT_PURCHASE_LIMIT,
+                proof: convert_merkle_proof(proof),
+            },
+            vec![
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new(mint, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(sale_authority.pubkey(), false),
+                AccountMeta::new(get_associated_token_address(&buyer.pubkey(), &mint), false),
+                AccountMeta::new(buyer_facts, false),
+                AccountMeta::new(buyer.pubkey(), true),
+                AccountMeta::new(sale_authority.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+                AccountMeta::new(crate::ID, false),
+                AccountMeta::new(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            &[buyer],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "CancelEscrow",
+            TokenSaleInstruction::CancelEscrow,
+            vec![
+                AccountMeta::new(token_base, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new_readonly(sale_authority.pubkey(), true),
+            ],
+            &[],
+            ctx,
+        )
+        .await;
+
+        self.measure(
+            "RefundEscrow",
+            TokenSaleInstruction::RefundEscrow,
+            vec![
+                AccountMeta::new(token_base, false),
+                AccountMeta::new(escrow, false),
+                AccountMeta::new(buyer_facts, false),
+                AccountMeta::new(buyer.pubkey(), false),
+            ],
+            &[],
+            ctx,
+        )
+        .await;
+    }
+}
+
+/// Write the measurements as JSON, one entry per instruction and depth
+fn write_report(measurements: &[Measurement]) {
+    let report = json!({
+        "program": "merkle_whitelist_token_sale",
+        "packet_data_size": PACKET_DATA_SIZE,
+        "measurements": measurements
+            .iter()
+            .map(|measurement| json!({
+                "instruction": measurement.instruction,
+                "proof_depth": measurement.proof_depth,
+                "compute_units": measurement.compute_units,
+                "budget": budget(measurement.instruction).compute_units,
+                "transaction_size": measurement.transaction_size,
+                "sendable": measurement.is_sendable(),
+            }))
+            .collect::<Vec<_>>(),
+    });
+
+    let path = std::env::var("COMPUTE_UNITS_REPORT").unwrap_or(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/../target/compute_units.json").to_string(),
+    );
+    let path = std::path::Path::new(&path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, serde_json::to_string_pretty(&report).unwrap() + "\n").unwrap();
+}
+
+/// Every instruction stays within its compute unit budget at every proof
+/// depth, and within a packet unless its proof outgrows it
+#[tokio::test]
+async fn test_compute_unit_budgets() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let mut measurements = Vec::new();
+    for proof_depth in PROOF_DEPTHS {
+        let mut bench = Bench {
+            program_id,
+            proof_depth,
+            measurements: Vec::new(),
+        };
+        bench.run_sale(&mut ctx).await;
+        measurements.append(&mut bench.measurements);
+    }
+
+    // report before failing, it shows by how much a budget is exceeded
+    write_report(&measurements);
+
+    // every budget is measured, stale entries hide renamed instructions
+    for budget in BUDGETS {
+        assert!(
+            measurements
+                .iter()
+                .any(|measurement| measurement.instruction == budget.instruction),
+            "{} is never measured",
+            budget.instruction
+        );
+    }
+
+    let exceeded = measurements
+        .iter()
+        .filter(|measurement| {
+            measurement.compute_units > budget(measurement.instruction).compute_units
+        })
+        .map(|measurement| {
+            format!(
+                "{} at proof depth {}: {} > {}",
+                measurement.instruction,
+                measurement.proof_depth,
+                measurement.compute_units,
+                budget(measurement.instruction).compute_units
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(exceeded.is_empty(), "over budget:\n{}", exceeded.join("\n"));
+
+    // only proofs may outgrow a packet, the report marks those unsendable
+    let oversized = measurements
+        .iter()
+        .filter(|measurement| {
+            !measurement.is_sendable()
+                && (!budget(measurement.instruction).takes_proof || measurement.proof_depth == 1)
+        })
+        .map(|measurement| {
+            format!(
+                "{} at proof depth {}: {} > {PACKET_DATA_SIZE} bytes",
+                measurement.instruction, measurement.proof_depth, measurement.transaction_size,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert!(
+        oversized.is_empty(),
+        "over packet size:\n{}",
+        oversized.join("\n")
+    );
+}
@@ -1,14 +1,15 @@
 use super::utils::TestHelper;
 use crate::*;
+use assert_matches::assert_matches;
 use borsh::BorshSerialize;
 use merkletreers::{tree::MerkleTree, Leaf};
 use solana_program_test::*;
 use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
+    instruction::{AccountMeta, Instruction, InstructionError},
     pubkey::Pubkey,
     signature::Keypair,
     signature::Signer,
-    transaction::Transaction,
+    transaction::{Transaction, TransactionError},
 };
 
 /// Test Happy Path
@@ -54,7 +55,7 @@ async fn test_configure_sale() {
     let instruction = crate::instruction::TokenSaleInstruction::ConfigureSale {
         price: Some(new_price),
         default_purchase_limit: Some(new_default_purchase_limit),
-        whitelist_root: Some(new_whitelist_root.clone()),
+        whitelist_root: Some(new_whitelist_root),
     };
 
     let mut instruction_data = Vec::new();
@@ -95,6 +96,8 @@ async fn test_configure_sale() {
         new_default_purchase_limit
     );
     assert_eq!(token_base.whitelist_root.0, new_whitelist_root.0);
+    // new price and whitelist start a new phase
+    assert_eq!(token_base.phase, 1);
 }
 
 #[tokio::test]
@@ -297,7 +300,7 @@ async fn test_configure_root_only() {
     let instruction = crate::instruction::TokenSaleInstruction::ConfigureSale {
         price: None,
         default_purchase_limit: None,
-        whitelist_root: Some(new_whitelist_root.clone()),
+        whitelist_root: Some(new_whitelist_root),
     };
 
     let mut instruction_data = Vec::new();
@@ -336,3 +339,377 @@ async fn test_configure_root_only() {
     assert_eq!(token_base.default_purchase_limit, default_purchase_limit);
     assert_eq!(token_base.whitelist_root.0, new_whitelist_root.0);
 }
+
+/// Test an empty configuration is rejected instead of panicking
+#[tokio::test]
+async fn test_configure_nothing_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::ConfigureSale {
+        price: None,
+        default_purchase_limit: None,
+        whitelist_root: None,
+    };
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base_pda, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::NothingToConfigure as u32
+    );
+}
+
+/// Test a referral reward above 100% is rejected
+#[tokio::test]
+async fn test_configure_referral_bps_over_max_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+        settings: crate::instruction::SaleSettings::V1(crate::instruction::SaleSettingsV1 {
+            referral_bps: Some(10_001),
+            ..Default::default()
+        }),
+    };
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base_pda, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::InvalidBasisPoints as u32
+    );
+}
+
+/// Test a holder gate without a gate key is rejected
+#[tokio::test]
+async fn test_configure_holder_gate_without_key_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+        settings: crate::instruction::SaleSettings::V1(crate::instruction::SaleSettingsV1 {
+            whitelist_mode: Some(state::WhitelistMode::CollectionHolder),
+            ..Default::default()
+        }),
+    };
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base_pda, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::GateKeyMissing as u32
+    );
+}
+
+/// Test a scheduled whitelist root is recorded next to the active one
+#[tokio::test]
+async fn test_configure_scheduled_whitelist_root() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let new_leaf: Leaf = Keypair::new().pubkey().to_bytes();
+    let new_whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(vec![new_leaf]).root);
+    let activation_slot = 1_000;
+
+    TestHelper::configure_sale(
+        token_base_pda,
+        mint,
+        crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+            settings: crate::instruction::SaleSettings::V1(crate::instruction::SaleSettingsV1 {
+                whitelist_root: Some(new_whitelist_root),
+                root_activation_slot: Some(activation_slot),
+                root_grace_slots: Some(50),
+                ..Default::default()
+            }),
+        },
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    // confirm state
+    let token_base = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::TokenBase>(token_base_pda)
+        .await
+        .unwrap();
+
+    let records = token_base.root_records().collect::<Vec<_>>();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].root.0, new_whitelist_root.0);
+    assert_eq!(records[0].activation_slot, activation_slot);
+    assert_eq!(records[1].root.0, whitelist_root.0);
+    assert_eq!(token_base.root_grace_slots, 50);
+
+    // the opening root verifies until the scheduled one activates
+    assert!(token_base.is_root_accepted(&whitelist_root.0, activation_slot - 1));
+    assert!(!token_base.is_root_accepted(&new_whitelist_root.0, activation_slot - 1));
+    assert!(token_base.is_root_accepted(&new_whitelist_root.0, activation_slot));
+}
+
+/// Test a root activation slot in the past is rejected
+#[tokio::test]
+async fn test_configure_root_activation_in_past_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    ctx.warp_to_slot(100).unwrap();
+
+    let new_leaf: Leaf = Keypair::new().pubkey().to_bytes();
+    let instruction = crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+        settings: crate::instruction::SaleSettings::V1(crate::instruction::SaleSettingsV1 {
+            whitelist_root: Some(crate::merkle::WhitelistRoot(
+                MerkleTree::new(vec![new_leaf]).root,
+            )),
+            root_activation_slot: Some(50),
+            ..Default::default()
+        }),
+    };
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base_pda, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        ctx.get_new_latest_blockhash().await.unwrap(),
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::InvalidRootActivation as u32
+    );
+}
+
+/// Test the ConfigureSale encoding stays the one of the first release,
+/// later settings go through UpdateSaleSettings
+#[test]
+fn test_configure_sale_encoding_is_stable() {
+    let data = borsh::to_vec(&crate::instruction::TokenSaleInstruction::ConfigureSale {
+        price: Some(7),
+        default_purchase_limit: None,
+        whitelist_root: Some(crate::merkle::WhitelistRoot([9; 32])),
+    })
+    .unwrap();
+
+    let mut expected = vec![2, 1];
+    expected.extend(7u64.to_le_bytes());
+    expected.push(0);
+    expected.push(1);
+    expected.extend([9; 32]);
+    assert_eq!(data, expected);
+
+    // settings are prefixed by their version
+    let data = borsh::to_vec(
+        &crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+            settings: crate::instruction::SaleSettings::V1(Default::default()),
+        },
+    )
+    .unwrap();
+    assert_eq!(data[1], 0);
+    assert_eq!(data.len(), 2 + 10);
+}
@@ -0,0 +1,282 @@
+use super::utils::TestHelper;
+use crate::cpi::{self, BuyTokenCpiAccounts, CloseReceiptCpiAccounts, RegisterBuyerCpiAccounts};
+use crate::merkle::{convert_merkle_proof, pubkey_to_sha256_leaf, WhitelistProof};
+use crate::*;
+use borsh::{BorshDeserialize, BorshSerialize};
+use merkletreers::tree::MerkleTree;
+use solana_program_test::*;
+use solana_sdk::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signature::Signer,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+    system_transaction,
+    transaction::Transaction,
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account;
+
+const BUYER_SEED: &[u8] = b"buyer";
+
+/// Instructions of the launchpad aggregator calling into the sale
+#[derive(BorshSerialize, BorshDeserialize)]
+enum AggregatorInstruction {
+    Register,
+    Buy { amount: u64, proof: WhitelistProof },
+}
+
+/// Launchpad aggregator buying through a PDA buyer it signs for
+///
+/// Accounts: token sale program, then the token sale accounts
+fn process_aggregator(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let (_, bump) = Pubkey::find_program_address(&[BUYER_SEED], program_id);
+    let signers_seeds: &[&[&[u8]]] = &[&[BUYER_SEED, &[bump]]];
+
+    match AggregatorInstruction::try_from_slice(data)? {
+        AggregatorInstruction::Register => cpi::register_buyer(
+            &accounts[0],
+            RegisterBuyerCpiAccounts {
+                token_base: &accounts[1],
+                buyer_facts: &accounts[2],
+                buyer: &accounts[3],
+                payer: &accounts[4],
+                system_program: &accounts[5],
+                holder_token_account: None,
+                holder_metadata: None,
+                holder_mint: None,
+            },
+            signers_seeds,
+        ),
+        AggregatorInstruction::Buy { amount, proof } => cpi::buy_token(
+            &accounts[0],
+            BuyTokenCpiAccounts {
+                token_base: &accounts[1],
+                mint: &accounts[2],
+                vault: &accounts[3],
+                sale_authority: &accounts[4],
+                buyer_token_account: &accounts[5],
+                buyer_facts: &accounts[6],
+                buyer: &accounts[7],
+                payer: &accounts[8],
+                system_program: &accounts[9],
+                token_program: &accounts[10],
+                associated_token_program: &accounts[11],
+                receipt: None,
+                referrer_facts: None,
+                referrer: None,
+                instructions_sysvar: None,
+                holder_token_account: None,
+                holder_metadata: None,
+                holder_mint: None,
+            },
+            amount,
+            proof,
+            signers_seeds,
+        ),
+    }
+}
+
+/// Send an aggregator instruction signed by the payer only
+async fn call_aggregator(
+    aggregator_id: Pubkey,
+    instruction: AggregatorInstruction,
+    accounts: Vec<AccountMeta>,
+    ctx: &mut ProgramTestContext,
+) {
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id: aggregator_id,
+            accounts: [vec![AccountMeta::new_readonly(crate::ID, false)], accounts].concat(),
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+}
+
+/// Test Happy Path, a program registers and buys for its PDA
+#[tokio::test]
+async fn test_cpi_buy_token_with_pda_buyer() {
+    let aggregator_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        // skipped optional accounts are passed as crate::ID
+        crate::ID,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+    program_test.add_program("aggregator", aggregator_id, processor!(process_aggregator));
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+    let vault = Keypair::new();
+    let empty_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base, _) = TestHelper::initialize_token_base(
+        1000,
+        100,
+        mint,
+        vault.pubkey(),
+        &empty_root,
+        crate::ID,
+        &mut ctx,
+    )
+    .await;
+
+    // fund the PDA buyer, it stays owned by the System Program
+    let (buyer, _) = Pubkey::find_program_address(&[BUYER_SEED], &aggregator_id);
+    ctx.banks_client
+        .process_transaction(system_transaction::transfer(
+            &ctx.payer,
+            &buyer,
+            1000000000,
+            ctx.last_blockhash,
+        ))
+        .await
+        .unwrap();
+
+    let (buyer_facts, _) = pda::BuyerFactsPDA::find_pda(&crate::ID, &token_base, &buyer);
+    call_aggregator(
+        aggregator_id,
+        AggregatorInstruction::Register,
+        vec![
+            AccountMeta::new_readonly(token_base, false),
+            AccountMeta::new(buyer_facts, false),
+            AccountMeta::new_readonly(buyer, false),
+            AccountMeta::new(ctx.payer.pubkey(), true),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+        ],
+        &mut ctx,
+    )
+    .await;
+
+    // whitelist the PDA buyer and start the sale
+    let tree = MerkleTree::new(vec![pubkey_to_sha256_leaf(&buyer)]);
+    TestHelper::configure_sale(
+        token_base,
+        mint,
+        crate::instruction::TokenSaleInstruction::ConfigureSale {
+            price: None,
+            default_purchase_limit: None,
+            whitelist_root: Some(crate::merkle::WhitelistRoot(tree.root)),
+        },
+        crate::ID,
+        &mut ctx,
+    )
+    .await;
+    TestHelper::toggle_running(token_base, mint, crate::ID, &mut ctx).await;
+
+    let amount: u64 = 40;
+    let buyer_token_account = get_associated_token_address(&buyer, &mint);
+    call_aggregator(
+        aggregator_id,
+        AggregatorInstruction::Buy {
+            amount,
+            proof: convert_merkle_proof(tree.make_proof(pubkey_to_sha256_leaf(&buyer))),
+        },
+        vec![
+            AccountMeta::new_readonly(token_base, false),
+            AccountMeta::new(mint, false),
+            AccountMeta::new(vault.pubkey(), false),
+            AccountMeta::new_readonly(ctx.payer.pubkey(), false),
+            AccountMeta::new(buyer_token_account, false),
+            AccountMeta::new(buyer_facts, false),
+            AccountMeta::new(buyer, false),
+            AccountMeta::new(ctx.payer.pubkey(), true),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+        ],
+        &mut ctx,
+    )
+    .await;
+
+    // confirm state
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(buyer_facts)
+        .await
+        .unwrap();
+    assert_eq!(buyer_facts.purchase_limit, 100 - amount);
+
+    let token_account = ctx
+        .banks_client
+        .get_account(buyer_token_account)
+        .await
+        .unwrap()
+        .unwrap();
+    let token_account = Account::unpack(&token_account.data).unwrap();
+    assert_eq!(token_account.owner, buyer);
+    assert_eq!(token_account.amount, amount);
+}
+
+/// Test the helpers refuse a token sale deployed away from crate::ID,
+/// it would read the placeholders of skipped optional accounts as passed
+#[test]
+fn test_cpi_rejects_other_program_id() {
+    let (program_id, receipt, buyer) = (
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    );
+    let (mut program_lamports, mut receipt_lamports, mut buyer_lamports) = (0, 0, 0);
+    let (mut program_data, mut receipt_data, mut buyer_data) = (vec![], vec![], vec![]);
+    let program = AccountInfo::new(
+        &program_id,
+        false,
+        false,
+        &mut program_lamports,
+        &mut program_data,
+        &program_id,
+        true,
+        0,
+    );
+    let receipt = AccountInfo::new(
+        &receipt,
+        false,
+        true,
+        &mut receipt_lamports,
+        &mut receipt_data,
+        &program_id,
+        false,
+        0,
+    );
+    let buyer = AccountInfo::new(
+        &buyer,
+        true,
+        true,
+        &mut buyer_lamports,
+        &mut buyer_data,
+        &SYSTEM_PROGRAM_ID,
+        false,
+        0,
+    );
+
+    let accounts = CloseReceiptCpiAccounts {
+        receipt: &receipt,
+        buyer: &buyer,
+        payer: &buyer,
+    };
+    assert_eq!(
+        cpi::close_receipt(&program, accounts, &[]),
+        Err(ProgramError::IncorrectProgramId)
+    );
+}
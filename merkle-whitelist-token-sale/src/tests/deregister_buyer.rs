@@ -5,11 +5,12 @@ use borsh::BorshSerialize;
 use merkletreers::tree::MerkleTree;
 use solana_program_test::*;
 use solana_sdk::{
-    instruction::{AccountMeta, Instruction},
+    instruction::{AccountMeta, Instruction, InstructionError},
     pubkey::Pubkey,
     signature::Keypair,
     signature::Signer,
-    transaction::Transaction,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+    transaction::{Transaction, TransactionError},
 };
 
 /// Test Happy Path
@@ -61,7 +62,8 @@ async fn test_deregister_buyer() {
             accounts: vec![
                 AccountMeta::new_readonly(token_base_pda, false),
                 AccountMeta::new(buyer_facts_pda, false),
-                AccountMeta::new(buyer.pubkey(), true),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(ctx.payer.pubkey(), false),
             ],
             data: instruction_data.clone(),
         }],
@@ -87,3 +89,314 @@ async fn test_deregister_buyer() {
         Err(BanksClientError::ClientError("Account not found"))
     );
 }
+
+/// Test a BuyerFacts can't be closed through a TokenBase copy owned by another program
+#[tokio::test]
+async fn test_deregister_buyer_rejects_foreign_token_base() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let (buyer, buyer_facts_pda, _) =
+        TestHelper::initialize_buyer_facts(token_base_pda, program_id, &mut ctx).await;
+
+    let spoofed_token_base = TestHelper::spoof_account(token_base_pda, &mut ctx).await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::DeregisterBuyer;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(spoofed_token_base, false),
+                AccountMeta::new(buyer_facts_pda, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(ctx.payer.pubkey(), false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidAccountOwner)
+    );
+}
+
+/// Test a referrer can't deregister before claiming its rewards
+#[tokio::test]
+async fn test_deregister_buyer_with_referral_rewards_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let (buyer, buyer_facts_pda, _) =
+        TestHelper::initialize_buyer_facts(token_base_pda, program_id, &mut ctx).await;
+    TestHelper::update_buyer_facts(
+        buyer_facts_pda,
+        |buyer_facts| buyer_facts.referral_rewards = 2_000,
+        &mut ctx,
+    )
+    .await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::DeregisterBuyer;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(token_base_pda, false),
+                AccountMeta::new(buyer_facts_pda, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(ctx.payer.pubkey(), false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::UnclaimedReferralRewards as u32
+    );
+}
+
+/// Test the buyer can't take the rent paid by a relayer
+#[tokio::test]
+async fn test_deregister_buyer_payer_mismatch_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    // rent is paid by ctx.payer, the relayer
+    let (buyer, buyer_facts_pda, _) =
+        TestHelper::initialize_buyer_facts(token_base_pda, program_id, &mut ctx).await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::DeregisterBuyer;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(token_base_pda, false),
+                AccountMeta::new(buyer_facts_pda, false),
+                AccountMeta::new(buyer.pubkey(), true),
+                AccountMeta::new(buyer.pubkey(), true),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::PayerMismatch as u32
+    );
+}
+
+/// Test a buyer can't reset its voucher allocation by deregistering and registering again
+#[tokio::test]
+async fn test_deregister_buyer_after_purchase_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let (buyer, buyer_facts_pda, _) =
+        TestHelper::initialize_buyer_facts(token_base_pda, program_id, &mut ctx).await;
+    TestHelper::update_buyer_facts(
+        buyer_facts_pda,
+        |buyer_facts| buyer_facts.total_purchased = 10,
+        &mut ctx,
+    )
+    .await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::DeregisterBuyer;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(token_base_pda, false),
+                AccountMeta::new(buyer_facts_pda, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(ctx.payer.pubkey(), false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::BuyerHasPurchases as u32
+    );
+
+    // total_purchased survives, RegisterBuyer can't start it over
+    let mut instruction_data = Vec::new();
+    crate::instruction::TokenSaleInstruction::RegisterBuyer
+        .serialize(&mut instruction_data)
+        .unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(token_base_pda, false),
+                AccountMeta::new(buyer_facts_pda, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::BuyerAlreadyRegistered as u32
+    );
+}
@@ -0,0 +1,40 @@
+use crate::error::{error_codes, TokenSaleError};
+use solana_sdk::program_error::ProgramError;
+use std::collections::HashSet;
+
+/// Codes are contiguous from 0 and round-trip through ProgramError
+#[test]
+fn test_error_codes_round_trip() {
+    for (code, error) in TokenSaleError::ALL.iter().enumerate() {
+        assert_eq!(error.code(), code as u32);
+        assert_eq!(TokenSaleError::from_code(code as u32), Some(*error));
+
+        let program_error: ProgramError = (*error).into();
+        assert_eq!(
+            TokenSaleError::from_program_error(&program_error),
+            Some(*error)
+        );
+    }
+
+    let unknown = TokenSaleError::ALL.len() as u32;
+    assert_eq!(TokenSaleError::from_code(unknown), None);
+    assert_eq!(
+        TokenSaleError::from_program_error(&ProgramError::InvalidSeeds),
+        None
+    );
+}
+
+/// Every entry of the published table is distinct
+#[test]
+fn test_error_table_is_unambiguous() {
+    let table = error_codes();
+    assert_eq!(table.len(), TokenSaleError::ALL.len());
+
+    let names: HashSet<_> = table.iter().map(|e| e.name.as_str()).collect();
+    let messages: HashSet<_> = table.iter().map(|e| e.message.as_str()).collect();
+    assert_eq!(names.len(), table.len());
+    assert_eq!(messages.len(), table.len());
+
+    assert_eq!(table[11].name, "SaleNotRunning");
+    assert_eq!(table[11].message, "Token sale is not running");
+}
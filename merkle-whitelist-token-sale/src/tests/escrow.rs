@@ -0,0 +1,762 @@
+use super::utils::TestHelper;
+use crate::pda::{BuyerFactsPDA, EscrowPDA};
+use crate::processor::quote::Quote;
+use crate::state::{BuyerFacts, Escrow, TokenBase};
+use crate::*;
+use assert_matches::assert_matches;
+use borsh::BorshSerialize;
+use bytemuck::Zeroable;
+use merkletreers::tree::MerkleTree;
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Keypair,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+};
+use spl_discriminator::SplDiscriminate;
+
+const MILESTONE_BPS: [u16; 2] = [4000, 6000];
+const VOTE_THRESHOLD_BPS: u16 = 5000;
+
+fn escrow(milestone_bps: &[u16], total_deposited: u64) -> Escrow {
+    let mut escrow = Escrow::zeroed();
+    escrow.discriminator = Escrow::SPL_DISCRIMINATOR.into();
+    escrow.milestone_bps[..milestone_bps.len()].copy_from_slice(milestone_bps);
+    escrow.milestone_count = milestone_bps.len() as u8;
+    escrow.vote_threshold_bps = VOTE_THRESHOLD_BPS;
+    escrow.total_deposited = total_deposited;
+    escrow
+}
+
+fn token_sale_transaction(
+    instruction: crate::instruction::TokenSaleInstruction,
+    accounts: Vec<AccountMeta>,
+    signers: &[&Keypair],
+    program_id: Pubkey,
+    ctx: &ProgramTestContext,
+) -> Transaction {
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts,
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&[&ctx.payer.insecure_clone()], signers].concat(),
+        ctx.last_blockhash,
+    )
+}
+
+fn assert_error(result: Result<(), BanksClientError>, error: error::TokenSaleError) {
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error as u32
+    );
+}
+
+/// Open a stopped sale escrowing its raise, returning its TokenBase,
+/// mint, Escrow and team
+async fn escrowed_sale(
+    approver: Option<Pubkey>,
+    program_id: Pubkey,
+    ctx: &mut ProgramTestContext,
+) -> (Pubkey, Pubkey, Pubkey, Pubkey) {
+    let mint = TestHelper::new_mint(0, ctx).await;
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+    let (token_base, _) = TestHelper::initialize_token_base(
+        100,
+        100,
+        mint,
+        Keypair::new().pubkey(),
+        &whitelist_root,
+        program_id,
+        ctx,
+    )
+    .await;
+    let (escrow, _) = EscrowPDA::find_pda(&program_id, &token_base);
+    let team = Keypair::new().pubkey();
+
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::InitializeEscrow {
+            team,
+            approver,
+            milestone_bps: MILESTONE_BPS.to_vec(),
+            vote_threshold_bps: VOTE_THRESHOLD_BPS,
+        },
+        initialize_escrow_accounts(token_base, mint, escrow, ctx),
+        &[],
+        program_id,
+        ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    (token_base, mint, escrow, team)
+}
+
+fn initialize_escrow_accounts(
+    token_base: Pubkey,
+    mint: Pubkey,
+    escrow: Pubkey,
+    ctx: &ProgramTestContext,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(token_base, false),
+        AccountMeta::new_readonly(mint, false),
+        AccountMeta::new(ctx.payer.pubkey(), true),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+    ]
+}
+
+/// Write a deposit of `lamports` by a new buyer straight into the bank,
+/// as BuyToken records it, returning the buyer and its BuyerFacts
+async fn deposit(
+    token_base: &Pubkey,
+    escrow: &Pubkey,
+    lamports: u64,
+    program_id: Pubkey,
+    ctx: &mut ProgramTestContext,
+) -> (Keypair, Pubkey) {
+    let buyer = Keypair::new();
+    let (buyer_facts_pda, buyer_facts_canonical_bump) =
+        BuyerFactsPDA::find_pda(&program_id, token_base, &buyer.pubkey());
+
+    let mut buyer_facts = BuyerFacts::zeroed();
+    buyer_facts.discriminator = BuyerFacts::SPL_DISCRIMINATOR.into();
+    buyer_facts.bump = buyer_facts_canonical_bump;
+    buyer_facts.version = BuyerFacts::VERSION;
+    buyer_facts.token_base = *token_base;
+    buyer_facts.buyer = buyer.pubkey();
+    buyer_facts.escrow_deposited = lamports;
+
+    let account = Account {
+        lamports: Rent::default().minimum_balance(BuyerFacts::LEN),
+        data: bytemuck::bytes_of(&buyer_facts).to_vec(),
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    };
+    ctx.set_account(&buyer_facts_pda, &account.into());
+
+    let mut account = ctx
+        .banks_client
+        .get_account(*escrow)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut escrow_state: Escrow = bytemuck::pod_read_unaligned(&account.data);
+    escrow_state.total_deposited += lamports;
+    account.data = bytemuck::bytes_of(&escrow_state).to_vec();
+    account.lamports += lamports;
+    ctx.set_account(escrow, &account.into());
+
+    (buyer, buyer_facts_pda)
+}
+
+async fn load_escrow(escrow: Pubkey, ctx: &mut ProgramTestContext) -> Escrow {
+    let account = ctx.banks_client.get_account(escrow).await.unwrap().unwrap();
+    bytemuck::pod_read_unaligned(&account.data)
+}
+
+fn vote_accounts(
+    token_base: Pubkey,
+    escrow: Pubkey,
+    buyer_facts: Pubkey,
+    buyer: &Keypair,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new_readonly(token_base, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new(buyer_facts, false),
+        AccountMeta::new_readonly(buyer.pubkey(), true),
+    ]
+}
+
+fn release_accounts(
+    token_base: Pubkey,
+    escrow: Pubkey,
+    team: Pubkey,
+    authority: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(token_base, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new(team, false),
+        AccountMeta::new_readonly(*authority, true),
+    ]
+}
+
+fn cancel_accounts(token_base: Pubkey, escrow: Pubkey, authority: &Pubkey) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(token_base, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new_readonly(*authority, true),
+    ]
+}
+
+fn refund_accounts(
+    token_base: Pubkey,
+    escrow: Pubkey,
+    buyer_facts: Pubkey,
+    buyer: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(token_base, false),
+        AccountMeta::new(escrow, false),
+        AccountMeta::new(buyer_facts, false),
+        AccountMeta::new(*buyer, false),
+    ]
+}
+
+/// Milestones release their cumulative share of every deposit
+#[test]
+fn test_release_target() {
+    let mut escrow = escrow(&MILESTONE_BPS, 1_000_001);
+    assert_eq!(escrow.release_target().unwrap(), 400_000);
+
+    escrow.milestones_released = 1;
+    assert_eq!(escrow.release_target().unwrap(), 1_000_001);
+}
+
+/// Refunds are pro rata to deposits and never exceed the pool
+#[test]
+fn test_refund() {
+    let mut escrow = escrow(&MILESTONE_BPS, 3);
+    escrow.refund_pool = 2;
+
+    let refunds = [1, 1, 1].map(|deposited| escrow.refund(deposited).unwrap());
+    assert_eq!(refunds, [0, 0, 0]);
+    assert_eq!(escrow.refund(3).unwrap(), 2);
+    assert_eq!(escrow.refund(2).unwrap(), 1);
+}
+
+/// The vote passes once approvals reach the threshold
+#[test]
+fn test_is_vote_passed() {
+    let mut escrow = escrow(&MILESTONE_BPS, 1_000);
+    assert!(!escrow.is_vote_passed());
+
+    escrow.approve_weight = 499;
+    assert!(!escrow.is_vote_passed());
+
+    escrow.approve_weight = 500;
+    assert!(escrow.is_vote_passed());
+
+    escrow.vote_threshold_bps = 0;
+    assert!(!escrow.is_vote_passed());
+}
+
+/// InitializeEscrow makes the Escrow the sale's vault
+#[tokio::test]
+async fn test_initialize_escrow() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let approver = Keypair::new().pubkey();
+    let (token_base, _, escrow, team) = escrowed_sale(Some(approver), program_id, &mut ctx).await;
+
+    let token_base_state = ctx
+        .banks_client
+        .get_account_data_with_borsh::<TokenBase>(token_base)
+        .await
+        .unwrap();
+    assert_eq!(token_base_state.vault, escrow);
+    assert!(token_base_state.is_escrow());
+    assert!(!token_base_state.is_escrow_settled());
+
+    let escrow = load_escrow(escrow, &mut ctx).await;
+    assert_eq!(escrow.token_base, token_base);
+    assert_eq!(escrow.team, team);
+    assert_eq!(escrow.approver, approver);
+    assert_eq!(escrow.milestone_bps, [4000, 6000, 0, 0]);
+    assert_eq!(escrow.milestone_count, 2);
+    assert_eq!(escrow.vote_threshold_bps, VOTE_THRESHOLD_BPS);
+}
+
+/// Milestone shares must sum to 10000 bps
+#[tokio::test]
+async fn test_initialize_escrow_invalid_milestones_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+    let (token_base, _) = TestHelper::initialize_token_base(
+        100,
+        100,
+        mint,
+        Keypair::new().pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+    let (escrow, _) = EscrowPDA::find_pda(&program_id, &token_base);
+
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::InitializeEscrow {
+            team: Keypair::new().pubkey(),
+            approver: None,
+            milestone_bps: vec![4000, 5000],
+            vote_threshold_bps: VOTE_THRESHOLD_BPS,
+        },
+        initialize_escrow_accounts(token_base, mint, escrow, &ctx),
+        &[],
+        program_id,
+        &ctx,
+    );
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_error(result, error::TokenSaleError::InvalidMilestones);
+}
+
+/// Buyers holding the threshold approve a milestone, anyone releases it
+#[tokio::test]
+async fn test_release_milestone_by_vote() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let (token_base, _, escrow, team) = escrowed_sale(None, program_id, &mut ctx).await;
+    let (buyer, buyer_facts) = deposit(&token_base, &escrow, 6_000_000, program_id, &mut ctx).await;
+    deposit(&token_base, &escrow, 4_000_000, program_id, &mut ctx).await;
+
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::VoteMilestone,
+        vote_accounts(token_base, escrow, buyer_facts, &buyer),
+        &[&buyer],
+        program_id,
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let anyone = Keypair::new();
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::ReleaseMilestone,
+        release_accounts(token_base, escrow, team, &anyone.pubkey()),
+        &[&anyone],
+        program_id,
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // confirm state
+    assert_eq!(ctx.banks_client.get_balance(team).await.unwrap(), 4_000_000);
+
+    let escrow = load_escrow(escrow, &mut ctx).await;
+    assert_eq!(escrow.total_released, 4_000_000);
+    assert_eq!(escrow.milestones_released, 1);
+    assert_eq!(escrow.approve_weight, 0);
+}
+
+/// Without the approver nor enough votes the milestone stays locked
+#[tokio::test]
+async fn test_release_milestone_not_approved_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let approver = Keypair::new();
+    let (token_base, _, escrow, team) =
+        escrowed_sale(Some(approver.pubkey()), program_id, &mut ctx).await;
+    let (buyer, buyer_facts) = deposit(&token_base, &escrow, 4_000_000, program_id, &mut ctx).await;
+    deposit(&token_base, &escrow, 6_000_000, program_id, &mut ctx).await;
+
+    // 40% of the escrowed lamports is below the threshold
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::VoteMilestone,
+        vote_accounts(token_base, escrow, buyer_facts, &buyer),
+        &[&buyer],
+        program_id,
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::ReleaseMilestone,
+        release_accounts(token_base, escrow, team, &buyer.pubkey()),
+        &[&buyer],
+        program_id,
+        &ctx,
+    );
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_error(result, error::TokenSaleError::MilestoneNotApproved);
+}
+
+/// The approver releases every milestone, settling the escrow
+#[tokio::test]
+async fn test_release_milestones_by_approver_settles() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let approver = Keypair::new();
+    let (token_base, mint, escrow, team) =
+        escrowed_sale(Some(approver.pubkey()), program_id, &mut ctx).await;
+    deposit(&token_base, &escrow, 10_000_000, program_id, &mut ctx).await;
+
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let escrow_rent = rent.minimum_balance(Escrow::LEN);
+
+    for _ in MILESTONE_BPS {
+        let transaction = token_sale_transaction(
+            crate::instruction::TokenSaleInstruction::ReleaseMilestone,
+            release_accounts(token_base, escrow, team, &approver.pubkey()),
+            &[&approver],
+            program_id,
+            &ctx,
+        );
+        ctx.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    // confirm state
+    assert_eq!(
+        ctx.banks_client.get_balance(team).await.unwrap(),
+        10_000_000
+    );
+    assert_eq!(
+        ctx.banks_client.get_balance(escrow).await.unwrap(),
+        escrow_rent
+    );
+
+    let token_base_state = ctx
+        .banks_client
+        .get_account_data_with_borsh::<TokenBase>(token_base)
+        .await
+        .unwrap();
+    assert!(token_base_state.is_escrow_settled());
+
+    // a settled escrow lets the sale close
+    TestHelper::close_sale(token_base, mint, program_id, &mut ctx).await;
+}
+
+/// A cancelled escrow refunds the unreleased lamports pro rata
+#[tokio::test]
+async fn test_cancel_escrow_refunds_buyers() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let approver = Keypair::new();
+    let (token_base, _, escrow, team) =
+        escrowed_sale(Some(approver.pubkey()), program_id, &mut ctx).await;
+    let (buyer, buyer_facts) = deposit(&token_base, &escrow, 3_000_000, program_id, &mut ctx).await;
+    deposit(&token_base, &escrow, 7_000_000, program_id, &mut ctx).await;
+
+    // 40% is released before the cancellation
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::ReleaseMilestone,
+        release_accounts(token_base, escrow, team, &approver.pubkey()),
+        &[&approver],
+        program_id,
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // the sale authority cancels
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::CancelEscrow,
+        cancel_accounts(token_base, escrow, &ctx.payer.pubkey()),
+        &[],
+        program_id,
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let refund_accounts = refund_accounts(token_base, escrow, buyer_facts, &buyer.pubkey());
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::RefundEscrow,
+        refund_accounts.clone(),
+        &[],
+        program_id,
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // confirm state, 30% of the 6_000_000 unreleased lamports
+    assert_eq!(
+        ctx.banks_client.get_balance(buyer.pubkey()).await.unwrap(),
+        1_800_000
+    );
+    let buyer_facts_state = ctx
+        .banks_client
+        .get_account_data_with_borsh::<BuyerFacts>(buyer_facts)
+        .await
+        .unwrap();
+    assert!(!buyer_facts_state.has_escrow_deposit());
+
+    // refunds are paid once
+    ctx.last_blockhash = ctx
+        .banks_client
+        .get_new_latest_blockhash(&ctx.last_blockhash)
+        .await
+        .unwrap();
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::RefundEscrow,
+        refund_accounts,
+        &[],
+        program_id,
+        &ctx,
+    );
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_error(result, error::TokenSaleError::NoEscrowDeposit);
+}
+
+/// A sale can't close while its escrow holds unreleased lamports
+#[tokio::test]
+async fn test_close_sale_unsettled_escrow_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let (token_base, mint, _, _) = escrowed_sale(None, program_id, &mut ctx).await;
+    TestHelper::end_sale(token_base, mint, program_id, &mut ctx).await;
+
+    let registry_accounts =
+        TestHelper::close_sale_registry_accounts(token_base, program_id, &mut ctx).await;
+    let escrow_account =
+        TestHelper::close_sale_escrow_account(token_base, program_id, &mut ctx).await;
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::CloseSale,
+        vec![
+            AccountMeta::new(token_base, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new(ctx.payer.pubkey(), true),
+        ]
+        .into_iter()
+        .chain(registry_accounts)
+        .chain([escrow_account])
+        .collect(),
+        &[],
+        program_id,
+        &ctx,
+    );
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_error(result, error::TokenSaleError::EscrowNotSettled);
+}
+
+/// The last refund settles a cancelled escrow, CloseSale then closes it
+#[tokio::test]
+async fn test_close_sale_after_last_refund() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let (token_base, mint, escrow, _) = escrowed_sale(None, program_id, &mut ctx).await;
+    let buyers = [
+        deposit(&token_base, &escrow, 3_000_000, program_id, &mut ctx).await,
+        deposit(&token_base, &escrow, 7_000_000, program_id, &mut ctx).await,
+    ];
+
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::CancelEscrow,
+        cancel_accounts(token_base, escrow, &ctx.payer.pubkey()),
+        &[],
+        program_id,
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    for (buyer, buyer_facts) in &buyers {
+        // not settled before the last refund
+        let token_base_state = ctx
+            .banks_client
+            .get_account_data_with_borsh::<TokenBase>(token_base)
+            .await
+            .unwrap();
+        assert!(!token_base_state.is_escrow_settled());
+
+        let transaction = token_sale_transaction(
+            crate::instruction::TokenSaleInstruction::RefundEscrow,
+            refund_accounts(token_base, escrow, *buyer_facts, &buyer.pubkey()),
+            &[],
+            program_id,
+            &ctx,
+        );
+        ctx.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    // confirm state
+    let escrow_state = load_escrow(escrow, &mut ctx).await;
+    assert_eq!(escrow_state.refunded_deposits, 10_000_000);
+    assert!(escrow_state.is_fully_refunded());
+    let token_base_state = ctx
+        .banks_client
+        .get_account_data_with_borsh::<TokenBase>(token_base)
+        .await
+        .unwrap();
+    assert!(token_base_state.is_escrow_settled());
+
+    TestHelper::close_sale(token_base, mint, program_id, &mut ctx).await;
+
+    // the escrow is closed with the sale
+    assert!(ctx
+        .banks_client
+        .get_account(escrow)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Quote reports a cancelled escrow like BuyToken rejects it
+#[tokio::test]
+async fn test_quote_cancelled_escrow_is_ineligible() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let (token_base, mint, escrow, _) = escrowed_sale(None, program_id, &mut ctx).await;
+    let (buyer, buyer_facts) = deposit(&token_base, &escrow, 1_000_000, program_id, &mut ctx).await;
+    TestHelper::toggle_running(token_base, mint, program_id, &mut ctx).await;
+
+    let transaction = token_sale_transaction(
+        crate::instruction::TokenSaleInstruction::CancelEscrow,
+        cancel_accounts(token_base, escrow, &ctx.payer.pubkey()),
+        &[],
+        program_id,
+        &ctx,
+    );
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let quote_accounts = |escrow_account| {
+        vec![
+            AccountMeta::new_readonly(token_base, false),
+            AccountMeta::new_readonly(mint, false),
+            AccountMeta::new_readonly(buyer_facts, false),
+            AccountMeta::new_readonly(buyer.pubkey(), false),
+            AccountMeta::new_readonly(crate::ID, false),
+            AccountMeta::new_readonly(crate::ID, false),
+            AccountMeta::new_readonly(crate::ID, false),
+            AccountMeta::new_readonly(escrow_account, false),
+            AccountMeta::new_readonly(crate::ID, false),
+        ]
+    };
+    let instruction = || crate::instruction::TokenSaleInstruction::Quote {
+        amount: 1,
+        proof: Vec::new(),
+    };
+
+    let transaction =
+        token_sale_transaction(instruction(), quote_accounts(escrow), &[], program_id, &ctx);
+    let simulation = ctx
+        .banks_client
+        .simulate_transaction(transaction)
+        .await
+        .unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    let quote = Quote::from_return_data(&return_data.data).unwrap();
+    assert_eq!(
+        quote.error,
+        Some(error::TokenSaleError::EscrowCancelled.code())
+    );
+
+    // escrowed sales are quoted with their escrow
+    let transaction = token_sale_transaction(
+        instruction(),
+        quote_accounts(crate::ID),
+        &[],
+        program_id,
+        &ctx,
+    );
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_error(result, error::TokenSaleError::InvalidEscrowSeeds);
+}
@@ -0,0 +1,243 @@
+use crate::events::*;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use solana_sdk::pubkey::Pubkey;
+
+/// Every event survives encode -> log -> decode
+#[test]
+fn test_event_round_trip() {
+    let token_base = Pubkey::new_unique();
+    let buyer = Pubkey::new_unique();
+    let buyer_facts = Pubkey::new_unique();
+
+    let events = vec![
+        TokenSaleEvent::SaleOpened(SaleOpened {
+            token_base,
+            sale_authority: Pubkey::new_unique(),
+            mint: Pubkey::new_unique(),
+            vault: Pubkey::new_unique(),
+            whitelist_root: [7u8; 32],
+            price: 1000,
+            default_purchase_limit: 100,
+        }),
+        TokenSaleEvent::SaleConfigured(SaleConfigured {
+            token_base,
+            whitelist_root: [9u8; 32],
+            price: 2000,
+            default_purchase_limit: 50,
+            phase: 1,
+            referral_bps: 250,
+            min_registration_slots: 10,
+            purchase_cooldown_slots: 2,
+            whitelist_mode: 1,
+            whitelist_signer: Pubkey::new_unique(),
+            gate_key: Pubkey::new_unique(),
+            gate_min_amount: 5,
+            root_activation_slot: 300,
+            root_grace_slots: 150,
+        }),
+        TokenSaleEvent::Toggled(Toggled {
+            token_base,
+            is_running: true,
+        }),
+        TokenSaleEvent::BuyerRegistered(BuyerRegistered {
+            token_base,
+            buyer,
+            buyer_facts,
+            purchase_limit: 100,
+        }),
+        TokenSaleEvent::BuyerDeregistered(BuyerDeregistered {
+            token_base,
+            buyer,
+            buyer_facts,
+        }),
+        TokenSaleEvent::LimitAssigned(LimitAssigned {
+            token_base,
+            buyer,
+            buyer_facts,
+            purchase_limit: 143,
+        }),
+        TokenSaleEvent::Purchased(Purchased {
+            token_base,
+            buyer,
+            buyer_token_account: Pubkey::new_unique(),
+            amount: 40,
+            lamports_paid: 40000,
+            remaining_limit: 60,
+            receipt: Some(Pubkey::new_unique()),
+            referrer: Some(Pubkey::new_unique()),
+            referral_reward: 1000,
+        }),
+        TokenSaleEvent::SaleClosed(SaleClosed {
+            token_base,
+            sale_authority: Pubkey::new_unique(),
+            reclaimed_lamports: 1_000_000,
+        }),
+        TokenSaleEvent::BuyerFactsReclaimed(BuyerFactsReclaimed {
+            token_base,
+            buyer,
+            buyer_facts,
+            reclaimed_lamports: 1_000,
+        }),
+        TokenSaleEvent::AccountMigrated(AccountMigrated {
+            account: token_base,
+            from_version: 1,
+            to_version: 2,
+        }),
+        TokenSaleEvent::ReceiptClosed(ReceiptClosed {
+            token_base,
+            buyer,
+            receipt: Pubkey::new_unique(),
+            nonce: 3,
+            reclaimed_lamports: 1_000,
+        }),
+        TokenSaleEvent::ReferralClaimed(ReferralClaimed {
+            token_base,
+            referrer: buyer,
+            buyer_facts,
+            claimed_lamports: 1_000,
+        }),
+        TokenSaleEvent::EscrowInitialized(EscrowInitialized {
+            token_base,
+            escrow: Pubkey::new_unique(),
+            team: Pubkey::new_unique(),
+            approver: Pubkey::new_unique(),
+            milestone_bps: vec![2_500, 7_500],
+            vote_threshold_bps: 5_000,
+        }),
+        TokenSaleEvent::MilestoneVoted(MilestoneVoted {
+            token_base,
+            buyer,
+            milestone: 0,
+            weight: 500,
+            approve_weight: 1_500,
+        }),
+        TokenSaleEvent::MilestoneReleased(MilestoneReleased {
+            token_base,
+            team: Pubkey::new_unique(),
+            milestone: 0,
+            by_vote: true,
+            released_lamports: 2_500,
+        }),
+        TokenSaleEvent::EscrowCancelled(EscrowCancelled {
+            token_base,
+            authority: Pubkey::new_unique(),
+            refund_pool: 7_500,
+        }),
+        TokenSaleEvent::EscrowRefunded(EscrowRefunded {
+            token_base,
+            buyer,
+            refunded_lamports: 750,
+        }),
+    ];
+
+    let logs: Vec<String> = events
+        .iter()
+        .map(|event| {
+            let data = match event {
+                TokenSaleEvent::SaleOpened(e) => e.to_bytes(),
+                TokenSaleEvent::SaleConfigured(e) => e.to_bytes(),
+                TokenSaleEvent::Toggled(e) => e.to_bytes(),
+                TokenSaleEvent::BuyerRegistered(e) => e.to_bytes(),
+                TokenSaleEvent::BuyerDeregistered(e) => e.to_bytes(),
+                TokenSaleEvent::LimitAssigned(e) => e.to_bytes(),
+                TokenSaleEvent::Purchased(e) => e.to_bytes(),
+                TokenSaleEvent::SaleClosed(e) => e.to_bytes(),
+                TokenSaleEvent::BuyerFactsReclaimed(e) => e.to_bytes(),
+                TokenSaleEvent::AccountMigrated(e) => e.to_bytes(),
+                TokenSaleEvent::ReceiptClosed(e) => e.to_bytes(),
+                TokenSaleEvent::ReferralClaimed(e) => e.to_bytes(),
+                TokenSaleEvent::EscrowInitialized(e) => e.to_bytes(),
+                TokenSaleEvent::MilestoneVoted(e) => e.to_bytes(),
+                TokenSaleEvent::MilestoneReleased(e) => e.to_bytes(),
+                TokenSaleEvent::EscrowCancelled(e) => e.to_bytes(),
+                TokenSaleEvent::EscrowRefunded(e) => e.to_bytes(),
+            }
+            .unwrap();
+            format!("{}{}", PROGRAM_DATA_LOG_PREFIX, STANDARD.encode(data))
+        })
+        .collect();
+
+    let program_id = Pubkey::new_unique();
+    let logs = invocation(&program_id, logs);
+    assert_eq!(TokenSaleEvent::from_logs(&program_id, &logs), events);
+}
+
+/// Unrelated logs are skipped
+#[test]
+fn test_ignores_foreign_logs() {
+    let program_id = Pubkey::new_unique();
+    let logs = invocation(
+        &program_id,
+        vec![
+            "Program log: Constraint failed: token_base".to_string(),
+            format!("{}{}", PROGRAM_DATA_LOG_PREFIX, STANDARD.encode([1u8; 16])),
+            format!("{}{}", PROGRAM_DATA_LOG_PREFIX, STANDARD.encode([1u8; 4])),
+        ],
+    );
+
+    assert!(TokenSaleEvent::from_logs(&program_id, &logs).is_empty());
+}
+
+/// Events logged by other programs are not decoded, at the top level
+/// nor in a CPI made by this program
+#[test]
+fn test_ignores_forged_events() {
+    let program_id = Pubkey::new_unique();
+    let forger = Pubkey::new_unique();
+    let event = TokenSaleEvent::Toggled(Toggled {
+        token_base: Pubkey::new_unique(),
+        is_running: true,
+    });
+    let log = match &event {
+        TokenSaleEvent::Toggled(e) => format!(
+            "{}{}",
+            PROGRAM_DATA_LOG_PREFIX,
+            STANDARD.encode(e.to_bytes().unwrap())
+        ),
+        _ => unreachable!(),
+    };
+
+    // the forger's event precedes, is nested in and follows ours
+    let nested = invocation(&forger, vec![log.clone()]);
+    let ours = invocation(
+        &program_id,
+        [vec![log.clone()], nested, vec![log.clone()]].concat(),
+    );
+    let logs = [
+        invocation(&forger, vec![log.clone()]),
+        ours,
+        invocation(&forger, vec![log]),
+    ]
+    .concat();
+
+    assert_eq!(
+        TokenSaleEvent::from_logs(&program_id, &logs),
+        vec![event.clone(), event]
+    );
+}
+
+/// `logs` framed as an invocation of `program_id`
+fn invocation(program_id: &Pubkey, logs: Vec<String>) -> Vec<String> {
+    [
+        vec![format!("Program {program_id} invoke [1]")],
+        logs,
+        vec![
+            format!("Program {program_id} consumed 1000 of 200000 compute units"),
+            format!("Program {program_id} success"),
+        ],
+    ]
+    .concat()
+}
+
+/// Truncated payloads are rejected instead of misparsed
+#[test]
+fn test_rejects_truncated_event() {
+    let data = Toggled {
+        token_base: Pubkey::new_unique(),
+        is_running: true,
+    }
+    .to_bytes()
+    .unwrap();
+
+    assert!(TokenSaleEvent::try_from_bytes(&data[..data.len() - 1]).is_err());
+}
@@ -0,0 +1,1046 @@
+//! Property-based sequence runner
+//!
+//! Generates random sequences of token sale instructions, signed either by
+//! the sale authority or by an attacker, optionally with one account swapped
+//! for another known account, and checks after every step that
+//!
+//! - TokenBase only changes through sale authority instructions, or when
+//!   its escrow settles
+//! - no lamports are created
+//! - no buyer receives more tokens than its purchase limit allowed
+//! - no buyer bought more over the sequence than the limits it was granted
+//!
+//! Purchases may issue receipts, credit referrers and use vouchers, the
+//! sale may be escrowed, migrated and given an end slot the clock warps past.
+use super::utils::TestHelper;
+use crate::instruction::{SaleSettings, SaleSettingsV1, SaleSettingsV2, TokenSaleInstruction};
+use crate::merkle::{convert_merkle_proof, pubkey_to_sha256_leaf, WhitelistRoot};
+use crate::state::{TokenBase, WhitelistMode};
+use crate::voucher::Voucher;
+use crate::*;
+use borsh::BorshSerialize;
+use merkletreers::tree::MerkleTree;
+use proptest::prelude::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signature::Signer,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+    system_transaction, sysvar,
+    transaction::Transaction,
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account;
+
+const PRICE: u64 = 1000;
+const DEFAULT_PURCHASE_LIMIT: u64 = 100;
+const MILESTONE_BPS: [u16; 2] = [5000, 5000];
+const VOTE_THRESHOLD_BPS: u16 = 5000;
+/// Receipts per actor whose lamports are tracked and that may be closed
+const RECEIPTS: u64 = 4;
+/// Actor 0 is the attacker, the others are whitelisted buyers
+const ACTORS: usize = 4;
+const ATTACKER: usize = 0;
+
+/// Who signs a sale authority instruction
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Authority {
+    SaleAuthority,
+    Attacker,
+}
+
+/// Purchase by BuyToken or RegisterAndBuy
+#[derive(Clone, Debug)]
+struct Purchase {
+    actor: usize,
+    amount: u64,
+    /// Issues a Receipt
+    receipt: bool,
+    /// Actor credited with the referral
+    referrer: Option<usize>,
+    /// Voucher allocation, signed by the whitelist signer for buyers and
+    /// by the attacker itself for the attacker
+    voucher: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+enum Action {
+    RegisterBuyer {
+        actor: usize,
+    },
+    DeregisterBuyer {
+        actor: usize,
+    },
+    BuyToken(Purchase),
+    RegisterAndBuy(Purchase),
+    ClaimReferral {
+        actor: usize,
+    },
+    CloseReceipt {
+        actor: usize,
+        nonce: u64,
+    },
+    AssignLimit {
+        signer: Authority,
+        actor: usize,
+        limit: u64,
+    },
+    ConfigureSale {
+        signer: Authority,
+        price: Option<u64>,
+        default_purchase_limit: Option<u64>,
+    },
+    UpdateSaleSettings {
+        signer: Authority,
+        referral_bps: Option<u16>,
+        /// Switches to vouchers if `true`, back to the Merkle whitelist if `false`
+        voucher_whitelist: Option<bool>,
+        /// End slot, in slots from the current one
+        end_in: Option<u64>,
+    },
+    ToggleRunning {
+        signer: Authority,
+    },
+    InitializeEscrow {
+        signer: Authority,
+        /// Milestones are approved by the sale authority rather than voted
+        approved: bool,
+    },
+    VoteMilestone {
+        actor: usize,
+    },
+    ReleaseMilestone {
+        signer: Authority,
+    },
+    CancelEscrow {
+        signer: Authority,
+    },
+    RefundEscrow {
+        actor: usize,
+    },
+    /// Migrates TokenBase, or the actor's BuyerFacts
+    Migrate {
+        actor: Option<usize>,
+    },
+    CloseSale {
+        signer: Authority,
+    },
+    /// Advances the clock, not an instruction
+    Warp {
+        slots: u64,
+    },
+}
+
+impl Action {
+    /// Is `true` if the action may change TokenBase
+    fn mutates_token_base(&self) -> bool {
+        matches!(
+            self,
+            Action::ConfigureSale {
+                signer: Authority::SaleAuthority,
+                ..
+            } | Action::UpdateSaleSettings {
+                signer: Authority::SaleAuthority,
+                ..
+            } | Action::ToggleRunning {
+                signer: Authority::SaleAuthority
+            } | Action::InitializeEscrow {
+                signer: Authority::SaleAuthority,
+                ..
+            } | Action::ReleaseMilestone {
+                signer: Authority::SaleAuthority
+            } | Action::CancelEscrow {
+                signer: Authority::SaleAuthority
+            } | Action::CloseSale {
+                signer: Authority::SaleAuthority
+            }
+        )
+    }
+
+    /// Is `true` if the action may settle the escrow, the only TokenBase
+    /// change anyone can cause
+    fn settles_escrow(&self) -> bool {
+        matches!(
+            self,
+            Action::ReleaseMilestone { .. } | Action::RefundEscrow { .. }
+        )
+    }
+
+    /// Is `true` if the action may register `actor`
+    fn registers(&self, actor: usize) -> bool {
+        match self {
+            Action::RegisterBuyer { actor: registered }
+            | Action::RegisterAndBuy(Purchase {
+                actor: registered, ..
+            }) => *registered == actor,
+            _ => false,
+        }
+    }
+
+    /// Limit the action may assign to `actor`
+    fn assigned_limit(&self, actor: usize) -> Option<u64> {
+        match self {
+            Action::AssignLimit {
+                signer: Authority::SaleAuthority,
+                actor: assigned,
+                limit,
+            } if *assigned == actor => Some(*limit),
+            _ => None,
+        }
+    }
+}
+
+/// Action with at most one account replaced by another known account
+#[derive(Clone, Debug)]
+struct Step {
+    action: Action,
+    /// (account index, index into `Fuzzer::substitutes`)
+    substitution: Option<(usize, usize)>,
+}
+
+fn authority() -> impl Strategy<Value = Authority> {
+    prop_oneof![
+        3 => Just(Authority::SaleAuthority),
+        1 => Just(Authority::Attacker),
+    ]
+}
+
+fn purchase() -> impl Strategy<Value = Purchase> {
+    (
+        0..ACTORS,
+        0..=DEFAULT_PURCHASE_LIMIT + 20,
+        any::<bool>(),
+        proptest::option::of(0..ACTORS),
+        proptest::option::weighted(0.3, 0..=DEFAULT_PURCHASE_LIMIT),
+    )
+        .prop_map(|(actor, amount, receipt, referrer, voucher)| Purchase {
+            actor,
+            amount,
+            receipt,
+            referrer,
+            voucher,
+        })
+}
+
+fn action() -> impl Strategy<Value = Action> {
+    let actor = 0..ACTORS;
+    prop_oneof![
+        2 => actor.clone().prop_map(|actor| Action::RegisterBuyer { actor }),
+        1 => actor.clone().prop_map(|actor| Action::DeregisterBuyer { actor }),
+        4 => purchase().prop_map(Action::BuyToken),
+        2 => purchase().prop_map(Action::RegisterAndBuy),
+        1 => actor.clone().prop_map(|actor| Action::ClaimReferral { actor }),
+        1 => (actor.clone(), 0..RECEIPTS)
+            .prop_map(|(actor, nonce)| Action::CloseReceipt { actor, nonce }),
+        1 => (authority(), actor.clone(), 0..=DEFAULT_PURCHASE_LIMIT * 2).prop_map(
+            |(signer, actor, limit)| Action::AssignLimit { signer, actor, limit }
+        ),
+        1 => (
+            authority(),
+            proptest::option::of(1..=PRICE * 2),
+            proptest::option::of(0..=DEFAULT_PURCHASE_LIMIT * 2),
+        )
+            .prop_map(|(signer, price, default_purchase_limit)| Action::ConfigureSale {
+                signer,
+                price,
+                default_purchase_limit,
+            }),
+        1 => (
+            authority(),
+            proptest::option::of(0..=1000u16),
+            proptest::option::of(any::<bool>()),
+            proptest::option::of(1..=8u64),
+        )
+            .prop_map(|(signer, referral_bps, voucher_whitelist, end_in)| {
+                Action::UpdateSaleSettings {
+                    signer,
+                    referral_bps,
+                    voucher_whitelist,
+                    end_in,
+                }
+            }),
+        1 => authority().prop_map(|signer| Action::ToggleRunning { signer }),
+        1 => (authority(), any::<bool>())
+            .prop_map(|(signer, approved)| Action::InitializeEscrow { signer, approved }),
+        1 => actor.clone().prop_map(|actor| Action::VoteMilestone { actor }),
+        1 => authority().prop_map(|signer| Action::ReleaseMilestone { signer }),
+        1 => authority().prop_map(|signer| Action::CancelEscrow { signer }),
+        1 => actor.clone().prop_map(|actor| Action::RefundEscrow { actor }),
+        1 => proptest::option::of(actor).prop_map(|actor| Action::Migrate { actor }),
+        1 => authority().prop_map(|signer| Action::CloseSale { signer }),
+        1 => (1..=8u64).prop_map(|slots| Action::Warp { slots }),
+    ]
+}
+
+fn step() -> impl Strategy<Value = Step> {
+    (
+        action(),
+        proptest::option::weighted(0.2, (0..17usize, any::<prop::sample::Index>())),
+    )
+        .prop_map(|(action, substitution)| Step {
+            action,
+            substitution: substitution.map(|(account, with)| (account, with.index(usize::MAX))),
+        })
+}
+
+/// Token sale instruction of a step, before the substitution
+struct StepInstruction {
+    /// Instructions placed before it, like the Ed25519 check of a voucher
+    preceding: Vec<Instruction>,
+    instruction: TokenSaleInstruction,
+    accounts: Vec<AccountMeta>,
+    /// Keypairs signing besides the relayer
+    signers: Vec<Keypair>,
+}
+
+/// Sale with its actors and every address an instruction may touch
+struct Fuzzer {
+    program_id: Pubkey,
+    mint: Pubkey,
+    /// Vault the sale opened with, the escrow replaces it once initialized
+    vault: Pubkey,
+    token_base: Pubkey,
+    escrow: Pubkey,
+    /// Receives released milestones
+    team: Pubkey,
+    /// Registries and pages listing the sale, as passed to CloseSale
+    registry_accounts: Vec<AccountMeta>,
+    /// Signs the vouchers of whitelisted actors
+    whitelist_signer: Keypair,
+    /// Pays fees and rent, never the sale authority
+    relayer: Keypair,
+    attacker: Keypair,
+    actors: Vec<Keypair>,
+    tree: MerkleTree,
+    /// Addresses swapped into instructions
+    substitutes: Vec<Pubkey>,
+}
+
+impl Fuzzer {
+    async fn setup(ctx: &mut ProgramTestContext, program_id: Pubkey) -> Self {
+        let relayer = Keypair::new();
+        let attacker = Keypair::new();
+        let actors = (0..ACTORS)
+            .map(|actor| match actor {
+                ATTACKER => attacker.insecure_clone(),
+                _ => Keypair::new(),
+            })
+            .collect::<Vec<_>>();
+        let vault = Keypair::new().pubkey();
+        let team = Keypair::new().pubkey();
+
+        // the vault and team are funded so small payments keep them rent exempt
+        for account in [relayer.pubkey(), vault, team]
+            .into_iter()
+            .chain(actors.iter().map(|actor| actor.pubkey()))
+        {
+            ctx.banks_client
+                .process_transaction(system_transaction::transfer(
+                    &ctx.payer,
+                    &account,
+                    100_000_000_000,
+                    ctx.last_blockhash,
+                ))
+                .await
+                .unwrap();
+        }
+
+        // every actor but the attacker is whitelisted
+        let tree = MerkleTree::new(
+            actors
+                .iter()
+                .skip(1)
+                .map(|actor| pubkey_to_sha256_leaf(&actor.pubkey()))
+                .collect(),
+        );
+
+        let mint = TestHelper::new_mint(0, ctx).await;
+        let (token_base, _) = TestHelper::initialize_token_base(
+            PRICE,
+            DEFAULT_PURCHASE_LIMIT,
+            mint,
+            vault,
+            &WhitelistRoot(tree.root),
+            program_id,
+            ctx,
+        )
+        .await;
+        TestHelper::toggle_running(token_base, mint, program_id, ctx).await;
+        let spoofed_token_base = TestHelper::spoof_account(token_base, ctx).await;
+        let registry_accounts =
+            TestHelper::close_sale_registry_accounts(token_base, program_id, ctx).await;
+
+        let mut fuzzer = Self {
+            program_id,
+            mint,
+            vault,
+            token_base,
+            escrow: pda::EscrowPDA::find_pda(&program_id, &token_base).0,
+            team,
+            registry_accounts,
+            whitelist_signer: Keypair::new(),
+            relayer,
+            attacker,
+            actors,
+            tree,
+            substitutes: Vec::new(),
+        };
+        fuzzer.substitutes = [
+            spoofed_token_base,
+            fuzzer.attacker.pubkey(),
+            ctx.payer.pubkey(),
+            vault,
+            mint,
+            token_base,
+            fuzzer.escrow,
+            team,
+        ]
+        .into_iter()
+        .chain(
+            fuzzer
+                .registry_accounts
+                .iter()
+                .map(|account| account.pubkey),
+        )
+        .chain((0..ACTORS).flat_map(|actor| {
+            [
+                fuzzer.actors[actor].pubkey(),
+                fuzzer.buyer_facts(actor),
+                fuzzer.buyer_token_account(actor),
+            ]
+        }))
+        .chain((0..ACTORS).flat_map(|actor| {
+            (0..RECEIPTS)
+                .map(|nonce| fuzzer.receipt(actor, nonce))
+                .collect::<Vec<_>>()
+        }))
+        .collect();
+
+        fuzzer
+    }
+
+    fn buyer_facts(&self, actor: usize) -> Pubkey {
+        pda::BuyerFactsPDA::find_pda(
+            &self.program_id,
+            &self.token_base,
+            &self.actors[actor].pubkey(),
+        )
+        .0
+    }
+
+    fn buyer_token_account(&self, actor: usize) -> Pubkey {
+        get_associated_token_address(&self.actors[actor].pubkey(), &self.mint)
+    }
+
+    fn receipt(&self, actor: usize, nonce: u64) -> Pubkey {
+        pda::ReceiptPDA::find_pda(
+            &self.program_id,
+            &self.token_base,
+            &self.actors[actor].pubkey(),
+            nonce,
+        )
+        .0
+    }
+
+    /// Every account whose lamports are tracked
+    fn tracked_accounts(&self, ctx: &ProgramTestContext) -> Vec<Pubkey> {
+        let mut accounts = self.substitutes.clone();
+        accounts.extend([self.relayer.pubkey(), ctx.payer.pubkey()]);
+        accounts.sort();
+        accounts.dedup();
+        accounts
+    }
+
+    /// TokenBase of the sale, None once closed
+    async fn token_base_state(&self, ctx: &mut ProgramTestContext) -> Option<TokenBase> {
+        ctx.banks_client
+            .get_account(self.token_base)
+            .await
+            .unwrap()
+            .filter(|account| account.data.len() == TokenBase::LEN)
+            .map(|account| bytemuck::pod_read_unaligned(&account.data))
+    }
+
+    /// Nonce of the actor's next receipt
+    async fn receipt_count(&self, actor: usize, ctx: &mut ProgramTestContext) -> u64 {
+        ctx.banks_client
+            .get_account_data_with_borsh::<state::BuyerFacts>(self.buyer_facts(actor))
+            .await
+            .map_or(0, |buyer_facts| buyer_facts.receipt_count)
+    }
+
+    /// Instruction for `action`, with the current vault and receipt nonce
+    async fn instruction(&self, action: &Action, ctx: &mut ProgramTestContext) -> StepInstruction {
+        let sale_authority = ctx.payer.insecure_clone();
+        let authority = |signer: &Authority| match signer {
+            Authority::SaleAuthority => sale_authority.insecure_clone(),
+            Authority::Attacker => self.attacker.insecure_clone(),
+        };
+        let instruction = |instruction, accounts, signers| StepInstruction {
+            preceding: Vec::new(),
+            instruction,
+            accounts,
+            signers,
+        };
+
+        match action {
+            Action::RegisterBuyer { actor } => instruction(
+                TokenSaleInstruction::RegisterBuyer,
+                vec![
+                    AccountMeta::new_readonly(self.token_base, false),
+                    AccountMeta::new(self.buyer_facts(*actor), false),
+                    AccountMeta::new_readonly(self.actors[*actor].pubkey(), true),
+                    AccountMeta::new(self.relayer.pubkey(), true),
+                    AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                    AccountMeta::new_readonly(crate::ID, false),
+                    AccountMeta::new_readonly(crate::ID, false),
+                    AccountMeta::new_readonly(crate::ID, false),
+                ],
+                vec![self.actors[*actor].insecure_clone()],
+            ),
+            Action::DeregisterBuyer { actor } => instruction(
+                TokenSaleInstruction::DeregisterBuyer,
+                vec![
+                    AccountMeta::new_readonly(self.token_base, false),
+                    AccountMeta::new(self.buyer_facts(*actor), false),
+                    AccountMeta::new_readonly(self.actors[*actor].pubkey(), true),
+                    AccountMeta::new(self.relayer.pubkey(), false),
+                ],
+                vec![self.actors[*actor].insecure_clone()],
+            ),
+            Action::BuyToken(purchase) => {
+                self.purchase_instruction(purchase, false, &sale_authority.pubkey(), ctx)
+                    .await
+            }
+            Action::RegisterAndBuy(purchase) => {
+                self.purchase_instruction(purchase, true, &sale_authority.pubkey(), ctx)
+                    .await
+            }
+            Action::ClaimReferral { actor } => instruction(
+                TokenSaleInstruction::ClaimReferral,
+                vec![
+                    AccountMeta::new_readonly(self.token_base, false),
+                    AccountMeta::new(self.buyer_facts(*actor), false),
+                    AccountMeta::new(self.actors[*actor].pubkey(), true),
+                ],
+                vec![self.actors[*actor].insecure_clone()],
+            ),
+            Action::CloseReceipt { actor, nonce } => instruction(
+                TokenSaleInstruction::CloseReceipt,
+                vec![
+                    AccountMeta::new(self.receipt(*actor, *nonce), false),
+                    AccountMeta::new_readonly(self.actors[*actor].pubkey(), true),
+                    // receipts are paid by the relayer
+                    AccountMeta::new(self.relayer.pubkey(), false),
+                ],
+                vec![self.actors[*actor].insecure_clone()],
+            ),
+            Action::AssignLimit {
+                signer,
+                actor,
+                limit,
+            } => instruction(
+                TokenSaleInstruction::AssignLimit {
+                    new_purchase_limit: *limit,
+                },
+                vec![
+                    AccountMeta::new_readonly(self.token_base, false),
+                    AccountMeta::new(self.buyer_facts(*actor), false),
+                    AccountMeta::new_readonly(self.actors[*actor].pubkey(), false),
+                    AccountMeta::new(authority(signer).pubkey(), true),
+                ],
+                vec![authority(signer)],
+            ),
+            Action::ConfigureSale {
+                signer,
+                price,
+                default_purchase_limit,
+            } => instruction(
+                TokenSaleInstruction::ConfigureSale {
+                    price: *price,
+                    default_purchase_limit: *default_purchase_limit,
+                    whitelist_root: None,
+                },
+                self.sale_authority_accounts(&authority(signer).pubkey()),
+                vec![authority(signer)],
+            ),
+            Action::UpdateSaleSettings {
+                signer,
+                referral_bps,
+                voucher_whitelist,
+                end_in,
+            } => {
+                let slot = ctx
+                    .banks_client
+                    .get_sysvar::<solana_sdk::clock::Clock>()
+                    .await
+                    .unwrap()
+                    .slot;
+                let (whitelist_mode, whitelist_signer) = match voucher_whitelist {
+                    Some(true) => (
+                        Some(WhitelistMode::Voucher),
+                        Some(self.whitelist_signer.pubkey()),
+                    ),
+                    Some(false) => (Some(WhitelistMode::Merkle), None),
+                    None => (None, None),
+                };
+
+                instruction(
+                    TokenSaleInstruction::UpdateSaleSettings {
+                        settings: SaleSettings::V2(SaleSettingsV2 {
+                            v1: SaleSettingsV1 {
+                                referral_bps: *referral_bps,
+                                whitelist_mode,
+                                whitelist_signer,
+                                ..Default::default()
+                            },
+                            end_slot: end_in.map(|end_in| slot + end_in),
+                        }),
+                    },
+                    self.sale_authority_accounts(&authority(signer).pubkey()),
+                    vec![authority(signer)],
+                )
+            }
+            Action::ToggleRunning { signer } => instruction(
+                TokenSaleInstruction::ToggleRunning,
+                self.sale_authority_accounts(&authority(signer).pubkey()),
+                vec![authority(signer)],
+            ),
+            Action::InitializeEscrow { signer, approved } => instruction(
+                TokenSaleInstruction::InitializeEscrow {
+                    team: self.team,
+                    approver: approved.then(|| sale_authority.pubkey()),
+                    milestone_bps: MILESTONE_BPS.to_vec(),
+                    vote_threshold_bps: VOTE_THRESHOLD_BPS,
+                },
+                [
+                    self.sale_authority_accounts(&authority(signer).pubkey()),
+                    vec![
+                        AccountMeta::new(self.escrow, false),
+                        AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                    ],
+                ]
+                .concat(),
+                vec![authority(signer)],
+            ),
+            Action::VoteMilestone { actor } => instruction(
+                TokenSaleInstruction::VoteMilestone,
+                vec![
+                    AccountMeta::new_readonly(self.token_base, false),
+                    AccountMeta::new(self.escrow, false),
+                    AccountMeta::new(self.buyer_facts(*actor), false),
+                    AccountMeta::new_readonly(self.actors[*actor].pubkey(), true),
+                ],
+                vec![self.actors[*actor].insecure_clone()],
+            ),
+            Action::ReleaseMilestone { signer } => instruction(
+                TokenSaleInstruction::ReleaseMilestone,
+                vec![
+                    AccountMeta::new(self.token_base, false),
+                    AccountMeta::new(self.escrow, false),
+                    AccountMeta::new(self.team, false),
+                    AccountMeta::new_readonly(authority(signer).pubkey(), true),
+                ],
+                vec![authority(signer)],
+            ),
+            Action::CancelEscrow { signer } => instruction(
+                TokenSaleInstruction::CancelEscrow,
+                vec![
+                    AccountMeta::new(self.token_base, false),
+                    AccountMeta::new(self.escrow, false),
+                    AccountMeta::new_readonly(authority(signer).pubkey(), true),
+                ],
+                vec![authority(signer)],
+            ),
+            Action::RefundEscrow { actor } => instruction(
+                TokenSaleInstruction::RefundEscrow,
+                vec![
+                    AccountMeta::new(self.token_base, false),
+                    AccountMeta::new(self.escrow, false),
+                    AccountMeta::new(self.buyer_facts(*actor), false),
+                    AccountMeta::new(self.actors[*actor].pubkey(), false),
+                ],
+                Vec::new(),
+            ),
+            Action::Migrate { actor } => {
+                let (account, sale) = match actor {
+                    Some(actor) => (
+                        self.buyer_facts(*actor),
+                        [
+                            AccountMeta::new_readonly(self.token_base, false),
+                            AccountMeta::new_readonly(self.actors[*actor].pubkey(), false),
+                        ],
+                    ),
+                    None => (
+                        self.token_base,
+                        [
+                            AccountMeta::new_readonly(crate::ID, false),
+                            AccountMeta::new_readonly(crate::ID, false),
+                        ],
+                    ),
+                };
+
+                instruction(
+                    TokenSaleInstruction::Migrate,
+                    [
+                        vec![
+                            AccountMeta::new(account, false),
+                            AccountMeta::new(self.relayer.pubkey(), true),
+                            AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                        ],
+                        sale.to_vec(),
+                    ]
+                    .concat(),
+                    Vec::new(),
+                )
+            }
+            Action::CloseSale { signer } => {
+                let escrow = match self.token_base_state(ctx).await {
+                    Some(token_base) if token_base.is_escrow() => {
+                        AccountMeta::new(self.escrow, false)
+                    }
+                    _ => AccountMeta::new_readonly(crate::ID, false),
+                };
+
+                instruction(
+                    TokenSaleInstruction::CloseSale,
+                    [
+                        self.sale_authority_accounts(&authority(signer).pubkey()),
+                        self.registry_accounts.clone(),
+                        vec![escrow],
+                    ]
+                    .concat(),
+                    vec![authority(signer)],
+                )
+            }
+            Action::Warp { .. } => unreachable!("warps are not instructions"),
+        }
+    }
+
+    /// BuyToken, or RegisterAndBuy if `register`, paying into the current vault
+    async fn purchase_instruction(
+        &self,
+        purchase: &Purchase,
+        register: bool,
+        sale_authority: &Pubkey,
+        ctx: &mut ProgramTestContext,
+    ) -> StepInstruction {
+        let actor = purchase.actor;
+        let buyer = self.actors[actor].pubkey();
+        let proof = convert_merkle_proof(self.tree.make_proof(pubkey_to_sha256_leaf(&buyer)));
+        let instruction = match register {
+            true => TokenSaleInstruction::RegisterAndBuy {
+                amount: purchase.amount,
+                proof,
+            },
+            false => TokenSaleInstruction::BuyToken {
+                amount: purchase.amount,
+                proof,
+            },
+        };
+
+        let vault = self
+            .token_base_state(ctx)
+            .await
+            .map_or(self.vault, |token_base| token_base.vault);
+        let receipt = match purchase.receipt {
+            true => self.receipt(actor, self.receipt_count(actor, ctx).await),
+            false => crate::ID,
+        };
+        let (referrer_facts, referrer) = match purchase.referrer {
+            Some(referrer) => (self.buyer_facts(referrer), self.actors[referrer].pubkey()),
+            None => (crate::ID, crate::ID),
+        };
+
+        // the attacker can only forge its own voucher
+        let preceding = purchase
+            .voucher
+            .map(|allocation| {
+                let signer = match actor {
+                    ATTACKER => &self.attacker,
+                    _ => &self.whitelist_signer,
+                };
+                let voucher = Voucher {
+                    buyer,
+                    token_base: self.token_base,
+                    allocation,
+                    expiry: i64::MAX,
+                };
+                let signature = signer.sign_message(&voucher.message());
+                voucher.ed25519_instruction(&self.whitelist_signer.pubkey(), &signature.into())
+            })
+            .into_iter()
+            .collect::<Vec<_>>();
+        let instructions_sysvar = match purchase.voucher {
+            Some(_) => sysvar::instructions::ID,
+            None => crate::ID,
+        };
+
+        StepInstruction {
+            preceding,
+            instruction,
+            accounts: vec![
+                AccountMeta::new_readonly(self.token_base, false),
+                AccountMeta::new(self.mint, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(*sale_authority, false),
+                AccountMeta::new(self.buyer_token_account(actor), false),
+                AccountMeta::new(self.buyer_facts(actor), false),
+                AccountMeta::new(buyer, true),
+                AccountMeta::new(self.relayer.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+                AccountMeta::new(receipt, false),
+                AccountMeta::new(referrer_facts, false),
+                AccountMeta::new_readonly(referrer, false),
+                AccountMeta::new_readonly(instructions_sysvar, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            signers: vec![self.actors[actor].insecure_clone()],
+        }
+    }
+
+    fn sale_authority_accounts(&self, sale_authority: &Pubkey) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.token_base, false),
+            AccountMeta::new_readonly(self.mint, false),
+            AccountMeta::new(*sale_authority, true),
+        ]
+    }
+
+    /// Run `step`, its outcome is irrelevant, only the invariants are
+    async fn run(&self, step: &Step, ctx: &mut ProgramTestContext) {
+        if let Action::Warp { slots } = step.action {
+            let slot = ctx
+                .banks_client
+                .get_sysvar::<solana_sdk::clock::Clock>()
+                .await
+                .unwrap()
+                .slot;
+            ctx.warp_to_slot(slot + slots).unwrap();
+            ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+            return;
+        }
+
+        let StepInstruction {
+            preceding,
+            instruction,
+            mut accounts,
+            signers,
+        } = self.instruction(&step.action, ctx).await;
+
+        // signer flags stay, a swapped in signer fails signature verification
+        if let Some((index, with)) = step.substitution {
+            if let Some(account) = accounts.get_mut(index) {
+                account.pubkey = self.substitutes[with % self.substitutes.len()];
+            }
+        }
+
+        let mut instruction_data = Vec::new();
+        instruction.serialize(&mut instruction_data).unwrap();
+
+        let mut transaction = Transaction::new_with_payer(
+            &[
+                preceding,
+                vec![Instruction {
+                    program_id: self.program_id,
+                    accounts,
+                    data: instruction_data,
+                }],
+            ]
+            .concat(),
+            Some(&self.relayer.pubkey()),
+        );
+        let signers = std::iter::once(&self.relayer)
+            .chain(signers.iter())
+            .collect::<Vec<_>>();
+        if transaction.try_sign(&signers, ctx.last_blockhash).is_err() {
+            return;
+        }
+
+        let _ = ctx.banks_client.process_transaction(transaction).await;
+    }
+}
+
+/// Chain state the invariants are checked against
+struct Snapshot {
+    token_base: Option<Vec<u8>>,
+    lamports: u64,
+    /// (purchase limit, token balance) per actor
+    buyers: Vec<(Option<u64>, u64)>,
+}
+
+impl Snapshot {
+    async fn take(fuzzer: &Fuzzer, ctx: &mut ProgramTestContext) -> Self {
+        let token_base = ctx
+            .banks_client
+            .get_account(fuzzer.token_base)
+            .await
+            .unwrap()
+            .map(|account| account.data);
+
+        let mut lamports = 0;
+        for account in fuzzer.tracked_accounts(ctx) {
+            lamports += ctx.banks_client.get_balance(account).await.unwrap();
+        }
+
+        let mut buyers = Vec::new();
+        for actor in 0..ACTORS {
+            let purchase_limit = ctx
+                .banks_client
+                .get_account_data_with_borsh::<state::BuyerFacts>(fuzzer.buyer_facts(actor))
+                .await
+                .ok()
+                .map(|buyer_facts| buyer_facts.purchase_limit);
+            let balance = ctx
+                .banks_client
+                .get_account(fuzzer.buyer_token_account(actor))
+                .await
+                .unwrap()
+                .map_or(0, |account| Account::unpack(&account.data).unwrap().amount);
+            buyers.push((purchase_limit, balance));
+        }
+
+        Self {
+            token_base,
+            lamports,
+            buyers,
+        }
+    }
+
+    /// TokenBase of the sale, None once closed
+    fn token_base_state(&self) -> Option<TokenBase> {
+        self.token_base
+            .as_deref()
+            .filter(|data| data.len() == TokenBase::LEN)
+            .map(bytemuck::pod_read_unaligned)
+    }
+
+    /// Purchase limit a buyer registered now starts with
+    fn default_purchase_limit(&self) -> u64 {
+        self.token_base_state()
+            .map_or(0, |token_base| token_base.default_purchase_limit)
+    }
+}
+
+/// Check the invariants between the snapshots around `step`
+///
+/// `granted` sums the limits each actor was granted so far, AssignLimit
+/// replaces the remaining limit so every assignment adds to it
+fn check_invariants(step: &Step, before: &Snapshot, after: &Snapshot, granted: &mut [u64]) {
+    // - TokenBase only changes through sale authority instructions,
+    //   anyone may settle the escrow
+    if !step.action.mutates_token_base() {
+        match (before.token_base_state(), after.token_base_state()) {
+            (Some(mut token_base_before), Some(token_base_after))
+                if step.action.settles_escrow() =>
+            {
+                token_base_before.is_escrow_settled = token_base_after.is_escrow_settled;
+                assert_eq!(
+                    bytemuck::bytes_of(&token_base_before),
+                    bytemuck::bytes_of(&token_base_after),
+                    "{step:?}"
+                );
+            }
+            _ => assert_eq!(before.token_base, after.token_base, "{step:?}"),
+        }
+    }
+
+    // - no lamports are created, fees leave the tracked accounts
+    assert!(after.lamports <= before.lamports, "{step:?}");
+
+    // - no buyer receives more tokens than its purchase limit allowed
+    for (actor, ((limit_before, balance_before), (limit_after, balance_after))) in
+        before.buyers.iter().zip(&after.buyers).enumerate()
+    {
+        let bought = balance_after
+            .checked_sub(*balance_before)
+            .expect("token balances never decrease");
+
+        // RegisterAndBuy buys with the limit it registers with
+        let registered_limit = match limit_before {
+            None if step.action.registers(actor) => Some(before.default_purchase_limit()),
+            _ => *limit_before,
+        };
+        assert!(bought <= registered_limit.unwrap_or(0), "{step:?}");
+
+        if actor == ATTACKER {
+            assert_eq!(*balance_after, 0, "{step:?}");
+        }
+
+        // limits only change by purchases, unless assigned or (de)registered
+        if let (Some(limit_before), Some(limit_after)) = (limit_before, limit_after) {
+            if step.action.assigned_limit(actor).is_none() {
+                assert_eq!(*limit_after, limit_before - bought, "{step:?}");
+            }
+        }
+
+        // - no buyer bought more over the sequence than the limits it was granted
+        if limit_before.is_none() && step.action.registers(actor) {
+            granted[actor] += before.default_purchase_limit();
+        }
+        if let Some(limit) = step.action.assigned_limit(actor) {
+            granted[actor] += limit;
+        }
+        assert!(
+            *balance_after <= granted[actor],
+            "{step:?}: {balance_after} bought, {} granted",
+            granted[actor]
+        );
+    }
+}
+
+async fn run_sequence(steps: Vec<Step>) {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let fuzzer = Fuzzer::setup(&mut ctx, program_id).await;
+    let mut granted = [0; ACTORS];
+
+    for step in &steps {
+        let before = Snapshot::take(&fuzzer, &mut ctx).await;
+        fuzzer.run(step, &mut ctx).await;
+        let after = Snapshot::take(&fuzzer, &mut ctx).await;
+
+        check_invariants(step, &before, &after, &mut granted);
+    }
+}
+
+proptest! {
+    // every case boots a bank, keep the default count low
+    // failing sequences are printed rather than persisted, a missing
+    // program build fails every case
+    #![proptest_config(ProptestConfig {
+        cases: std::env::var("PROPTEST_CASES")
+            .ok()
+            .and_then(|cases| cases.parse().ok())
+            .unwrap_or(32),
+        failure_persistence: None,
+        ..ProptestConfig::default()
+    })]
+
+    /// Random instruction sequences never break the sale invariants
+    #[test]
+    fn test_instruction_sequences_keep_invariants(
+        steps in proptest::collection::vec(step(), 1..24)
+    ) {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(run_sequence(steps));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::error::TokenSaleError;
+use crate::holder::*;
+use crate::state::{TokenBase, WhitelistMode};
+use bytemuck::Zeroable;
+use solana_sdk::{
+    account_info::AccountInfo, program_error::ProgramError, program_pack::Pack, pubkey::Pubkey,
+};
+use spl_token::state::{Account, AccountState, Mint};
+
+/// Owned account backing an AccountInfo
+struct Fixture {
+    key: Pubkey,
+    owner: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+}
+
+impl Fixture {
+    fn new(owner: Pubkey, data: Vec<u8>) -> Self {
+        Self {
+            key: Pubkey::new_unique(),
+            owner,
+            lamports: 0,
+            data,
+        }
+    }
+
+    fn info(&mut self) -> AccountInfo<'_> {
+        AccountInfo::new(
+            &self.key,
+            false,
+            false,
+            &mut self.lamports,
+            &mut self.data,
+            &self.owner,
+            false,
+            0,
+        )
+    }
+}
+
+fn token_base(mode: WhitelistMode, gate_key: Pubkey, gate_min_amount: u64) -> TokenBase {
+    let mut token_base = TokenBase::zeroed();
+    token_base.whitelist_mode = mode as u8;
+    token_base.gate_key = gate_key;
+    token_base.gate_min_amount = gate_min_amount;
+    token_base
+}
+
+fn token_account(mint: Pubkey, owner: Pubkey, amount: u64) -> Fixture {
+    let mut data = vec![0; Account::LEN];
+    Account {
+        mint,
+        owner,
+        amount,
+        state: AccountState::Initialized,
+        ..Default::default()
+    }
+    .pack_into_slice(&mut data);
+    Fixture::new(spl_token::ID, data)
+}
+
+fn mint(key: Pubkey, supply: u64, decimals: u8) -> Fixture {
+    let mut data = vec![0; Mint::LEN];
+    Mint {
+        supply,
+        decimals,
+        is_initialized: true,
+        ..Default::default()
+    }
+    .pack_into_slice(&mut data);
+    let mut fixture = Fixture::new(spl_token::ID, data);
+    fixture.key = key;
+    fixture
+}
+
+fn metadata(mint: Pubkey, collection: Option<Collection>) -> Fixture {
+    let mut data = borsh::to_vec(&MetadataPrefix {
+        key: METADATA_V1_KEY,
+        update_authority: Pubkey::new_unique(),
+        mint,
+        name: "Holder".to_string(),
+        symbol: "HLD".to_string(),
+        uri: "https://example.com/holder.json".to_string(),
+        seller_fee_basis_points: 500,
+        creators: Some(vec![Creator {
+            address: Pubkey::new_unique(),
+            verified: true,
+            share: 100,
+        }]),
+        primary_sale_happened: true,
+        is_mutable: true,
+        edition_nonce: Some(255),
+        token_standard: Some(0),
+        collection,
+    })
+    .unwrap();
+    // uses, collection details and padding follow on chain
+    data.extend_from_slice(&[0; 64]);
+    Fixture::new(TOKEN_METADATA_PROGRAM_ID, data)
+}
+
+/// Test Happy Path, governance token holder
+#[test]
+fn test_check_token_holder() {
+    let holder = Pubkey::new_unique();
+    let gate_mint = Pubkey::new_unique();
+    let token_base = token_base(WhitelistMode::TokenHolder, gate_mint, 10);
+    let mut token_account = token_account(gate_mint, holder, 10);
+
+    check_holder(
+        &token_base,
+        &holder,
+        Some(&token_account.info()),
+        None,
+        None,
+    )
+    .unwrap();
+}
+
+/// Test a balance below `gate_min_amount` or of another mint is rejected
+#[test]
+fn test_check_token_holder_insufficient_fails() {
+    let holder = Pubkey::new_unique();
+    let gate_mint = Pubkey::new_unique();
+    let token_base = token_base(WhitelistMode::TokenHolder, gate_mint, 10);
+
+    let mut below_min = token_account(gate_mint, holder, 9);
+    let mut other_mint = token_account(Pubkey::new_unique(), holder, 10);
+    let mut other_owner = token_account(gate_mint, Pubkey::new_unique(), 10);
+
+    for fixture in [&mut below_min, &mut other_mint, &mut other_owner] {
+        assert_eq!(
+            check_holder(&token_base, &holder, Some(&fixture.info()), None, None),
+            Err(TokenSaleError::NotHolder.into())
+        );
+    }
+    assert_eq!(
+        check_holder(&token_base, &holder, None, None, None),
+        Err(TokenSaleError::NotHolder.into())
+    );
+}
+
+/// Test Happy Path, NFT of the verified collection
+#[test]
+fn test_check_collection_holder() {
+    let holder = Pubkey::new_unique();
+    let collection = Pubkey::new_unique();
+    let nft_mint = Pubkey::new_unique();
+    let token_base = token_base(WhitelistMode::CollectionHolder, collection, 0);
+
+    let mut token_account = token_account(nft_mint, holder, 1);
+    let mut metadata = metadata(
+        nft_mint,
+        Some(Collection {
+            verified: true,
+            key: collection,
+        }),
+    );
+
+    let mut nft = mint(nft_mint, 1, 0);
+
+    check_holder(
+        &token_base,
+        &holder,
+        Some(&token_account.info()),
+        Some(&metadata.info()),
+        Some(&nft.info()),
+    )
+    .unwrap();
+}
+
+/// Test unverified, foreign or mismatched metadata is rejected
+#[test]
+fn test_check_collection_holder_unverified_fails() {
+    let holder = Pubkey::new_unique();
+    let collection = Pubkey::new_unique();
+    let nft_mint = Pubkey::new_unique();
+    let token_base = token_base(WhitelistMode::CollectionHolder, collection, 0);
+    let mut token_account = token_account(nft_mint, holder, 1);
+    let mut nft = mint(nft_mint, 1, 0);
+
+    let mut unverified = metadata(
+        nft_mint,
+        Some(Collection {
+            verified: false,
+            key: collection,
+        }),
+    );
+    let mut other_collection = metadata(
+        nft_mint,
+        Some(Collection {
+            verified: true,
+            key: Pubkey::new_unique(),
+        }),
+    );
+    let mut other_mint = metadata(
+        Pubkey::new_unique(),
+        Some(Collection {
+            verified: true,
+            key: collection,
+        }),
+    );
+    let mut spoofed = metadata(
+        nft_mint,
+        Some(Collection {
+            verified: true,
+            key: collection,
+        }),
+    );
+    spoofed.owner = Pubkey::new_unique();
+
+    for fixture in [
+        &mut unverified,
+        &mut other_collection,
+        &mut other_mint,
+        &mut spoofed,
+    ] {
+        assert_eq!(
+            check_holder(
+                &token_base,
+                &holder,
+                Some(&token_account.info()),
+                Some(&fixture.info()),
+                Some(&nft.info()),
+            ),
+            Err(TokenSaleError::NotInCollection.into())
+        );
+    }
+}
+
+/// Test a fungible token of the verified collection is rejected
+#[test]
+fn test_check_collection_holder_fungible_fails() {
+    let holder = Pubkey::new_unique();
+    let collection = Pubkey::new_unique();
+    let fungible_mint = Pubkey::new_unique();
+    let token_base = token_base(WhitelistMode::CollectionHolder, collection, 0);
+
+    let mut token_account = token_account(fungible_mint, holder, 1);
+    let mut metadata = metadata(
+        fungible_mint,
+        Some(Collection {
+            verified: true,
+            key: collection,
+        }),
+    );
+
+    let mut supply = mint(fungible_mint, 1_000, 0);
+    let mut decimals = mint(fungible_mint, 1, 6);
+    let mut other_mint = mint(Pubkey::new_unique(), 1, 0);
+    let mut spoofed = mint(fungible_mint, 1, 0);
+    spoofed.owner = Pubkey::new_unique();
+
+    for fixture in [&mut supply, &mut decimals, &mut other_mint, &mut spoofed] {
+        assert_eq!(
+            check_holder(
+                &token_base,
+                &holder,
+                Some(&token_account.info()),
+                Some(&metadata.info()),
+                Some(&fixture.info()),
+            ),
+            Err(TokenSaleError::HolderNotNft.into())
+        );
+    }
+    assert_eq!(
+        check_holder(
+            &token_base,
+            &holder,
+            Some(&token_account.info()),
+            Some(&metadata.info()),
+            None,
+        ),
+        Err(TokenSaleError::HolderNotNft.into())
+    );
+}
+
+/// Test Merkle and voucher sales ignore the holder accounts
+#[test]
+fn test_check_holder_skips_other_modes() {
+    let holder = Pubkey::new_unique();
+
+    for mode in [WhitelistMode::Merkle, WhitelistMode::Voucher] {
+        let token_base = token_base(mode, Pubkey::default(), 0);
+        assert_eq!(
+            check_holder(&token_base, &holder, None, None, None),
+            Ok::<(), ProgramError>(())
+        );
+    }
+}
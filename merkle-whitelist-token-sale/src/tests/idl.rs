@@ -0,0 +1,341 @@
+use crate::instruction::TokenSaleInstruction;
+use crate::pda::{
+    BuyerFactsPDA, EscrowPDA, ReceiptPDA, RegistryPDA, RegistryPagePDA, Seed, TokenBasePDA,
+};
+use serde_json::{json, Map, Value};
+use shank_idl::{extract_idl, ParseIdlOpts};
+use std::collections::HashSet;
+
+const LIB_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/lib.rs");
+const IDL_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/idl/merkle_whitelist_token_sale.json"
+);
+
+/// Tuple newtypes shank can't parse, borsh encoded like the wrapped type
+fn transparent_types() -> [(&'static str, Value); 2] {
+    [
+        ("WhitelistRoot", json!({ "array": ["u8", 32] })),
+        ("PodBool", json!("bool")),
+    ]
+}
+
+/// Pod structs nested in accounts, skipped along with the Pod newtypes
+fn pod_types() -> [Value; 1] {
+    [json!({
+        "name": "RootRecord",
+        "type": {
+            "kind": "struct",
+            "fields": [
+                { "name": "root", "type": { "array": ["u8", 32] } },
+                { "name": "activationSlot", "type": "u64" }
+            ]
+        }
+    })]
+}
+
+/// Replace `{ "defined": <newtype> }` with the wrapped type
+fn inline_transparent_types(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::String(defined)) = object.get("defined") {
+                if let Some((_, ty)) = transparent_types()
+                    .into_iter()
+                    .find(|(name, _)| name == defined)
+                {
+                    *value = ty;
+                    return;
+                }
+            }
+            object.values_mut().for_each(inline_transparent_types);
+        }
+        Value::Array(array) => array.iter_mut().for_each(inline_transparent_types),
+        _ => {}
+    }
+}
+
+/// Accounts whose addresses are PDAs, with the program deriving them
+fn pdas() -> [(&'static str, Vec<Seed>, Option<String>); 10] {
+    [
+        ("token_base", TokenBasePDA::SEEDS.to_vec(), None),
+        ("buyer_facts", BuyerFactsPDA::SEEDS.to_vec(), None),
+        (
+            "referrer_facts",
+            vec![
+                Seed::Literal(BuyerFactsPDA::NAME),
+                Seed::Account("token_base"),
+                Seed::Account("referrer"),
+            ],
+            None,
+        ),
+        ("receipt", ReceiptPDA::SEEDS.to_vec(), None),
+        ("escrow", EscrowPDA::SEEDS.to_vec(), None),
+        ("registry", RegistryPDA::SEEDS.to_vec(), None),
+        (
+            "registry_page",
+            RegistryPagePDA::seeds("registry", "registry_page.page").to_vec(),
+            None,
+        ),
+        (
+            "authority_registry",
+            RegistryPDA::AUTHORITY_SEEDS.to_vec(),
+            None,
+        ),
+        (
+            "authority_registry_page",
+            RegistryPagePDA::seeds("authority_registry", "authority_registry_page.page").to_vec(),
+            None,
+        ),
+        (
+            "buyer_token_account",
+            vec![
+                Seed::Account("buyer"),
+                Seed::Account("token_program"),
+                Seed::Account("mint"),
+            ],
+            Some(spl_associated_token_account::ID.to_string()),
+        ),
+    ]
+}
+
+fn camel_case(name: &str) -> String {
+    let mut parts = name.split('_');
+    let mut camel = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            camel.extend(first.to_uppercase());
+            camel.push_str(chars.as_str());
+        }
+    }
+    camel
+}
+
+fn pascal_case(name: &str) -> String {
+    let camel = camel_case(name);
+    let mut chars = camel.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => camel,
+    }
+}
+
+/// Seeds as IDL metadata
+///
+/// Account seeds missing from the instruction are read from the
+/// `source` account's data, e.g. `tokenBase.saleAuthority`, and so
+/// are nonces, from the PDA's stored `nonce`
+fn seeds_json(seeds: &[Seed], accounts: Option<&[String]>, source: &str) -> Value {
+    let seeds = seeds
+        .iter()
+        .map(|seed| match seed {
+            Seed::Literal(value) => json!({ "kind": "const", "type": "string", "value": value }),
+            Seed::Account(name) => {
+                let name = camel_case(name);
+                match accounts {
+                    Some(accounts) if !accounts.contains(&name) => {
+                        json!({ "kind": "accountData", "type": "publicKey", "path": format!("{source}.{name}") })
+                    }
+                    _ => json!({ "kind": "account", "type": "publicKey", "path": name }),
+                }
+            }
+            Seed::Nonce(path) => {
+                let (account, field) = path.split_once('.').unwrap();
+                let account = camel_case(account);
+                let path = match accounts {
+                    Some(accounts) if accounts.contains(&account) => {
+                        format!("{account}.{}", camel_case(field))
+                    }
+                    // nonce stored by the PDA account itself
+                    None if account == source => format!("{source}.{}", camel_case(field)),
+                    _ => format!("{source}.nonce"),
+                };
+                json!({ "kind": "accountData", "type": "u64", "path": path })
+            }
+        })
+        .collect::<Vec<_>>();
+    Value::Array(seeds)
+}
+
+fn pda_json(seeds: Value, program_id: &Option<String>) -> Value {
+    let mut pda = Map::new();
+    pda.insert("seeds".to_string(), seeds);
+    if let Some(program_id) = program_id {
+        pda.insert("programId".to_string(), json!(program_id));
+    }
+    Value::Object(pda)
+}
+
+/// Generate the IDL from source, shank output plus PDA seed metadata
+///
+/// Regenerate the checked-in IDL with
+/// `UPDATE_IDL=1 cargo test -p merkle_whitelist_token_sale idl`
+fn generate_idl() -> Value {
+    let mut opts = ParseIdlOpts::default();
+    // transparent_types() are all Pod newtypes
+    opts.detect_custom_struct.skip_derives =
+        HashSet::from_iter(["Pod", "ShankAccount", "ShankInstruction"].map(String::from));
+
+    let idl = extract_idl(LIB_PATH, opts).unwrap().expect("shank IDL");
+    let mut idl: Value = serde_json::from_str(&idl.try_into_json().unwrap()).unwrap();
+    inline_transparent_types(&mut idl);
+    idl["types"].as_array_mut().unwrap().extend(pod_types());
+
+    for instruction in idl["instructions"].as_array_mut().unwrap() {
+        let names = instruction["accounts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|account| account["name"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        for account in instruction["accounts"].as_array_mut().unwrap() {
+            let name = account["name"].as_str().unwrap().to_string();
+            if let Some((_, seeds, program_id)) = pdas()
+                .into_iter()
+                .find(|(pda, _, _)| camel_case(pda) == name)
+            {
+                let seeds = seeds_json(&seeds, Some(&names), &name);
+                account
+                    .as_object_mut()
+                    .unwrap()
+                    .insert("pda".to_string(), pda_json(seeds, &program_id));
+            }
+        }
+    }
+
+    for account in idl["accounts"].as_array_mut().unwrap() {
+        let name = account["name"].as_str().unwrap().to_string();
+        if let Some((pda, seeds, program_id)) = pdas()
+            .into_iter()
+            .find(|(pda, _, _)| pascal_case(pda) == name)
+        {
+            let seeds = seeds_json(&seeds, None, &camel_case(pda));
+            account
+                .as_object_mut()
+                .unwrap()
+                .insert("pda".to_string(), pda_json(seeds, &program_id));
+        }
+    }
+
+    idl
+}
+
+/// The checked-in IDL matches the source
+#[test]
+fn test_idl_in_sync() {
+    let generated = generate_idl();
+
+    if std::env::var_os("UPDATE_IDL").is_some() {
+        let json = serde_json::to_string_pretty(&generated).unwrap() + "\n";
+        std::fs::write(IDL_PATH, json).unwrap();
+    }
+
+    let checked_in: Value =
+        serde_json::from_str(&std::fs::read_to_string(IDL_PATH).unwrap()).unwrap();
+    assert!(
+        checked_in == generated,
+        "{IDL_PATH} is out of date, regenerate it with `UPDATE_IDL=1 cargo test idl`"
+    );
+}
+
+/// Name of the variant, exhaustive so new variants must be listed below
+fn variant_name(instruction: &TokenSaleInstruction) -> &'static str {
+    match instruction {
+        TokenSaleInstruction::OpenSale { .. } => "OpenSale",
+        TokenSaleInstruction::ToggleRunning => "ToggleRunning",
+        TokenSaleInstruction::ConfigureSale { .. } => "ConfigureSale",
+        TokenSaleInstruction::CloseSale => "CloseSale",
+        TokenSaleInstruction::AssignLimit { .. } => "AssignLimit",
+        TokenSaleInstruction::RegisterBuyer => "RegisterBuyer",
+        TokenSaleInstruction::DeregisterBuyer => "DeregisterBuyer",
+        TokenSaleInstruction::BuyToken { .. } => "BuyToken",
+        TokenSaleInstruction::ReclaimBuyerFacts => "ReclaimBuyerFacts",
+        TokenSaleInstruction::Migrate => "Migrate",
+        TokenSaleInstruction::CloseReceipt => "CloseReceipt",
+        TokenSaleInstruction::ClaimReferral => "ClaimReferral",
+        TokenSaleInstruction::Quote { .. } => "Quote",
+        TokenSaleInstruction::RegisterAndBuy { .. } => "RegisterAndBuy",
+        TokenSaleInstruction::InitializeEscrow { .. } => "InitializeEscrow",
+        TokenSaleInstruction::VoteMilestone => "VoteMilestone",
+        TokenSaleInstruction::ReleaseMilestone => "ReleaseMilestone",
+        TokenSaleInstruction::CancelEscrow => "CancelEscrow",
+        TokenSaleInstruction::RefundEscrow => "RefundEscrow",
+        TokenSaleInstruction::UpdateSaleSettings { .. } => "UpdateSaleSettings",
+    }
+}
+
+/// Every TokenSaleInstruction variant is in the IDL under its borsh tag
+#[test]
+fn test_idl_covers_instructions() {
+    let idl = generate_idl();
+    let instructions = idl["instructions"].as_array().unwrap();
+
+    let variants = [
+        TokenSaleInstruction::OpenSale {
+            price: 0,
+            purchase_limit: 0,
+            whitelist_root: crate::merkle::WhitelistRoot([0; 32]),
+        },
+        TokenSaleInstruction::ToggleRunning,
+        TokenSaleInstruction::ConfigureSale {
+            price: None,
+            default_purchase_limit: None,
+            whitelist_root: None,
+        },
+        TokenSaleInstruction::CloseSale,
+        TokenSaleInstruction::AssignLimit {
+            new_purchase_limit: 0,
+        },
+        TokenSaleInstruction::RegisterBuyer,
+        TokenSaleInstruction::DeregisterBuyer,
+        TokenSaleInstruction::BuyToken {
+            amount: 0,
+            proof: crate::merkle::convert_merkle_proof(Vec::new()),
+        },
+        TokenSaleInstruction::ReclaimBuyerFacts,
+        TokenSaleInstruction::Migrate,
+        TokenSaleInstruction::CloseReceipt,
+        TokenSaleInstruction::ClaimReferral,
+        TokenSaleInstruction::Quote {
+            amount: 0,
+            proof: crate::merkle::convert_merkle_proof(Vec::new()),
+        },
+        TokenSaleInstruction::RegisterAndBuy {
+            amount: 0,
+            proof: crate::merkle::convert_merkle_proof(Vec::new()),
+        },
+        TokenSaleInstruction::InitializeEscrow {
+            team: solana_program::pubkey::Pubkey::default(),
+            approver: None,
+            milestone_bps: Vec::new(),
+            vote_threshold_bps: 0,
+        },
+        TokenSaleInstruction::VoteMilestone,
+        TokenSaleInstruction::ReleaseMilestone,
+        TokenSaleInstruction::CancelEscrow,
+        TokenSaleInstruction::RefundEscrow,
+        TokenSaleInstruction::UpdateSaleSettings {
+            settings: crate::instruction::SaleSettings::V1(Default::default()),
+        },
+    ];
+    assert_eq!(instructions.len(), variants.len());
+
+    for (instruction, variant) in instructions.iter().zip(variants) {
+        let tag = borsh::to_vec(&variant).unwrap()[0];
+
+        assert_eq!(instruction["name"], variant_name(&variant));
+        assert_eq!(instruction["discriminant"]["value"], tag);
+    }
+
+    // seeds are published for every PDA account
+    for name in ["TokenBase", "BuyerFacts", "Receipt", "Escrow"] {
+        let account = idl["accounts"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|account| account["name"] == name)
+            .unwrap();
+        assert!(account["pda"]["seeds"].is_array());
+    }
+}
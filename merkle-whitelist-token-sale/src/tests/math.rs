@@ -0,0 +1,108 @@
+use crate::error::TokenSaleError;
+use crate::math::*;
+use proptest::prelude::*;
+use solana_sdk::program_error::ProgramError;
+
+/// Exact rational cost of `amount` base units, in u128
+fn exact_cost(price: u64, amount: u64, decimals: u8) -> (u128, u128) {
+    let numerator = price as u128 * amount as u128;
+    let denominator = 10u128.pow(decimals as u32);
+    (numerator, denominator)
+}
+
+proptest! {
+    /// Buyers pay at least the exact price, and less than one lamport more
+    #[test]
+    fn test_buyer_never_undercharged(
+        price in any::<u64>(),
+        amount in any::<u64>(),
+        decimals in 0u8..=9,
+    ) {
+        let (numerator, denominator) = exact_cost(price, amount, decimals);
+        match purchase_cost(price, amount, decimals) {
+            Ok(cost) => {
+                let cost = cost as u128 * denominator;
+                prop_assert!(cost >= numerator);
+                prop_assert!(cost < numerator + denominator);
+            }
+            // only when the exact cost does not fit in u64
+            Err(e) => {
+                prop_assert_eq!(e, ProgramError::from(TokenSaleError::MathOverflow));
+                prop_assert!(numerator.div_ceil(denominator) > u64::MAX as u128);
+            }
+        }
+    }
+
+    /// Tokens bought with a budget never cost more than the budget
+    #[test]
+    fn test_quote_never_exceeds_budget(
+        lamports in any::<u64>(),
+        price in 1u64..,
+        decimals in 0u8..=9,
+    ) {
+        if let Ok(amount) = tokens_for_lamports(lamports, price, decimals) {
+            prop_assert!(purchase_cost(price, amount, decimals)? <= lamports);
+        }
+    }
+
+    /// The fee is rounded up and fee + remainder is the whole amount
+    #[test]
+    fn test_fee_split_favours_protocol(amount in any::<u64>(), fee_bps in 0u16..=10_000) {
+        let (fee, remainder) = fee_split(amount, fee_bps)?;
+        prop_assert_eq!(fee as u128 + remainder as u128, amount as u128);
+        prop_assert!(fee as u128 * BPS_DENOMINATOR as u128 >= amount as u128 * fee_bps as u128);
+    }
+
+    /// Pro-rata payouts never sum past the total
+    #[test]
+    fn test_pro_rata_never_overpays(
+        total in any::<u64>(),
+        shares in proptest::collection::vec(0u64..u32::MAX as u64, 1..16),
+    ) {
+        let whole: u64 = shares.iter().sum();
+        prop_assume!(whole > 0);
+        let mut paid: u128 = 0;
+        for share in shares {
+            paid += pro_rata(total, share, whole)? as u128;
+        }
+        prop_assert!(paid <= total as u128);
+    }
+}
+
+/// Rounding only differs on a remainder
+#[test]
+fn test_mul_div_rounding() {
+    assert_eq!(mul_div(10, 3, 4, Rounding::Down).unwrap(), 7);
+    assert_eq!(mul_div(10, 3, 4, Rounding::Up).unwrap(), 8);
+    assert_eq!(mul_div(10, 4, 4, Rounding::Up).unwrap(), 10);
+
+    // u64::MAX * u64::MAX fits the u128 intermediate
+    assert_eq!(
+        mul_div(u64::MAX, u64::MAX, u64::MAX, Rounding::Down).unwrap(),
+        u64::MAX
+    );
+
+    let overflow = ProgramError::from(TokenSaleError::MathOverflow);
+    assert_eq!(mul_div(1, 1, 0, Rounding::Down).unwrap_err(), overflow);
+    assert_eq!(
+        mul_div(u64::MAX, 2, 1, Rounding::Down).unwrap_err(),
+        overflow
+    );
+    assert_eq!(decimals_factor(MAX_DECIMALS + 1).unwrap_err(), overflow);
+    assert_eq!(fee_split(1, 10_001).unwrap_err(), overflow);
+    assert_eq!(pro_rata(1, 2, 1).unwrap_err(), overflow);
+}
+
+/// Price is per whole token
+#[test]
+fn test_purchase_cost_scales_by_decimals() {
+    // 1.5 tokens at 2 SOL per token
+    assert_eq!(
+        purchase_cost(2_000_000_000, 1_500_000, 6).unwrap(),
+        3_000_000_000
+    );
+    // one base unit of a 9 decimal token still costs a lamport
+    assert_eq!(purchase_cost(1, 1, 9).unwrap(), 1);
+    // decimals 0 charges price per base unit
+    assert_eq!(purchase_cost(100, 3, 0).unwrap(), 300);
+}
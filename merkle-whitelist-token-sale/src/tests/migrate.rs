@@ -0,0 +1,396 @@
+use crate::pda::BuyerFactsPDA;
+use crate::state::{BuyerFacts, BuyerFactsV1, BuyerFactsV2, TokenBase, TokenBaseV1};
+use crate::{client, error};
+use assert_matches::assert_matches;
+use borsh::BorshSerialize;
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    rent::Rent,
+    signature::Signer,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+    transaction::{Transaction, TransactionError},
+};
+use spl_discriminator::SplDiscriminate;
+
+fn v1_account(data: Vec<u8>, program_id: Pubkey) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: program_id,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+fn migrate_transaction(
+    account: Pubkey,
+    sale: Option<(Pubkey, Pubkey)>,
+    program_id: Pubkey,
+    ctx: &ProgramTestContext,
+    blockhash: Hash,
+) -> Transaction {
+    let instruction = crate::instruction::TokenSaleInstruction::Migrate;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let (token_base, buyer) = sale.unwrap_or((crate::ID, crate::ID));
+
+    Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(account, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(token_base, false),
+                AccountMeta::new_readonly(buyer, false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        blockhash,
+    )
+}
+
+/// Migrate `account`, `sale` is the (token_base, buyer) of a BuyerFacts
+async fn migrate(
+    account: Pubkey,
+    sale: Option<(Pubkey, Pubkey)>,
+    program_id: Pubkey,
+    ctx: &mut ProgramTestContext,
+) {
+    let blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    let transaction = migrate_transaction(account, sale, program_id, ctx, blockhash);
+
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+}
+
+/// Test v1 -> v2 TokenBase
+#[tokio::test]
+async fn test_migrate_token_base_v1() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let v1 = TokenBaseV1 {
+        sale_authority: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        vault: Pubkey::new_unique(),
+        whitelist_root: crate::merkle::WhitelistRoot([3u8; 32]),
+        discriminator: TokenBase::SPL_DISCRIMINATOR.into(),
+        price: 100000000000,
+        default_purchase_limit: 100,
+        is_running: true,
+        bump: 254,
+        _padding: [0; 6],
+    };
+    let token_base_pda = Pubkey::new_unique();
+    program_test.add_account(
+        token_base_pda,
+        v1_account(borsh::to_vec(&v1).unwrap(), program_id),
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    migrate(token_base_pda, None, program_id, &mut ctx).await;
+
+    // confirm state
+    let account = ctx
+        .banks_client
+        .get_account(token_base_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.data.len(), TokenBase::LEN);
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    assert_eq!(account.lamports, rent.minimum_balance(TokenBase::LEN));
+
+    let token_base = ctx
+        .banks_client
+        .get_account_data_with_borsh::<TokenBase>(token_base_pda)
+        .await
+        .unwrap();
+
+    assert!(token_base.is_initialized());
+    assert_eq!(token_base.version, TokenBase::VERSION);
+    assert_eq!(token_base.sale_authority, v1.sale_authority);
+    assert_eq!(token_base.mint, v1.mint);
+    assert_eq!(token_base.vault, v1.vault);
+    assert_eq!(token_base.whitelist_root.0, v1.whitelist_root.0);
+    assert_eq!(token_base.price, v1.price);
+    assert_eq!(token_base.default_purchase_limit, v1.default_purchase_limit);
+    assert_eq!(token_base.is_running(), v1.is_running);
+    assert_eq!(token_base.bump, v1.bump);
+
+    // migrating again is a no-op
+    migrate(token_base_pda, None, program_id, &mut ctx).await;
+    let again = ctx
+        .banks_client
+        .get_account(token_base_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(again.data, account.data);
+    assert_eq!(again.lamports, account.lamports);
+}
+
+/// Test v1 -> current BuyerFacts
+#[tokio::test]
+async fn test_migrate_buyer_facts_v1() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let (token_base_pda, buyer) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (buyer_facts_pda, bump) = BuyerFactsPDA::find_pda(&program_id, &token_base_pda, &buyer);
+    let v1 = BuyerFactsV1 {
+        discriminator: BuyerFacts::SPL_DISCRIMINATOR.into(),
+        purchase_limit: 143,
+        bump,
+        _padding: [0; 7],
+    };
+    program_test.add_account(
+        buyer_facts_pda,
+        v1_account(borsh::to_vec(&v1).unwrap(), program_id),
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    migrate(
+        buyer_facts_pda,
+        Some((token_base_pda, buyer)),
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    // confirm state
+    let account = ctx
+        .banks_client
+        .get_account(buyer_facts_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.data.len(), BuyerFacts::LEN);
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    assert_eq!(account.lamports, rent.minimum_balance(BuyerFacts::LEN));
+
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<BuyerFacts>(buyer_facts_pda)
+        .await
+        .unwrap();
+
+    assert!(buyer_facts.is_initialized());
+    assert_eq!(buyer_facts.version, BuyerFacts::VERSION);
+    assert_eq!(buyer_facts.purchase_limit, v1.purchase_limit);
+    assert_eq!(buyer_facts.bump, v1.bump);
+    assert_eq!(buyer_facts.token_base, token_base_pda);
+    assert_eq!(buyer_facts.buyer, buyer);
+}
+
+fn buyer_facts_v2(referrer: Pubkey, bump: u8) -> BuyerFactsV2 {
+    BuyerFactsV2 {
+        discriminator: BuyerFacts::SPL_DISCRIMINATOR.into(),
+        purchase_limit: 60,
+        bump,
+        version: 2,
+        _padding: [0; 6],
+        receipt_count: 2,
+        referral_rewards: 0,
+        referrer,
+        registered_slot: 7,
+        last_purchase_slot: 12,
+        total_purchased: 40,
+        _reserved: [0; 56],
+    }
+}
+
+/// Test v2 -> v3 BuyerFacts keeps its values and records its sale, so
+/// snapshots of the sale list it
+#[tokio::test]
+async fn test_migrate_buyer_facts_v2() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let (token_base_pda, buyer) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (buyer_facts_pda, bump) = BuyerFactsPDA::find_pda(&program_id, &token_base_pda, &buyer);
+    let v2 = buyer_facts_v2(Pubkey::new_unique(), bump);
+    program_test.add_account(
+        buyer_facts_pda,
+        v1_account(borsh::to_vec(&v2).unwrap(), program_id),
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    migrate(
+        buyer_facts_pda,
+        Some((token_base_pda, buyer)),
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    // confirm state
+    let account = ctx
+        .banks_client
+        .get_account(buyer_facts_pda)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.data.len(), BuyerFacts::LEN);
+
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<BuyerFacts>(buyer_facts_pda)
+        .await
+        .unwrap();
+
+    assert_eq!(buyer_facts.version, BuyerFacts::VERSION);
+    assert_eq!(buyer_facts.purchase_limit, v2.purchase_limit);
+    assert_eq!(buyer_facts.receipt_count, v2.receipt_count);
+    assert_eq!(buyer_facts.referrer, v2.referrer);
+    assert_eq!(buyer_facts.total_purchased, v2.total_purchased);
+    assert_eq!(buyer_facts.token_base, token_base_pda);
+    assert_eq!(buyer_facts.buyer, buyer);
+
+    // snapshots filter on the recorded sale
+    let snapshot = client::list_buyer_facts(
+        &token_base_pda,
+        [(buyer_facts_pda, account.data.as_slice())],
+    );
+    assert_eq!(snapshot.len(), 1);
+    assert_eq!(snapshot[0].0, buyer_facts_pda);
+    assert_eq!(snapshot[0].1.buyer, buyer);
+}
+
+/// Test BuyerFacts can't be migrated without its sale, nor into another sale
+#[tokio::test]
+async fn test_migrate_buyer_facts_wrong_sale_fails() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let (token_base_pda, buyer) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let (buyer_facts_pda, bump) = BuyerFactsPDA::find_pda(&program_id, &token_base_pda, &buyer);
+    let v2 = buyer_facts_v2(Pubkey::new_unique(), bump);
+    program_test.add_account(
+        buyer_facts_pda,
+        v1_account(borsh::to_vec(&v2).unwrap(), program_id),
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+
+    for sale in [None, Some((Pubkey::new_unique(), buyer))] {
+        let transaction = migrate_transaction(buyer_facts_pda, sale, program_id, &ctx, blockhash);
+        let result = ctx.banks_client.process_transaction(transaction).await;
+        assert_matches!(
+            result.unwrap_err().unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(code)
+            ) if code == error::TokenSaleError::InvalidBuyerFactsSeeds as u32
+        );
+    }
+}
+
+/// Borsh encoding must fill exactly the `size_of` allocation
+#[test]
+fn test_layout_sizes() {
+    let token_base_v1 = TokenBaseV1 {
+        sale_authority: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        vault: Pubkey::new_unique(),
+        whitelist_root: crate::merkle::WhitelistRoot([0u8; 32]),
+        discriminator: TokenBase::SPL_DISCRIMINATOR.into(),
+        price: 0,
+        default_purchase_limit: 0,
+        is_running: false,
+        bump: 0,
+        _padding: [0; 6],
+    };
+    let buyer_facts_v1 = BuyerFactsV1 {
+        discriminator: BuyerFacts::SPL_DISCRIMINATOR.into(),
+        purchase_limit: 0,
+        bump: 0,
+        _padding: [0; 7],
+    };
+
+    assert_eq!(
+        borsh::to_vec(&token_base_v1).unwrap().len(),
+        TokenBaseV1::LEN
+    );
+    assert_eq!(
+        borsh::to_vec(&TokenBase::from(token_base_v1.clone()))
+            .unwrap()
+            .len(),
+        TokenBase::LEN
+    );
+    assert_eq!(
+        borsh::to_vec(&buyer_facts_v1).unwrap().len(),
+        BuyerFactsV1::LEN
+    );
+    assert_eq!(
+        borsh::to_vec(&BuyerFacts::from(buyer_facts_v1))
+            .unwrap()
+            .len(),
+        BuyerFacts::LEN
+    );
+    assert_eq!(
+        borsh::to_vec(&buyer_facts_v2(Pubkey::new_unique(), 252))
+            .unwrap()
+            .len(),
+        BuyerFactsV2::LEN
+    );
+
+    // Migrate finds the TokenBase discriminator at its offset in every version
+    for data in [
+        borsh::to_vec(&token_base_v1).unwrap(),
+        borsh::to_vec(&TokenBase::from(token_base_v1)).unwrap(),
+    ] {
+        assert_eq!(
+            &data[TokenBase::DISCRIMINATOR_OFFSET..TokenBase::DISCRIMINATOR_OFFSET + 8],
+            TokenBase::SPL_DISCRIMINATOR_SLICE
+        );
+    }
+
+    // memcmp filters rely on the token_base offset
+    let mut buyer_facts = BuyerFacts::from(buyer_facts_v2(Pubkey::new_unique(), 252));
+    buyer_facts.token_base = Pubkey::new_unique();
+    let data = bytemuck::bytes_of(&buyer_facts);
+    assert_eq!(
+        &data[BuyerFacts::TOKEN_BASE_OFFSET..BuyerFacts::TOKEN_BASE_OFFSET + 32],
+        buyer_facts.token_base.as_ref()
+    );
+}
@@ -1,8 +1,30 @@
 mod assign_limit;
+mod buy_token;
+mod claim_referral;
+mod client;
+mod close_receipt;
 mod close_sale;
+mod compute_units;
 mod configure_sale;
+mod cpi;
 mod deregister_buyer;
+mod error;
+mod escrow;
+mod events;
+mod fuzz;
+mod holder;
+mod idl;
+mod math;
+mod migrate;
 mod open_sale;
+mod quote;
+mod reclaim_buyer_facts;
+mod register_and_buy;
 mod register_buyer;
+mod registry;
+mod root_history;
 mod toggle_running;
 mod utils;
+mod validation;
+mod voucher;
+mod zero_copy;
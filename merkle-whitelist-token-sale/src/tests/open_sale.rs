@@ -57,6 +57,6 @@ async fn test_open_sale() {
     assert_eq!(token_base.price, price);
     assert_eq!(token_base.default_purchase_limit, default_purchase_limit);
     assert_eq!(token_base.bump, token_base_canonical_bump);
-    assert!(!token_base.is_running);
+    assert!(!token_base.is_running());
     assert!(token_base.discriminator != ArrayDiscriminator::UNINITIALIZED.as_slice());
 }
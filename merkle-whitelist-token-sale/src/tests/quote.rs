@@ -0,0 +1,237 @@
+use super::utils::TestHelper;
+use crate::merkle::{convert_merkle_proof, pubkey_to_sha256_leaf};
+use crate::processor::quote::Quote;
+use crate::*;
+use borsh::BorshSerialize;
+use merkletreers::tree::MerkleTree;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signature::Signer,
+    transaction::Transaction,
+};
+
+const PRICE: u64 = 1000;
+const DEFAULT_PURCHASE_LIMIT: u64 = 100;
+
+struct QuotedSale {
+    program_id: Pubkey,
+    mint: Pubkey,
+    token_base: Pubkey,
+    buyer: Pubkey,
+    buyer_facts: Pubkey,
+    tree: MerkleTree,
+    /// Whitelisted buyer without a BuyerFacts
+    newcomer: Pubkey,
+}
+
+/// Running sale whitelisting a registered buyer and a newcomer
+async fn setup_running_sale(ctx: &mut ProgramTestContext, program_id: Pubkey) -> QuotedSale {
+    let mint = TestHelper::new_mint(0, ctx).await;
+    let empty_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    let (token_base, _) = TestHelper::initialize_token_base(
+        PRICE,
+        DEFAULT_PURCHASE_LIMIT,
+        mint,
+        Keypair::new().pubkey(),
+        &empty_root,
+        program_id,
+        ctx,
+    )
+    .await;
+
+    let (buyer, buyer_facts, _) =
+        TestHelper::initialize_buyer_facts(token_base, program_id, ctx).await;
+
+    let newcomer = Keypair::new().pubkey();
+    let tree = MerkleTree::new(vec![
+        pubkey_to_sha256_leaf(&buyer.pubkey()),
+        pubkey_to_sha256_leaf(&newcomer),
+    ]);
+    TestHelper::configure_sale(
+        token_base,
+        mint,
+        crate::instruction::TokenSaleInstruction::ConfigureSale {
+            price: None,
+            default_purchase_limit: None,
+            whitelist_root: Some(crate::merkle::WhitelistRoot(tree.root)),
+        },
+        program_id,
+        ctx,
+    )
+    .await;
+
+    // is_running: false -> true
+    TestHelper::toggle_running(token_base, mint, program_id, ctx).await;
+
+    QuotedSale {
+        program_id,
+        mint,
+        token_base,
+        buyer: buyer.pubkey(),
+        buyer_facts,
+        tree,
+        newcomer,
+    }
+}
+
+/// Simulate Quote, anyone can pay for the simulation
+async fn simulate_quote(sale: &QuotedSale, amount: u64, ctx: &mut ProgramTestContext) -> Quote {
+    let instruction = crate::instruction::TokenSaleInstruction::Quote {
+        amount,
+        proof: convert_merkle_proof(sale.tree.make_proof(pubkey_to_sha256_leaf(&sale.buyer))),
+    };
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id: sale.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(sale.token_base, false),
+                AccountMeta::new_readonly(sale.mint, false),
+                AccountMeta::new_readonly(sale.buyer_facts, false),
+                AccountMeta::new_readonly(sale.buyer, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let simulation = ctx
+        .banks_client
+        .simulate_transaction(transaction)
+        .await
+        .unwrap();
+    assert_eq!(simulation.result, Some(Ok(())));
+
+    let return_data = simulation.simulation_details.unwrap().return_data.unwrap();
+    assert_eq!(return_data.program_id, sale.program_id);
+    Quote::from_return_data(&return_data.data).unwrap()
+}
+
+/// Test Happy Path
+#[tokio::test]
+async fn test_quote() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+
+    let amount: u64 = 40;
+    let quote = simulate_quote(&sale, amount, &mut ctx).await;
+
+    assert_eq!(
+        quote,
+        Quote {
+            amount,
+            lamports_cost: PRICE * amount,
+            purchase_limit: DEFAULT_PURCHASE_LIMIT,
+            remaining_limit: DEFAULT_PURCHASE_LIMIT - amount,
+            error: None,
+        }
+    );
+    assert!(quote.is_eligible());
+
+    // nothing was bought
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(sale.buyer_facts)
+        .await
+        .unwrap();
+    assert_eq!(buyer_facts.purchase_limit, DEFAULT_PURCHASE_LIMIT);
+}
+
+/// Test a purchase BuyToken would reject is quoted with its error
+#[tokio::test]
+async fn test_quote_over_limit_is_ineligible() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+
+    let amount = DEFAULT_PURCHASE_LIMIT + 1;
+    let quote = simulate_quote(&sale, amount, &mut ctx).await;
+
+    assert_eq!(quote.lamports_cost, PRICE * amount);
+    assert_eq!(quote.remaining_limit, 0);
+    assert_eq!(
+        quote.error,
+        Some(error::TokenSaleError::LimitExceeded.code())
+    );
+    assert!(!quote.is_eligible());
+}
+
+/// Test a buyer without a BuyerFacts is quoted as RegisterAndBuy registers it
+#[tokio::test]
+async fn test_quote_unregistered_buyer() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let sale = setup_running_sale(&mut ctx, program_id).await;
+    let (newcomer_facts, _) =
+        pda::BuyerFactsPDA::find_pda(&program_id, &sale.token_base, &sale.newcomer);
+    let sale = QuotedSale {
+        buyer: sale.newcomer,
+        buyer_facts: newcomer_facts,
+        ..sale
+    };
+
+    let amount: u64 = 40;
+    let quote = simulate_quote(&sale, amount, &mut ctx).await;
+
+    // default purchase limit, no purchase cooldown
+    assert_eq!(
+        quote,
+        Quote {
+            amount,
+            lamports_cost: PRICE * amount,
+            purchase_limit: DEFAULT_PURCHASE_LIMIT,
+            remaining_limit: DEFAULT_PURCHASE_LIMIT - amount,
+            error: None,
+        }
+    );
+
+    // nothing was registered
+    assert!(ctx
+        .banks_client
+        .get_account(newcomer_facts)
+        .await
+        .unwrap()
+        .is_none());
+}
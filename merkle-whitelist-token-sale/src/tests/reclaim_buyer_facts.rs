@@ -0,0 +1,162 @@
+use super::utils::TestHelper;
+use crate::*;
+use assert_matches::assert_matches;
+use borsh::BorshSerialize;
+use merkletreers::tree::MerkleTree;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+};
+
+/// Test Happy Path
+#[tokio::test]
+async fn test_reclaim_buyer_facts() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let price: u64 = 100000000000;
+    let default_purchase_limit: u64 = 100;
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        price,
+        default_purchase_limit,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let (buyer, buyer_facts_pda, _) =
+        TestHelper::initialize_buyer_facts(token_base_pda, program_id, &mut ctx).await;
+
+    let instruction = crate::instruction::TokenSaleInstruction::ReclaimBuyerFacts;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(token_base_pda, false),
+        AccountMeta::new(buyer_facts_pda, false),
+        AccountMeta::new(buyer.pubkey(), false),
+        AccountMeta::new(ctx.payer.pubkey(), false),
+    ];
+
+    // ReclaimBuyerFacts fails while the sale is open
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: accounts.clone(),
+            data: instruction_data.clone(),
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::SaleNotClosed as u32
+    );
+
+    TestHelper::close_sale(token_base_pda, mint, program_id, &mut ctx).await;
+
+    let buyer_lamports = ctx.banks_client.get_balance(buyer.pubkey()).await.unwrap();
+    let buyer_facts_lamports = ctx.banks_client.get_balance(buyer_facts_pda).await.unwrap();
+
+    let new_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+
+    // rent paid by the relayer can't be sent to the buyer
+    let mut drain_accounts = accounts.clone();
+    drain_accounts[3] = AccountMeta::new(buyer.pubkey(), false);
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: drain_accounts,
+            data: instruction_data.clone(),
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        new_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::PayerMismatch as u32
+    );
+
+    let payer_lamports = ctx
+        .banks_client
+        .get_balance(ctx.payer.pubkey())
+        .await
+        .unwrap();
+
+    // ReclaimBuyerFacts Transaction, paid and signed by a third party
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts,
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        new_blockhash,
+    );
+
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // confirm state
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(buyer_facts_pda)
+        .await;
+
+    // must be account not found
+    assert_matches!(
+        buyer_facts,
+        Err(BanksClientError::ClientError("Account not found"))
+    );
+
+    // rent went back to the relayer, minus the transaction fee
+    assert_eq!(
+        ctx.banks_client.get_balance(buyer.pubkey()).await.unwrap(),
+        buyer_lamports
+    );
+    assert!(
+        ctx.banks_client
+            .get_balance(ctx.payer.pubkey())
+            .await
+            .unwrap()
+            > payer_lamports + buyer_facts_lamports - 10_000
+    );
+}
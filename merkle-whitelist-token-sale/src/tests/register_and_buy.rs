@@ -0,0 +1,300 @@
+use super::utils::TestHelper;
+use crate::events::{BuyerRegistered, Purchased, TokenSaleEvent};
+use crate::merkle::{convert_merkle_proof, pubkey_to_sha256_leaf};
+use crate::*;
+use assert_matches::assert_matches;
+use borsh::BorshSerialize;
+use merkletreers::tree::MerkleTree;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Keypair,
+    signature::Signer,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+    system_transaction,
+    transaction::{Transaction, TransactionError},
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::Account;
+
+const PRICE: u64 = 1000;
+const DEFAULT_PURCHASE_LIMIT: u64 = 100;
+
+/// Running sale whitelisting `buyers`
+struct Sale {
+    program_id: Pubkey,
+    mint: Pubkey,
+    vault: Pubkey,
+    token_base: Pubkey,
+    tree: MerkleTree,
+}
+
+async fn setup_running_sale(
+    buyers: &[Pubkey],
+    ctx: &mut ProgramTestContext,
+    program_id: Pubkey,
+) -> Sale {
+    let mint = TestHelper::new_mint(0, ctx).await;
+    let vault = Keypair::new();
+    let tree = MerkleTree::new(buyers.iter().map(pubkey_to_sha256_leaf).collect());
+
+    let (token_base, _) = TestHelper::initialize_token_base(
+        PRICE,
+        DEFAULT_PURCHASE_LIMIT,
+        mint,
+        vault.pubkey(),
+        &crate::merkle::WhitelistRoot(tree.root),
+        program_id,
+        ctx,
+    )
+    .await;
+
+    // is_running: false -> true
+    TestHelper::toggle_running(token_base, mint, program_id, ctx).await;
+
+    Sale {
+        program_id,
+        mint,
+        vault: vault.pubkey(),
+        token_base,
+        tree,
+    }
+}
+
+/// Buyer with lamports to pay for its tokens
+async fn new_buyer(ctx: &mut ProgramTestContext) -> Keypair {
+    let buyer = Keypair::new();
+    ctx.banks_client
+        .process_transaction(system_transaction::transfer(
+            &ctx.payer,
+            &buyer.pubkey(),
+            1000000000000,
+            ctx.last_blockhash,
+        ))
+        .await
+        .unwrap();
+    buyer
+}
+
+fn register_and_buy_transaction(
+    sale: &Sale,
+    buyer: &Keypair,
+    amount: u64,
+    ctx: &ProgramTestContext,
+) -> Transaction {
+    let instruction = crate::instruction::TokenSaleInstruction::RegisterAndBuy {
+        amount,
+        proof: convert_merkle_proof(sale.tree.make_proof(pubkey_to_sha256_leaf(&buyer.pubkey()))),
+    };
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let (buyer_facts, _) =
+        pda::BuyerFactsPDA::find_pda(&sale.program_id, &sale.token_base, &buyer.pubkey());
+
+    // payer covers the BuyerFacts and ATA rent
+    Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id: sale.program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(sale.token_base, false),
+                AccountMeta::new(sale.mint, false),
+                AccountMeta::new(sale.vault, false),
+                AccountMeta::new_readonly(ctx.payer.pubkey(), false),
+                AccountMeta::new(
+                    get_associated_token_address(&buyer.pubkey(), &sale.mint),
+                    false,
+                ),
+                AccountMeta::new(buyer_facts, false),
+                AccountMeta::new(buyer.pubkey(), true),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(spl_associated_token_account::ID, false),
+                AccountMeta::new(crate::ID, false),
+                AccountMeta::new(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    )
+}
+
+/// Test Happy Path, unregistered buyer registers and buys at once
+#[tokio::test]
+async fn test_register_and_buy() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let buyer = new_buyer(&mut ctx).await;
+    let sale = setup_running_sale(&[buyer.pubkey()], &mut ctx, program_id).await;
+
+    let (buyer_facts_pda, buyer_facts_canonical_bump) =
+        pda::BuyerFactsPDA::find_pda(&program_id, &sale.token_base, &buyer.pubkey());
+    assert!(ctx
+        .banks_client
+        .get_account(buyer_facts_pda)
+        .await
+        .unwrap()
+        .is_none());
+
+    let amount: u64 = 40;
+    let transaction = register_and_buy_transaction(&sale, &buyer, amount, &ctx);
+
+    let result = ctx
+        .banks_client
+        .process_transaction_with_metadata(transaction)
+        .await
+        .unwrap();
+    assert!(result.result.is_ok());
+
+    // confirm state
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(buyer_facts_pda)
+        .await
+        .unwrap();
+    assert!(buyer_facts.is_initialized());
+    assert_eq!(buyer_facts.bump, buyer_facts_canonical_bump);
+    assert_eq!(buyer_facts.purchase_limit, DEFAULT_PURCHASE_LIMIT - amount);
+    assert_eq!(buyer_facts.total_purchased, amount);
+
+    let buyer_token_account = get_associated_token_address(&buyer.pubkey(), &sale.mint);
+    let token_account = ctx
+        .banks_client
+        .get_account(buyer_token_account)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(Account::unpack(&token_account.data).unwrap().amount, amount);
+
+    // registration and purchase are both reported
+    let events = TokenSaleEvent::from_logs(&program_id, &result.metadata.unwrap().log_messages);
+    assert_eq!(
+        events,
+        vec![
+            TokenSaleEvent::BuyerRegistered(BuyerRegistered {
+                token_base: sale.token_base,
+                buyer: buyer.pubkey(),
+                buyer_facts: buyer_facts_pda,
+                purchase_limit: DEFAULT_PURCHASE_LIMIT,
+            }),
+            TokenSaleEvent::Purchased(Purchased {
+                token_base: sale.token_base,
+                buyer: buyer.pubkey(),
+                buyer_token_account,
+                amount,
+                lamports_paid: PRICE * amount,
+                remaining_limit: DEFAULT_PURCHASE_LIMIT - amount,
+                receipt: None,
+                referrer: None,
+                referral_reward: 0,
+            }),
+        ]
+    );
+}
+
+/// Test a registered buyer's BuyerFacts is reused, not initialized again
+#[tokio::test]
+async fn test_register_and_buy_registered_buyer() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let buyer = new_buyer(&mut ctx).await;
+    let sale = setup_running_sale(&[buyer.pubkey()], &mut ctx, program_id).await;
+
+    // first purchase registers the buyer
+    let transaction = register_and_buy_transaction(&sale, &buyer, 40, &ctx);
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let (buyer_facts_pda, _) =
+        pda::BuyerFactsPDA::find_pda(&program_id, &sale.token_base, &buyer.pubkey());
+    let registered = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(buyer_facts_pda)
+        .await
+        .unwrap();
+
+    // second purchase keeps the existing BuyerFacts
+    ctx.warp_to_slot(registered.registered_slot + 10).unwrap();
+    let transaction = register_and_buy_transaction(&sale, &buyer, 25, &ctx);
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(buyer_facts_pda)
+        .await
+        .unwrap();
+    assert_eq!(buyer_facts.registered_slot, registered.registered_slot);
+    assert_eq!(buyer_facts.purchase_limit, DEFAULT_PURCHASE_LIMIT - 40 - 25);
+    assert_eq!(buyer_facts.total_purchased, 40 + 25);
+}
+
+/// Test a rejected purchase leaves the buyer unregistered
+#[tokio::test]
+async fn test_register_and_buy_not_whitelisted_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    let buyer = new_buyer(&mut ctx).await;
+    let sale = setup_running_sale(&[Pubkey::new_unique()], &mut ctx, program_id).await;
+
+    let transaction = register_and_buy_transaction(&sale, &buyer, 40, &ctx);
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::NotWhitelisted as u32
+    );
+
+    let (buyer_facts_pda, _) =
+        pda::BuyerFactsPDA::find_pda(&program_id, &sale.token_base, &buyer.pubkey());
+    assert!(ctx
+        .banks_client
+        .get_account(buyer_facts_pda)
+        .await
+        .unwrap()
+        .is_none());
+}
@@ -1,10 +1,20 @@
 use super::utils::TestHelper;
 use crate::*;
+use assert_matches::assert_matches;
+use borsh::BorshSerialize;
 use merkletreers::tree::MerkleTree;
 use std::assert_eq;
 use {
     solana_program_test::*,
-    solana_sdk::{pubkey::Pubkey, signature::Keypair, signature::Signer},
+    solana_sdk::{
+        instruction::{AccountMeta, Instruction, InstructionError},
+        pubkey::Pubkey,
+        signature::Keypair,
+        signature::Signer,
+        system_program::ID as SYSTEM_PROGRAM_ID,
+        system_transaction,
+        transaction::{Transaction, TransactionError},
+    },
     spl_discriminator::discriminator::ArrayDiscriminator,
 };
 
@@ -42,7 +52,7 @@ async fn test_register_buyer() {
     )
     .await;
 
-    let (_, buyer_facts_pda, buyer_facts_canonical_bump) =
+    let (buyer, buyer_facts_pda, buyer_facts_canonical_bump) =
         TestHelper::initialize_buyer_facts(token_base_pda, program_id, &mut ctx).await;
 
     // confirm state
@@ -55,5 +65,349 @@ async fn test_register_buyer() {
     // instruction went through
     assert_eq!(buyer_facts.purchase_limit, default_purchase_limit);
     assert_eq!(buyer_facts.bump, buyer_facts_canonical_bump);
+    assert_eq!(buyer_facts.token_base, token_base_pda);
+    assert_eq!(buyer_facts.buyer, buyer.pubkey());
     assert!(buyer_facts.discriminator != ArrayDiscriminator::UNINITIALIZED.as_slice());
 }
+
+/// Test a relayer can sponsor a buyer holding no SOL
+#[tokio::test]
+async fn test_register_buyer_sponsored() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let price: u64 = 100000000000;
+    let default_purchase_limit: u64 = 100;
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        price,
+        default_purchase_limit,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    // buyer never receives any lamports
+    let buyer = Keypair::new();
+    let (buyer_facts_pda, buyer_facts_canonical_bump) =
+        pda::BuyerFactsPDA::find_pda(&program_id, &token_base_pda, &buyer.pubkey());
+
+    let instruction = crate::instruction::TokenSaleInstruction::RegisterBuyer;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    // RegisterBuyer Transaction, payer is the relayer
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(token_base_pda, false),
+                AccountMeta::new(buyer_facts_pda, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // confirm state
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(buyer_facts_pda)
+        .await
+        .unwrap();
+
+    assert_eq!(buyer_facts.purchase_limit, default_purchase_limit);
+    assert_eq!(buyer_facts.bump, buyer_facts_canonical_bump);
+    // relayer gets the rent back on close
+    assert_eq!(buyer_facts.payer, ctx.payer.pubkey());
+
+    // buyer still holds nothing
+    assert_eq!(
+        ctx.banks_client.get_balance(buyer.pubkey()).await.unwrap(),
+        0
+    );
+}
+
+/// Test lamports sent to the BuyerFacts address beforehand don't block registration
+#[tokio::test]
+async fn test_register_buyer_prefunded() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let buyer = Keypair::new();
+    let (buyer_facts_pda, _) =
+        pda::BuyerFactsPDA::find_pda(&program_id, &token_base_pda, &buyer.pubkey());
+
+    // griefer sends a single lamport to the BuyerFacts address
+    ctx.banks_client
+        .process_transaction(system_transaction::transfer(
+            &ctx.payer,
+            &buyer_facts_pda,
+            1,
+            ctx.last_blockhash,
+        ))
+        .await
+        .unwrap();
+
+    let instruction = crate::instruction::TokenSaleInstruction::RegisterBuyer;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(token_base_pda, false),
+                AccountMeta::new(buyer_facts_pda, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    ctx.banks_client
+        .process_transaction(transaction)
+        .await
+        .unwrap();
+
+    // confirm state
+    let buyer_facts = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::BuyerFacts>(buyer_facts_pda)
+        .await
+        .unwrap();
+
+    assert!(buyer_facts.is_initialized());
+}
+
+/// Test a TokenBase copy owned by another program is rejected
+#[tokio::test]
+async fn test_register_buyer_rejects_foreign_token_base() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100000000000,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let spoofed_token_base = TestHelper::spoof_account(token_base_pda, &mut ctx).await;
+
+    let buyer = Keypair::new();
+    let (buyer_facts_pda, _) =
+        pda::BuyerFactsPDA::find_pda(&program_id, &spoofed_token_base, &buyer.pubkey());
+
+    let instruction = crate::instruction::TokenSaleInstruction::RegisterBuyer;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(spoofed_token_base, false),
+                AccountMeta::new(buyer_facts_pda, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::InvalidAccountOwner)
+    );
+
+    // nothing was created
+    assert!(ctx
+        .banks_client
+        .get_account(buyer_facts_pda)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+/// Test holder-gated sales reject buyers without the gating token
+#[tokio::test]
+async fn test_register_buyer_token_holder_gate_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+
+    // create Mint
+    let mint = TestHelper::new_mint(0, &mut ctx).await;
+
+    let vault = Keypair::new();
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+
+    // create TokenBase
+    let (token_base_pda, _) = TestHelper::initialize_token_base(
+        100,
+        100,
+        mint,
+        vault.pubkey(),
+        &whitelist_root,
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    TestHelper::configure_sale(
+        token_base_pda,
+        mint,
+        crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+            settings: crate::instruction::SaleSettings::V1(crate::instruction::SaleSettingsV1 {
+                whitelist_mode: Some(state::WhitelistMode::TokenHolder),
+                gate_key: Some(Pubkey::new_unique()),
+                gate_min_amount: Some(1),
+                ..Default::default()
+            }),
+        },
+        program_id,
+        &mut ctx,
+    )
+    .await;
+
+    let buyer = Keypair::new();
+    let (buyer_facts_pda, _) =
+        pda::BuyerFactsPDA::find_pda(&program_id, &token_base_pda, &buyer.pubkey());
+
+    let instruction = crate::instruction::TokenSaleInstruction::RegisterBuyer;
+
+    let mut instruction_data = Vec::new();
+    instruction.serialize(&mut instruction_data).unwrap();
+
+    // no holder token account passed
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new_readonly(token_base_pda, false),
+                AccountMeta::new(buyer_facts_pda, false),
+                AccountMeta::new_readonly(buyer.pubkey(), true),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone(), &buyer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::NotHolder as u32
+    );
+}
@@ -0,0 +1,263 @@
+use super::utils::TestHelper;
+use crate::client::{find_registry_page, list_registry_sales, registry_append_page};
+use crate::pda::{RegistryPDA, RegistryPagePDA};
+use crate::state::{Registry, RegistryPage};
+use crate::*;
+use assert_matches::assert_matches;
+use borsh::BorshSerialize;
+use bytemuck::Zeroable;
+use merkletreers::tree::MerkleTree;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+};
+use spl_discriminator::SplDiscriminate;
+
+fn registry(page_count: u64, tail_len: u64) -> Vec<u8> {
+    let mut registry = Registry::zeroed();
+    registry.discriminator = Registry::SPL_DISCRIMINATOR.into();
+    registry.page_count = page_count;
+    registry.tail_len = tail_len;
+    bytemuck::bytes_of(&registry).to_vec()
+}
+
+fn page(page: u64, entries: &[Pubkey]) -> Vec<u8> {
+    let mut header = RegistryPage::zeroed();
+    header.discriminator = RegistryPage::SPL_DISCRIMINATOR.into();
+    header.page = page;
+    header.len = entries.len() as u64;
+    [bytemuck::bytes_of(&header), bytemuck::cast_slice(entries)].concat()
+}
+
+/// Open a sale of a new mint, returning its TokenBase
+async fn open_sale(program_id: Pubkey, ctx: &mut ProgramTestContext) -> (Pubkey, Pubkey) {
+    let mint = TestHelper::new_mint(0, ctx).await;
+    let whitelist_root = crate::merkle::WhitelistRoot(MerkleTree::new(Vec::new()).root);
+    let (token_base, _) = TestHelper::initialize_token_base(
+        100,
+        100,
+        mint,
+        Keypair::new().pubkey(),
+        &whitelist_root,
+        program_id,
+        ctx,
+    )
+    .await;
+    (token_base, mint)
+}
+
+/// Sales listed by page 0 of the registry of `authority`
+async fn listed_sales(
+    program_id: Pubkey,
+    authority: Option<&Pubkey>,
+    ctx: &mut ProgramTestContext,
+) -> (Registry, Vec<Pubkey>) {
+    let (registry, _) = RegistryPDA::find_pda(&program_id, authority);
+    let (page, _) = RegistryPagePDA::find_pda(&program_id, &registry, 0);
+    let registry = ctx
+        .banks_client
+        .get_account_data_with_borsh::<Registry>(registry)
+        .await
+        .unwrap();
+    let page = ctx.banks_client.get_account(page).await.unwrap().unwrap();
+    (registry, list_registry_sales([page.data.as_slice()]))
+}
+
+/// OpenSale appends to the last page until it is full
+#[test]
+fn test_registry_append_page() {
+    assert_eq!(registry_append_page(None), 0);
+    assert_eq!(registry_append_page(Some(&registry(0, 0))), 0);
+    assert_eq!(registry_append_page(Some(&registry(1, 5))), 0);
+    assert_eq!(
+        registry_append_page(Some(&registry(2, RegistryPage::CAPACITY - 1))),
+        1
+    );
+    assert_eq!(
+        registry_append_page(Some(&registry(2, RegistryPage::CAPACITY))),
+        2
+    );
+}
+
+/// Sales are listed by page, pages with a bad length are skipped
+#[test]
+fn test_list_registry_sales() {
+    let sales = [
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+        Pubkey::new_unique(),
+    ];
+    let first = page(0, &sales[..2]);
+    let second = page(1, &sales[2..]);
+    let truncated = &page(2, &[Pubkey::new_unique()])[..RegistryPage::LEN];
+
+    let pages = [second.as_slice(), truncated, first.as_slice()];
+    assert_eq!(list_registry_sales(pages), sales);
+    assert_eq!(find_registry_page(&sales[2], pages), Some(1));
+    assert_eq!(find_registry_page(&Pubkey::new_unique(), pages), None);
+}
+
+/// OpenSale lists the sale in the global and sale authority registries,
+/// CloseSale removes it and shrinks the pages
+#[tokio::test]
+async fn test_registry_lists_open_sales() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let sale_authority = ctx.payer.pubkey();
+
+    let (first, first_mint) = open_sale(program_id, &mut ctx).await;
+    let (second, _) = open_sale(program_id, &mut ctx).await;
+
+    for authority in [None, Some(&sale_authority)] {
+        let (registry, sales) = listed_sales(program_id, authority, &mut ctx).await;
+        assert_eq!(registry.authority(), authority.copied());
+        assert_eq!(registry.sale_count, 2);
+        assert_eq!(registry.page_count, 1);
+        assert_eq!(registry.tail_len, 2);
+        assert_eq!(sales, [first, second]);
+    }
+
+    let token_base = ctx
+        .banks_client
+        .get_account_data_with_borsh::<state::TokenBase>(first)
+        .await
+        .unwrap();
+    assert!(token_base.is_registered());
+
+    TestHelper::close_sale(first, first_mint, program_id, &mut ctx).await;
+
+    for authority in [None, Some(&sale_authority)] {
+        let (registry, sales) = listed_sales(program_id, authority, &mut ctx).await;
+        assert_eq!(registry.sale_count, 1);
+        assert_eq!(registry.tail_len, 1);
+        assert_eq!(sales, [second]);
+
+        let (page, _) = RegistryPagePDA::find_pda(
+            &program_id,
+            &RegistryPDA::find_pda(&program_id, authority).0,
+            0,
+        );
+        let page = ctx.banks_client.get_account(page).await.unwrap().unwrap();
+        let rent = ctx.banks_client.get_rent().await.unwrap();
+        assert_eq!(page.data.len(), RegistryPage::space(1));
+        assert_eq!(page.lamports, rent.minimum_balance(RegistryPage::space(1)));
+    }
+}
+
+/// A registered sale can't be closed without its registry pages
+#[tokio::test]
+async fn test_close_sale_without_registry_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let (token_base, mint) = open_sale(program_id, &mut ctx).await;
+
+    let mut instruction_data = Vec::new();
+    crate::instruction::TokenSaleInstruction::CloseSale
+        .serialize(&mut instruction_data)
+        .unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(ctx.payer.pubkey(), true),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+                AccountMeta::new_readonly(crate::ID, false),
+            ],
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::InvalidRegistry as u32
+    );
+}
+
+/// CloseSale rejects a page of another registry
+#[tokio::test]
+async fn test_close_sale_wrong_registry_page_fails() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new(
+        // .so fixture is  retrieved from /target/deploy
+        "merkle_whitelist_token_sale",
+        program_id,
+        // shank is incompatible with instantiating the BuiltInFunction
+        None,
+    );
+
+    let mut ctx = program_test.start_with_context().await;
+    let sale_authority = ctx.payer.pubkey();
+    let (token_base, mint) = open_sale(program_id, &mut ctx).await;
+    TestHelper::end_sale(token_base, mint, program_id, &mut ctx).await;
+
+    // the global registry page lists the sale but belongs to the global registry
+    let mut accounts =
+        TestHelper::close_sale_registry_accounts(token_base, program_id, &mut ctx).await;
+    accounts[3] = accounts[1].clone();
+    accounts.push(AccountMeta::new_readonly(crate::ID, false));
+
+    let mut instruction_data = Vec::new();
+    crate::instruction::TokenSaleInstruction::CloseSale
+        .serialize(&mut instruction_data)
+        .unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[Instruction {
+            program_id,
+            accounts: vec![
+                AccountMeta::new(token_base, false),
+                AccountMeta::new_readonly(mint, false),
+                AccountMeta::new(sale_authority, true),
+            ]
+            .into_iter()
+            .chain(accounts)
+            .collect(),
+            data: instruction_data,
+        }],
+        Some(&ctx.payer.pubkey()),
+        &[&ctx.payer.insecure_clone()],
+        ctx.last_blockhash,
+    );
+
+    let result = ctx.banks_client.process_transaction(transaction).await;
+    assert_matches!(
+        result.unwrap_err().unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(code)
+        ) if code == error::TokenSaleError::InvalidRegistryPage as u32
+    );
+}
@@ -0,0 +1,82 @@
+use crate::merkle::WhitelistRoot;
+use crate::state::{TokenBase, ROOT_HISTORY_LEN};
+use bytemuck::Zeroable;
+
+fn root(byte: u8) -> WhitelistRoot {
+    WhitelistRoot([byte; 32])
+}
+
+fn token_base(grace_slots: u64) -> TokenBase {
+    let mut token_base = TokenBase::zeroed();
+    token_base.root_grace_slots = grace_slots;
+    token_base
+}
+
+/// Sales without recorded roots keep verifying `whitelist_root`
+#[test]
+fn test_unrecorded_root() {
+    let mut token_base = token_base(10);
+    token_base.whitelist_root = root(1);
+
+    assert!(token_base.is_root_accepted(&root(1).0, 500));
+    assert!(!token_base.is_root_accepted(&root(2).0, 500));
+
+    // the first rotation records the root verified so far
+    token_base.rotate_whitelist_root(root(2), 500, 500);
+    let records = token_base.root_records().collect::<Vec<_>>();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[1].root.0, root(1).0);
+    assert!(token_base.is_root_accepted(&root(1).0, 509));
+    assert!(!token_base.is_root_accepted(&root(1).0, 510));
+}
+
+/// Replaced roots verify until the grace period after their successor activates
+#[test]
+fn test_grace_period() {
+    let mut token_base = token_base(10);
+    token_base.rotate_whitelist_root(root(1), 100, 100);
+    token_base.rotate_whitelist_root(root(2), 200, 200);
+
+    assert_eq!(token_base.whitelist_root.0, root(2).0);
+    assert!(token_base.is_root_accepted(&root(2).0, 200));
+    assert!(token_base.is_root_accepted(&root(1).0, 209));
+    assert!(!token_base.is_root_accepted(&root(1).0, 210));
+    assert!(token_base.is_root_accepted(&root(2).0, 10_000));
+}
+
+/// Scheduled roots verify from their activation slot, pending ones are replaced
+#[test]
+fn test_scheduled_root() {
+    let mut token_base = token_base(0);
+    token_base.rotate_whitelist_root(root(1), 100, 100);
+    token_base.rotate_whitelist_root(root(2), 300, 150);
+
+    assert!(token_base.is_root_accepted(&root(1).0, 299));
+    assert!(!token_base.is_root_accepted(&root(2).0, 299));
+    assert!(token_base.is_root_accepted(&root(2).0, 300));
+    assert!(!token_base.is_root_accepted(&root(1).0, 300));
+
+    // rescheduling before activation replaces the pending root
+    token_base.rotate_whitelist_root(root(3), 400, 200);
+    let records = token_base.root_records().collect::<Vec<_>>();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].root.0, root(3).0);
+    assert!(!token_base.is_root_accepted(&root(2).0, 300));
+    assert!(token_base.is_root_accepted(&root(1).0, 399));
+}
+
+/// Only the latest `ROOT_HISTORY_LEN` roots are kept
+#[test]
+fn test_history_wraps() {
+    let mut token_base = token_base(1_000);
+    for (slot, byte) in (1..=ROOT_HISTORY_LEN as u8 + 1).enumerate() {
+        token_base.rotate_whitelist_root(root(byte), slot as u64, slot as u64);
+    }
+
+    let roots = token_base
+        .root_records()
+        .map(|record| record.root.0[0])
+        .collect::<Vec<_>>();
+    assert_eq!(roots, vec![4, 3, 2]);
+    assert!(!token_base.is_root_accepted(&root(1).0, 5));
+}
@@ -80,7 +80,7 @@ async fn test_toggle_running() {
         .unwrap();
 
     // instruction went through
-    assert!(token_base.is_running);
+    assert!(token_base.is_running());
 
     let new_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
 
@@ -112,5 +112,5 @@ async fn test_toggle_running() {
         .await
         .unwrap();
 
-    assert!(!token_base.is_running);
+    assert!(!token_base.is_running());
 }
@@ -2,7 +2,7 @@ pub struct TestHelper {}
 
 use crate::*;
 use borsh::BorshSerialize;
-use spl_token::state::Mint;
+use spl_token::state::{Account, Mint};
 use {
     solana_program_test::*,
     solana_sdk::{
@@ -71,22 +71,27 @@ impl TestHelper {
         let instruction = crate::instruction::TokenSaleInstruction::OpenSale {
             price,
             purchase_limit: default_purchase_limit,
-            whitelist_root: whitelist_root.clone(),
+            whitelist_root: *whitelist_root,
         };
 
         let mut instruction_data = Vec::new();
         instruction.serialize(&mut instruction_data).unwrap();
+        let registry_accounts = TestHelper::open_sale_registry_accounts(program_id, ctx).await;
 
         let transaction = Transaction::new_signed_with_payer(
             &[Instruction {
                 program_id,
                 accounts: vec![
                     AccountMeta::new(token_base_pda, false),
-                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new(mint, false),
                     AccountMeta::new_readonly(vault, false),
                     AccountMeta::new(ctx.payer.pubkey(), true),
                     AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
-                ],
+                ]
+                .into_iter()
+                .chain(registry_accounts)
+                .chain([AccountMeta::new_readonly(spl_token::ID, false)])
+                .collect(),
                 data: instruction_data,
             }],
             Some(&ctx.payer.pubkey()),
@@ -135,8 +140,12 @@ impl TestHelper {
                 accounts: vec![
                     AccountMeta::new_readonly(token_base, false),
                     AccountMeta::new(buyer_facts_pda, false),
-                    AccountMeta::new(buyer.pubkey(), true),
+                    AccountMeta::new_readonly(buyer.pubkey(), true),
+                    AccountMeta::new(ctx.payer.pubkey(), true),
                     AccountMeta::new_readonly(SYSTEM_PROGRAM_ID, false),
+                    AccountMeta::new_readonly(crate::ID, false),
+                    AccountMeta::new_readonly(crate::ID, false),
+                    AccountMeta::new_readonly(crate::ID, false),
                 ],
                 data: instruction_data,
             }],
@@ -153,4 +162,303 @@ impl TestHelper {
 
         (buyer, buyer_facts_pda, buyer_facts_canonical_bump)
     }
+
+    pub async fn new_token_account(
+        mint: Pubkey,
+        owner: Pubkey,
+        ctx: &mut ProgramTestContext,
+    ) -> Pubkey {
+        // create token account
+        let token_account = Keypair::new();
+        let rent = ctx.banks_client.get_rent().await.unwrap();
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                system_instruction::create_account(
+                    &ctx.payer.pubkey(),
+                    &token_account.pubkey(),
+                    rent.minimum_balance(Account::LEN),
+                    Account::LEN as u64,
+                    &spl_token::id(),
+                ),
+                spl_token::instruction::initialize_account3(
+                    &spl_token::id(),
+                    &token_account.pubkey(),
+                    &mint,
+                    &owner,
+                )
+                .unwrap(),
+            ],
+            Some(&ctx.payer.pubkey()),
+            &[ctx.payer.insecure_clone(), token_account.insecure_clone()],
+            ctx.last_blockhash,
+        );
+        ctx.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+
+        token_account.pubkey()
+    }
+
+    pub async fn toggle_running(
+        token_base: Pubkey,
+        mint: Pubkey,
+        program_id: Pubkey,
+        ctx: &mut ProgramTestContext,
+    ) {
+        let instruction = crate::instruction::TokenSaleInstruction::ToggleRunning;
+
+        let mut instruction_data = Vec::new();
+        instruction.serialize(&mut instruction_data).unwrap();
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(token_base, false),
+                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new(ctx.payer.pubkey(), true),
+                ],
+                data: instruction_data,
+            }],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer.insecure_clone()],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    /// Send a ConfigureSale `instruction` signed by the sale authority
+    pub async fn configure_sale(
+        token_base: Pubkey,
+        mint: Pubkey,
+        instruction: crate::instruction::TokenSaleInstruction,
+        program_id: Pubkey,
+        ctx: &mut ProgramTestContext,
+    ) {
+        let mut instruction_data = Vec::new();
+        instruction.serialize(&mut instruction_data).unwrap();
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(token_base, false),
+                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new(ctx.payer.pubkey(), true),
+                ],
+                data: instruction_data,
+            }],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer.insecure_clone()],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    /// Set the sale's end slot to the next slot and warp past it
+    pub async fn end_sale(
+        token_base: Pubkey,
+        mint: Pubkey,
+        program_id: Pubkey,
+        ctx: &mut ProgramTestContext,
+    ) {
+        let slot = ctx
+            .banks_client
+            .get_sysvar::<solana_sdk::clock::Clock>()
+            .await
+            .unwrap()
+            .slot;
+
+        TestHelper::configure_sale(
+            token_base,
+            mint,
+            crate::instruction::TokenSaleInstruction::UpdateSaleSettings {
+                settings: crate::instruction::SaleSettings::V2(
+                    crate::instruction::SaleSettingsV2 {
+                        end_slot: Some(slot + 1),
+                        ..Default::default()
+                    },
+                ),
+            },
+            program_id,
+            ctx,
+        )
+        .await;
+
+        ctx.warp_to_slot(slot + 2).unwrap();
+        ctx.last_blockhash = ctx.get_new_latest_blockhash().await.unwrap();
+    }
+
+    /// End the sale, then close it
+    pub async fn close_sale(
+        token_base: Pubkey,
+        mint: Pubkey,
+        program_id: Pubkey,
+        ctx: &mut ProgramTestContext,
+    ) {
+        TestHelper::end_sale(token_base, mint, program_id, ctx).await;
+
+        let instruction = crate::instruction::TokenSaleInstruction::CloseSale;
+
+        let mut instruction_data = Vec::new();
+        instruction.serialize(&mut instruction_data).unwrap();
+        let registry_accounts =
+            TestHelper::close_sale_registry_accounts(token_base, program_id, ctx).await;
+        let escrow_account =
+            TestHelper::close_sale_escrow_account(token_base, program_id, ctx).await;
+
+        let transaction = Transaction::new_signed_with_payer(
+            &[Instruction {
+                program_id,
+                accounts: vec![
+                    AccountMeta::new(token_base, false),
+                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new(ctx.payer.pubkey(), true),
+                ]
+                .into_iter()
+                .chain(registry_accounts)
+                .chain([escrow_account])
+                .collect(),
+                data: instruction_data,
+            }],
+            Some(&ctx.payer.pubkey()),
+            &[&ctx.payer.insecure_clone()],
+            ctx.last_blockhash,
+        );
+
+        ctx.banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap();
+    }
+
+    /// Global and payer registries with their append pages, the
+    /// registry accounts of OpenSale
+    pub async fn open_sale_registry_accounts(
+        program_id: Pubkey,
+        ctx: &mut ProgramTestContext,
+    ) -> Vec<AccountMeta> {
+        let mut accounts = Vec::new();
+        for authority in [None, Some(ctx.payer.pubkey())] {
+            let registry = pda::RegistryPDA::find_pda(&program_id, authority.as_ref()).0;
+            let data = ctx
+                .banks_client
+                .get_account(registry)
+                .await
+                .unwrap()
+                .map(|account| account.data);
+            let page = client::registry_append_page(data.as_deref());
+
+            accounts.push(AccountMeta::new(registry, false));
+            accounts.push(AccountMeta::new(
+                pda::RegistryPagePDA::find_pda(&program_id, &registry, page).0,
+                false,
+            ));
+        }
+        accounts
+    }
+
+    /// Global and payer registries with the pages listing `token_base`,
+    /// the registry accounts of CloseSale
+    pub async fn close_sale_registry_accounts(
+        token_base: Pubkey,
+        program_id: Pubkey,
+        ctx: &mut ProgramTestContext,
+    ) -> Vec<AccountMeta> {
+        let mut accounts = Vec::new();
+        for authority in [None, Some(ctx.payer.pubkey())] {
+            let registry = pda::RegistryPDA::find_pda(&program_id, authority.as_ref()).0;
+            let page_count = ctx
+                .banks_client
+                .get_account(registry)
+                .await
+                .unwrap()
+                .and_then(|account| client::decode_registry(&account.data))
+                .map_or(0, |registry| registry.page_count);
+
+            let mut page = program_id;
+            for address in client::registry_page_addresses(&program_id, &registry, page_count) {
+                let data = ctx
+                    .banks_client
+                    .get_account(address)
+                    .await
+                    .unwrap()
+                    .map(|account| account.data)
+                    .unwrap_or_default();
+                if client::find_registry_page(&token_base, [data.as_slice()]).is_some() {
+                    page = address;
+                }
+            }
+
+            accounts.push(AccountMeta::new(registry, false));
+            accounts.push(AccountMeta::new(page, false));
+        }
+        accounts
+    }
+
+    /// Escrow of an escrowed `token_base`, the escrow account of CloseSale
+    pub async fn close_sale_escrow_account(
+        token_base: Pubkey,
+        program_id: Pubkey,
+        ctx: &mut ProgramTestContext,
+    ) -> AccountMeta {
+        let account = ctx
+            .banks_client
+            .get_account(token_base)
+            .await
+            .unwrap()
+            .unwrap();
+        let token_base_state: state::TokenBase = bytemuck::pod_read_unaligned(&account.data);
+        if token_base_state.is_escrow() {
+            AccountMeta::new(pda::EscrowPDA::find_pda(&program_id, &token_base).0, false)
+        } else {
+            AccountMeta::new_readonly(program_id, false)
+        }
+    }
+
+    /// Rewrite the BuyerFacts at `buyer_facts` straight in the bank
+    pub async fn update_buyer_facts(
+        buyer_facts: Pubkey,
+        update: impl FnOnce(&mut state::BuyerFacts),
+        ctx: &mut ProgramTestContext,
+    ) {
+        let mut account = ctx
+            .banks_client
+            .get_account(buyer_facts)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut state: state::BuyerFacts = bytemuck::pod_read_unaligned(&account.data);
+        update(&mut state);
+        account.data = bytemuck::bytes_of(&state).to_vec();
+        ctx.set_account(&buyer_facts, &account.into());
+    }
+
+    /// Copy an account to a fresh address owned by another program
+    ///
+    /// The copy holds the same data, only the owner check can tell them apart
+    pub async fn spoof_account(original: Pubkey, ctx: &mut ProgramTestContext) -> Pubkey {
+        let mut account = ctx
+            .banks_client
+            .get_account(original)
+            .await
+            .unwrap()
+            .unwrap();
+        account.owner = Pubkey::new_unique();
+
+        let spoofed = Pubkey::new_unique();
+        ctx.set_account(&spoofed, &account.into());
+
+        spoofed
+    }
 }
@@ -0,0 +1,49 @@
+use crate::error::TokenSaleError;
+use crate::state::ClosedTokenBase;
+use crate::validation::ValidatedTokenBase;
+use bytemuck::Zeroable;
+use solana_sdk::{
+    account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey,
+    system_program::ID as SYSTEM_PROGRAM_ID,
+};
+use spl_discriminator::SplDiscriminate;
+
+/// Validate `data` owned by `owner` as a TokenBase
+fn validate_token_base(program_id: &Pubkey, owner: &Pubkey, mut data: Vec<u8>) -> ProgramError {
+    let key = Pubkey::new_unique();
+    let mut lamports = 1_000_000;
+    let info = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        owner,
+        false,
+        0,
+    );
+    ValidatedTokenBase::new(program_id, &info).err().unwrap()
+}
+
+/// Test only a ClosedTokenBase tombstone reads as an ended sale, any empty
+/// wallet passed as TokenBase is rejected by its owner
+#[test]
+fn test_validated_token_base_closed_sale() {
+    let program_id = Pubkey::new_unique();
+
+    let mut closed = ClosedTokenBase::zeroed();
+    closed.discriminator = ClosedTokenBase::SPL_DISCRIMINATOR.into();
+    assert_eq!(
+        validate_token_base(
+            &program_id,
+            &program_id,
+            bytemuck::bytes_of(&closed).to_vec()
+        ),
+        TokenSaleError::SaleEnded.into()
+    );
+
+    assert_eq!(
+        validate_token_base(&program_id, &SYSTEM_PROGRAM_ID, Vec::new()),
+        ProgramError::InvalidAccountOwner
+    );
+}
@@ -0,0 +1,138 @@
+use crate::error::TokenSaleError;
+use crate::voucher::Voucher;
+use solana_sdk::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::instructions::{
+        construct_instructions_data, store_current_index, BorrowedAccountMeta, BorrowedInstruction,
+    },
+};
+
+const SIGNATURE: [u8; 64] = [7; 64];
+
+fn voucher() -> Voucher {
+    Voucher {
+        buyer: Pubkey::new_unique(),
+        token_base: Pubkey::new_unique(),
+        allocation: 100,
+        expiry: 1_700_000_000,
+    }
+}
+
+/// Instructions sysvar data for `instructions`, executing the last one
+fn instructions_sysvar_data(instructions: &[Instruction]) -> Vec<u8> {
+    let borrowed = instructions
+        .iter()
+        .map(|instruction| BorrowedInstruction {
+            program_id: &instruction.program_id,
+            accounts: instruction
+                .accounts
+                .iter()
+                .map(|meta| BorrowedAccountMeta {
+                    pubkey: &meta.pubkey,
+                    is_signer: meta.is_signer,
+                    is_writable: meta.is_writable,
+                })
+                .collect(),
+            data: &instruction.data,
+        })
+        .collect::<Vec<_>>();
+
+    let mut data = construct_instructions_data(&borrowed);
+    store_current_index(&mut data, instructions.len() as u16 - 1);
+    data
+}
+
+/// Run `load_verified` with `previous` placed right before a BuyToken stand-in
+fn load_verified(previous: Vec<Instruction>, signer: &Pubkey) -> Result<Voucher, ProgramError> {
+    let mut instructions = previous;
+    instructions.push(Instruction {
+        program_id: crate::ID,
+        accounts: vec![AccountMeta::new(Pubkey::new_unique(), true)],
+        data: vec![4],
+    });
+
+    let key = solana_sdk::sysvar::instructions::ID;
+    let owner = solana_sdk::sysvar::ID;
+    let mut lamports = 0;
+    let mut data = instructions_sysvar_data(&instructions);
+    let info = AccountInfo::new(
+        &key,
+        false,
+        false,
+        &mut lamports,
+        &mut data,
+        &owner,
+        false,
+        0,
+    );
+
+    Voucher::load_verified(&info, signer)
+}
+
+/// Test Happy Path
+#[test]
+fn test_load_verified_voucher() {
+    let voucher = voucher();
+    let signer = Pubkey::new_unique();
+
+    let loaded = load_verified(
+        vec![voucher.ed25519_instruction(&signer, &SIGNATURE)],
+        &signer,
+    )
+    .unwrap();
+
+    assert_eq!(loaded, voucher);
+}
+
+/// Test a voucher signed by someone else is rejected
+#[test]
+fn test_load_verified_wrong_signer_fails() {
+    let voucher = voucher();
+
+    let result = load_verified(
+        vec![voucher.ed25519_instruction(&Pubkey::new_unique(), &SIGNATURE)],
+        &Pubkey::new_unique(),
+    );
+
+    assert_eq!(result, Err(TokenSaleError::InvalidVoucher.into()));
+}
+
+/// Test offsets pointing into another instruction are rejected
+#[test]
+fn test_load_verified_foreign_offsets_fail() {
+    let voucher = voucher();
+    let signer = Pubkey::new_unique();
+
+    let mut instruction = voucher.ed25519_instruction(&signer, &SIGNATURE);
+    // message_instruction_index -> instruction 0
+    instruction.data[14..16].copy_from_slice(&0u16.to_le_bytes());
+
+    let result = load_verified(vec![instruction], &signer);
+
+    assert_eq!(result, Err(TokenSaleError::InvalidVoucher.into()));
+}
+
+/// Test a previous instruction other than the Ed25519 precompile is rejected
+#[test]
+fn test_load_verified_not_ed25519_fails() {
+    let voucher = voucher();
+    let signer = Pubkey::new_unique();
+
+    let mut instruction = voucher.ed25519_instruction(&signer, &SIGNATURE);
+    instruction.program_id = Pubkey::new_unique();
+
+    let result = load_verified(vec![instruction], &signer);
+
+    assert_eq!(result, Err(TokenSaleError::InvalidVoucher.into()));
+}
+
+/// Test BuyToken as the first instruction is rejected
+#[test]
+fn test_load_verified_without_previous_instruction_fails() {
+    let result = load_verified(Vec::new(), &Pubkey::new_unique());
+
+    assert_eq!(result, Err(TokenSaleError::InvalidVoucher.into()));
+}
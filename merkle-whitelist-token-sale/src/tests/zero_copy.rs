@@ -0,0 +1,88 @@
+use crate::error::TokenSaleError;
+use crate::state::{BuyerFacts, BuyerFactsV1, TokenBase, TokenBaseV1, ZeroCopyAccount};
+use borsh::BorshDeserialize;
+use solana_sdk::{program_error::ProgramError, pubkey::Pubkey};
+use spl_discriminator::SplDiscriminate;
+
+/// Account data is 8 byte aligned onchain, mirror that here
+fn aligned(bytes: &[u8]) -> Vec<u64> {
+    let mut buffer = vec![0u64; bytes.len().div_ceil(8)];
+    bytemuck::cast_slice_mut::<u64, u8>(&mut buffer)[..bytes.len()].copy_from_slice(bytes);
+    buffer
+}
+
+/// Zero-copy view agrees with borsh
+#[test]
+fn test_load_matches_borsh() {
+    let token_base = TokenBase::from(TokenBaseV1 {
+        sale_authority: Pubkey::new_unique(),
+        mint: Pubkey::new_unique(),
+        vault: Pubkey::new_unique(),
+        whitelist_root: crate::merkle::WhitelistRoot([5u8; 32]),
+        discriminator: TokenBase::SPL_DISCRIMINATOR.into(),
+        price: 1000,
+        default_purchase_limit: 100,
+        is_running: true,
+        bump: 255,
+        _padding: [0; 6],
+    });
+    let data = borsh::to_vec(&token_base).unwrap();
+    let mut buffer = aligned(&data);
+
+    let loaded = TokenBase::load(bytemuck::cast_slice(&buffer)).unwrap();
+    let decoded = TokenBase::try_from_slice(&data).unwrap();
+    assert_eq!(bytemuck::bytes_of(loaded), data.as_slice());
+    assert_eq!(loaded.sale_authority, decoded.sale_authority);
+    assert_eq!(loaded.price, decoded.price);
+    assert!(loaded.is_running());
+
+    // writes land in the account data
+    TokenBase::load_mut(bytemuck::cast_slice_mut(&mut buffer))
+        .unwrap()
+        .price = 2000;
+    let decoded = TokenBase::try_from_slice(bytemuck::cast_slice(&buffer)).unwrap();
+    assert_eq!(decoded.price, 2000);
+}
+
+/// Size and discriminator are enforced
+#[test]
+fn test_load_checks() {
+    let buyer_facts = BuyerFacts::from(BuyerFactsV1 {
+        discriminator: BuyerFacts::SPL_DISCRIMINATOR.into(),
+        purchase_limit: 100,
+        bump: 255,
+        _padding: [0; 7],
+    });
+    let data = borsh::to_vec(&buyer_facts).unwrap();
+
+    // wrong size
+    let short = aligned(&data[..BuyerFacts::LEN - 8]);
+    assert_eq!(
+        BuyerFacts::load(bytemuck::cast_slice(&short)).unwrap_err(),
+        TokenSaleError::InvalidAccountDataLength.into()
+    );
+
+    // wrong account type
+    let mut other = data.clone();
+    other[..8].copy_from_slice(TokenBase::SPL_DISCRIMINATOR_SLICE);
+    let other = aligned(&other);
+    assert_eq!(
+        BuyerFacts::load(bytemuck::cast_slice(&other)).unwrap_err(),
+        TokenSaleError::AccountUninitialized.into()
+    );
+
+    // already initialized
+    let mut initialized = aligned(&data);
+    assert_eq!(
+        BuyerFacts::load_uninitialized_mut(bytemuck::cast_slice_mut(&mut initialized)).unwrap_err(),
+        ProgramError::AccountAlreadyInitialized
+    );
+
+    // freshly allocated
+    let mut zeroed = vec![0u64; BuyerFacts::LEN / 8];
+    assert!(BuyerFacts::load_uninitialized_mut(bytemuck::cast_slice_mut(&mut zeroed)).is_ok());
+    assert_eq!(
+        BuyerFacts::load(bytemuck::cast_slice(&zeroed)).unwrap_err(),
+        TokenSaleError::AccountUninitialized.into()
+    );
+}
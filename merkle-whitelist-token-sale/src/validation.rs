@@ -0,0 +1,354 @@
+/// Validated account wrappers
+///
+/// Every instruction runs its owner, length, discriminator, seed, signer
+/// and mint checks through these types instead of repeating them inline.
+/// Holding a wrapper means the account passed every check for its role.
+use crate::error::TokenSaleError;
+use crate::pda::{BuyerFactsPDA, EscrowPDA, ReceiptPDA, TokenBasePDA};
+use crate::require;
+use crate::state::{BuyerFacts, ClosedTokenBase, Escrow, Receipt, TokenBase, ZeroCopyAccount};
+use solana_program::{
+    account_info::AccountInfo, msg, program_error::ProgramError, program_option::COption,
+    program_pack::Pack, pubkey::Pubkey, system_program::ID as SYSTEM_PROGRAM_ID,
+};
+use spl_token::state::Mint;
+use std::cell::RefMut;
+use std::ops::{Deref, DerefMut};
+
+/// Initialized program account, cast in place
+///
+/// - owner is token_sale (this) program
+/// - correct allocation length
+/// - account is initialized (discriminator)
+///
+/// Seeds are checked by the per-account constructors
+pub struct ValidatedAccount<'a, 'info, T: ZeroCopyAccount> {
+    pub info: &'a AccountInfo<'info>,
+    data: RefMut<'a, T>,
+}
+
+impl<'a, 'info, T: ZeroCopyAccount> ValidatedAccount<'a, 'info, T> {
+    fn load(
+        program_id: &Pubkey,
+        info: &'a AccountInfo<'info>,
+        name: &str,
+    ) -> Result<Self, ProgramError> {
+        // - owner is token_sale (this) program
+        require!(
+            info.owner == program_id,
+            ProgramError::InvalidAccountOwner,
+            name
+        );
+
+        // - correct allocation length
+        // - account is initialized
+        let mut data = info.try_borrow_mut_data()?;
+        T::load_mut(&mut data)?;
+
+        // infallible, size and alignment were just checked
+        let data = RefMut::map(data, |data| bytemuck::from_bytes_mut(data));
+
+        Ok(Self { info, data })
+    }
+}
+
+impl<T: ZeroCopyAccount> Deref for ValidatedAccount<'_, '_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.data
+    }
+}
+
+impl<T: ZeroCopyAccount> DerefMut for ValidatedAccount<'_, '_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.data
+    }
+}
+
+/// [`TokenBase`] owned by this program with matching seeds
+pub type ValidatedTokenBase<'a, 'info> = ValidatedAccount<'a, 'info, TokenBase>;
+
+/// [`BuyerFacts`] owned by this program with matching seeds
+pub type ValidatedBuyerFacts<'a, 'info> = ValidatedAccount<'a, 'info, BuyerFacts>;
+
+impl<'a, 'info> ValidatedAccount<'a, 'info, TokenBase> {
+    /// - account is not a closed sale
+    /// - seeds must be ["token_base", token_base.sale_authority, token_base.mint]
+    pub fn new(program_id: &Pubkey, info: &'a AccountInfo<'info>) -> Result<Self, ProgramError> {
+        require!(
+            !is_closed_token_base(program_id, info),
+            TokenSaleError::SaleEnded,
+            "token_base"
+        );
+
+        let token_base = Self::load(program_id, info, "token_base")?;
+
+        let token_base_pda = TokenBasePDA::create_pda(
+            program_id,
+            &token_base.sale_authority,
+            &token_base.mint,
+            token_base.bump,
+        )
+        .map_err(|_| TokenSaleError::InvalidTokenBaseSeeds)?;
+        require!(
+            *info.key == token_base_pda,
+            TokenSaleError::InvalidTokenBaseSeeds,
+            "token_base"
+        );
+
+        Ok(token_base)
+    }
+
+    /// token_base sale_authority is sale_authority, a signer or not
+    pub fn check_sale_authority(&self, sale_authority: &AccountInfo) -> Result<(), ProgramError> {
+        require!(
+            self.sale_authority == *sale_authority.key,
+            TokenSaleError::SaleAuthorityMismatch,
+            "sale_authority"
+        );
+        Ok(())
+    }
+
+    /// token_base mint is mint
+    pub fn check_mint(&self, mint: &ValidatedMint) -> Result<(), ProgramError> {
+        require!(
+            self.mint == *mint.info.key,
+            TokenSaleError::MintMismatch,
+            "mint"
+        );
+        Ok(())
+    }
+
+    /// token_base vault is vault
+    pub fn check_vault(&self, vault: &AccountInfo) -> Result<(), ProgramError> {
+        require!(
+            self.vault == *vault.key,
+            TokenSaleError::VaultMismatch,
+            "vault"
+        );
+        Ok(())
+    }
+}
+
+impl<'a, 'info> ValidatedAccount<'a, 'info, BuyerFacts> {
+    /// - seeds must be ["buyer_facts", pubkey(token_base), pubkey(buyer)]
+    pub fn new(
+        program_id: &Pubkey,
+        info: &'a AccountInfo<'info>,
+        token_base: &Pubkey,
+        buyer: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let buyer_facts = Self::load(program_id, info, "buyer_facts")?;
+
+        let buyer_facts_pda =
+            BuyerFactsPDA::create_pda(program_id, token_base, buyer, buyer_facts.bump)
+                .map_err(|_| TokenSaleError::InvalidBuyerFactsSeeds)?;
+        require!(
+            *info.key == buyer_facts_pda,
+            TokenSaleError::InvalidBuyerFactsSeeds,
+            "buyer_facts"
+        );
+
+        Ok(buyer_facts)
+    }
+}
+
+/// [`Receipt`] owned by this program with matching seeds
+pub type ValidatedReceipt<'a, 'info> = ValidatedAccount<'a, 'info, Receipt>;
+
+impl<'a, 'info> ValidatedAccount<'a, 'info, Receipt> {
+    /// - seeds must be ["receipt", receipt.token_base, pubkey(buyer), receipt.nonce]
+    pub fn new(
+        program_id: &Pubkey,
+        info: &'a AccountInfo<'info>,
+        buyer: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let receipt = Self::load(program_id, info, "receipt")?;
+
+        let receipt_pda = ReceiptPDA::create_pda(
+            program_id,
+            &receipt.token_base,
+            buyer,
+            receipt.nonce,
+            receipt.bump,
+        )
+        .map_err(|_| TokenSaleError::InvalidReceiptSeeds)?;
+        require!(
+            *info.key == receipt_pda,
+            TokenSaleError::InvalidReceiptSeeds,
+            "receipt"
+        );
+
+        Ok(receipt)
+    }
+}
+
+/// [`Escrow`] owned by this program with matching seeds
+pub type ValidatedEscrow<'a, 'info> = ValidatedAccount<'a, 'info, Escrow>;
+
+impl<'a, 'info> ValidatedAccount<'a, 'info, Escrow> {
+    /// - escrow token_base is token_base
+    /// - seeds must be ["escrow", pubkey(token_base)]
+    pub fn new(
+        program_id: &Pubkey,
+        info: &'a AccountInfo<'info>,
+        token_base: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let escrow = Self::load(program_id, info, "escrow")?;
+
+        let escrow_pda = EscrowPDA::create_pda(program_id, token_base, escrow.bump)
+            .map_err(|_| TokenSaleError::InvalidEscrowSeeds)?;
+        require!(
+            escrow.token_base == *token_base && *info.key == escrow_pda,
+            TokenSaleError::InvalidEscrowSeeds,
+            "escrow"
+        );
+
+        Ok(escrow)
+    }
+}
+
+/// SPL Token mint whose mint authority is the expected authority
+///
+/// The sale authority hands the mint authority to the [`TokenBase`] PDA
+/// on OpenSale, so purchases mint without its signature
+///
+/// - owner is SPL Token Program
+/// - is_initialized is true
+/// - has a mint_authority
+/// - mint_authority is `mint_authority`, the sale authority on OpenSale
+///   and the TokenBase PDA after
+pub struct ValidatedMint<'a, 'info> {
+    pub info: &'a AccountInfo<'info>,
+    pub state: Mint,
+}
+
+impl<'a, 'info> ValidatedMint<'a, 'info> {
+    pub fn new(
+        info: &'a AccountInfo<'info>,
+        mint_authority: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        // - owner is SPL Token Program
+        require!(
+            *info.owner == spl_token::ID,
+            TokenSaleError::InvalidMint,
+            "mint"
+        );
+
+        // - is_initialized is true
+        let state = Mint::unpack(&info.try_borrow_data()?)?;
+
+        // - mint_authority is `mint_authority`
+        let COption::Some(current_mint_authority) = state.mint_authority else {
+            msg!("Constraint failed: mint");
+            return Err(TokenSaleError::MintAuthorityMissing.into());
+        };
+        require!(
+            current_mint_authority == *mint_authority,
+            TokenSaleError::MintAndSaleAuthorityMismatch,
+            "mint"
+        );
+
+        Ok(Self { info, state })
+    }
+}
+
+macro_rules! signer {
+    ($(#[$doc:meta])* $name:ident, $label:literal) => {
+        $(#[$doc])*
+        ///
+        /// - not executable
+        /// - must be signer
+        pub struct $name<'a, 'info>(pub &'a AccountInfo<'info>);
+
+        impl<'a, 'info> $name<'a, 'info> {
+            pub fn new(info: &'a AccountInfo<'info>) -> Result<Self, ProgramError> {
+                require!(
+                    !info.executable,
+                    TokenSaleError::MustBeNonExecutable,
+                    $label
+                );
+                require!(info.is_signer, TokenSaleError::NeedSigner, $label);
+                Ok(Self(info))
+            }
+        }
+
+        impl<'a, 'info> Deref for $name<'a, 'info> {
+            type Target = AccountInfo<'info>;
+
+            fn deref(&self) -> &AccountInfo<'info> {
+                self.0
+            }
+        }
+    };
+}
+
+signer!(
+    /// Sale authority signing for its own sale
+    SaleAuthoritySigner,
+    "sale_authority"
+);
+signer!(
+    /// Buyer signing for its own BuyerFacts
+    BuyerSigner,
+    "buyer"
+);
+signer!(
+    /// Referrer signing for its own BuyerFacts
+    ReferrerSigner,
+    "referrer"
+);
+signer!(
+    /// Account funding rent for new accounts
+    PayerSigner,
+    "payer"
+);
+signer!(
+    /// Escrow approver, or anyone once the buyers' vote passed
+    EscrowAuthoritySigner,
+    "authority"
+);
+
+/// Is `true` if `info` is a TokenBase address CloseSale closed
+///
+/// - a [`ClosedTokenBase`] tombstone owned by this program
+/// - an empty System Program account, for sales closed before tombstones
+///   (or never opened)
+pub fn is_closed_sale(program_id: &Pubkey, info: &AccountInfo) -> bool {
+    if *info.owner == SYSTEM_PROGRAM_ID {
+        return info.data_is_empty();
+    }
+    is_closed_token_base(program_id, info)
+}
+
+/// Is `true` if `info` is a [`ClosedTokenBase`] tombstone owned by this program
+///
+/// Only CloseSale writes tombstones, at a validated TokenBase address, so
+/// unlike an empty System Program account it can't be an unrelated wallet
+pub fn is_closed_token_base(program_id: &Pubkey, info: &AccountInfo) -> bool {
+    info.owner == program_id
+        && info
+            .try_borrow_data()
+            .is_ok_and(|data| ClosedTokenBase::load(&data).is_ok())
+}
+
+/// Wallet that is not an executable program
+pub fn check_not_executable(info: &AccountInfo, name: &str) -> Result<(), ProgramError> {
+    require!(!info.executable, TokenSaleError::MustBeNonExecutable, name);
+    Ok(())
+}
+
+/// Account is the expected program
+pub fn check_program_id(
+    info: &AccountInfo,
+    program_id: &Pubkey,
+    name: &str,
+) -> Result<(), ProgramError> {
+    require!(
+        info.key == program_id,
+        ProgramError::IncorrectProgramId,
+        name
+    );
+    Ok(())
+}
@@ -0,0 +1,145 @@
+/// Signed voucher whitelist
+///
+/// In [`WhitelistMode::Voucher`](crate::state::WhitelistMode) sales a buyer is
+/// eligible if the sale's `whitelist_signer` signed a [`Voucher`] for it.
+/// The signature is checked by the Ed25519 precompile in an instruction
+/// placed right before BuyToken, this program only introspects that
+/// instruction through the instructions sysvar.
+use crate::error::TokenSaleError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    account_info::AccountInfo,
+    ed25519_program,
+    instruction::Instruction,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::instructions::{load_current_index_checked, load_instruction_at_checked},
+};
+
+/// Size of an ed25519 public key
+const PUBKEY_LEN: usize = 32;
+/// Size of an ed25519 signature
+const SIGNATURE_LEN: usize = 64;
+/// `num_signatures: u8` and a padding byte
+const OFFSETS_START: usize = 2;
+/// Seven `u16` offsets per signature
+const OFFSETS_LEN: usize = 14;
+/// Offsets index meaning "this instruction's own data"
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Eligibility granted by the sale's whitelist signer
+///
+/// Signed as its borsh encoding
+#[derive(Clone, BorshSerialize, BorshDeserialize, Debug, PartialEq, Eq)]
+pub struct Voucher {
+    /// Wallet allowed to buy
+    pub buyer: Pubkey,
+    /// Sale the voucher is valid for
+    pub token_base: Pubkey,
+    /// Total amount of tokens the buyer may buy with vouchers
+    pub allocation: u64,
+    /// Unix timestamp after which the voucher is rejected
+    pub expiry: i64,
+}
+
+impl Voucher {
+    /// Signed message, `borsh(voucher)`
+    pub fn message(&self) -> Vec<u8> {
+        borsh::to_vec(self).unwrap()
+    }
+
+    /// Ed25519 precompile instruction verifying `signature` of this voucher
+    /// by `signer`
+    ///
+    /// Place it right before BuyToken in the same transaction
+    pub fn ed25519_instruction(
+        &self,
+        signer: &Pubkey,
+        signature: &[u8; SIGNATURE_LEN],
+    ) -> Instruction {
+        let message = self.message();
+
+        let public_key_offset = OFFSETS_START + OFFSETS_LEN;
+        let signature_offset = public_key_offset + PUBKEY_LEN;
+        let message_offset = signature_offset + SIGNATURE_LEN;
+
+        let mut data = vec![1, 0];
+        for offset in [
+            signature_offset as u16,
+            CURRENT_INSTRUCTION,
+            public_key_offset as u16,
+            CURRENT_INSTRUCTION,
+            message_offset as u16,
+            message.len() as u16,
+            CURRENT_INSTRUCTION,
+        ] {
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        data.extend_from_slice(signer.as_ref());
+        data.extend_from_slice(signature);
+        data.extend_from_slice(&message);
+
+        Instruction {
+            program_id: ed25519_program::ID,
+            accounts: Vec::new(),
+            data,
+        }
+    }
+
+    /// Voucher signed by `signer` in the instruction right before the current one
+    ///
+    /// - previous instruction is the Ed25519 precompile
+    /// - it verifies exactly one signature
+    /// - public key, signature and message are all in its own data
+    /// - public key is `signer`
+    pub fn load_verified(
+        instructions_sysvar: &AccountInfo,
+        signer: &Pubkey,
+    ) -> Result<Self, ProgramError> {
+        let current = load_current_index_checked(instructions_sysvar)?;
+        let previous = current
+            .checked_sub(1)
+            .ok_or(TokenSaleError::InvalidVoucher)?;
+        let instruction = load_instruction_at_checked(previous as usize, instructions_sysvar)?;
+
+        if instruction.program_id != ed25519_program::ID {
+            return Err(TokenSaleError::InvalidVoucher.into());
+        }
+
+        Self::from_ed25519_data(&instruction.data, signer)
+            .ok_or(TokenSaleError::InvalidVoucher.into())
+    }
+
+    fn from_ed25519_data(data: &[u8], signer: &Pubkey) -> Option<Self> {
+        // - it verifies exactly one signature
+        if *data.first()? != 1 {
+            return None;
+        }
+
+        let offsets = data.get(OFFSETS_START..OFFSETS_START + OFFSETS_LEN)?;
+        let offset = |i: usize| u16::from_le_bytes([offsets[2 * i], offsets[2 * i + 1]]);
+        let [_, signature_index, public_key_offset, public_key_index, message_offset, message_len, message_index] =
+            [0, 1, 2, 3, 4, 5, 6].map(offset);
+
+        // - public key, signature and message are all in its own data
+        // otherwise they could point at data this program never sees
+        if [signature_index, public_key_index, message_index]
+            .iter()
+            .any(|index| *index != CURRENT_INSTRUCTION)
+        {
+            return None;
+        }
+
+        // - public key is `signer`
+        let public_key_offset = public_key_offset as usize;
+        let public_key = data.get(public_key_offset..public_key_offset + PUBKEY_LEN)?;
+        if public_key != signer.as_ref() {
+            return None;
+        }
+
+        let message_offset = message_offset as usize;
+        let mut message = data.get(message_offset..message_offset + message_len as usize)?;
+        let voucher = Self::deserialize(&mut message).ok()?;
+        message.is_empty().then_some(voucher)
+    }
+}